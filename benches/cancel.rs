@@ -0,0 +1,74 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rust_decimal_macros::*;
+
+use orderbook::clock::ManualClock;
+use orderbook::id_generator::{sequential_id, SequentialIdGenerator};
+use orderbook::order::Side;
+use orderbook::OrderBook;
+
+/// Builds a book with `depth` orders resting at a single price. Order ids are
+/// `sequential_id(1..=depth)` in time priority.
+fn deep_level(depth: u64) -> OrderBook {
+    let mut order_book = OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+
+    for _ in 0..depth {
+        order_book.submit_limit_order(Side::Ask, dec!(1), dec!(100));
+    }
+
+    order_book
+}
+
+fn bench_cancel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cancel");
+
+    for depth in [1_000, 10_000, 100_000] {
+        group.bench_with_input(
+            BenchmarkId::new("middle_of_level", depth),
+            &depth,
+            |b, &depth| {
+                b.iter_batched_ref(
+                    || deep_level(depth),
+                    |order_book| order_book.remove(sequential_id(depth / 2)),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("back_of_level", depth),
+            &depth,
+            |b, &depth| {
+                b.iter_batched_ref(
+                    || deep_level(depth),
+                    |order_book| order_book.remove(sequential_id(depth)),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_amend(c: &mut Criterion) {
+    let mut group = c.benchmark_group("amend");
+
+    for depth in [1_000, 10_000, 100_000] {
+        group.bench_with_input(
+            BenchmarkId::new("reduce_middle_of_level", depth),
+            &depth,
+            |b, &depth| {
+                b.iter_batched_ref(
+                    || deep_level(depth),
+                    |order_book| order_book.amend(sequential_id(depth / 2), dec!(0.5), dec!(100)),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_cancel, bench_amend);
+criterion_main!(benches);
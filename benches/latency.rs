@@ -0,0 +1,92 @@
+//! Replays synthetic order flow and reports throughput and the distribution of
+//! per-command latencies, which criterion's averages hide.
+//!
+//! Run with `cargo bench --bench latency`. The number of commands and the
+//! seed can be given as arguments: `cargo bench --bench latency -- 1000000 42`.
+
+use std::env;
+use std::time::{Duration, Instant};
+
+use orderbook::clock::ManualClock;
+use orderbook::command::Command;
+use orderbook::id_generator::SequentialIdGenerator;
+use orderbook::order_flow::{FlowConfig, OrderFlow};
+use orderbook::OrderBook;
+
+const KINDS: [&str; 4] = ["limit", "market", "cancel", "amend"];
+
+fn kind(command: &Command) -> usize {
+    match command {
+        Command::Limit { .. } => 0,
+        Command::Market { .. } => 1,
+        Command::Cancel { .. } => 2,
+        Command::Amend { .. } => 3,
+    }
+}
+
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((p / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank]
+}
+
+fn report(name: &str, latencies: &mut [u64]) {
+    latencies.sort_unstable();
+    println!(
+        "{:<8} {:>10} {:>8} {:>8} {:>8} {:>8} {:>10}",
+        name,
+        latencies.len(),
+        percentile(latencies, 50.0),
+        percentile(latencies, 90.0),
+        percentile(latencies, 99.0),
+        percentile(latencies, 99.9),
+        latencies.last().copied().unwrap_or(0),
+    );
+}
+
+fn main() {
+    // Cargo passes `--bench` to every bench target; only look at numbers.
+    let args: Vec<u64> = env::args().skip(1).filter_map(|a| a.parse().ok()).collect();
+    let n = args.first().copied().unwrap_or(1_000_000) as usize;
+    let seed = args.get(1).copied().unwrap_or(0);
+
+    let config = FlowConfig {
+        seed,
+        ..FlowConfig::default()
+    };
+    let commands: Vec<Command> = OrderFlow::new(config).take(n).collect();
+
+    let mut order_book = OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+    let mut latencies: Vec<Vec<u64>> = vec![Vec::new(); KINDS.len()];
+    let mut total = Duration::ZERO;
+
+    for &command in commands.iter() {
+        let start = Instant::now();
+        order_book.execute(command);
+        let elapsed = start.elapsed();
+
+        total += elapsed;
+        latencies[kind(&command)].push(elapsed.as_nanos() as u64);
+    }
+
+    println!(
+        "{} commands (seed {}) in {:?}: {:.0} commands/s",
+        n,
+        seed,
+        total,
+        n as f64 / total.as_secs_f64()
+    );
+    println!();
+    println!(
+        "{:<8} {:>10} {:>8} {:>8} {:>8} {:>8} {:>10}",
+        "latency", "count", "p50 ns", "p90 ns", "p99 ns", "p99.9 ns", "max ns"
+    );
+
+    let mut all: Vec<u64> = latencies.iter().flatten().copied().collect();
+    for (name, latencies) in KINDS.iter().zip(latencies.iter_mut()) {
+        report(name, latencies);
+    }
+    report("all", &mut all);
+}
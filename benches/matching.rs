@@ -0,0 +1,166 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+
+use orderbook::clock::ManualClock;
+use orderbook::command::Command;
+use orderbook::id_generator::{sequential_id, SequentialIdGenerator};
+use orderbook::order::Side;
+use orderbook::order_flow::{FlowConfig, OrderFlow};
+use orderbook::OrderBook;
+
+fn empty_book() -> OrderBook {
+    OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new())
+}
+
+/// A book in the state `OrderFlow` leaves it in after `n` commands, along with
+/// the flow so more commands can be drawn from the same state.
+fn warmed_up(n: usize) -> (OrderBook, OrderFlow) {
+    let mut order_book = empty_book();
+    let mut flow = OrderFlow::new(FlowConfig::default());
+
+    for command in flow.by_ref().take(n) {
+        order_book.execute(command);
+    }
+
+    (order_book, flow)
+}
+
+/// A book with one ask of size 1 on each of `levels` ticks above 100.00.
+/// Order ids are `sequential_id(1..=levels)`, best price first.
+fn ladder(levels: u64) -> OrderBook {
+    let mut order_book = empty_book();
+
+    for i in 0..levels {
+        let price = dec!(100.00) + Decimal::from(i) * dec!(0.01);
+        order_book.submit_limit_order(Side::Ask, dec!(1), price);
+    }
+
+    order_book
+}
+
+fn bench_flow(c: &mut Criterion) {
+    let commands: Vec<Command> = OrderFlow::new(FlowConfig::default())
+        .take(100_000)
+        .collect();
+
+    let mut group = c.benchmark_group("flow");
+    group.throughput(Throughput::Elements(commands.len() as u64));
+    group.sample_size(20);
+
+    group.bench_function("replay", |b| {
+        b.iter_batched_ref(
+            empty_book,
+            |order_book| {
+                for &command in commands.iter() {
+                    order_book.execute(command);
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_submit_limit_order(c: &mut Criterion) {
+    let mut group = c.benchmark_group("submit_limit_order");
+
+    group.bench_function("passive", |b| {
+        b.iter_batched_ref(
+            || warmed_up(10_000).0,
+            |order_book| order_book.submit_limit_order(Side::Bid, dec!(1), dec!(1.00)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("new_level", |b| {
+        b.iter_batched_ref(
+            || ladder(1_000),
+            |order_book| order_book.submit_limit_order(Side::Ask, dec!(1), dec!(99.995)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("crossing", |b| {
+        b.iter_batched_ref(
+            || ladder(1_000),
+            |order_book| order_book.submit_limit_order(Side::Bid, dec!(5), dec!(100.10)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_submit_market_order(c: &mut Criterion) {
+    let mut group = c.benchmark_group("submit_market_order");
+
+    group.bench_function("top_of_book", |b| {
+        b.iter_batched_ref(
+            || ladder(1_000),
+            |order_book| order_book.submit_market_order(Side::Bid, dec!(1)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remove");
+
+    group.bench_function("best_level", |b| {
+        b.iter_batched_ref(
+            || ladder(1_000),
+            |order_book| order_book.remove(sequential_id(1)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("deep_level", |b| {
+        b.iter_batched_ref(
+            || ladder(1_000),
+            |order_book| order_book.remove(sequential_id(500)),
+            BatchSize::LargeInput,
+        )
+    });
+
+    group.bench_function("unknown_id", |b| {
+        let mut order_book = ladder(1_000);
+        b.iter(|| order_book.remove(sequential_id(0)))
+    });
+
+    group.finish();
+}
+
+fn bench_sweep(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sweep");
+
+    for levels in [10, 100, 1_000] {
+        group.throughput(Throughput::Elements(levels));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(levels),
+            &levels,
+            |b, &levels| {
+                b.iter_batched_ref(
+                    || ladder(levels),
+                    |order_book| order_book.submit_market_order(Side::Bid, Decimal::from(levels)),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_flow,
+    bench_submit_limit_order,
+    bench_submit_market_order,
+    bench_remove,
+    bench_sweep
+);
+criterion_main!(benches);
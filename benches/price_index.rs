@@ -0,0 +1,123 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use orderbook::book_side::BookSide;
+use orderbook::clock::ManualClock;
+use orderbook::id_generator::SequentialIdGenerator;
+use orderbook::numeric::{Lots, Ticks};
+use orderbook::order::Side;
+use orderbook::price_index::{PriceIndex, PriceTree};
+use orderbook::price_ladder::PriceLadder;
+use orderbook::OrderBook;
+
+const MID: i64 = 10_000;
+
+/// A cheap, deterministic stream of limit orders within 50 ticks of `MID`,
+/// some of which cross the spread.
+fn limit_orders(n: usize) -> Vec<(Side, Lots, Ticks)> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    (0..n)
+        .map(|_| {
+            let side = if next() % 2 == 0 {
+                Side::Bid
+            } else {
+                Side::Ask
+            };
+            let offset = (next() % 50) as i64 - 5;
+            let price = match side {
+                Side::Bid => MID - offset,
+                Side::Ask => MID + offset,
+            };
+            (side, Lots(1 + next() % 10), Ticks(price))
+        })
+        .collect()
+}
+
+fn book<I: PriceIndex<Ticks>>(bids: I, asks: I) -> OrderBook<Ticks, Lots, I> {
+    OrderBook::with_book_sides(
+        BookSide::with_index(bids),
+        BookSide::with_index(asks),
+        ManualClock::new(0),
+        SequentialIdGenerator::new(),
+    )
+}
+
+fn tree_book() -> OrderBook<Ticks, Lots, PriceTree<Ticks>> {
+    book(PriceTree::new(), PriceTree::new())
+}
+
+fn ladder_book() -> OrderBook<Ticks, Lots, PriceLadder<Ticks>> {
+    let ladder = PriceLadder::new(Ticks(MID - 1_000), Ticks(1), 2_000);
+    book(ladder.clone(), ladder)
+}
+
+fn run<I: PriceIndex<Ticks>>(
+    order_book: &mut OrderBook<Ticks, Lots, I>,
+    orders: &[(Side, Lots, Ticks)],
+) {
+    for &(side, quantity, price) in orders {
+        order_book.submit_limit_order(side, quantity, price);
+    }
+}
+
+fn bench_limit_orders(c: &mut Criterion) {
+    let orders = limit_orders(10_000);
+    let mut group = c.benchmark_group("price_index/limit_orders");
+
+    group.bench_function("tree", |b| {
+        b.iter_batched_ref(
+            tree_book,
+            |order_book| run(order_book, &orders),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("ladder", |b| {
+        b.iter_batched_ref(
+            ladder_book,
+            |order_book| run(order_book, &orders),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_sweep(c: &mut Criterion) {
+    let mut group = c.benchmark_group("price_index/sweep");
+
+    fn fill<I: PriceIndex<Ticks>>(
+        mut order_book: OrderBook<Ticks, Lots, I>,
+    ) -> OrderBook<Ticks, Lots, I> {
+        // One order on every tick for 500 ticks above the mid.
+        for i in 0..500 {
+            order_book.submit_limit_order(Side::Ask, Lots(1), Ticks(MID + i));
+        }
+        order_book
+    }
+
+    group.bench_function("tree", |b| {
+        b.iter_batched_ref(
+            || fill(tree_book()),
+            |order_book| order_book.submit_market_order(Side::Bid, Lots(500)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("ladder", |b| {
+        b.iter_batched_ref(
+            || fill(ladder_book()),
+            |order_book| order_book.submit_market_order(Side::Bid, Lots(500)),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_limit_orders, bench_sweep);
+criterion_main!(benches);
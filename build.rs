@@ -0,0 +1,23 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    compile_protos();
+}
+
+/// Generates the gRPC messages and service from the proto schema, with the
+/// protoc bundled by `protoc-bin-vendored` unless `PROTOC` names another.
+#[cfg(feature = "grpc")]
+fn compile_protos() {
+    println!("cargo:rerun-if-changed=proto/orderbook.proto");
+    println!("cargo:rerun-if-env-changed=PROTOC");
+    if std::env::var_os("PROTOC").is_none() {
+        let protoc = protoc_bin_vendored::protoc_bin_path().unwrap();
+        std::env::set_var("PROTOC", protoc);
+    }
+    // The generated `connect` helper needs the 2021 prelude, so clients
+    // connect a `Channel` themselves and pass it to `new`
+    tonic_prost_build::configure()
+        .build_transport(false)
+        .compile_protos(&["proto/orderbook.proto"], &["proto"])
+        .unwrap();
+}
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use orderbook::OrderBook;
+use rust_decimal::Decimal;
+
+fuzz_target!(|data: &[u8]| {
+    let ops = orderbook_fuzz::decode(data);
+
+    // Prices between 0.00 and 2.55 in cents, so most orders land within a
+    // few levels of each other and cross.
+    orderbook_fuzz::run(
+        OrderBook::new(),
+        &ops,
+        |p| Decimal::new(p as i64, 2),
+        Decimal::from,
+    );
+});
@@ -0,0 +1,25 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use orderbook::book_side::BookSide;
+use orderbook::clock::SystemClock;
+use orderbook::id_generator::SequentialIdGenerator;
+use orderbook::numeric::{Lots, Ticks};
+use orderbook::price_ladder::PriceLadder;
+use orderbook::OrderBook;
+
+fuzz_target!(|data: &[u8]| {
+    let ops = orderbook_fuzz::decode(data);
+
+    // The ladder only covers part of the price range, so orders also go
+    // through its overflow levels on both ends.
+    let ladder = || BookSide::with_index(PriceLadder::new(Ticks(64), Ticks(1), 128));
+    let order_book = OrderBook::with_book_sides(
+        ladder(),
+        ladder(),
+        SystemClock,
+        SequentialIdGenerator::new(),
+    );
+
+    orderbook_fuzz::run(order_book, &ops, |p| Ticks(p as i64), |q| Lots(q as u64));
+});
@@ -0,0 +1,183 @@
+use orderbook::numeric::Numeric;
+use orderbook::order::Side;
+use orderbook::price_index::PriceIndex;
+use orderbook::OrderBook;
+use uuid::Uuid;
+
+/// An operation decoded from fuzzer input. Cancels and amends refer to
+/// orders that rested earlier by index, so most of them hit a live order.
+#[derive(Copy, Clone, Debug)]
+pub enum Op {
+    Limit { side: Side, quantity: u8, price: u8 },
+    Market { side: Side, quantity: u8 },
+    Cancel { order: u8 },
+    Amend { order: u8, quantity: u8, price: u8 },
+}
+
+/// Decodes `data` into operations, four bytes each: a tag whose low bit is
+/// the side and whose next two bits pick the kind of operation, then up to
+/// three argument bytes. A trailing partial operation is dropped.
+pub fn decode(data: &[u8]) -> Vec<Op> {
+    data.chunks_exact(4)
+        .map(|chunk| {
+            let side = if chunk[0] & 1 == 0 {
+                Side::Bid
+            } else {
+                Side::Ask
+            };
+            match (chunk[0] >> 1) & 3 {
+                0 => Op::Limit {
+                    side,
+                    quantity: chunk[1],
+                    price: chunk[2],
+                },
+                1 => Op::Market {
+                    side,
+                    quantity: chunk[1],
+                },
+                2 => Op::Cancel { order: chunk[1] },
+                _ => Op::Amend {
+                    order: chunk[1],
+                    quantity: chunk[2],
+                    price: chunk[3],
+                },
+            }
+        })
+        .collect()
+}
+
+/// Runs `ops` against `order_book`, checking after every operation that the
+/// book is consistent, no order, level or side has a negative (or empty)
+/// volume, the book isn't crossed, and that every unit of quantity submitted
+/// has been filled, is resting or was canceled. Panics on the first failure.
+///
+/// `price` and `quantity` map decoded bytes to the book's types.
+pub fn run<P, Q, I>(
+    mut order_book: OrderBook<P, Q, I>,
+    ops: &[Op],
+    price: impl Fn(u8) -> P,
+    quantity: impl Fn(u8) -> Q,
+) where
+    P: Numeric,
+    Q: Numeric,
+    I: PriceIndex<P>,
+{
+    let mut ids: Vec<Uuid> = Vec::new();
+    let mut submitted = Q::ZERO;
+    let mut filled = Q::ZERO;
+    let mut canceled = Q::ZERO;
+
+    for op in ops.iter() {
+        match *op {
+            Op::Limit {
+                side,
+                quantity: q,
+                price: p,
+            } => {
+                let result = order_book.submit_limit_order(side, quantity(q), price(p));
+                submitted += quantity(q);
+                // Both the taker and each maker are filled.
+                filled += result.quantity_filled;
+                filled += result.quantity_filled;
+                ids.extend(result.partial.map(|order| order.id));
+            }
+            Op::Market { side, quantity: q } => {
+                let result = order_book.submit_market_order(side, quantity(q));
+                submitted += quantity(q);
+                filled += result.quantity_filled;
+                filled += result.quantity_filled;
+                // Whatever a market order can't fill is dropped.
+                canceled += quantity(q) - result.quantity_filled;
+            }
+            Op::Cancel { order } => {
+                if let Some(id) = pick(&ids, order) {
+                    if let Some(order) = order_book.remove(id) {
+                        canceled += order.quantity;
+                    }
+                }
+            }
+            Op::Amend {
+                order,
+                quantity: q,
+                price: p,
+            } => {
+                let id = match pick(&ids, order) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                let previous = match order_book.get(id) {
+                    Some(order) => *order,
+                    None => continue,
+                };
+
+                // Count an amend as canceling what was left and submitting
+                // the new quantity, which holds whether or not it keeps its
+                // place in the queue.
+                let result = order_book
+                    .amend(id, quantity(q), price(p))
+                    .expect("amending a resting order");
+                canceled += previous.quantity;
+                submitted += quantity(q);
+                filled += result.quantity_filled;
+                filled += result.quantity_filled;
+            }
+        }
+
+        check(&order_book, submitted, filled, canceled);
+    }
+}
+
+fn pick(ids: &[Uuid], i: u8) -> Option<Uuid> {
+    if ids.is_empty() {
+        return None;
+    }
+    Some(ids[i as usize % ids.len()])
+}
+
+fn check<P, Q, I>(order_book: &OrderBook<P, Q, I>, submitted: Q, filled: Q, canceled: Q)
+where
+    P: Numeric,
+    Q: Numeric,
+    I: PriceIndex<P>,
+{
+    if let Err(violation) = order_book.check_invariants() {
+        panic!("{}", violation);
+    }
+
+    if let (Some(bid), Some(ask)) = (order_book.best_bid(), order_book.best_ask()) {
+        assert!(bid < ask, "book is crossed: bid {} >= ask {}", bid, ask);
+    }
+
+    let mut resting = Q::ZERO;
+    for side in [Side::Bid, Side::Ask].iter() {
+        for level in order_book.levels(*side) {
+            assert!(
+                level.volume > Q::ZERO,
+                "level {} has volume {}",
+                level.price,
+                level.volume
+            );
+
+            for order in order_book.orders_at(*side, level.price) {
+                assert!(
+                    order.quantity > Q::ZERO,
+                    "order {} rests with quantity {}",
+                    order.id,
+                    order.quantity
+                );
+            }
+
+            resting += level.volume;
+        }
+    }
+
+    let accounted = filled + resting + canceled;
+    assert!(
+        submitted == accounted,
+        "submitted {} but filled {}, resting {} and canceled {}",
+        submitted,
+        filled,
+        resting,
+        canceled
+    );
+}
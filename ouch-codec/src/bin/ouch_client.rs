@@ -0,0 +1,169 @@
+use std::env;
+use std::io::{self, BufRead};
+use std::process;
+use std::thread;
+
+use ouch_codec::{format_price, parse_price, time_in_force, Client, Outbound, Side, MARKET_PRICE};
+
+const USAGE: &str = "usage: ouch_client [--addr HOST:PORT] [--symbol SYMBOL]
+
+Connects to an OUCH server (127.0.0.1:9879 unless given) and sends orders for
+a symbol (ABC unless given), one command per line on stdin:
+
+  buy TOKEN SHARES PRICE [ioc]     or market instead of a price
+  sell TOKEN SHARES PRICE [ioc]
+  replace TOKEN NEW_TOKEN SHARES PRICE
+  cancel TOKEN [SHARES]            cut down to SHARES, or cancel
+
+Messages from the server are printed as they arrive.";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut addr = String::from("127.0.0.1:9879");
+    let mut symbol = String::from("ABC");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--addr" => addr = args.next().unwrap_or_else(|| usage_error()),
+            "--symbol" => symbol = args.next().unwrap_or_else(|| usage_error()),
+            _ => usage_error(),
+        }
+    }
+
+    let mut client = Client::connect(&addr).unwrap_or_else(|e| {
+        eprintln!("can't connect to {}: {}", addr, e);
+        process::exit(2);
+    });
+    let mut receiver = client.try_clone().unwrap();
+    thread::spawn(move || loop {
+        match receiver.receive() {
+            Ok(Some(message)) => println!("{}", describe(&message)),
+            Ok(None) => {
+                println!("server closed the connection");
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    });
+
+    for line in io::stdin().lock().lines() {
+        let line = line.unwrap_or_default();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let sent = match send(&mut client, &symbol, &words) {
+            Some(sent) => sent,
+            None => {
+                eprintln!("can't parse '{}', try --help", line);
+                continue;
+            }
+        };
+        if let Err(e) = sent {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+    let _ = client.close();
+}
+
+/// Sends the command in `words`, or returns `None` if it doesn't parse.
+fn send(client: &mut Client, symbol: &str, words: &[&str]) -> Option<io::Result<()>> {
+    match *words {
+        [side @ ("buy" | "sell"), token, shares, price, ref rest @ ..] => {
+            let side = if side == "buy" { Side::Buy } else { Side::Sell };
+            let price = match price {
+                "market" => MARKET_PRICE,
+                price => parse_price(price)?,
+            };
+            let time_in_force = match rest {
+                [] => time_in_force::DAY,
+                ["ioc"] => time_in_force::IMMEDIATE,
+                _ => return None,
+            };
+            let shares = shares.parse().ok()?;
+            Some(client.enter_order(token, side, shares, symbol, price, time_in_force))
+        }
+        ["replace", token, new_token, shares, price] => {
+            let (shares, price) = (shares.parse().ok()?, parse_price(price)?);
+            Some(client.replace_order(token, new_token, shares, price))
+        }
+        ["cancel", token] => Some(client.cancel_order(token, 0)),
+        ["cancel", token, shares] => Some(client.cancel_order(token, shares.parse().ok()?)),
+        _ => None,
+    }
+}
+
+fn describe(message: &Outbound) -> String {
+    let token = message.token();
+    match *message {
+        Outbound::Accepted {
+            side,
+            shares,
+            price,
+            order_reference,
+            ..
+        } => format!(
+            "accepted {}: {:?} {} at {} (order {})",
+            token,
+            side,
+            shares,
+            describe_price(price),
+            order_reference
+        ),
+        Outbound::Replaced {
+            shares,
+            price,
+            previous_token,
+            ..
+        } => format!(
+            "replaced {} with {}: {} at {}",
+            previous_token,
+            token,
+            shares,
+            describe_price(price)
+        ),
+        Outbound::Executed {
+            executed_shares,
+            execution_price,
+            liquidity_flag,
+            match_number,
+            ..
+        } => format!(
+            "executed {}: {} at {} ({}, match {})",
+            token,
+            executed_shares,
+            format_price(execution_price),
+            liquidity_flag as char,
+            match_number
+        ),
+        Outbound::Canceled {
+            decrement_shares,
+            reason,
+            ..
+        } => format!(
+            "canceled {}: {} ({})",
+            token, decrement_shares, reason as char
+        ),
+        Outbound::Rejected { reason, .. } => format!("rejected {} ({})", token, reason as char),
+    }
+}
+
+fn describe_price(price: u32) -> String {
+    if price == MARKET_PRICE {
+        return String::from("market");
+    }
+    format_price(price)
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,98 @@
+use std::io;
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::message::{read_frame, stock, write_frame, Inbound, OuchError, Outbound, Side, Token};
+
+/// A blocking order-entry client for one connection.
+///
+/// Orders only live as long as the connection: the server cancels whatever
+/// is still open when it drops.
+#[derive(Debug)]
+pub struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        return Ok(Client { stream });
+    }
+
+    /// Another handle on the same connection, so responses can be read on
+    /// one thread while orders are sent from another.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        return Ok(Client {
+            stream: self.stream.try_clone()?,
+        });
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        return self.stream.local_addr();
+    }
+
+    /// Makes `receive` fail after waiting `timeout`, or never with `None`.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        return self.stream.set_read_timeout(timeout);
+    }
+
+    pub fn send(&mut self, message: &Inbound) -> io::Result<()> {
+        return write_frame(&mut self.stream, &message.encode());
+    }
+
+    /// Waits for the next message from the server. Returns `None` once the
+    /// server has closed the connection.
+    pub fn receive(&mut self) -> Result<Option<Outbound>, OuchError> {
+        match read_frame(&mut self.stream)? {
+            Some(bytes) => return Outbound::decode(&bytes).map(Some),
+            None => return Ok(None),
+        }
+    }
+
+    pub fn enter_order(
+        &mut self,
+        token: &str,
+        side: Side,
+        shares: u32,
+        symbol: &str,
+        price: u32,
+        time_in_force: u32,
+    ) -> io::Result<()> {
+        return self.send(&Inbound::EnterOrder {
+            token: Token::new(token),
+            side,
+            shares,
+            stock: stock(symbol),
+            price,
+            time_in_force,
+        });
+    }
+
+    pub fn replace_order(
+        &mut self,
+        existing_token: &str,
+        replacement_token: &str,
+        shares: u32,
+        price: u32,
+    ) -> io::Result<()> {
+        return self.send(&Inbound::ReplaceOrder {
+            existing_token: Token::new(existing_token),
+            replacement_token: Token::new(replacement_token),
+            shares,
+            price,
+        });
+    }
+
+    /// Cuts an order down to `shares`, or cancels it with zero.
+    pub fn cancel_order(&mut self, token: &str, shares: u32) -> io::Result<()> {
+        return self.send(&Inbound::CancelOrder {
+            token: Token::new(token),
+            shares,
+        });
+    }
+
+    pub fn close(self) -> io::Result<()> {
+        return self.stream.shutdown(Shutdown::Both);
+    }
+}
@@ -0,0 +1,10 @@
+#![allow(clippy::needless_return)]
+
+pub mod client;
+pub mod message;
+
+pub use client::Client;
+pub use message::{
+    cancel_reason, format_price, liquidity, parse_price, read_frame, reject_reason, stock,
+    time_in_force, write_frame, Inbound, OuchError, Outbound, Side, Token, MARKET_PRICE,
+};
@@ -0,0 +1,641 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct OuchError(pub String);
+
+impl fmt::Display for OuchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.0);
+    }
+}
+
+impl Error for OuchError {}
+
+impl From<io::Error> for OuchError {
+    fn from(e: io::Error) -> Self {
+        return OuchError(e.to_string());
+    }
+}
+
+/// The price of a market order: it trades at whatever prices are on the
+/// book, and never rests.
+pub const MARKET_PRICE: u32 = 0x7FFF_FFFF;
+
+/// Time in force values.
+pub mod time_in_force {
+    /// Trade what's possible on arrival and cancel the rest.
+    pub const IMMEDIATE: u32 = 0;
+    /// Rest on the book until canceled.
+    pub const DAY: u32 = 99_999;
+}
+
+/// Why an order was rejected.
+pub mod reject_reason {
+    pub const INVALID_STOCK: u8 = b'S';
+    pub const INVALID_SHARES: u8 = b'Z';
+    pub const INVALID_PRICE: u8 = b'X';
+    pub const INVALID_TIME_IN_FORCE: u8 = b'Y';
+    pub const DUPLICATE_TOKEN: u8 = b'D';
+    /// A replace or cancel named a token with no open order.
+    pub const UNKNOWN_TOKEN: u8 = b'U';
+}
+
+/// Why some or all of an order was canceled.
+pub mod cancel_reason {
+    pub const USER_REQUESTED: u8 = b'U';
+    /// An immediate or market order had shares left after trading.
+    pub const IMMEDIATE_OR_CANCEL: u8 = b'I';
+}
+
+/// Whether an execution added liquidity to the book or removed it.
+pub mod liquidity {
+    pub const ADDED: u8 = b'A';
+    pub const REMOVED: u8 = b'R';
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// A client's name for one of its orders, unique for the connection.
+/// Tokens are 14 bytes, padded with spaces.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Token(pub [u8; 14]);
+
+impl Token {
+    /// Pads `token` with spaces, cutting it short if it's too long.
+    pub fn new(token: &str) -> Self {
+        let mut bytes = [b' '; 14];
+        for (byte, c) in bytes.iter_mut().zip(token.bytes()) {
+            *byte = c;
+        }
+        return Token(bytes);
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", String::from_utf8_lossy(&self.0).trim_end());
+    }
+}
+
+/// Pads a symbol with spaces to the 8 bytes of a Stock field. Nasdaq's
+/// other binary protocols, ITCH among them, lay out Stock the same way.
+pub fn stock(symbol: &str) -> [u8; 8] {
+    let mut stock = [b' '; 8];
+    for (byte, c) in stock.iter_mut().zip(symbol.bytes()) {
+        *byte = c;
+    }
+    return stock;
+}
+
+/// Parses a decimal price such as "100.25" into units of 1/10000.
+pub fn parse_price(price: &str) -> Option<u32> {
+    let (whole, fraction) = match price.find('.') {
+        Some(i) => (&price[..i], &price[i + 1..]),
+        None => (price, ""),
+    };
+    if whole.is_empty() || fraction.len() > 4 || !fraction.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let fraction = format!("{:0<4}", fraction).parse::<u32>().ok()?;
+    return whole
+        .parse::<u32>()
+        .ok()?
+        .checked_mul(10_000)?
+        .checked_add(fraction);
+}
+
+/// Formats a price in units of 1/10000, without trailing zeros.
+pub fn format_price(price: u32) -> String {
+    let fraction = format!("{:04}", price % 10_000);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        return format!("{}", price / 10_000);
+    }
+    return format!("{}.{}", price / 10_000, fraction);
+}
+
+/// A message from a client.
+///
+/// Prices are in units of 1/10000 and quantities in whole shares.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Inbound {
+    /// `O`: a new order, limit or at `MARKET_PRICE`.
+    EnterOrder {
+        token: Token,
+        side: Side,
+        shares: u32,
+        stock: [u8; 8],
+        price: u32,
+        time_in_force: u32,
+    },
+    /// `U`: moves an open order to a new price and size under a new token.
+    /// `shares` is the new number of open shares. Keeping the price and
+    /// lowering the shares keeps the order's place in the queue.
+    ReplaceOrder {
+        existing_token: Token,
+        replacement_token: Token,
+        shares: u32,
+        price: u32,
+    },
+    /// `X`: cuts an open order down to `shares`, or cancels it with zero.
+    /// Asking for more shares than are open does nothing.
+    CancelOrder { token: Token, shares: u32 },
+}
+
+/// A message to a client. Timestamps are in nanoseconds since midnight.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Outbound {
+    /// `A`: an order was accepted. Executions for any shares that traded on
+    /// arrival follow it.
+    Accepted {
+        timestamp: u64,
+        token: Token,
+        side: Side,
+        shares: u32,
+        stock: [u8; 8],
+        price: u32,
+        time_in_force: u32,
+        order_reference: u64,
+    },
+    /// `U`: an order was replaced, and is now known by `token`.
+    Replaced {
+        timestamp: u64,
+        token: Token,
+        side: Side,
+        shares: u32,
+        stock: [u8; 8],
+        price: u32,
+        time_in_force: u32,
+        order_reference: u64,
+        previous_token: Token,
+    },
+    /// `E`: part or all of an order traded.
+    Executed {
+        timestamp: u64,
+        token: Token,
+        executed_shares: u32,
+        execution_price: u32,
+        /// See `liquidity`.
+        liquidity_flag: u8,
+        match_number: u64,
+    },
+    /// `C`: `decrement_shares` of an order were canceled. See
+    /// `cancel_reason`.
+    Canceled {
+        timestamp: u64,
+        token: Token,
+        decrement_shares: u32,
+        reason: u8,
+    },
+    /// `J`: a message was rejected. See `reject_reason`. For a rejected
+    /// replace, `token` is the replacement token.
+    Rejected {
+        timestamp: u64,
+        token: Token,
+        reason: u8,
+    },
+}
+
+fn side_code(side: Side) -> u8 {
+    match side {
+        Side::Buy => return b'B',
+        Side::Sell => return b'S',
+    }
+}
+
+impl Inbound {
+    pub fn message_type(&self) -> u8 {
+        match self {
+            Inbound::EnterOrder { .. } => return b'O',
+            Inbound::ReplaceOrder { .. } => return b'U',
+            Inbound::CancelOrder { .. } => return b'X',
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.message_type()];
+        match *self {
+            Inbound::EnterOrder {
+                token,
+                side,
+                shares,
+                stock,
+                price,
+                time_in_force,
+            } => {
+                bytes.extend(&token.0);
+                bytes.push(side_code(side));
+                bytes.extend(&shares.to_be_bytes());
+                bytes.extend(&stock);
+                bytes.extend(&price.to_be_bytes());
+                bytes.extend(&time_in_force.to_be_bytes());
+            }
+            Inbound::ReplaceOrder {
+                existing_token,
+                replacement_token,
+                shares,
+                price,
+            } => {
+                bytes.extend(&existing_token.0);
+                bytes.extend(&replacement_token.0);
+                bytes.extend(&shares.to_be_bytes());
+                bytes.extend(&price.to_be_bytes());
+            }
+            Inbound::CancelOrder { token, shares } => {
+                bytes.extend(&token.0);
+                bytes.extend(&shares.to_be_bytes());
+            }
+        }
+        return bytes;
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Inbound, OuchError> {
+        let mut reader = Reader { bytes, position: 0 };
+        let message = match reader.u8()? {
+            b'O' => Inbound::EnterOrder {
+                token: reader.token()?,
+                side: reader.side()?,
+                shares: reader.u32()?,
+                stock: reader.stock()?,
+                price: reader.u32()?,
+                time_in_force: reader.u32()?,
+            },
+            b'U' => Inbound::ReplaceOrder {
+                existing_token: reader.token()?,
+                replacement_token: reader.token()?,
+                shares: reader.u32()?,
+                price: reader.u32()?,
+            },
+            b'X' => Inbound::CancelOrder {
+                token: reader.token()?,
+                shares: reader.u32()?,
+            },
+            t => return Err(OuchError(format!("unknown message type {:?}", t as char))),
+        };
+        reader.finish()?;
+        return Ok(message);
+    }
+}
+
+impl Outbound {
+    pub fn message_type(&self) -> u8 {
+        match self {
+            Outbound::Accepted { .. } => return b'A',
+            Outbound::Replaced { .. } => return b'U',
+            Outbound::Executed { .. } => return b'E',
+            Outbound::Canceled { .. } => return b'C',
+            Outbound::Rejected { .. } => return b'J',
+        }
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        match *self {
+            Outbound::Accepted { timestamp, .. }
+            | Outbound::Replaced { timestamp, .. }
+            | Outbound::Executed { timestamp, .. }
+            | Outbound::Canceled { timestamp, .. }
+            | Outbound::Rejected { timestamp, .. } => return timestamp,
+        }
+    }
+
+    /// The token of the order the message is about.
+    pub fn token(&self) -> Token {
+        match *self {
+            Outbound::Accepted { token, .. }
+            | Outbound::Replaced { token, .. }
+            | Outbound::Executed { token, .. }
+            | Outbound::Canceled { token, .. }
+            | Outbound::Rejected { token, .. } => return token,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.message_type()];
+        bytes.extend(&self.timestamp().to_be_bytes());
+        bytes.extend(&self.token().0);
+        match *self {
+            Outbound::Accepted {
+                side,
+                shares,
+                stock,
+                price,
+                time_in_force,
+                order_reference,
+                ..
+            } => {
+                bytes.push(side_code(side));
+                bytes.extend(&shares.to_be_bytes());
+                bytes.extend(&stock);
+                bytes.extend(&price.to_be_bytes());
+                bytes.extend(&time_in_force.to_be_bytes());
+                bytes.extend(&order_reference.to_be_bytes());
+            }
+            Outbound::Replaced {
+                side,
+                shares,
+                stock,
+                price,
+                time_in_force,
+                order_reference,
+                previous_token,
+                ..
+            } => {
+                bytes.push(side_code(side));
+                bytes.extend(&shares.to_be_bytes());
+                bytes.extend(&stock);
+                bytes.extend(&price.to_be_bytes());
+                bytes.extend(&time_in_force.to_be_bytes());
+                bytes.extend(&order_reference.to_be_bytes());
+                bytes.extend(&previous_token.0);
+            }
+            Outbound::Executed {
+                executed_shares,
+                execution_price,
+                liquidity_flag,
+                match_number,
+                ..
+            } => {
+                bytes.extend(&executed_shares.to_be_bytes());
+                bytes.extend(&execution_price.to_be_bytes());
+                bytes.push(liquidity_flag);
+                bytes.extend(&match_number.to_be_bytes());
+            }
+            Outbound::Canceled {
+                decrement_shares,
+                reason,
+                ..
+            } => {
+                bytes.extend(&decrement_shares.to_be_bytes());
+                bytes.push(reason);
+            }
+            Outbound::Rejected { reason, .. } => bytes.push(reason),
+        }
+        return bytes;
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Outbound, OuchError> {
+        let mut reader = Reader { bytes, position: 0 };
+        let message_type = reader.u8()?;
+        let timestamp = reader.u64()?;
+        let token = reader.token()?;
+
+        let message = match message_type {
+            b'A' => Outbound::Accepted {
+                timestamp,
+                token,
+                side: reader.side()?,
+                shares: reader.u32()?,
+                stock: reader.stock()?,
+                price: reader.u32()?,
+                time_in_force: reader.u32()?,
+                order_reference: reader.u64()?,
+            },
+            b'U' => Outbound::Replaced {
+                timestamp,
+                token,
+                side: reader.side()?,
+                shares: reader.u32()?,
+                stock: reader.stock()?,
+                price: reader.u32()?,
+                time_in_force: reader.u32()?,
+                order_reference: reader.u64()?,
+                previous_token: reader.token()?,
+            },
+            b'E' => Outbound::Executed {
+                timestamp,
+                token,
+                executed_shares: reader.u32()?,
+                execution_price: reader.u32()?,
+                liquidity_flag: reader.u8()?,
+                match_number: reader.u64()?,
+            },
+            b'C' => Outbound::Canceled {
+                timestamp,
+                token,
+                decrement_shares: reader.u32()?,
+                reason: reader.u8()?,
+            },
+            b'J' => Outbound::Rejected {
+                timestamp,
+                token,
+                reason: reader.u8()?,
+            },
+            t => return Err(OuchError(format!("unknown message type {:?}", t as char))),
+        };
+        reader.finish()?;
+        return Ok(message);
+    }
+}
+
+/// Writes a message with a two-byte length prefix, as in SoupBinTCP.
+pub fn write_frame(writer: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    let mut frame = (message.len() as u16).to_be_bytes().to_vec();
+    frame.extend(message);
+    return writer.write_all(&frame);
+}
+
+/// Reads a length-prefixed message. Returns `None` once the other end has
+/// closed the connection.
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 2];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let mut bytes = vec![0; u16::from_be_bytes(length) as usize];
+    reader.read_exact(&mut bytes)?;
+    return Ok(Some(bytes));
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], OuchError> {
+        let end = self.position + n;
+        if end > self.bytes.len() {
+            return Err(OuchError(String::from("message is too short")));
+        }
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        return Ok(bytes);
+    }
+
+    fn finish(&self) -> Result<(), OuchError> {
+        if self.position != self.bytes.len() {
+            return Err(OuchError(format!(
+                "{} bytes left over after a {:?} message",
+                self.bytes.len() - self.position,
+                self.bytes[0] as char
+            )));
+        }
+        return Ok(());
+    }
+
+    fn u8(&mut self) -> Result<u8, OuchError> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u32(&mut self) -> Result<u32, OuchError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        return Ok(u32::from_be_bytes(bytes));
+    }
+
+    fn u64(&mut self) -> Result<u64, OuchError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        return Ok(u64::from_be_bytes(bytes));
+    }
+
+    fn side(&mut self) -> Result<Side, OuchError> {
+        match self.u8()? {
+            b'B' => return Ok(Side::Buy),
+            b'S' => return Ok(Side::Sell),
+            c => return Err(OuchError(format!("invalid side {:?}", c as char))),
+        }
+    }
+
+    fn token(&mut self) -> Result<Token, OuchError> {
+        let mut token = [0; 14];
+        token.copy_from_slice(self.take(14)?);
+        return Ok(Token(token));
+    }
+
+    fn stock(&mut self) -> Result<[u8; 8], OuchError> {
+        let mut stock = [0; 8];
+        stock.copy_from_slice(self.take(8)?);
+        return Ok(stock);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let token = Token::new("abc1");
+        let inbound = [
+            Inbound::EnterOrder {
+                token,
+                side: Side::Buy,
+                shares: 100,
+                stock: stock("ABC"),
+                price: 1_002_500,
+                time_in_force: time_in_force::DAY,
+            },
+            Inbound::ReplaceOrder {
+                existing_token: token,
+                replacement_token: Token::new("abc2"),
+                shares: 50,
+                price: 1_000_000,
+            },
+            Inbound::CancelOrder { token, shares: 0 },
+        ];
+        let lengths: Vec<usize> = inbound.iter().map(|m| m.encode().len()).collect();
+        assert_eq!(lengths, [36, 37, 19]);
+        for message in inbound.iter() {
+            assert_eq!(Inbound::decode(&message.encode()), Ok(*message));
+        }
+
+        let outbound = [
+            Outbound::Accepted {
+                timestamp: 34_200_000_000_000,
+                token,
+                side: Side::Sell,
+                shares: 100,
+                stock: stock("ABC"),
+                price: MARKET_PRICE,
+                time_in_force: time_in_force::IMMEDIATE,
+                order_reference: 7,
+            },
+            Outbound::Replaced {
+                timestamp: 1,
+                token: Token::new("abc2"),
+                side: Side::Buy,
+                shares: 50,
+                stock: stock("ABC"),
+                price: 1_000_000,
+                time_in_force: time_in_force::DAY,
+                order_reference: 8,
+                previous_token: token,
+            },
+            Outbound::Executed {
+                timestamp: 2,
+                token,
+                executed_shares: 10,
+                execution_price: 1_000_000,
+                liquidity_flag: liquidity::ADDED,
+                match_number: 3,
+            },
+            Outbound::Canceled {
+                timestamp: 3,
+                token,
+                decrement_shares: 40,
+                reason: cancel_reason::USER_REQUESTED,
+            },
+            Outbound::Rejected {
+                timestamp: 4,
+                token,
+                reason: reject_reason::UNKNOWN_TOKEN,
+            },
+        ];
+        let lengths: Vec<usize> = outbound.iter().map(|m| m.encode().len()).collect();
+        assert_eq!(lengths, [52, 66, 40, 28, 24]);
+        for message in outbound.iter() {
+            assert_eq!(Outbound::decode(&message.encode()), Ok(*message));
+        }
+
+        assert!(Inbound::decode(b"Q").is_err());
+        assert!(Inbound::decode(&inbound[2].encode()[..18]).is_err());
+        let mut long = outbound[4].encode();
+        long.push(0);
+        assert!(Outbound::decode(&long).is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut stream = Vec::new();
+        write_frame(&mut stream, b"hello").unwrap();
+        write_frame(&mut stream, b"").unwrap();
+        assert_eq!(&stream[..3], [0, 5, b'h']);
+
+        let mut reader = stream.as_slice();
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(b"hello".to_vec()));
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(Vec::new()));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+        assert!(read_frame(&mut &[0, 5, b'h'][..]).is_err());
+    }
+
+    #[test]
+    fn test_prices_and_tokens() {
+        assert_eq!(parse_price("100.25"), Some(1_002_500));
+        assert_eq!(parse_price("7"), Some(70_000));
+        assert_eq!(parse_price("0.0001"), Some(1));
+        assert_eq!(parse_price("1.00001"), None);
+        assert_eq!(parse_price("-1"), None);
+        assert_eq!(parse_price(".5"), None);
+        assert_eq!(parse_price("1e3"), None);
+        assert_eq!(format_price(1_002_500), "100.25");
+        assert_eq!(format_price(70_000), "7");
+        assert_eq!(format_price(1), "0.0001");
+
+        assert_eq!(Token::new("abc").to_string(), "abc");
+        assert_eq!(
+            Token::new("a-very-long-token").to_string(),
+            "a-very-long-to"
+        );
+        assert_eq!(&stock("ABC"), b"ABC     ");
+    }
+}
@@ -0,0 +1,181 @@
+use std::ops::{Index, IndexMut};
+
+/// A slab of values addressed by stable `usize` keys.
+///
+/// Keys stay valid until the value is removed, after which the slot is put on
+/// a free list and reused by a later insert. This lets the book link levels
+/// and orders together by index instead of through shared pointers.
+#[derive(Clone, Debug)]
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    next_free: Option<usize>,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+enum Slot<T> {
+    Occupied(T),
+    Vacant(Option<usize>),
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Self {
+        return Arena::with_capacity(0);
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        return Arena {
+            slots: Vec::with_capacity(capacity),
+            next_free: None,
+            len: 0,
+        };
+    }
+
+    pub fn insert(&mut self, value: T) -> usize {
+        self.len += 1;
+
+        match self.next_free {
+            Some(key) => {
+                if let Slot::Vacant(next_free) = self.slots[key] {
+                    self.next_free = next_free;
+                }
+                self.slots[key] = Slot::Occupied(value);
+                return key;
+            }
+            None => {
+                self.slots.push(Slot::Occupied(value));
+                return self.slots.len() - 1;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: usize) -> Option<T> {
+        match self.slots.get(key) {
+            Some(Slot::Occupied(_)) => {
+                let slot = std::mem::replace(&mut self.slots[key], Slot::Vacant(self.next_free));
+                self.next_free = Some(key);
+                self.len -= 1;
+
+                match slot {
+                    Slot::Occupied(value) => return Some(value),
+                    Slot::Vacant(_) => unreachable!(),
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    pub fn get(&self, key: usize) -> Option<&T> {
+        match self.slots.get(key) {
+            Some(Slot::Occupied(value)) => return Some(value),
+            _ => return None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        match self.slots.get_mut(key) {
+            Some(Slot::Occupied(value)) => return Some(value),
+            _ => return None,
+        }
+    }
+
+    pub fn contains(&self, key: usize) -> bool {
+        return self.get(key).is_some();
+    }
+
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    /// Iterates over occupied slots in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        return self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(key, slot)| match slot {
+                Slot::Occupied(value) => Some((key, value)),
+                Slot::Vacant(_) => None,
+            });
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        return Arena::new();
+    }
+}
+
+impl<T> Index<usize> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, key: usize) -> &T {
+        return self.get(key).expect("no value in arena slot");
+    }
+}
+
+impl<T> IndexMut<usize> for Arena<T> {
+    fn index_mut(&mut self, key: usize) -> &mut T {
+        return self.get_mut(key).expect("no value in arena slot");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut arena = Arena::new();
+
+        let a = arena.insert("a");
+        let b = arena.insert("b");
+
+        assert_eq!(arena.get(a), Some(&"a"));
+        assert_eq!(arena[b], "b");
+        assert_eq!(arena.len(), 2);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut arena = Arena::new();
+
+        let a = arena.insert("a");
+
+        assert_eq!(arena.remove(a), Some("a"));
+        assert_eq!(arena.remove(a), None);
+        assert_eq!(arena.get(a), None);
+        assert!(arena.is_empty());
+    }
+
+    #[test]
+    fn test_reuses_freed_slots() {
+        let mut arena = Arena::new();
+
+        let a = arena.insert("a");
+        let b = arena.insert("b");
+        arena.remove(a);
+        arena.remove(b);
+
+        // Most recently freed slot is reused first
+        assert_eq!(arena.insert("c"), b);
+        assert_eq!(arena.insert("d"), a);
+        assert_eq!(arena.insert("e"), 2);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut arena = Arena::new();
+
+        let a = arena.insert(1);
+        let b = arena.insert(2);
+        let c = arena.insert(3);
+        arena.remove(b);
+
+        assert_eq!(arena.iter().collect::<Vec<_>>(), vec![(a, &1), (c, &3)]);
+    }
+}
@@ -0,0 +1,816 @@
+use rust_decimal::prelude::*;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::clock::{Clock, ManualClock};
+use crate::command::{Command, CommandResult};
+use crate::id_generator::SequentialIdGenerator;
+use crate::order::Side;
+use crate::schedule::Schedule;
+use crate::trade::Trade;
+use crate::{OrderBook, OrderResult};
+
+/// Latencies, in nanoseconds, and what strategies get to see.
+#[derive(Copy, Clone, Debug)]
+pub struct BacktestConfig {
+    /// From a strategy sending an order request to the book acting on it.
+    /// Fills and cancel confirmations take as long to get back.
+    pub order_latency: u64,
+    /// From the book changing to strategies hearing about it.
+    pub market_data_latency: u64,
+    /// How many levels per side book updates carry.
+    pub depth: usize,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        return BacktestConfig {
+            order_latency: 0,
+            market_data_latency: 0,
+            depth: 10,
+        };
+    }
+}
+
+/// The top of the book as a strategy sees it, best price first on each side.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct BookUpdate {
+    /// When the book was in this state.
+    pub timestamp: u64,
+    pub bids: Vec<(Decimal, Decimal)>,
+    pub asks: Vec<(Decimal, Decimal)>,
+}
+
+/// An order request from a strategy. Orders are referred to by the client
+/// ids `Context` hands out, since the book's id isn't known until the order
+/// gets there.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum OrderRequest {
+    Limit {
+        side: Side,
+        quantity: Decimal,
+        price: Decimal,
+    },
+    Market {
+        side: Side,
+        quantity: Decimal,
+    },
+    Cancel {
+        client_id: u64,
+    },
+    Amend {
+        client_id: u64,
+        quantity: Decimal,
+        price: Decimal,
+    },
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+/// Part or all of a strategy's order being filled.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct StrategyFill {
+    pub client_id: u64,
+    /// When the fill happened on the book, not when the strategy heard.
+    pub timestamp: u64,
+    pub side: Side,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub liquidity: Liquidity,
+}
+
+/// A strategy's fills and position, as of the book rather than as of what
+/// the strategy has heard.
+#[derive(Clone, Debug, Default)]
+pub struct StrategyStats {
+    pub fills: Vec<StrategyFill>,
+    /// Bought minus sold.
+    pub position: Decimal,
+    /// Received for sales minus paid for purchases.
+    pub cash: Decimal,
+    /// Total quantity traded on either side.
+    pub volume: Decimal,
+}
+
+impl StrategyStats {
+    /// Profit and loss with the position valued at `mark`.
+    pub fn pnl(&self, mark: Decimal) -> Decimal {
+        return self.cash + self.position * mark;
+    }
+
+    fn record(&mut self, fill: StrategyFill) {
+        match fill.side {
+            Side::Bid => {
+                self.position += fill.quantity;
+                self.cash -= fill.price * fill.quantity;
+            }
+            Side::Ask => {
+                self.position -= fill.quantity;
+                self.cash += fill.price * fill.quantity;
+            }
+        }
+        self.volume += fill.quantity;
+        self.fills.push(fill);
+    }
+}
+
+/// A trading strategy under test. Every callback happens at the simulated
+/// time the strategy hears about something, and can send order requests
+/// through the `Context`.
+pub trait Strategy {
+    fn on_book(&mut self, _update: &BookUpdate, _context: &mut Context) {}
+
+    fn on_trade(&mut self, _trade: &Trade, _context: &mut Context) {}
+
+    fn on_fill(&mut self, _fill: &StrategyFill, _context: &mut Context) {}
+
+    /// An order was canceled, with `quantity` left unfilled.
+    fn on_canceled(&mut self, _client_id: u64, _quantity: Decimal, _context: &mut Context) {}
+}
+
+/// A strategy's view of the backtest during a callback.
+pub struct Context<'a> {
+    now: u64,
+    order_book: &'a OrderBook,
+    orders: &'a HashMap<u64, Uuid>,
+    stats: &'a StrategyStats,
+    next_client_id: &'a mut u64,
+    requests: Vec<(u64, OrderRequest)>,
+}
+
+impl<'a> Context<'a> {
+    pub fn now(&self) -> u64 {
+        return self.now;
+    }
+
+    pub fn stats(&self) -> &StrategyStats {
+        return self.stats;
+    }
+
+    /// Sends a limit order, returning its client id.
+    pub fn submit_limit_order(&mut self, side: Side, quantity: Decimal, price: Decimal) -> u64 {
+        return self.send(OrderRequest::Limit {
+            side,
+            quantity,
+            price,
+        });
+    }
+
+    /// Sends a market order, returning its client id.
+    pub fn submit_market_order(&mut self, side: Side, quantity: Decimal) -> u64 {
+        return self.send(OrderRequest::Market { side, quantity });
+    }
+
+    pub fn cancel(&mut self, client_id: u64) {
+        self.requests
+            .push((client_id, OrderRequest::Cancel { client_id }));
+    }
+
+    /// Changes a resting order. Amending to a quantity of zero cancels it,
+    /// and a negative quantity is ignored.
+    pub fn amend(&mut self, client_id: u64, quantity: Decimal, price: Decimal) {
+        self.requests.push((
+            client_id,
+            OrderRequest::Amend {
+                client_id,
+                quantity,
+                price,
+            },
+        ));
+    }
+
+    /// Whether an order is resting on the book right now. Requests still on
+    /// their way aren't.
+    pub fn is_resting(&self, client_id: u64) -> bool {
+        return self.orders.contains_key(&client_id);
+    }
+
+    /// How much quantity is ahead of a resting order at its price right now.
+    pub fn queue_ahead(&self, client_id: u64) -> Option<Decimal> {
+        return queue_ahead(self.order_book, *self.orders.get(&client_id)?);
+    }
+
+    fn send(&mut self, request: OrderRequest) -> u64 {
+        let client_id = *self.next_client_id;
+        *self.next_client_id += 1;
+        self.requests.push((client_id, request));
+        return client_id;
+    }
+}
+
+fn queue_ahead(order_book: &OrderBook, id: Uuid) -> Option<Decimal> {
+    let order = order_book.get(id)?;
+    let mut ahead = Decimal::zero();
+    for other in order_book.orders_at(order.side, order.price) {
+        if other.id == id {
+            break;
+        }
+        ahead += other.quantity;
+    }
+    return Some(ahead);
+}
+
+/// Something a strategy hears about.
+#[derive(Clone, Debug)]
+enum Notification {
+    Book(BookUpdate),
+    Trade(Trade),
+    Fill(StrategyFill),
+    Canceled(u64, Decimal),
+}
+
+#[derive(Clone, Debug)]
+enum Event {
+    Request(usize, u64, OrderRequest),
+    Notify(usize, Notification),
+}
+
+struct Slot {
+    strategy: Box<dyn Strategy>,
+    orders: HashMap<u64, Uuid>,
+    stats: StrategyStats,
+    next_client_id: u64,
+}
+
+/// Replays historical order flow into a book while strategies trade on the
+/// same book, on a simulated clock.
+///
+/// Historical commands are `Command`s as recorded against a book that
+/// assigned ids with `SequentialIdGenerator::new()`, such as the output of
+/// `OrderFlow`. They're also run against a book of their own, which gives
+/// each historical order the id it was recorded with, so cancels and amends
+/// still find the right order when strategy orders take ids of their own or
+/// change what gets filled. A historical order that strategies took out of
+/// the book is simply gone by the time it's canceled.
+pub struct Backtest {
+    config: BacktestConfig,
+    clock: ManualClock,
+    order_book: OrderBook,
+    history: OrderBook,
+    history_ids: HashMap<Uuid, Uuid>,
+    owners: HashMap<Uuid, (usize, u64)>,
+    strategies: Vec<Slot>,
+    events: Schedule<Event>,
+    last_trade: Option<Decimal>,
+}
+
+impl Backtest {
+    pub fn new(config: BacktestConfig) -> Self {
+        let clock = ManualClock::new(0);
+
+        return Backtest {
+            config,
+            clock: clock.clone(),
+            order_book: OrderBook::with_clock(clock.clone(), SequentialIdGenerator::new()),
+            history: OrderBook::with_clock(clock, SequentialIdGenerator::new()),
+            history_ids: HashMap::new(),
+            owners: HashMap::new(),
+            strategies: Vec::new(),
+            events: Schedule::new(),
+            last_trade: None,
+        };
+    }
+
+    /// Adds a strategy, returning its index.
+    pub fn add_strategy(&mut self, strategy: impl Strategy + 'static) -> usize {
+        self.strategies.push(Slot {
+            strategy: Box::new(strategy),
+            orders: HashMap::new(),
+            stats: StrategyStats::default(),
+            next_client_id: 1,
+        });
+        return self.strategies.len() - 1;
+    }
+
+    pub fn order_book(&self) -> &OrderBook {
+        return &self.order_book;
+    }
+
+    pub fn now(&self) -> u64 {
+        return self.clock.now();
+    }
+
+    pub fn stats(&self, strategy: usize) -> &StrategyStats {
+        return &self.strategies[strategy].stats;
+    }
+
+    /// The price positions are marked at: the mid if both sides have orders,
+    /// otherwise the last trade.
+    pub fn mark(&self) -> Option<Decimal> {
+        if let (Some(bid), Some(ask)) = (self.order_book.best_bid(), self.order_book.best_ask()) {
+            return Some((bid + ask) / Decimal::from(2));
+        }
+        return self.last_trade;
+    }
+
+    /// A strategy's PnL at the current mark.
+    pub fn pnl(&self, strategy: usize) -> Decimal {
+        let stats = self.stats(strategy);
+        return stats.pnl(self.mark().unwrap_or_default());
+    }
+
+    /// How much quantity is ahead of a strategy's resting order at its price.
+    pub fn queue_ahead(&self, strategy: usize, client_id: u64) -> Option<Decimal> {
+        let id = *self.strategies[strategy].orders.get(&client_id)?;
+        return queue_ahead(&self.order_book, id);
+    }
+
+    /// Replays `history`, timestamped commands in time order, together with
+    /// everything strategies do in response. Stops at the last historical
+    /// command: requests and notifications due after it are dropped.
+    pub fn run(&mut self, history: impl IntoIterator<Item = (u64, Command)>) {
+        let mut end = self.now();
+
+        for (time, command) in history {
+            self.run_until(time, false);
+            self.clock.set(time.max(self.now()));
+            self.apply_history(command);
+            end = time;
+        }
+
+        self.run_until(end, true);
+        self.events.clear();
+    }
+
+    /// Handles scheduled events due before `time`, or at it too if
+    /// `inclusive`.
+    fn run_until(&mut self, time: u64, inclusive: bool) {
+        while let Some(next) = self.events.next_time() {
+            if next > time || (next == time && !inclusive) {
+                break;
+            }
+
+            let (next, event) = self.events.pop().unwrap();
+            self.clock.set(next);
+            match event {
+                Event::Request(strategy, client_id, request) => {
+                    self.apply_request(strategy, client_id, request)
+                }
+                Event::Notify(strategy, notification) => self.notify(strategy, notification),
+            }
+        }
+    }
+
+    fn schedule(&mut self, delay: u64, event: Event) {
+        self.events.push(self.now() + delay, event);
+    }
+
+    fn apply_history(&mut self, command: Command) {
+        let recorded = self.history.execute(command);
+        match command {
+            Command::Limit {
+                side,
+                quantity,
+                price,
+            } => {
+                let result = self.order_book.submit_limit_order(side, quantity, price);
+                if let CommandResult::Matched(recorded) = recorded {
+                    if let (Some(recorded), Some(order)) = (recorded.partial, result.partial) {
+                        self.history_ids.insert(recorded.id, order.id);
+                    }
+                }
+                self.after_match(side, None, &result);
+            }
+            Command::Market { side, quantity } => {
+                let result = self.order_book.submit_market_order(side, quantity);
+                self.after_match(side, None, &result);
+            }
+            Command::Cancel { id } => {
+                if let Some(id) = self.history_ids.remove(&id) {
+                    self.order_book.remove(id);
+                }
+                self.publish_book();
+            }
+            Command::Amend {
+                id,
+                quantity,
+                price,
+            } => {
+                let resting = self
+                    .history_ids
+                    .get(&id)
+                    .and_then(|id| self.order_book.get(*id));
+                let (book_id, side) = match resting {
+                    Some(order) => (order.id, order.side),
+                    None => return self.publish_book(),
+                };
+
+                let result = self.order_book.amend(book_id, quantity, price).unwrap();
+                if result.partial.is_none() {
+                    self.history_ids.remove(&id);
+                }
+                self.after_match(side, None, &result);
+            }
+        }
+    }
+
+    fn apply_request(&mut self, strategy: usize, client_id: u64, request: OrderRequest) {
+        match request {
+            OrderRequest::Limit {
+                side,
+                quantity,
+                price,
+            } => {
+                let result = self.order_book.submit_limit_order(side, quantity, price);
+                if let Some(order) = result.partial {
+                    self.rest(strategy, client_id, order.id);
+                }
+                self.after_match(side, Some((strategy, client_id)), &result);
+            }
+            OrderRequest::Market { side, quantity } => {
+                let result = self.order_book.submit_market_order(side, quantity);
+                self.after_match(side, Some((strategy, client_id)), &result);
+
+                // Whatever couldn't be filled is canceled.
+                let left = quantity - result.quantity_filled;
+                if left > Decimal::zero() {
+                    let canceled = Notification::Canceled(client_id, left);
+                    self.schedule(self.config.order_latency, Event::Notify(strategy, canceled));
+                }
+            }
+            OrderRequest::Cancel { client_id } => {
+                let id = match self.strategies[strategy].orders.remove(&client_id) {
+                    Some(id) => id,
+                    None => return,
+                };
+                self.owners.remove(&id);
+
+                if let Some(order) = self.order_book.remove(id) {
+                    let canceled = Notification::Canceled(client_id, order.quantity);
+                    self.schedule(self.config.order_latency, Event::Notify(strategy, canceled));
+                }
+                self.publish_book();
+            }
+            OrderRequest::Amend {
+                client_id,
+                quantity,
+                price,
+            } => {
+                if quantity < Decimal::zero() {
+                    return;
+                }
+                let id = match self.strategies[strategy].orders.get(&client_id) {
+                    Some(id) => *id,
+                    None => return,
+                };
+                let order = match self.order_book.get(id) {
+                    Some(order) => *order,
+                    None => return,
+                };
+
+                let result = match self.order_book.amend(id, quantity, price) {
+                    Some(result) => result,
+                    None => return,
+                };
+                if result.partial.is_none() {
+                    self.strategies[strategy].orders.remove(&client_id);
+                    self.owners.remove(&id);
+                }
+                if quantity == Decimal::zero() {
+                    let canceled = Notification::Canceled(client_id, order.quantity);
+                    self.schedule(self.config.order_latency, Event::Notify(strategy, canceled));
+                }
+                self.after_match(order.side, Some((strategy, client_id)), &result);
+            }
+        }
+    }
+
+    fn rest(&mut self, strategy: usize, client_id: u64, id: Uuid) {
+        self.strategies[strategy].orders.insert(client_id, id);
+        self.owners.insert(id, (strategy, client_id));
+    }
+
+    /// Records and publishes the trades in `result`, for an incoming order
+    /// on `side` that belongs to `taker` if it's a strategy's.
+    fn after_match(&mut self, side: Side, taker: Option<(usize, u64)>, result: &OrderResult) {
+        let now = self.now();
+
+        for trade in Trade::from_result(side, result) {
+            self.last_trade = Some(trade.price);
+
+            let maker_side = match side {
+                Side::Bid => Side::Ask,
+                Side::Ask => Side::Bid,
+            };
+            let maker = match self.order_book.get(trade.maker_id) {
+                Some(_) => self.owners.get(&trade.maker_id).copied(),
+                // Filled completely, so it no longer belongs to anyone.
+                None => self.owners.remove(&trade.maker_id),
+            };
+            if let Some((strategy, client_id)) = maker {
+                if self.order_book.get(trade.maker_id).is_none() {
+                    self.strategies[strategy].orders.remove(&client_id);
+                }
+                self.fill(
+                    strategy,
+                    client_id,
+                    maker_side,
+                    &trade,
+                    Liquidity::Maker,
+                    now,
+                );
+            }
+            if let Some((strategy, client_id)) = taker {
+                self.fill(strategy, client_id, side, &trade, Liquidity::Taker, now);
+            }
+
+            for strategy in 0..self.strategies.len() {
+                self.schedule(
+                    self.config.market_data_latency,
+                    Event::Notify(strategy, Notification::Trade(trade)),
+                );
+            }
+        }
+
+        self.publish_book();
+    }
+
+    fn fill(
+        &mut self,
+        strategy: usize,
+        client_id: u64,
+        side: Side,
+        trade: &Trade,
+        liquidity: Liquidity,
+        timestamp: u64,
+    ) {
+        let fill = StrategyFill {
+            client_id,
+            timestamp,
+            side,
+            price: trade.price,
+            quantity: trade.quantity,
+            liquidity,
+        };
+
+        self.strategies[strategy].stats.record(fill);
+        self.schedule(
+            self.config.order_latency,
+            Event::Notify(strategy, Notification::Fill(fill)),
+        );
+    }
+
+    fn publish_book(&mut self) {
+        if self.strategies.is_empty() {
+            return;
+        }
+
+        let depth = self.config.depth;
+        let levels = |side| {
+            self.order_book
+                .levels(side)
+                .take(depth)
+                .map(|l| (l.price, l.volume))
+                .collect()
+        };
+        let update = BookUpdate {
+            timestamp: self.now(),
+            bids: levels(Side::Bid),
+            asks: levels(Side::Ask),
+        };
+
+        for strategy in 0..self.strategies.len() {
+            self.schedule(
+                self.config.market_data_latency,
+                Event::Notify(strategy, Notification::Book(update.clone())),
+            );
+        }
+    }
+
+    fn notify(&mut self, strategy: usize, notification: Notification) {
+        let now = self.now();
+        let slot = &mut self.strategies[strategy];
+        let mut context = Context {
+            now,
+            order_book: &self.order_book,
+            orders: &slot.orders,
+            stats: &slot.stats,
+            next_client_id: &mut slot.next_client_id,
+            requests: Vec::new(),
+        };
+
+        match &notification {
+            Notification::Book(update) => slot.strategy.on_book(update, &mut context),
+            Notification::Trade(trade) => slot.strategy.on_trade(trade, &mut context),
+            Notification::Fill(fill) => slot.strategy.on_fill(fill, &mut context),
+            Notification::Canceled(client_id, quantity) => {
+                slot.strategy
+                    .on_canceled(*client_id, *quantity, &mut context)
+            }
+        }
+
+        let requests = context.requests;
+        for (client_id, request) in requests {
+            self.schedule(
+                self.config.order_latency,
+                Event::Request(strategy, client_id, request),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id_generator::sequential_id;
+    use crate::order_flow::{FlowConfig, OrderFlow};
+    use rust_decimal_macros::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Bids once when it first sees the book, and records what it hears.
+    #[derive(Default)]
+    struct Bidder {
+        price: Decimal,
+        sent: bool,
+        heard: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Strategy for Bidder {
+        fn on_book(&mut self, update: &BookUpdate, context: &mut Context) {
+            if !self.sent {
+                self.sent = true;
+                context.submit_limit_order(Side::Bid, dec!(5), self.price);
+            }
+            self.heard
+                .borrow_mut()
+                .push(format!("book at {}: {:?}", context.now(), update.bids));
+        }
+
+        fn on_fill(&mut self, fill: &StrategyFill, context: &mut Context) {
+            self.heard.borrow_mut().push(format!(
+                "fill at {}: {} @ {}",
+                context.now(),
+                fill.quantity,
+                fill.price
+            ));
+        }
+    }
+
+    /// Bids, then amends the bid to `amends` one book update at a time.
+    struct Amender {
+        client_id: Option<u64>,
+        amends: Vec<Decimal>,
+        canceled: Rc<RefCell<Vec<(u64, Decimal)>>>,
+    }
+
+    impl Strategy for Amender {
+        fn on_book(&mut self, _update: &BookUpdate, context: &mut Context) {
+            match self.client_id {
+                None => {
+                    self.client_id = Some(context.submit_limit_order(Side::Bid, dec!(5), dec!(90)));
+                }
+                Some(client_id) if !self.amends.is_empty() => {
+                    context.amend(client_id, self.amends.remove(0), dec!(90));
+                }
+                Some(_) => {}
+            }
+        }
+
+        fn on_canceled(&mut self, client_id: u64, quantity: Decimal, _context: &mut Context) {
+            self.canceled.borrow_mut().push((client_id, quantity));
+        }
+    }
+
+    fn limit(side: Side, quantity: Decimal, price: Decimal) -> Command {
+        return Command::Limit {
+            side,
+            quantity,
+            price,
+        };
+    }
+
+    #[test]
+    fn test_strategy_is_filled_by_history() {
+        let mut backtest = Backtest::new(BacktestConfig::default());
+        let bidder = backtest.add_strategy(Bidder {
+            price: dec!(99),
+            ..Bidder::default()
+        });
+
+        backtest.run(vec![
+            (1, limit(Side::Ask, dec!(1), dec!(101))),
+            (2, limit(Side::Ask, dec!(3), dec!(99))),
+        ]);
+
+        let stats = backtest.stats(bidder);
+        assert_eq!(stats.position, dec!(3));
+        assert_eq!(stats.cash, dec!(-297));
+        assert_eq!(stats.fills[0].liquidity, Liquidity::Maker);
+        assert_eq!(stats.fills[0].timestamp, 2);
+        assert_eq!(backtest.queue_ahead(bidder, 1), Some(dec!(0)));
+
+        // Marked at the mid of 99 and 101
+        assert_eq!(backtest.pnl(bidder), dec!(3));
+    }
+
+    #[test]
+    fn test_latency() {
+        let config = BacktestConfig {
+            order_latency: 10,
+            market_data_latency: 5,
+            depth: 1,
+        };
+        let mut backtest = Backtest::new(config);
+        let heard = Rc::new(RefCell::new(Vec::new()));
+        let bidder = backtest.add_strategy(Bidder {
+            price: dec!(99),
+            sent: false,
+            heard: heard.clone(),
+        });
+
+        backtest.run(vec![
+            (100, limit(Side::Bid, dec!(1), dec!(98))),
+            // The bidder hears about the book at 105 and its bid arrives at
+            // 115, after this sell has already rested at 99.
+            (110, limit(Side::Ask, dec!(2), dec!(99))),
+            (200, limit(Side::Ask, dec!(1), dec!(99))),
+        ]);
+
+        let stats = backtest.stats(bidder);
+        assert_eq!(stats.fills.len(), 2);
+        assert_eq!(stats.fills[0].timestamp, 115);
+        assert_eq!(stats.fills[0].liquidity, Liquidity::Taker);
+        assert_eq!(stats.fills[1].timestamp, 200);
+        assert_eq!(stats.fills[1].liquidity, Liquidity::Maker);
+
+        let heard = heard.borrow();
+        assert_eq!(heard[0], "book at 105: [(98, 1)]");
+        assert!(heard.contains(&String::from("fill at 125: 2 @ 99")));
+        // The fill at 200 would be heard at 210, after the history ends
+        assert!(!heard.iter().any(|h| h.starts_with("fill at 210")));
+    }
+
+    #[test]
+    fn test_history_cancels_find_their_orders() {
+        let mut backtest = Backtest::new(BacktestConfig::default());
+        backtest.add_strategy(Bidder {
+            price: dec!(90),
+            ..Bidder::default()
+        });
+
+        backtest.run(vec![
+            // The bidder's order takes the book's next id after this one
+            (1, limit(Side::Ask, dec!(1), dec!(101))),
+            (2, limit(Side::Ask, dec!(1), dec!(102))),
+            (
+                3,
+                Command::Cancel {
+                    id: sequential_id(2),
+                },
+            ),
+        ]);
+
+        assert_eq!(backtest.order_book().best_ask(), Some(dec!(101)));
+        assert_eq!(backtest.order_book().best_bid(), Some(dec!(90)));
+    }
+
+    #[test]
+    fn test_amend_to_zero_cancels() {
+        let mut backtest = Backtest::new(BacktestConfig::default());
+        let canceled = Rc::new(RefCell::new(Vec::new()));
+        backtest.add_strategy(Amender {
+            client_id: None,
+            amends: vec![dec!(-1), dec!(3), dec!(0)],
+            canceled: canceled.clone(),
+        });
+
+        backtest.run((1..10).map(|t| (t, limit(Side::Ask, dec!(1), dec!(100) + Decimal::from(t)))));
+
+        // The negative amend is ignored and the order is canceled with the 3
+        // left after the second.
+        assert_eq!(backtest.order_book().best_bid(), None);
+        assert_eq!(*canceled.borrow(), vec![(1, dec!(3))]);
+    }
+
+    #[test]
+    fn test_replays_order_flow_unchanged_without_strategies() {
+        let commands: Vec<Command> = OrderFlow::new(FlowConfig::default()).take(5_000).collect();
+
+        let mut backtest = Backtest::new(BacktestConfig::default());
+        backtest.run(commands.iter().enumerate().map(|(i, c)| (i as u64, *c)));
+
+        let mut order_book =
+            OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        for command in commands.iter() {
+            order_book.execute(*command);
+        }
+
+        for side in [Side::Bid, Side::Ask] {
+            let expected: Vec<(Decimal, Decimal)> = order_book
+                .levels(side)
+                .map(|l| (l.price, l.volume))
+                .collect();
+            let actual: Vec<(Decimal, Decimal)> = backtest
+                .order_book()
+                .levels(side)
+                .map(|l| (l.price, l.volume))
+                .collect();
+            assert_eq!(actual, expected);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::env;
+use std::process;
+
+use orderbook::clock::SystemClock;
+use orderbook::fix::{Acceptor, Gateway};
+use orderbook::id_generator::SequentialIdGenerator;
+use orderbook::OrderBook;
+
+const USAGE: &str = "usage: fix_gateway [--port N] [--comp-id ID] [--symbol SYMBOL]
+
+Accepts FIX 4.4 sessions on localhost (port 9878 unless given) as comp id
+EXCHANGE, trading a single symbol (ABC unless given). Clients can send
+NewOrderSingle, OrderCancelRequest and OrderCancelReplaceRequest, and get
+ExecutionReports and OrderCancelRejects back.";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut port = 9878;
+    let mut comp_id = String::from("EXCHANGE");
+    let mut symbol = String::from("ABC");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--port" => match args.next().map(|port| port.parse::<u16>()) {
+                Some(Ok(p)) => port = p,
+                _ => usage_error(),
+            },
+            "--comp-id" => comp_id = args.next().unwrap_or_else(|| usage_error()),
+            "--symbol" => symbol = args.next().unwrap_or_else(|| usage_error()),
+            _ => usage_error(),
+        }
+    }
+
+    let order_book = OrderBook::with_clock(SystemClock, SequentialIdGenerator::new());
+    let gateway = Gateway::new(&symbol, order_book);
+    let acceptor = Acceptor::bind(("127.0.0.1", port), &comp_id, gateway).unwrap_or_else(|e| {
+        eprintln!("can't listen on port {}: {}", port, e);
+        process::exit(2);
+    });
+
+    println!(
+        "accepting FIX 4.4 sessions for {} on {} as {}",
+        symbol,
+        acceptor.local_addr().unwrap(),
+        comp_id
+    );
+    if let Err(e) = acceptor.run() {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,52 @@
+use std::env;
+use std::process;
+use tokio::net::TcpListener;
+
+use orderbook::clock::SystemClock;
+use orderbook::grpc::{serve, Engine};
+use orderbook::id_generator::SequentialIdGenerator;
+use orderbook::OrderBook;
+
+const USAGE: &str = "usage: grpc_server [--port N]
+
+Serves the MatchingEngine gRPC service from proto/orderbook.proto on
+localhost (port 9882 unless given), for a single book.";
+
+#[tokio::main]
+async fn main() {
+    let mut args = env::args().skip(1);
+    let mut port = 9882;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--port" => match args.next().map(|port| port.parse::<u16>()) {
+                Some(Ok(p)) => port = p,
+                _ => usage_error(),
+            },
+            _ => usage_error(),
+        }
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("can't listen on port {}: {}", port, e);
+            process::exit(2);
+        });
+    println!("serving on {}", listener.local_addr().unwrap());
+
+    let order_book = OrderBook::with_clock(SystemClock, SequentialIdGenerator::new());
+    if let Err(e) = serve(listener, Engine::new(order_book)).await {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,222 @@
+use std::env;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use orderbook::clock::{Clock, ManualClock};
+use orderbook::id_generator::SequentialIdGenerator;
+use orderbook::itch::message::event_code;
+use orderbook::itch::{read_message, Body, Feed, L3Book, Message, Packet, Sink, UdpSink};
+use orderbook::order::Side;
+use orderbook::order_flow::{FlowConfig, OrderFlow};
+use orderbook::OrderBook;
+
+const USAGE: &str = "usage: itch publish (<file> | --udp <addr>) [--seed <seed>] [--commands <n>]
+       itch decode (<file> | --udp <addr>) [--seed <seed>] [--commands <n>]
+
+publish runs synthetic order flow through a book and writes its ITCH 5.0
+feed to a file, or sends it in MoldUDP64 packets to a unicast or multicast
+address. decode rebuilds the order-by-order book from a file, or from packets
+received on the address until the End of Messages event, and prints its top
+levels. Given a seed, decode regenerates the same flow and checks that the
+rebuilt book matches it exactly. The flow is 10000 commands unless given.";
+
+/// Nanoseconds since midnight of the first message: 09:30.
+const MARKET_OPEN: u64 = 34_200_000_000_000;
+
+/// A pause between commands when sending over UDP, so a receiver on the
+/// same machine can keep up.
+const UDP_PACING: Duration = Duration::from_micros(100);
+
+enum Target {
+    File(String),
+    Udp(SocketAddr),
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mode = args.next().unwrap_or_else(|| usage_error());
+    let mut target = None;
+    let mut seed = None;
+    let mut commands = 10_000;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--udp" => match args.next().and_then(|a| a.parse().ok()) {
+                Some(addr) => target = Some(Target::Udp(addr)),
+                None => usage_error(),
+            },
+            "--seed" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(s) => seed = Some(s),
+                None => usage_error(),
+            },
+            "--commands" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => commands = n,
+                None => usage_error(),
+            },
+            _ if target.is_none() && !arg.starts_with('-') => target = Some(Target::File(arg)),
+            _ => usage_error(),
+        }
+    }
+
+    let target = target.unwrap_or_else(|| usage_error());
+    match mode.as_str() {
+        "-h" | "--help" => println!("{}", USAGE),
+        "publish" => {
+            let config = FlowConfig {
+                seed: seed.unwrap_or(0),
+                ..FlowConfig::default()
+            };
+            match target {
+                Target::File(path) => {
+                    let file = File::create(&path).unwrap_or_else(|e| fail(&path, e));
+                    publish(config, commands, BufWriter::new(file), None)
+                        .unwrap_or_else(|e| fail(&path, e));
+                }
+                Target::Udp(addr) => {
+                    let socket = UdpSocket::bind("0.0.0.0:0").unwrap_or_else(|e| fail(addr, e));
+                    let sink = UdpSink::new(socket, addr, *b"ORDERBOOK1");
+                    publish(config, commands, sink, Some(UDP_PACING))
+                        .unwrap_or_else(|e| fail(addr, e));
+                }
+            }
+        }
+        "decode" => {
+            let book = match target {
+                Target::File(path) => decode_file(&path).unwrap_or_else(|e| fail(&path, e)),
+                Target::Udp(addr) => decode_udp(addr).unwrap_or_else(|e| fail(addr, e)),
+            };
+            print_book(&book);
+
+            if let Some(seed) = seed {
+                let config = FlowConfig {
+                    seed,
+                    ..FlowConfig::default()
+                };
+                let mut flow = OrderFlow::new(config);
+                flow.by_ref().take(commands).for_each(drop);
+                match book.compare(flow.book()) {
+                    Ok(()) => println!("book matches seed {}", seed),
+                    Err(e) => {
+                        println!("book doesn't match seed {}: {}", seed, e);
+                        process::exit(1);
+                    }
+                }
+            }
+        }
+        _ => usage_error(),
+    }
+}
+
+fn publish<S: Sink>(
+    config: FlowConfig,
+    commands: usize,
+    sink: S,
+    pacing: Option<Duration>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let clock = ManualClock::new(MARKET_OPEN);
+    let mut order_book = OrderBook::with_clock(clock.clone(), SequentialIdGenerator::new());
+    order_book.set_event_recording(true);
+    let mut feed = Feed::new("ABC", sink);
+    feed.start(MARKET_OPEN)?;
+
+    for command in OrderFlow::new(config).take(commands) {
+        clock.advance(1_000_000);
+        order_book.execute(command);
+        feed.publish(&order_book.take_events())?;
+        if let Some(pause) = pacing {
+            thread::sleep(pause);
+        }
+    }
+
+    feed.finish(clock.now())?;
+    Ok(())
+}
+
+fn decode_file(path: &str) -> Result<L3Book, Box<dyn std::error::Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut book = L3Book::new();
+    let mut count = 0;
+    while let Some(message) = read_message(&mut reader)? {
+        book.apply(&message)?;
+        count += 1;
+    }
+    println!("{} messages", count);
+    Ok(book)
+}
+
+fn decode_udp(addr: SocketAddr) -> Result<L3Book, Box<dyn std::error::Error>> {
+    let socket = match addr {
+        SocketAddr::V4(v4) if v4.ip().is_multicast() => {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, v4.port()))?;
+            socket.join_multicast_v4(v4.ip(), &Ipv4Addr::UNSPECIFIED)?;
+            socket
+        }
+        _ => UdpSocket::bind(addr)?,
+    };
+    let mut buffer = [0; 65_536];
+    let mut book = L3Book::new();
+    let mut next_sequence_number = 1;
+    println!("listening on {}", socket.local_addr()?);
+
+    loop {
+        let n = socket.recv(&mut buffer)?;
+        let packet = Packet::decode(&buffer[..n])?;
+        if packet.sequence_number != next_sequence_number {
+            return Err(format!(
+                "expected message {} but got {}: messages were lost",
+                next_sequence_number, packet.sequence_number
+            )
+            .into());
+        }
+        next_sequence_number += packet.messages.len() as u64;
+
+        for message in packet.messages.iter() {
+            book.apply(message)?;
+            if is_end_of_messages(message) {
+                println!("{} messages", next_sequence_number - 1);
+                return Ok(book);
+            }
+        }
+    }
+}
+
+fn is_end_of_messages(message: &Message) -> bool {
+    match message.body {
+        Body::SystemEvent { event_code } => event_code == event_code::END_OF_MESSAGES,
+        _ => false,
+    }
+}
+
+fn print_book(book: &L3Book) {
+    println!(
+        "{} orders resting, {} shares executed",
+        book.num_orders(),
+        book.volume()
+    );
+    let asks = book.levels(Side::Ask);
+    for (price, volume, orders) in asks.iter().take(5).rev() {
+        println!("  ask {:>10} {:>8} ({})", price, volume, orders);
+    }
+    for (price, volume, orders) in book.levels(Side::Bid).iter().take(5) {
+        println!("  bid {:>10} {:>8} ({})", price, volume, orders);
+    }
+}
+
+fn fail(context: impl Display, error: impl Display) -> ! {
+    eprintln!("{}: {}", context, error);
+    process::exit(2);
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
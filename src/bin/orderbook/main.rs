@@ -0,0 +1,144 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process;
+
+use orderbook::script::{self, Session, Statement};
+
+#[cfg(feature = "tui")]
+mod tui;
+
+const USAGE: &str = "usage: orderbook [script]
+       orderbook --tui [--speed <lines per second>] [script]
+
+Runs the commands in `script`, or reads them from stdin. Type `help` for the
+list of commands.
+
+With --tui, shows the book, the trade tape and an order entry line full
+screen, replaying `script` at --speed lines per second (2 by default).";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut tui = false;
+    let mut speed = 2.0;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--tui" => tui = true,
+            "--speed" => match args.next().and_then(|s| s.parse::<f64>().ok()) {
+                Some(s) if s > 0.0 => speed = s,
+                _ => usage_error(),
+            },
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => usage_error(),
+        }
+    }
+
+    if tui {
+        run_tui(path, speed);
+        return;
+    }
+
+    match path {
+        None => repl(),
+        Some(path) => {
+            let file = File::open(&path).unwrap_or_else(|e| {
+                eprintln!("can't open {}: {}", path, e);
+                process::exit(1);
+            });
+
+            if let Err(e) = run_script(BufReader::new(file)) {
+                eprintln!("{}:{}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+#[cfg(feature = "tui")]
+fn run_tui(path: Option<String>, speed: f64) {
+    let script = match path {
+        Some(path) => match std::fs::read_to_string(&path) {
+            Ok(text) => text.lines().map(String::from).collect(),
+            Err(e) => {
+                eprintln!("can't open {}: {}", path, e);
+                process::exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+
+    if let Err(e) = tui::run(script, speed) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(_path: Option<String>, _speed: f64) {
+    eprintln!("orderbook was built without the tui feature");
+    process::exit(2);
+}
+
+/// Runs a script, echoing each command before its output so the transcript
+/// reads on its own. Stops at the first line that fails.
+fn run_script(input: impl BufRead) -> Result<(), String> {
+    let mut session = Session::new();
+
+    for (n, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| format!("{}: {}", n + 1, e))?;
+        let statement = match script::parse(&line) {
+            Ok(Some(statement)) => statement,
+            Ok(None) => continue,
+            Err(e) => return Err(format!("{}: {}", n + 1, e)),
+        };
+
+        if statement == Statement::Quit {
+            break;
+        }
+
+        println!("> {}", line.trim());
+        let out = session
+            .execute(statement)
+            .map_err(|e| format!("{}: {}", n + 1, e))?;
+        print!("{}", out);
+    }
+
+    Ok(())
+}
+
+fn repl() {
+    let mut session = Session::new();
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+
+        match script::parse(&line) {
+            Ok(Some(Statement::Quit)) => break,
+            Ok(Some(statement)) => match session.execute(statement) {
+                Ok(out) => print!("{}", out),
+                Err(e) => println!("error: {}", e),
+            },
+            Ok(None) => {}
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::Frame;
+use rust_decimal::prelude::*;
+
+use orderbook::order::Side;
+use orderbook::script::{self, Session, Statement};
+
+const MIN_SPEED: f64 = 0.125;
+const MAX_SPEED: f64 = 1024.0;
+const OUTPUT_LINES: usize = 4;
+
+/// State of the full-screen mode: a session, a script being replayed into it
+/// and the order entry line.
+struct App {
+    session: Session,
+    script: Vec<String>,
+    next_line: usize,
+    paused: bool,
+    /// Script lines replayed per second.
+    speed: f64,
+    input: String,
+    output: Vec<String>,
+    quit: bool,
+}
+
+impl App {
+    fn new(script: Vec<String>, speed: f64) -> Self {
+        App {
+            session: Session::new(),
+            script,
+            next_line: 0,
+            paused: false,
+            speed: speed.clamp(MIN_SPEED, MAX_SPEED),
+            input: String::new(),
+            output: Vec::new(),
+            quit: false,
+        }
+    }
+
+    fn replaying(&self) -> bool {
+        self.next_line < self.script.len()
+    }
+
+    /// Replays the next command in the script, skipping blank lines and
+    /// comments. A `quit` in the script ends the replay, not the program.
+    fn step(&mut self) {
+        while self.replaying() {
+            let n = self.next_line;
+            let line = self.script[n].clone();
+            self.next_line += 1;
+
+            match script::parse(&line) {
+                Ok(None) => continue,
+                Ok(Some(Statement::Quit)) => self.next_line = self.script.len(),
+                Ok(Some(statement)) => {
+                    let out = self.session.execute(statement);
+                    self.show(format!("{}: {}", n + 1, line.trim()), out);
+                }
+                Err(e) => self.show(format!("{}: {}", n + 1, line.trim()), Err(e)),
+            }
+            return;
+        }
+    }
+
+    fn submit_input(&mut self) {
+        let line = std::mem::take(&mut self.input);
+
+        match script::parse(&line) {
+            Ok(None) => {}
+            Ok(Some(Statement::Quit)) => self.quit = true,
+            Ok(Some(statement)) => {
+                let out = self.session.execute(statement);
+                self.show(format!("> {}", line.trim()), out);
+            }
+            Err(e) => self.show(format!("> {}", line.trim()), Err(e)),
+        }
+    }
+
+    fn show(&mut self, command: String, out: Result<String, script::ScriptError>) {
+        self.output = vec![command];
+        match out {
+            Ok(out) => self.output.extend(out.lines().map(String::from)),
+            Err(e) => self.output.push(format!("error: {}", e)),
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+
+        match key.code {
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Tab => self.paused = !self.paused,
+            KeyCode::Right => self.step(),
+            KeyCode::Up => self.speed = (self.speed * 2.0).min(MAX_SPEED),
+            KeyCode::Down => self.speed = (self.speed / 2.0).max(MIN_SPEED),
+            KeyCode::Enter => self.submit_input(),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) => self.input.push(c),
+            _ => {}
+        }
+    }
+
+    fn step_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.speed)
+    }
+}
+
+/// Runs the full-screen mode until the user quits, replaying `script` at
+/// `speed` lines per second.
+pub fn run(script: Vec<String>, speed: f64) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let mut app = App::new(script, speed);
+    let mut last_step = Instant::now();
+
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| draw(frame, &app)) {
+            break Err(e);
+        }
+
+        let timeout = if app.replaying() && !app.paused {
+            app.step_interval().saturating_sub(last_step.elapsed())
+        } else {
+            Duration::from_millis(250)
+        };
+
+        match event::poll(timeout) {
+            Ok(true) => match event::read() {
+                Ok(Event::Key(key)) => app.handle_key(key),
+                Ok(_) => {}
+                Err(e) => break Err(e),
+            },
+            Ok(false) => {}
+            Err(e) => break Err(e),
+        }
+
+        if app.quit {
+            break Ok(());
+        }
+
+        if app.replaying() && !app.paused && last_step.elapsed() >= app.step_interval() {
+            app.step();
+            last_step = Instant::now();
+        }
+    };
+
+    ratatui::restore();
+    result
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [main, status, output, input] = Layout::vertical([
+        Constraint::Min(6),
+        Constraint::Length(3),
+        Constraint::Length(OUTPUT_LINES as u16 + 2),
+        Constraint::Length(3),
+    ])
+    .areas(frame.area());
+    let [ladder, tape] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+
+    draw_ladder(frame, app, ladder);
+    draw_tape(frame, app, tape);
+    draw_status(frame, app, status);
+
+    let lines: Vec<Line> = app
+        .output
+        .iter()
+        .take(OUTPUT_LINES)
+        .map(|l| Line::from(l.as_str()))
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered()), output);
+
+    let keys = " enter: submit  tab: pause  →: step  ↑/↓: speed  esc: quit ";
+    frame.render_widget(
+        Paragraph::new(format!("> {}", app.input))
+            .block(Block::bordered().title(" Order entry ").title_bottom(keys)),
+        input,
+    );
+    frame.set_cursor_position((input.x + 3 + app.input.len() as u16, input.y + 1));
+}
+
+/// Draws asks above bids with a bar for each level's volume, scaled to the
+/// largest level shown. The spread stays in the middle of the panel.
+fn draw_ladder(frame: &mut Frame, app: &App, area: Rect) {
+    let order_book = app.session.order_book();
+    let depth = (area.height.saturating_sub(3) / 2) as usize;
+
+    let mut asks: Vec<(Decimal, Decimal)> = order_book
+        .levels(Side::Ask)
+        .take(depth)
+        .map(|l| (l.price, l.volume))
+        .collect();
+    asks.reverse();
+    let bids: Vec<(Decimal, Decimal)> = order_book
+        .levels(Side::Bid)
+        .take(depth)
+        .map(|l| (l.price, l.volume))
+        .collect();
+
+    let max_volume = asks
+        .iter()
+        .chain(bids.iter())
+        .map(|(_, volume)| *volume)
+        .max()
+        .unwrap_or_default();
+    let bar_width = area.width.saturating_sub(28) as f64;
+
+    let row = |(price, volume): &(Decimal, Decimal), color: Color| {
+        let ratio = (*volume / max_volume).to_f64().unwrap_or(0.0);
+        let bar = "█".repeat(((ratio * bar_width).round() as usize).max(1));
+        Line::from(vec![
+            Span::styled(format!("{:>12} ", price), Style::default().fg(color)),
+            Span::raw(format!("{:>12} ", volume)),
+            Span::styled(bar, Style::default().fg(color)),
+        ])
+    };
+
+    let mut lines = vec![Line::from(""); depth - asks.len()];
+    lines.extend(asks.iter().map(|level| row(level, Color::Red)));
+    lines.push(match (order_book.best_bid(), order_book.best_ask()) {
+        (Some(bid), Some(ask)) => Line::from(format!("{:>12} spread", ask - bid)),
+        _ => Line::from(""),
+    });
+    lines.extend(bids.iter().map(|level| row(level, Color::Green)));
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Book ")),
+        area,
+    );
+}
+
+/// Draws the most recent trades first, colored by the aggressor's side.
+fn draw_tape(frame: &mut Frame, app: &App, area: Rect) {
+    let lines: Vec<Line> = app
+        .session
+        .trades()
+        .iter()
+        .rev()
+        .take(area.height as usize)
+        .map(|trade| {
+            let (side, color) = match trade.aggressor {
+                Side::Bid => ("buy ", Color::Green),
+                Side::Ask => ("sell", Color::Red),
+            };
+            Line::styled(
+                format!("{} {:>10} @ {:<10}", side, trade.quantity, trade.price),
+                Style::default().fg(color),
+            )
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Trades ")),
+        area,
+    );
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+    let order_book = app.session.order_book();
+    let price = |price: Option<Decimal>| price.map_or(String::from("-"), |p| p.to_string());
+
+    let (spread, mid) = match (order_book.best_bid(), order_book.best_ask()) {
+        (Some(bid), Some(ask)) => (Some(ask - bid), Some((bid + ask) / Decimal::from(2))),
+        _ => (None, None),
+    };
+
+    let replay = if app.script.is_empty() {
+        String::new()
+    } else {
+        let state = if !app.replaying() {
+            "done"
+        } else if app.paused {
+            "paused"
+        } else {
+            "playing"
+        };
+        format!(
+            "   replay {}/{} {} at {} lines/s",
+            app.next_line,
+            app.script.len(),
+            state,
+            app.speed
+        )
+    };
+
+    let text = format!(
+        "bid {}  ask {}  spread {}  mid {}{}",
+        price(order_book.best_bid()),
+        price(order_book.best_ask()),
+        price(spread),
+        price(mid),
+        replay
+    );
+    frame.render_widget(Paragraph::new(text).block(Block::bordered()), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_step_skips_comments() {
+        let mut app = App::new(
+            script(&[
+                "# setup",
+                "sell 1 @ 10",
+                "",
+                "buy 1 @ 10",
+                "quit",
+                "sell 1 @ 11",
+            ]),
+            1.0,
+        );
+
+        app.step();
+        assert_eq!(app.next_line, 2);
+        assert_eq!(app.output, vec!["2: sell 1 @ 10", "resting #1 sell 1 @ 10"]);
+
+        app.step();
+        assert_eq!(app.session.trades().len(), 1);
+
+        // quit ends the replay
+        app.step();
+        assert!(!app.replaying());
+        assert!(!app.quit);
+        assert_eq!(app.session.order_book().best_ask(), None);
+    }
+
+    #[test]
+    fn test_order_entry() {
+        let mut app = App::new(Vec::new(), 1.0);
+
+        for c in "sell 2 @ 5".chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+
+        assert!(app.input.is_empty());
+        assert_eq!(app.session.order_book().best_ask(), Some(Decimal::from(5)));
+
+        app.input = String::from("cancel 9");
+        app.submit_input();
+        assert_eq!(app.output[1], "error: no order #9");
+
+        app.handle_key(KeyEvent::from(KeyCode::Esc));
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn test_speed_is_clamped() {
+        let mut app = App::new(Vec::new(), 1.0);
+
+        for _ in 0..20 {
+            app.handle_key(KeyEvent::from(KeyCode::Up));
+        }
+        assert_eq!(app.speed, MAX_SPEED);
+
+        for _ in 0..20 {
+            app.handle_key(KeyEvent::from(KeyCode::Down));
+        }
+        assert_eq!(app.speed, MIN_SPEED);
+    }
+}
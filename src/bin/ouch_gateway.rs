@@ -0,0 +1,57 @@
+use std::env;
+use std::process;
+
+use orderbook::clock::SystemClock;
+use orderbook::id_generator::SequentialIdGenerator;
+use orderbook::ouch::{Gateway, Server};
+use orderbook::OrderBook;
+
+const USAGE: &str = "usage: ouch_gateway [--port N] [--symbol SYMBOL]
+
+Accepts OUCH order-entry connections on localhost (port 9879 unless given),
+trading a single symbol (ABC unless given). Clients can enter, replace and
+cancel orders, and get Accepted, Replaced, Executed, Canceled and Rejected
+messages back. A connection's orders are canceled when it closes.";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut port = 9879;
+    let mut symbol = String::from("ABC");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--port" => match args.next().map(|port| port.parse::<u16>()) {
+                Some(Ok(p)) => port = p,
+                _ => usage_error(),
+            },
+            "--symbol" => symbol = args.next().unwrap_or_else(|| usage_error()),
+            _ => usage_error(),
+        }
+    }
+
+    let order_book = OrderBook::with_clock(SystemClock, SequentialIdGenerator::new());
+    let gateway = Gateway::new(&symbol, order_book);
+    let server = Server::bind(("127.0.0.1", port), gateway).unwrap_or_else(|e| {
+        eprintln!("can't listen on port {}: {}", port, e);
+        process::exit(2);
+    });
+
+    println!(
+        "accepting OUCH connections for {} on {}",
+        symbol,
+        server.local_addr().unwrap()
+    );
+    if let Err(e) = server.run() {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
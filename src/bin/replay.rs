@@ -0,0 +1,77 @@
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+use orderbook::replay::{Format, Replay};
+
+const USAGE: &str = "usage: replay [--format lobster|csv] <messages> [snapshots]
+
+Rebuilds the book from an exchange message file and prints every message that
+disagrees with it. Given a snapshot file with a row per message, also checks
+the book's depth after each message. Exits with 1 if there were any
+discrepancies.";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut format = Format::Lobster;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--format" => match args.next().as_deref() {
+                Some("lobster") => format = Format::Lobster,
+                Some("csv") => format = Format::Csv,
+                _ => usage_error(),
+            },
+            _ if !arg.starts_with('-') => paths.push(arg),
+            _ => usage_error(),
+        }
+    }
+
+    let (messages, snapshots) = match paths.as_slice() {
+        [messages] => (open(messages), None),
+        [messages, snapshots] => (open(messages), Some(open(snapshots))),
+        _ => usage_error(),
+    };
+
+    let report = Replay::new()
+        .run(format, messages, snapshots)
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(2);
+        });
+
+    for (n, discrepancy) in report.discrepancies.iter() {
+        println!("message {}: {}", n, discrepancy);
+    }
+    println!(
+        "{} messages, {} snapshots, {} discrepancies",
+        report.messages,
+        report.snapshots,
+        report.discrepancies.len()
+    );
+
+    if !report.discrepancies.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn open(path: &str) -> BufReader<File> {
+    match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(e) => {
+            eprintln!("can't open {}: {}", path, e);
+            process::exit(2);
+        }
+    }
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,48 @@
+use std::env;
+use std::process;
+
+use orderbook::clock::SystemClock;
+use orderbook::id_generator::SequentialIdGenerator;
+use orderbook::rest::{Api, Server};
+use orderbook::OrderBook;
+
+const USAGE: &str = "usage: rest_server [--port N]
+
+Serves an HTTP API for managing orders on a book on localhost (port 9881
+unless given). GET /openapi.json describes it; see the README for a summary.";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut port = 9881;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--port" => match args.next().map(|port| port.parse::<u16>()) {
+                Some(Ok(p)) => port = p,
+                _ => usage_error(),
+            },
+            _ => usage_error(),
+        }
+    }
+
+    let order_book = OrderBook::with_clock(SystemClock, SequentialIdGenerator::new());
+    let server = Server::bind(("127.0.0.1", port), Api::new(order_book)).unwrap_or_else(|e| {
+        eprintln!("can't listen on port {}: {}", port, e);
+        process::exit(2);
+    });
+
+    println!("serving on http://{}", server.local_addr().unwrap());
+    if let Err(e) = server.run() {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,173 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use orderbook::order::Side;
+use orderbook::simulation::{Simulation, SimulationConfig, Summary};
+
+const USAGE: &str =
+    "usage: simulate <config.toml> [--seed <seed>] [--trades <file>] [--snapshots <file>]
+
+Runs the agent-based simulation described by the config file and prints
+summary statistics. --seed overrides the config's seed. --trades writes the
+trade tape and --snapshots the periodic L2 snapshots, both as CSV.";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut config_path = None;
+    let mut seed = None;
+    let mut trades_path = None;
+    let mut snapshots_path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--seed" => match args.next().and_then(|s| s.parse().ok()) {
+                Some(s) => seed = Some(s),
+                None => usage_error(),
+            },
+            "--trades" => trades_path = Some(args.next().unwrap_or_else(|| usage_error())),
+            "--snapshots" => snapshots_path = Some(args.next().unwrap_or_else(|| usage_error())),
+            _ if config_path.is_none() && !arg.starts_with('-') => config_path = Some(arg),
+            _ => usage_error(),
+        }
+    }
+
+    let config_path = config_path.unwrap_or_else(|| usage_error());
+    let text = fs::read_to_string(&config_path).unwrap_or_else(|e| fail(&config_path, e));
+    let mut config: SimulationConfig =
+        toml::from_str(&text).unwrap_or_else(|e| fail(&config_path, e));
+    if let Some(seed) = seed {
+        config.seed = seed;
+    }
+
+    let mut simulation = Simulation::new(config).unwrap_or_else(|e| fail(&config_path, e));
+    simulation.run();
+
+    if let Some(path) = trades_path {
+        write_trades(&simulation, &path).unwrap_or_else(|e| fail(&path, e));
+    }
+    if let Some(path) = snapshots_path {
+        write_snapshots(&simulation, &path).unwrap_or_else(|e| fail(&path, e));
+    }
+
+    print_summary(&simulation.summary());
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn fail(path: &str, e: impl std::fmt::Display) -> ! {
+    eprintln!("{}: {}", path, e);
+    process::exit(1);
+}
+
+fn write_trades(simulation: &Simulation, path: &str) -> io::Result<()> {
+    let instrument = simulation.instrument();
+    let mut out = BufWriter::new(File::create(path)?);
+
+    writeln!(out, "timestamp,aggressor,price,quantity")?;
+    for (timestamp, trade) in simulation.trades() {
+        let aggressor = match trade.aggressor {
+            Side::Bid => "buy",
+            Side::Ask => "sell",
+        };
+        writeln!(
+            out,
+            "{},{},{},{}",
+            timestamp,
+            aggressor,
+            instrument.from_ticks(trade.price),
+            instrument.from_lots(trade.quantity)
+        )?;
+    }
+
+    out.flush()
+}
+
+/// Writes a row per snapshot in the same layout as the replay tool's CSV
+/// snapshots, after a timestamp: ask price, ask size, bid price and bid size
+/// for each level, left blank where a side has no level.
+fn write_snapshots(simulation: &Simulation, path: &str) -> io::Result<()> {
+    let instrument = simulation.instrument();
+    let depth = simulation
+        .snapshots()
+        .iter()
+        .map(|s| s.bids.len().max(s.asks.len()))
+        .max()
+        .unwrap_or(0);
+    let mut out = BufWriter::new(File::create(path)?);
+
+    write!(out, "timestamp")?;
+    for level in 1..=depth {
+        write!(
+            out,
+            ",ask_price_{0},ask_size_{0},bid_price_{0},bid_size_{0}",
+            level
+        )?;
+    }
+    writeln!(out)?;
+
+    let cell = |level: Option<&(_, _)>| match level {
+        Some(&(price, quantity)) => format!(
+            "{},{}",
+            instrument.from_ticks(price),
+            instrument.from_lots(quantity)
+        ),
+        None => String::from(","),
+    };
+
+    for snapshot in simulation.snapshots() {
+        write!(out, "{}", snapshot.timestamp)?;
+        for level in 0..depth {
+            write!(
+                out,
+                ",{},{}",
+                cell(snapshot.asks.get(level)),
+                cell(snapshot.bids.get(level))
+            )?;
+        }
+        writeln!(out)?;
+    }
+
+    out.flush()
+}
+
+fn print_summary(summary: &Summary) {
+    let price =
+        |p: Option<_>| p.map_or(String::from("-"), |p: rust_decimal::Decimal| p.to_string());
+
+    println!("trades          {}", summary.trades);
+    println!("volume          {}", summary.volume);
+    println!("first price     {}", price(summary.first_price));
+    println!("last price      {}", price(summary.last_price));
+    println!("fundamental     {}", summary.fundamental);
+    println!("volatility      {:.6}", summary.volatility);
+    println!(
+        "mean spread     {}",
+        price(summary.mean_spread.map(|s| s.round_dp(4)))
+    );
+    println!("mean bid depth  {}", summary.mean_bid_depth.round_dp(2));
+    println!("mean ask depth  {}", summary.mean_ask_depth.round_dp(2));
+    println!();
+    println!(
+        "{:<14}{:>8}{:>12}{:>12}{:>14}",
+        "population", "agents", "volume", "position", "pnl"
+    );
+    for population in summary.populations.iter() {
+        println!(
+            "{:<14}{:>8}{:>12}{:>12}{:>14}",
+            format!("{:?}", population.kind),
+            population.agents,
+            population.volume,
+            population.position,
+            population.pnl
+        );
+    }
+}
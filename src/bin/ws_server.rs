@@ -0,0 +1,62 @@
+use std::env;
+use std::process;
+
+use orderbook::clock::SystemClock;
+use orderbook::id_generator::SequentialIdGenerator;
+use orderbook::ws::{Hub, Server};
+use orderbook::OrderBook;
+
+const USAGE: &str = "usage: ws_server [--port N] [SYMBOL...]
+
+Serves market data and order entry over WebSocket on localhost (port 9880
+unless given), with a book for each symbol (just ABC unless given). Requests
+and responses are JSON text messages; see the README for their format.";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut port = 9880;
+    let mut symbols = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--port" => match args.next().map(|port| port.parse::<u16>()) {
+                Some(Ok(p)) => port = p,
+                _ => usage_error(),
+            },
+            _ if !arg.starts_with('-') => symbols.push(arg),
+            _ => usage_error(),
+        }
+    }
+    if symbols.is_empty() {
+        symbols.push(String::from("ABC"));
+    }
+
+    let mut hub = Hub::new();
+    for symbol in symbols.iter() {
+        let order_book = OrderBook::with_clock(SystemClock, SequentialIdGenerator::new());
+        hub.add_market(symbol, order_book);
+    }
+    let server = Server::bind(("127.0.0.1", port), hub).unwrap_or_else(|e| {
+        eprintln!("can't listen on port {}: {}", port, e);
+        process::exit(2);
+    });
+
+    println!(
+        "serving {} on ws://{}",
+        symbols.join(", "),
+        server.local_addr().unwrap()
+    );
+    if let Err(e) = server.run() {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,67 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::order::{Order, Side};
+
+/// A change an `OrderBook` made to one of its sides.
+///
+/// Applying a book's events in order to an empty book rebuilds it exactly,
+/// down to the queue position of every order. An order that fully executes
+/// leaves the book with its last `Execute`, without a `Delete`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum BookEvent<P = Decimal, Q = Decimal> {
+    /// An order was added to the back of the queue at its price.
+    Add(Order<P, Q>),
+    /// A resting order traded `quantity` with an incoming order.
+    Execute {
+        id: Uuid,
+        side: Side,
+        price: P,
+        quantity: Q,
+    },
+    /// `quantity` was canceled from a resting order, which kept its place in
+    /// the queue.
+    Reduce {
+        id: Uuid,
+        side: Side,
+        price: P,
+        quantity: Q,
+    },
+    /// A resting order was taken off the book with `quantity` left on it.
+    Delete {
+        id: Uuid,
+        side: Side,
+        price: P,
+        quantity: Q,
+    },
+}
+
+impl<P: Copy, Q> BookEvent<P, Q> {
+    pub fn id(&self) -> Uuid {
+        match self {
+            BookEvent::Add(order) => return order.id,
+            BookEvent::Execute { id, .. }
+            | BookEvent::Reduce { id, .. }
+            | BookEvent::Delete { id, .. } => return *id,
+        }
+    }
+
+    pub fn side(&self) -> Side {
+        match self {
+            BookEvent::Add(order) => return order.side,
+            BookEvent::Execute { side, .. }
+            | BookEvent::Reduce { side, .. }
+            | BookEvent::Delete { side, .. } => return *side,
+        }
+    }
+
+    pub fn price(&self) -> P {
+        match self {
+            BookEvent::Add(order) => return order.price,
+            BookEvent::Execute { price, .. }
+            | BookEvent::Reduce { price, .. }
+            | BookEvent::Delete { price, .. } => return *price,
+        }
+    }
+}
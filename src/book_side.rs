@@ -1,120 +1,281 @@
-use rbtree::RBTree;
 use rust_decimal::prelude::*;
-use rust_decimal_macros::*;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
 
+use crate::arena::Arena;
+use crate::invariants::{ensure, InvariantViolation};
+use crate::numeric::Numeric;
 use crate::order::Order;
-use crate::price_level::PriceLevel;
-
-#[derive(Debug)]
-pub struct BookSide {
-    prices: HashMap<Decimal, Rc<RefCell<PriceLevel>>>,
-    price_tree: RBTree<Decimal, Rc<RefCell<PriceLevel>>>,
-    pub volume: Decimal,
+use crate::price_index::{PriceIndex, PriceTree};
+use crate::price_level::{OrderNode, PriceLevel};
+
+/// One side of the book.
+///
+/// Orders and price levels are stored in arenas and refer to each other by
+/// key, and `price_index` maps each price to the key of its level. Nothing is
+/// reference counted, so a `BookSide` is `Send` and can be moved to whichever
+/// thread does the matching.
+///
+/// The index defaults to a `PriceTree`; a side built with `with_index` can
+/// use a `PriceLadder` or any other `PriceIndex` instead.
+///
+/// `append` hands back the key of the new order, which acts as a handle for
+/// removing or reducing it later without searching its level.
+#[derive(Clone, Debug)]
+pub struct BookSide<P = Decimal, Q = Decimal, I = PriceTree<P>> {
+    orders: Arena<OrderNode<P, Q>>,
+    levels: Arena<PriceLevel<P, Q>>,
+    price_index: I,
+    pub volume: Q,
     pub num_orders: u32,
     pub depth: u32,
 }
 
-impl BookSide {
+impl<P: Numeric, Q: Numeric> BookSide<P, Q> {
     pub fn new() -> Self {
+        return BookSide::with_index(PriceTree::new());
+    }
+}
+
+impl<P: Numeric, Q: Numeric, I: PriceIndex<P>> BookSide<P, Q, I> {
+    pub fn with_index(price_index: I) -> Self {
         return BookSide {
-            prices: HashMap::new(),
-            price_tree: RBTree::new(),
-            volume: dec!(0),
+            orders: Arena::new(),
+            levels: Arena::new(),
+            price_index,
+            volume: Q::ZERO,
             num_orders: 0,
             depth: 0,
         };
     }
 
-    pub fn append(&mut self, order: Order) {
-        let price_level: Rc<RefCell<PriceLevel>>;
-
-        if let Some(pl) = self.prices.get(&order.price) {
-            price_level = pl.clone();
-        } else {
-            price_level = Rc::new(RefCell::new(PriceLevel::new(order.price)));
-            self.prices.insert(order.price, price_level.clone());
-            self.price_tree.insert(order.price, price_level.clone());
-            self.depth += 1;
-        }
+    pub fn append(&mut self, order: Order<P, Q>) -> usize {
+        let level_key = match self.price_index.get(order.price) {
+            Some(key) => key,
+            None => {
+                let key = self.levels.insert(PriceLevel::new(order.price));
+                self.price_index.insert(order.price, key);
+                self.depth += 1;
+                key
+            }
+        };
 
-        let mut price_level = price_level.borrow_mut();
-        price_level.append(order);
+        let order_key = self.orders.insert(OrderNode::new(order, level_key));
+        self.levels[level_key].append(&mut self.orders, order_key);
         self.num_orders += 1;
         self.volume += order.quantity;
+
+        return order_key;
     }
 
-    pub fn remove(&mut self, order: Order) -> Option<Order> {
-        let mut result = None;
-        let mut remove_price_level = false;
+    pub fn remove(&mut self, key: usize) -> Option<Order<P, Q>> {
+        let level_key = self.orders.get(key)?.level;
 
-        if let Some(price_level) = self.prices.get(&order.price) {
-            self.num_orders -= 1;
-            self.volume -= order.quantity;
-            let mut price_level = price_level.borrow_mut();
-            result = price_level.remove(order);
+        let level = &mut self.levels[level_key];
+        level.remove(&mut self.orders, key);
 
-            if price_level.len() <= 0 {
-                remove_price_level = true;
-            }
-        }
-
-        if remove_price_level {
-            self.prices.remove(&order.price);
-            self.price_tree.remove(&order.price);
+        if level.is_empty() {
+            let price = level.price;
+            self.levels.remove(level_key);
+            self.price_index.remove(price);
             self.depth -= 1;
         }
 
-        return result;
+        let order = self.orders.remove(key)?.order;
+        self.num_orders -= 1;
+        self.volume -= order.quantity;
+
+        return Some(order);
     }
 
-    pub fn min_price_level(&self) -> Option<Rc<RefCell<PriceLevel>>> {
-        if self.depth > 0 {
-            if let Some((&_price, price_level)) = self.price_tree.get_first() {
-                return Some(price_level.clone());
-            }
+    pub fn get(&self, key: usize) -> Option<&Order<P, Q>> {
+        return self.orders.get(key).map(|node| &node.order);
+    }
+
+    /// Takes `quantity` off the order at `key` without changing its time
+    /// priority, removing it from the book if nothing is left. Returns the
+    /// order as it stands afterwards.
+    pub fn reduce(&mut self, key: usize, quantity: Q) -> Option<Order<P, Q>> {
+        let node = self.orders.get_mut(key)?;
+        let quantity = quantity.min(node.order.quantity);
+        node.order.quantity -= quantity;
+        let order = node.order;
+
+        self.levels[node.level].volume -= quantity;
+        self.volume -= quantity;
+
+        if order.quantity == Q::ZERO {
+            self.remove(key);
         }
 
-        return None;
+        return Some(order);
+    }
+
+    /// Returns the key of the order with the best time priority at `price`.
+    pub fn front(&self, price: P) -> Option<usize> {
+        return self.price_level(price)?.front();
+    }
+
+    /// Iterates over the orders resting at `price` in time priority.
+    pub fn orders_at(&self, price: P) -> impl Iterator<Item = &Order<P, Q>> {
+        let orders = &self.orders;
+        return self
+            .price_level(price)
+            .into_iter()
+            .flat_map(move |level| level.iter(orders))
+            .map(move |key| &orders[key].order);
+    }
+
+    pub fn price_level(&self, price: P) -> Option<&PriceLevel<P, Q>> {
+        return self.price_index.get(price).map(|key| &self.levels[key]);
+    }
+
+    /// Iterates over the levels from the lowest price up.
+    pub fn levels_ascending(&self) -> impl Iterator<Item = &PriceLevel<P, Q>> {
+        return std::iter::successors(self.min_price_level(), move |level| {
+            self.price_index
+                .next_above(level.price)
+                .map(|key| &self.levels[key])
+        });
+    }
+
+    /// Iterates over the levels from the highest price down.
+    pub fn levels_descending(&self) -> impl Iterator<Item = &PriceLevel<P, Q>> {
+        return std::iter::successors(self.max_price_level(), move |level| {
+            self.price_index
+                .next_below(level.price)
+                .map(|key| &self.levels[key])
+        });
     }
 
-    pub fn max_price_level(&self) -> Option<Rc<RefCell<PriceLevel>>> {
-        if self.depth > 0 {
-            if let Some((&_price, price_level)) = self.price_tree.get_last() {
-                return Some(price_level.clone());
+    pub fn min_price_level(&self) -> Option<&PriceLevel<P, Q>> {
+        return self.price_index.first().map(|key| &self.levels[key]);
+    }
+
+    pub fn max_price_level(&self) -> Option<&PriceLevel<P, Q>> {
+        return self.price_index.last().map(|key| &self.levels[key]);
+    }
+
+    /// Checks that `volume`, `num_orders` and `depth` match the orders that
+    /// are actually resting, that every level is consistent, and that the
+    /// price index reaches each level exactly once, in price order. This walks
+    /// the whole side, so it is meant for tests and debugging.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        let mut volume = Q::ZERO;
+        let mut num_orders = 0;
+
+        for (key, level) in self.levels.iter() {
+            ensure!(!level.is_empty(), "level {} is empty", level.price);
+            ensure!(
+                self.price_index.get(level.price) == Some(key),
+                "price index doesn't point to level {}",
+                level.price
+            );
+            level.check_invariants(key, &self.orders)?;
+
+            volume += level.volume;
+            num_orders += level.len();
+        }
+
+        ensure!(
+            num_orders == self.orders.len(),
+            "{} orders are linked into levels but {} are stored",
+            num_orders,
+            self.orders.len()
+        );
+        ensure!(
+            num_orders == self.num_orders as usize,
+            "side has {} orders but counts {}",
+            num_orders,
+            self.num_orders
+        );
+        ensure!(
+            volume == self.volume,
+            "side has volume {} but counts {}",
+            volume,
+            self.volume
+        );
+        ensure!(
+            self.levels.len() == self.depth as usize,
+            "side has {} levels but counts a depth of {}",
+            self.levels.len(),
+            self.depth
+        );
+        ensure!(
+            self.price_index.len() == self.levels.len(),
+            "price index has {} prices but there are {} levels",
+            self.price_index.len(),
+            self.levels.len()
+        );
+
+        let mut reached = 0;
+        let mut prev: Option<P> = None;
+        let mut next = self.price_index.first();
+        while let Some(key) = next {
+            ensure!(reached < self.levels.len(), "price index loops");
+
+            let level = match self.levels.get(key) {
+                Some(level) => level,
+                None => {
+                    return Err(InvariantViolation(format!(
+                        "price index points to freed level slot {}",
+                        key
+                    )))
+                }
+            };
+            if let Some(prev) = prev {
+                ensure!(
+                    prev < level.price,
+                    "price index is out of order at {}",
+                    level.price
+                );
             }
+
+            reached += 1;
+            prev = Some(level.price);
+            next = self.price_index.next_above(level.price);
         }
 
-        return None;
+        ensure!(
+            reached == self.levels.len(),
+            "price index reaches {} of {} levels",
+            reached,
+            self.levels.len()
+        );
+        ensure!(
+            self.price_index.last().map(|key| self.levels[key].price) == prev,
+            "price index's last level isn't its highest"
+        );
+
+        return Ok(());
+    }
+}
+
+impl<P: Numeric, Q: Numeric> Default for BookSide<P, Q> {
+    fn default() -> Self {
+        return BookSide::new();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::numeric::{Lots, Ticks};
     use crate::order::Side;
-    use std::time;
+    use crate::price_ladder::PriceLadder;
+    use rust_decimal_macros::*;
+    use uuid::Uuid;
 
     #[test]
     fn test_append_with_no_price_levels() {
         let mut side = BookSide::new();
 
         let price = dec!(10.00);
-        let order = Order::new(Side::Ask, dec!(1.0), price, time::Instant::now());
+        let order = Order::new(Uuid::new_v4(), Side::Ask, dec!(1.0), price, 0);
 
-        side.append(order);
+        let key = side.append(order);
 
-        let pl = side.prices.get(&price).unwrap();
-        assert_eq!(
-            *pl.borrow().front().unwrap(),
-            order,
-            "Order wasn't appended"
-        );
-
-        let pl2 = side.price_tree.get(&price).unwrap();
-        assert_eq!(*pl.borrow(), *pl2.borrow(), "Internal data is inconsistent");
+        assert_eq!(side.front(price), Some(key), "Order wasn't appended");
+        assert_eq!(*side.get(key).unwrap(), order);
+        assert_eq!(side.price_level(price).unwrap().volume, order.quantity);
 
         assert_eq!(side.depth, 1);
         assert_eq!(side.volume, order.quantity);
@@ -126,22 +287,18 @@ mod tests {
         let mut side = BookSide::new();
 
         let price = dec!(10.00);
-        let order = Order::new(Side::Ask, dec!(1.0), price, time::Instant::now());
-        let order2 = Order::new(Side::Ask, dec!(2.0), price, time::Instant::now());
+        let order = Order::new(Uuid::new_v4(), Side::Ask, dec!(1.0), price, 0);
+        let order2 = Order::new(Uuid::new_v4(), Side::Ask, dec!(2.0), price, 0);
 
-        side.append(order);
+        let key = side.append(order);
         side.append(order2);
 
-        let pl = side.prices.get(&price).unwrap();
+        assert_eq!(side.front(price), Some(key), "Order wasn't appended");
         assert_eq!(
-            *pl.borrow().front().unwrap(),
-            order,
-            "Order wasn't appended"
+            side.orders_at(price).copied().collect::<Vec<_>>(),
+            vec![order, order2]
         );
 
-        let pl2 = side.price_tree.get(&price).unwrap();
-        assert_eq!(*pl.borrow(), *pl2.borrow(), "Internal data is inconsistent");
-
         assert_eq!(side.depth, 1, "Depth should not increment");
         assert_eq!(side.volume, order.quantity + order2.quantity);
         assert_eq!(side.num_orders, 2);
@@ -151,44 +308,155 @@ mod tests {
     fn test_append_with_new_price_level() {
         let mut side = BookSide::new();
 
-        let order = Order::new(Side::Ask, dec!(1.0), dec!(5.0), time::Instant::now());
-        let order2 = Order::new(Side::Ask, dec!(2.0), dec!(10.0), time::Instant::now());
+        let order = Order::new(Uuid::new_v4(), Side::Ask, dec!(1.0), dec!(5.0), 0);
+        let order2 = Order::new(Uuid::new_v4(), Side::Ask, dec!(2.0), dec!(10.0), 0);
 
         side.append(order);
         side.append(order2);
 
-        assert_eq!(side.prices.len(), 2);
+        assert_eq!(side.price_index.len(), 2);
         assert_eq!(side.depth, 2);
+        assert_eq!(side.min_price_level().unwrap().price, dec!(5.0));
+        assert_eq!(side.max_price_level().unwrap().price, dec!(10.0));
+    }
+
+    #[test]
+    fn test_levels_in_price_order() {
+        let mut side = BookSide::new();
+
+        for price in [dec!(10.0), dec!(5.0), dec!(7.5)] {
+            side.append(Order::new(Uuid::new_v4(), Side::Bid, dec!(1.0), price, 0));
+        }
+
+        let ascending: Vec<Decimal> = side.levels_ascending().map(|l| l.price).collect();
+        let descending: Vec<Decimal> = side.levels_descending().map(|l| l.price).collect();
+
+        assert_eq!(ascending, vec![dec!(5.0), dec!(7.5), dec!(10.0)]);
+        assert_eq!(descending, vec![dec!(10.0), dec!(7.5), dec!(5.0)]);
     }
 
     #[test]
     fn test_remove() {
         let mut side = BookSide::new();
 
-        let order = Order::new(Side::Ask, dec!(1.0), dec!(10.0), time::Instant::now());
-        let order2 = Order::new(Side::Ask, dec!(2.0), dec!(10.0), time::Instant::now());
+        let order = Order::new(Uuid::new_v4(), Side::Ask, dec!(1.0), dec!(10.0), 0);
+        let order2 = Order::new(Uuid::new_v4(), Side::Ask, dec!(2.0), dec!(10.0), 0);
 
         side.append(order);
-        side.append(order2);
+        let key2 = side.append(order2);
 
-        side.remove(order2);
+        assert_eq!(side.remove(key2), Some(order2));
 
         assert_eq!(side.depth, 1);
         assert_eq!(side.volume, order.quantity);
         assert_eq!(side.num_orders, 1);
+        assert_eq!(side.get(key2), None);
     }
 
     #[test]
     fn test_remove_with_last_order_at_price_level() {
         let mut side = BookSide::new();
 
-        let order = Order::new(Side::Ask, dec!(1.0), dec!(10.0), time::Instant::now());
+        let order = Order::new(Uuid::new_v4(), Side::Ask, dec!(1.0), dec!(10.0), 0);
 
-        side.append(order);
-        side.remove(order);
+        let key = side.append(order);
+        side.remove(key);
 
         assert_eq!(side.depth, 0);
         assert_eq!(side.volume, Decimal::zero());
         assert_eq!(side.num_orders, 0);
+        assert!(side.price_level(dec!(10.0)).is_none());
+    }
+
+    #[test]
+    fn test_remove_missing_order() {
+        let mut side = BookSide::new();
+
+        let order = Order::new(Uuid::new_v4(), Side::Ask, dec!(1.0), dec!(10.0), 0);
+
+        let key = side.append(order);
+
+        assert_eq!(side.remove(key + 1), None);
+
+        // Counters are untouched when nothing was removed
+        assert_eq!(side.volume, order.quantity);
+        assert_eq!(side.num_orders, 1);
+    }
+
+    #[test]
+    fn test_reduce() {
+        let mut side = BookSide::new();
+
+        let order = Order::new(Uuid::new_v4(), Side::Ask, dec!(3.0), dec!(10.0), 0);
+        let order2 = Order::new(Uuid::new_v4(), Side::Ask, dec!(2.0), dec!(10.0), 0);
+
+        let key = side.append(order);
+        let key2 = side.append(order2);
+
+        let reduced = side.reduce(key, dec!(1.0)).unwrap();
+        assert_eq!(reduced.quantity, dec!(2.0));
+        assert_eq!(side.volume, dec!(4.0));
+        assert_eq!(side.price_level(dec!(10.0)).unwrap().volume, dec!(4.0));
+
+        // Order keeps its place in the queue
+        assert_eq!(side.front(dec!(10.0)), Some(key));
+
+        // Reducing the rest takes the order off the book
+        let reduced = side.reduce(key, dec!(2.0)).unwrap();
+        assert_eq!(reduced.quantity, Decimal::zero());
+        assert_eq!(side.front(dec!(10.0)), Some(key2));
+        assert_eq!(side.num_orders, 1);
+    }
+
+    #[test]
+    fn test_with_price_ladder() {
+        let ladder = PriceLadder::new(Ticks(100), Ticks(1), 100);
+        let mut side = BookSide::with_index(ladder);
+
+        let order = Order::new(Uuid::new_v4(), Side::Bid, Lots(1), Ticks(150), 0);
+        let order2 = Order::new(Uuid::new_v4(), Side::Bid, Lots(2), Ticks(120), 0);
+        let order3 = Order::new(Uuid::new_v4(), Side::Bid, Lots(3), Ticks(120), 0);
+
+        let key = side.append(order);
+        side.append(order2);
+        side.append(order3);
+
+        assert_eq!(side.max_price_level().unwrap().price, Ticks(150));
+        assert_eq!(side.min_price_level().unwrap().volume, Lots(5));
+        assert_eq!(side.depth, 2);
+
+        side.remove(key);
+
+        assert_eq!(side.max_price_level().unwrap().price, Ticks(120));
+        assert_eq!(side.volume, Lots(5));
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        let mut side = BookSide::new();
+
+        let order = Order::new(Uuid::new_v4(), Side::Ask, dec!(1.0), dec!(10.0), 0);
+        let order2 = Order::new(Uuid::new_v4(), Side::Ask, dec!(2.0), dec!(11.0), 0);
+
+        let key = side.append(order);
+        side.append(order2);
+        side.reduce(key, dec!(0.5));
+
+        assert_eq!(side.check_invariants(), Ok(()));
+
+        side.depth += 1;
+        assert!(side.check_invariants().is_err());
+    }
+
+    #[test]
+    fn test_check_invariants_finds_missing_price() {
+        let mut side = BookSide::new();
+
+        let order = Order::new(Uuid::new_v4(), Side::Ask, dec!(1.0), dec!(10.0), 0);
+        side.append(order);
+
+        side.price_index.remove(dec!(10.0));
+
+        assert!(side.check_invariants().is_err());
     }
 }
@@ -0,0 +1,77 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of timestamps for orders, in nanoseconds since the Unix epoch.
+pub trait Clock: fmt::Debug + Send {
+    fn now(&self) -> u64;
+}
+
+/// Reads the system wall clock.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        return SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// Clones share the same underlying time, so a test can keep a handle to a
+/// clock after moving it into an `OrderBook` and advance it from the outside.
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    now: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new(now: u64) -> Self {
+        return ManualClock {
+            now: Arc::new(AtomicU64::new(now)),
+        };
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, nanos: u64) {
+        self.now.fetch_add(nanos, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        return self.now.load(Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_clock_is_after_epoch() {
+        assert!(SystemClock.now() > 0);
+    }
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new(100);
+        let handle = clock.clone();
+
+        assert_eq!(clock.now(), 100);
+
+        handle.advance(5);
+        assert_eq!(clock.now(), 105);
+
+        handle.set(42);
+        assert_eq!(clock.now(), 42);
+    }
+}
@@ -0,0 +1,27 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::order::{Order, Side};
+use crate::OrderResult;
+
+/// A single instruction to an `OrderBook`, for recording and replaying order
+/// flow.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Command<P = Decimal, Q = Decimal> {
+    Limit { side: Side, quantity: Q, price: P },
+    Market { side: Side, quantity: Q },
+    Cancel { id: Uuid },
+    Amend { id: Uuid, quantity: Q, price: P },
+}
+
+/// What a `Command` did to the book it was executed on.
+#[derive(Debug)]
+pub enum CommandResult<P = Decimal, Q = Decimal> {
+    /// The fills of a new or amended order, and whatever of it rests.
+    Matched(OrderResult<P, Q>),
+    /// The order a cancel took off the book.
+    Canceled(Order<P, Q>),
+    /// A cancel or amend named an order that isn't on the book.
+    NotFound,
+}
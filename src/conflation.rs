@@ -0,0 +1,411 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::depth::{DepthBook, DepthLevel, DepthSnapshot, LevelDelta, SequenceGap};
+use crate::numeric::Numeric;
+use crate::order::Side;
+
+/// How often a conflator publishes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Throttle {
+    /// After this many deltas.
+    Events(usize),
+    /// At most once every this many nanoseconds. Changes held back are
+    /// published by the first delta or `tick` after the interval is up.
+    Interval(u64),
+}
+
+/// The levels that changed between two publications, as of the book change
+/// numbered `sequence`. A level with no volume and no orders has gone, or has
+/// fallen out of the levels being published.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DepthUpdate<P = Decimal, Q = Decimal> {
+    pub sequence: u64,
+    pub bids: Vec<DepthLevel<P, Q>>,
+    pub asks: Vec<DepthLevel<P, Q>>,
+}
+
+/// The best bid and offer as of the book change numbered `sequence`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Bbo<P = Decimal, Q = Decimal> {
+    pub sequence: u64,
+    pub bid: Option<DepthLevel<P, Q>>,
+    pub ask: Option<DepthLevel<P, Q>>,
+}
+
+/// A copy of the book that holds changes back until the throttle says
+/// they're due.
+#[derive(Clone, Debug)]
+struct Throttled<P, Q> {
+    book: DepthBook<P, Q>,
+    throttle: Throttle,
+    last_published: Option<u64>,
+    events: usize,
+    pending: bool,
+}
+
+impl<P: Numeric, Q: Numeric> Throttled<P, Q> {
+    fn new(snapshot: &DepthSnapshot<P, Q>, throttle: Throttle) -> Self {
+        return Throttled {
+            book: DepthBook::from_snapshot(snapshot),
+            throttle,
+            last_published: None,
+            events: 0,
+            pending: false,
+        };
+    }
+
+    fn apply(&mut self, delta: &LevelDelta<P, Q>) -> Result<(), SequenceGap> {
+        let sequence = self.book.sequence();
+        self.book.apply(delta)?;
+        if self.book.sequence() != sequence {
+            self.events += 1;
+            self.pending = true;
+        }
+        return Ok(());
+    }
+
+    fn is_due(&self, now: u64) -> bool {
+        if !self.pending {
+            return false;
+        }
+        return match self.throttle {
+            Throttle::Events(count) => self.events >= count,
+            Throttle::Interval(interval) => self
+                .last_published
+                .is_none_or(|last| now >= last.saturating_add(interval)),
+        };
+    }
+
+    fn published(&mut self, now: u64) {
+        self.last_published = Some(now);
+        self.events = 0;
+        self.pending = false;
+    }
+}
+
+/// Merges L2 deltas per price level and publishes the levels that changed,
+/// at most as often as its `Throttle` allows.
+///
+/// Only the final state of each level is published, so a level that changes
+/// back before it's due isn't published at all. With a depth limit only the
+/// best levels are tracked, and levels moving into or out of them are
+/// published as they do.
+#[derive(Clone, Debug)]
+pub struct DepthConflator<P = Decimal, Q = Decimal> {
+    throttled: Throttled<P, Q>,
+    depth: usize,
+    published: DepthSnapshot<P, Q>,
+}
+
+impl<P: Numeric, Q: Numeric> DepthConflator<P, Q> {
+    /// Starts from `snapshot`, publishing the best `depth` levels a side, or
+    /// all of them.
+    pub fn new(snapshot: &DepthSnapshot<P, Q>, throttle: Throttle, depth: Option<usize>) -> Self {
+        let depth = depth.unwrap_or(usize::MAX);
+        let throttled = Throttled::new(snapshot, throttle);
+        let published = DepthSnapshot {
+            sequence: snapshot.sequence,
+            bids: throttled.book.top(Side::Bid, depth),
+            asks: throttled.book.top(Side::Ask, depth),
+        };
+        return DepthConflator {
+            throttled,
+            depth,
+            published,
+        };
+    }
+
+    /// The levels as of the last update published, for new subscribers to
+    /// start from.
+    pub fn published(&self) -> &DepthSnapshot<P, Q> {
+        return &self.published;
+    }
+
+    /// Applies `delta`, and publishes if an update is due.
+    pub fn on_delta(
+        &mut self,
+        now: u64,
+        delta: &LevelDelta<P, Q>,
+    ) -> Result<Option<DepthUpdate<P, Q>>, SequenceGap> {
+        self.throttled.apply(delta)?;
+        return Ok(self.tick(now));
+    }
+
+    /// Publishes changes held back by an interval that's now up.
+    pub fn tick(&mut self, now: u64) -> Option<DepthUpdate<P, Q>> {
+        if !self.throttled.is_due(now) {
+            return None;
+        }
+        return self.publish(now);
+    }
+
+    /// Publishes any changes held back, whether or not they're due, so
+    /// subscribers see the final state.
+    pub fn flush(&mut self, now: u64) -> Option<DepthUpdate<P, Q>> {
+        if !self.throttled.pending {
+            return None;
+        }
+        return self.publish(now);
+    }
+
+    /// Rebuilds from `snapshot` after a gap. The next update corrects
+    /// whatever was published from the deltas before it.
+    pub fn reset(&mut self, snapshot: &DepthSnapshot<P, Q>) {
+        self.throttled.book = DepthBook::from_snapshot(snapshot);
+        self.throttled.pending = true;
+    }
+
+    fn publish(&mut self, now: u64) -> Option<DepthUpdate<P, Q>> {
+        self.throttled.published(now);
+        let book = &self.throttled.book;
+        let bids = book.top(Side::Bid, self.depth);
+        let asks = book.top(Side::Ask, self.depth);
+        let update = DepthUpdate {
+            sequence: book.sequence(),
+            bids: changed_levels(&self.published.bids, &bids),
+            asks: changed_levels(&self.published.asks, &asks),
+        };
+        self.published = DepthSnapshot {
+            sequence: book.sequence(),
+            bids,
+            asks,
+        };
+
+        if update.bids.is_empty() && update.asks.is_empty() {
+            return None;
+        }
+        return Some(update);
+    }
+}
+
+/// Merges L2 deltas and publishes the best bid and offer when either has
+/// changed, at most as often as its `Throttle` allows.
+#[derive(Clone, Debug)]
+pub struct BboConflator<P = Decimal, Q = Decimal> {
+    throttled: Throttled<P, Q>,
+    published: Bbo<P, Q>,
+}
+
+impl<P: Numeric, Q: Numeric> BboConflator<P, Q> {
+    pub fn new(snapshot: &DepthSnapshot<P, Q>, throttle: Throttle) -> Self {
+        let throttled = Throttled::new(snapshot, throttle);
+        let published = best(&throttled.book);
+        return BboConflator {
+            throttled,
+            published,
+        };
+    }
+
+    /// The best bid and offer as of the last update published.
+    pub fn published(&self) -> &Bbo<P, Q> {
+        return &self.published;
+    }
+
+    /// Applies `delta`, and publishes if an update is due.
+    pub fn on_delta(
+        &mut self,
+        now: u64,
+        delta: &LevelDelta<P, Q>,
+    ) -> Result<Option<Bbo<P, Q>>, SequenceGap> {
+        self.throttled.apply(delta)?;
+        return Ok(self.tick(now));
+    }
+
+    /// Publishes changes held back by an interval that's now up.
+    pub fn tick(&mut self, now: u64) -> Option<Bbo<P, Q>> {
+        if !self.throttled.is_due(now) {
+            return None;
+        }
+        return self.publish(now);
+    }
+
+    /// Publishes any changes held back, whether or not they're due, so
+    /// subscribers see the final state.
+    pub fn flush(&mut self, now: u64) -> Option<Bbo<P, Q>> {
+        if !self.throttled.pending {
+            return None;
+        }
+        return self.publish(now);
+    }
+
+    /// Rebuilds from `snapshot` after a gap.
+    pub fn reset(&mut self, snapshot: &DepthSnapshot<P, Q>) {
+        self.throttled.book = DepthBook::from_snapshot(snapshot);
+        self.throttled.pending = true;
+    }
+
+    fn publish(&mut self, now: u64) -> Option<Bbo<P, Q>> {
+        self.throttled.published(now);
+        let bbo = best(&self.throttled.book);
+        let changed = bbo.bid != self.published.bid || bbo.ask != self.published.ask;
+        self.published = bbo;
+
+        if !changed {
+            return None;
+        }
+        return Some(bbo);
+    }
+}
+
+fn best<P: Numeric, Q: Numeric>(book: &DepthBook<P, Q>) -> Bbo<P, Q> {
+    return Bbo {
+        sequence: book.sequence(),
+        bid: book.top(Side::Bid, 1).pop(),
+        ask: book.top(Side::Ask, 1).pop(),
+    };
+}
+
+/// The levels in `current` that differ from `previous`, then the levels that
+/// have gone from it with no volume and no orders.
+fn changed_levels<P: Numeric, Q: Numeric>(
+    previous: &[DepthLevel<P, Q>],
+    current: &[DepthLevel<P, Q>],
+) -> Vec<DepthLevel<P, Q>> {
+    let mut gone: BTreeMap<P, DepthLevel<P, Q>> =
+        previous.iter().map(|level| (level.price, *level)).collect();
+    let mut changed = Vec::new();
+    for level in current.iter() {
+        if gone.remove(&level.price) != Some(*level) {
+            changed.push(*level);
+        }
+    }
+    for price in gone.keys() {
+        changed.push(DepthLevel {
+            price: *price,
+            volume: Q::ZERO,
+            order_count: 0,
+        });
+    }
+    return changed;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+    use crate::id_generator::SequentialIdGenerator;
+    use crate::order_flow::{FlowConfig, OrderFlow};
+    use crate::OrderBook;
+    use rust_decimal_macros::*;
+
+    fn merge(levels: &mut BTreeMap<Decimal, DepthLevel>, update: &[DepthLevel]) {
+        for level in update.iter() {
+            if level.order_count == 0 {
+                levels.remove(&level.price);
+            } else {
+                levels.insert(level.price, *level);
+            }
+        }
+    }
+
+    #[test]
+    fn test_merges_changes_to_a_level() {
+        let mut order_book = OrderBook::new();
+        order_book.set_depth_recording(true);
+        let mut conflator =
+            DepthConflator::new(&order_book.depth_snapshot(), Throttle::Interval(100), None);
+
+        order_book.submit_limit_order(Side::Bid, dec!(1), dec!(10));
+        let deltas = order_book.take_deltas();
+        let update = conflator.on_delta(0, &deltas[0]).unwrap().unwrap();
+        assert_eq!(update.bids[0].volume, dec!(1));
+
+        let bid = order_book.submit_limit_order(Side::Bid, dec!(2), dec!(10));
+        order_book.submit_limit_order(Side::Bid, dec!(4), dec!(10));
+        let ask = order_book.submit_limit_order(Side::Ask, dec!(1), dec!(12));
+        order_book.remove(bid.partial.unwrap().id);
+        order_book.remove(ask.partial.unwrap().id);
+        for delta in order_book.take_deltas().iter() {
+            assert_eq!(conflator.on_delta(50, delta).unwrap(), None);
+        }
+
+        let update = conflator.tick(100).unwrap();
+        assert_eq!(
+            update,
+            DepthUpdate {
+                sequence: 6,
+                bids: vec![DepthLevel {
+                    price: dec!(10),
+                    volume: dec!(5),
+                    order_count: 2,
+                }],
+                asks: vec![],
+            }
+        );
+        assert_eq!(conflator.tick(200), None);
+        assert_eq!(conflator.flush(200), None);
+    }
+
+    #[test]
+    fn test_depth_follows_order_flow() {
+        let clock = ManualClock::new(0);
+        let mut order_book = OrderBook::with_clock(clock.clone(), SequentialIdGenerator::new());
+        order_book.set_depth_recording(true);
+        let snapshot = order_book.depth_snapshot();
+        let mut conflator = DepthConflator::new(&snapshot, Throttle::Events(50), Some(5));
+        let mut bids = BTreeMap::new();
+        let mut asks = BTreeMap::new();
+
+        let mut deltas = 0;
+        let mut updates = 0;
+        for command in OrderFlow::new(FlowConfig::default()).take(5_000) {
+            clock.advance(1_000);
+            order_book.execute(command);
+            for delta in order_book.take_deltas().iter() {
+                deltas += 1;
+                if let Some(update) = conflator.on_delta(clock.now(), delta).unwrap() {
+                    updates += 1;
+                    merge(&mut bids, &update.bids);
+                    merge(&mut asks, &update.asks);
+                }
+            }
+        }
+        if let Some(update) = conflator.flush(clock.now()) {
+            merge(&mut bids, &update.bids);
+            merge(&mut asks, &update.asks);
+        }
+
+        assert!(updates * 10 < deltas);
+        let snapshot = order_book.depth_snapshot();
+        let top = |levels: &[DepthLevel]| levels.iter().take(5).copied().collect::<Vec<_>>();
+        assert_eq!(
+            bids.values().rev().copied().collect::<Vec<_>>(),
+            top(&snapshot.bids)
+        );
+        assert_eq!(
+            asks.values().copied().collect::<Vec<_>>(),
+            top(&snapshot.asks)
+        );
+        assert_eq!(conflator.published().bids, top(&snapshot.bids));
+    }
+
+    #[test]
+    fn test_bbo_follows_order_flow() {
+        let clock = ManualClock::new(0);
+        let mut order_book = OrderBook::with_clock(clock.clone(), SequentialIdGenerator::new());
+        order_book.set_depth_recording(true);
+        let mut conflator =
+            BboConflator::new(&order_book.depth_snapshot(), Throttle::Interval(1_000_000));
+
+        let mut last = *conflator.published();
+        for command in OrderFlow::new(FlowConfig::default()).take(5_000) {
+            clock.advance(1_000);
+            order_book.execute(command);
+            for delta in order_book.take_deltas().iter() {
+                if let Some(bbo) = conflator.on_delta(clock.now(), delta).unwrap() {
+                    assert!(bbo.bid != last.bid || bbo.ask != last.ask);
+                    last = bbo;
+                }
+            }
+        }
+        if let Some(bbo) = conflator.flush(clock.now()) {
+            last = bbo;
+        }
+
+        let snapshot = order_book.depth_snapshot();
+        assert_eq!(last.bid, snapshot.bids.first().copied());
+        assert_eq!(last.ask, snapshot.asks.first().copied());
+    }
+}
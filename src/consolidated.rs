@@ -0,0 +1,394 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+use crate::book_side::BookSide;
+use crate::depth::{DepthLevel, DepthSnapshot, LevelDelta, SequenceGap};
+use crate::id_generator::{IdGenerator, SequentialIdGenerator};
+use crate::numeric::Numeric;
+use crate::order::{Order, Side};
+
+/// A venue added to a `ConsolidatedBook`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct VenueId(pub usize);
+
+/// One venue's share of a consolidated level.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct VenueLevel<Q = Decimal> {
+    pub venue: VenueId,
+    pub volume: Q,
+    pub order_count: u32,
+}
+
+/// Everything resting at one price across all venues.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ConsolidatedLevel<P = Decimal, Q = Decimal> {
+    pub price: P,
+    pub volume: Q,
+    pub order_count: u32,
+    pub venues: Vec<VenueLevel<Q>>,
+}
+
+/// How the best bid across venues compares to the best offer.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum MarketState {
+    /// The best bid is below the best offer, or a side is empty.
+    Normal,
+    /// The best bid and offer are at the same price. That's normally two
+    /// venues quoting each other's price, but only the prices are compared,
+    /// so one venue whose feed has gone wrong locks the market too.
+    Locked,
+    /// The best bid is above the best offer, on whichever venues.
+    Crossed,
+}
+
+#[derive(Clone, Debug)]
+struct Venue<P> {
+    name: String,
+    sequence: u64,
+    bids: BTreeMap<P, usize>,
+    asks: BTreeMap<P, usize>,
+}
+
+impl<P> Venue<P> {
+    fn levels(&mut self, side: Side) -> &mut BTreeMap<P, usize> {
+        match side {
+            Side::Bid => return &mut self.bids,
+            Side::Ask => return &mut self.asks,
+        }
+    }
+}
+
+/// The depth of several venues trading the same instrument, merged into one
+/// book.
+///
+/// Each venue's level is kept as a single order on a `BookSide`, so the
+/// combined levels are ordered and totalled the same way as an `OrderBook`'s,
+/// and the orders at each price say which venues it came from. Venues are
+/// fed with L2 snapshots and deltas, from an `OrderBook`'s `depth_snapshot`
+/// and `take_deltas` or from an external feed, or level by level.
+#[derive(Clone, Debug)]
+pub struct ConsolidatedBook<P = Decimal, Q = Decimal> {
+    venues: Vec<Venue<P>>,
+    bids: BookSide<P, Q>,
+    asks: BookSide<P, Q>,
+    order_counts: HashMap<Uuid, (VenueId, u32)>,
+    ids: SequentialIdGenerator,
+}
+
+impl<P: Numeric, Q: Numeric> ConsolidatedBook<P, Q> {
+    pub fn new() -> Self {
+        return ConsolidatedBook {
+            venues: Vec::new(),
+            bids: BookSide::new(),
+            asks: BookSide::new(),
+            order_counts: HashMap::new(),
+            ids: SequentialIdGenerator::new(),
+        };
+    }
+
+    pub fn add_venue(&mut self, name: &str) -> VenueId {
+        self.venues.push(Venue {
+            name: name.to_string(),
+            sequence: 0,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+        });
+        return VenueId(self.venues.len() - 1);
+    }
+
+    /// Panics if `venue` is from another book.
+    pub fn venue_name(&self, venue: VenueId) -> &str {
+        return &self.venues[venue.0].name;
+    }
+
+    /// Replaces everything from `venue` with `snapshot`.
+    pub fn load_snapshot(&mut self, venue: VenueId, snapshot: &DepthSnapshot<P, Q>) {
+        self.clear_venue(venue);
+        for level in snapshot.bids.iter() {
+            self.set_level(venue, Side::Bid, level);
+        }
+        for level in snapshot.asks.iter() {
+            self.set_level(venue, Side::Ask, level);
+        }
+        self.venues[venue.0].sequence = snapshot.sequence;
+    }
+
+    /// Applies a delta from `venue`'s feed, with the same sequencing as
+    /// `DepthBook::apply`: deltas already applied are skipped, and after a
+    /// gap the venue needs a new snapshot.
+    pub fn apply(&mut self, venue: VenueId, delta: &LevelDelta<P, Q>) -> Result<(), SequenceGap> {
+        let sequence = self.venues[venue.0].sequence;
+        if delta.sequence <= sequence {
+            return Ok(());
+        }
+        if delta.sequence != sequence + 1 {
+            return Err(SequenceGap {
+                expected: sequence + 1,
+                received: delta.sequence,
+            });
+        }
+
+        let level = DepthLevel {
+            price: delta.price,
+            volume: delta.volume,
+            order_count: delta.order_count,
+        };
+        self.set_level(venue, delta.side, &level);
+        self.venues[venue.0].sequence = delta.sequence;
+        return Ok(());
+    }
+
+    /// Sets `venue`'s level at `level.price`, removing it if it has no
+    /// orders, for feeds that aren't sequenced.
+    pub fn set_level(&mut self, venue: VenueId, side: Side, level: &DepthLevel<P, Q>) {
+        if let Some(key) = self.venues[venue.0].levels(side).remove(&level.price) {
+            self.remove_order(side, key);
+        }
+        if level.order_count == 0 || level.volume == Q::ZERO {
+            return;
+        }
+
+        let id = self.ids.next_id();
+        let order = Order::new(id, side, level.volume, level.price, 0);
+        let key = self.book_side_mut(side).append(order);
+        self.order_counts.insert(id, (venue, level.order_count));
+        self.venues[venue.0].levels(side).insert(level.price, key);
+    }
+
+    /// Takes everything from `venue` off the book, such as when its feed
+    /// goes down.
+    pub fn clear_venue(&mut self, venue: VenueId) {
+        for side in [Side::Bid, Side::Ask].iter() {
+            let levels = std::mem::take(self.venues[venue.0].levels(*side));
+            for key in levels.values() {
+                self.remove_order(*side, *key);
+            }
+        }
+        self.venues[venue.0].sequence = 0;
+    }
+
+    pub fn best_bid(&self) -> Option<P> {
+        return self.bids.max_price_level().map(|level| level.price);
+    }
+
+    pub fn best_ask(&self) -> Option<P> {
+        return self.asks.min_price_level().map(|level| level.price);
+    }
+
+    /// The best level on `side` across all venues.
+    pub fn best(&self, side: Side) -> Option<ConsolidatedLevel<P, Q>> {
+        return self.levels(side).next();
+    }
+
+    /// Iterates over the combined levels on `side`, best price first.
+    pub fn levels(&self, side: Side) -> Box<dyn Iterator<Item = ConsolidatedLevel<P, Q>> + '_> {
+        let book_side = self.book_side(side);
+        let levels: Box<dyn Iterator<Item = _>> = match side {
+            Side::Bid => Box::new(book_side.levels_descending()),
+            Side::Ask => Box::new(book_side.levels_ascending()),
+        };
+        return Box::new(levels.map(move |level| {
+            let venues: Vec<VenueLevel<Q>> = book_side
+                .orders_at(level.price)
+                .map(|order| {
+                    let (venue, order_count) = self.order_counts[&order.id];
+                    return VenueLevel {
+                        venue,
+                        volume: order.quantity,
+                        order_count,
+                    };
+                })
+                .collect();
+            return ConsolidatedLevel {
+                price: level.price,
+                volume: level.volume,
+                order_count: venues.iter().map(|venue| venue.order_count).sum(),
+                venues,
+            };
+        }));
+    }
+
+    pub fn market_state(&self) -> MarketState {
+        return match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) if bid > ask => MarketState::Crossed,
+            (Some(bid), Some(ask)) if bid == ask => MarketState::Locked,
+            _ => MarketState::Normal,
+        };
+    }
+
+    fn remove_order(&mut self, side: Side, key: usize) {
+        if let Some(order) = self.book_side_mut(side).remove(key) {
+            self.order_counts.remove(&order.id);
+        }
+    }
+
+    fn book_side(&self, side: Side) -> &BookSide<P, Q> {
+        match side {
+            Side::Bid => return &self.bids,
+            Side::Ask => return &self.asks,
+        }
+    }
+
+    fn book_side_mut(&mut self, side: Side) -> &mut BookSide<P, Q> {
+        match side {
+            Side::Bid => return &mut self.bids,
+            Side::Ask => return &mut self.asks,
+        }
+    }
+}
+
+impl<P: Numeric, Q: Numeric> Default for ConsolidatedBook<P, Q> {
+    fn default() -> Self {
+        return ConsolidatedBook::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::order_flow::{FlowConfig, OrderFlow};
+    use crate::OrderBook;
+    use rust_decimal_macros::*;
+
+    fn level(price: Decimal, volume: Decimal, order_count: u32) -> DepthLevel {
+        return DepthLevel {
+            price,
+            volume,
+            order_count,
+        };
+    }
+
+    #[test]
+    fn test_best_across_venues() {
+        let mut book = ConsolidatedBook::new();
+        let a = book.add_venue("A");
+        let b = book.add_venue("B");
+        book.set_level(a, Side::Bid, &level(dec!(99), dec!(5), 2));
+        book.set_level(b, Side::Bid, &level(dec!(99), dec!(3), 1));
+        book.set_level(b, Side::Bid, &level(dec!(98), dec!(7), 1));
+        book.set_level(a, Side::Ask, &level(dec!(101), dec!(4), 1));
+        book.set_level(b, Side::Ask, &level(dec!(102), dec!(6), 3));
+
+        assert_eq!(
+            book.best(Side::Bid),
+            Some(ConsolidatedLevel {
+                price: dec!(99),
+                volume: dec!(8),
+                order_count: 3,
+                venues: vec![
+                    VenueLevel {
+                        venue: a,
+                        volume: dec!(5),
+                        order_count: 2,
+                    },
+                    VenueLevel {
+                        venue: b,
+                        volume: dec!(3),
+                        order_count: 1,
+                    },
+                ],
+            })
+        );
+        assert_eq!(book.best_ask(), Some(dec!(101)));
+        assert_eq!(book.market_state(), MarketState::Normal);
+
+        book.set_level(b, Side::Ask, &level(dec!(99), dec!(2), 1));
+        assert_eq!(book.market_state(), MarketState::Locked);
+        book.set_level(a, Side::Bid, &level(dec!(99), dec!(0), 0));
+        book.set_level(a, Side::Bid, &level(dec!(100), dec!(1), 1));
+        assert_eq!(book.market_state(), MarketState::Crossed);
+        assert_eq!(book.best(Side::Bid).unwrap().venues[0].venue, a);
+        assert_eq!(
+            book.venue_name(book.best(Side::Ask).unwrap().venues[0].venue),
+            "B"
+        );
+
+        book.clear_venue(a);
+        assert_eq!(book.best_bid(), Some(dec!(99)));
+        assert_eq!(book.best_ask(), Some(dec!(99)));
+        assert_eq!(book.levels(Side::Bid).count(), 2);
+    }
+
+    #[test]
+    fn test_market_state_on_one_venue_and_across_venues() {
+        let mut book = ConsolidatedBook::new();
+        let a = book.add_venue("A");
+        let b = book.add_venue("B");
+
+        // Across venues
+        book.set_level(a, Side::Bid, &level(dec!(100), dec!(1), 1));
+        book.set_level(b, Side::Ask, &level(dec!(100), dec!(1), 1));
+        assert_eq!(book.market_state(), MarketState::Locked);
+        book.set_level(b, Side::Ask, &level(dec!(100), dec!(0), 0));
+        book.set_level(b, Side::Ask, &level(dec!(99), dec!(1), 1));
+        assert_eq!(book.market_state(), MarketState::Crossed);
+
+        // On one venue
+        book.clear_venue(b);
+        book.set_level(a, Side::Ask, &level(dec!(100), dec!(1), 1));
+        assert_eq!(book.market_state(), MarketState::Locked);
+        book.set_level(a, Side::Ask, &level(dec!(100), dec!(0), 0));
+        book.set_level(a, Side::Ask, &level(dec!(99), dec!(1), 1));
+        assert_eq!(book.market_state(), MarketState::Crossed);
+
+        book.clear_venue(a);
+        assert_eq!(book.market_state(), MarketState::Normal);
+    }
+
+    #[test]
+    fn test_merges_order_books() {
+        let mut order_books: Vec<OrderBook> = (0..3)
+            .map(|_| {
+                let mut order_book =
+                    OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+                order_book.set_depth_recording(true);
+                return order_book;
+            })
+            .collect();
+        let mut book = ConsolidatedBook::new();
+        let venues: Vec<VenueId> = (0..3)
+            .map(|venue| book.add_venue(&venue.to_string()))
+            .collect();
+
+        let flows = (0..3).map(|venue| {
+            let config = FlowConfig {
+                seed: venue,
+                ..FlowConfig::default()
+            };
+            return OrderFlow::new(config).take(2_000);
+        });
+        for (venue, flow) in flows.enumerate() {
+            let order_book = &mut order_books[venue];
+            book.load_snapshot(venues[venue], &order_book.depth_snapshot());
+            for command in flow {
+                order_book.execute(command);
+                for delta in order_book.take_deltas().iter() {
+                    book.apply(venues[venue], delta).unwrap();
+                }
+            }
+        }
+
+        let mut expected: BTreeMap<Decimal, Decimal> = BTreeMap::new();
+        for order_book in order_books.iter() {
+            for level in order_book.depth_snapshot().bids.iter() {
+                *expected.entry(level.price).or_default() += level.volume;
+            }
+        }
+        let bids: Vec<(Decimal, Decimal)> = book
+            .levels(Side::Bid)
+            .map(|level| (level.price, level.volume))
+            .collect();
+        assert_eq!(bids, expected.into_iter().rev().collect::<Vec<_>>());
+
+        let best_ask = order_books.iter().filter_map(|b| b.best_ask()).min();
+        assert_eq!(book.best_ask(), best_ask);
+        let ask = book.best(Side::Ask).unwrap();
+        for venue_level in ask.venues.iter() {
+            let order_book = &order_books[venue_level.venue.0];
+            assert_eq!(order_book.best_ask(), Some(ask.price));
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::numeric::Numeric;
+use crate::order::Side;
+
+/// The state of one price level after a change to it.
+///
+/// The book numbers every change to a level in turn, so each delta's
+/// `sequence` is one more than the last. A level that's gone has no volume
+/// and no orders.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LevelDelta<P = Decimal, Q = Decimal> {
+    pub sequence: u64,
+    pub side: Side,
+    pub price: P,
+    pub volume: Q,
+    pub order_count: u32,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DepthLevel<P = Decimal, Q = Decimal> {
+    pub price: P,
+    pub volume: Q,
+    pub order_count: u32,
+}
+
+/// Every price level on the book, best first, as of the change numbered
+/// `sequence`.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DepthSnapshot<P = Decimal, Q = Decimal> {
+    pub sequence: u64,
+    pub bids: Vec<DepthLevel<P, Q>>,
+    pub asks: Vec<DepthLevel<P, Q>>,
+}
+
+/// A delta that doesn't follow on from the last one applied, because the
+/// ones in between were missed.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SequenceGap {
+    pub expected: u64,
+    pub received: u64,
+}
+
+impl fmt::Display for SequenceGap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(
+            f,
+            "expected delta {} but received {}",
+            self.expected, self.received
+        );
+    }
+}
+
+impl Error for SequenceGap {}
+
+/// A copy of a book's depth, kept up to date from a snapshot and the deltas
+/// after it.
+///
+/// Deltas from before the snapshot are skipped, so a consumer can start
+/// listening for deltas, then fetch a snapshot, then apply everything it
+/// heard. A gap means the copy can't be trusted until it's rebuilt from a
+/// new snapshot.
+#[derive(Clone, Debug)]
+pub struct DepthBook<P = Decimal, Q = Decimal> {
+    sequence: u64,
+    bids: BTreeMap<P, DepthLevel<P, Q>>,
+    asks: BTreeMap<P, DepthLevel<P, Q>>,
+}
+
+impl<P: Numeric, Q: Numeric> DepthBook<P, Q> {
+    pub fn from_snapshot(snapshot: &DepthSnapshot<P, Q>) -> Self {
+        let by_price = |levels: &[DepthLevel<P, Q>]| {
+            return levels.iter().map(|level| (level.price, *level)).collect();
+        };
+        return DepthBook {
+            sequence: snapshot.sequence,
+            bids: by_price(&snapshot.bids),
+            asks: by_price(&snapshot.asks),
+        };
+    }
+
+    /// The sequence number of the last change applied.
+    pub fn sequence(&self) -> u64 {
+        return self.sequence;
+    }
+
+    /// Applies `delta` if it's the next one, skips it if it's already
+    /// applied, and fails, leaving the book as it was, if some are missing.
+    pub fn apply(&mut self, delta: &LevelDelta<P, Q>) -> Result<(), SequenceGap> {
+        if delta.sequence <= self.sequence {
+            return Ok(());
+        }
+        if delta.sequence != self.sequence + 1 {
+            return Err(SequenceGap {
+                expected: self.sequence + 1,
+                received: delta.sequence,
+            });
+        }
+
+        let levels = match delta.side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        if delta.order_count == 0 {
+            levels.remove(&delta.price);
+        } else {
+            let level = DepthLevel {
+                price: delta.price,
+                volume: delta.volume,
+                order_count: delta.order_count,
+            };
+            levels.insert(delta.price, level);
+        }
+        self.sequence = delta.sequence;
+        return Ok(());
+    }
+
+    /// Up to `count` of the best levels on `side`, best first.
+    pub fn top(&self, side: Side, count: usize) -> Vec<DepthLevel<P, Q>> {
+        return match side {
+            Side::Bid => self.bids.values().rev().take(count).copied().collect(),
+            Side::Ask => self.asks.values().take(count).copied().collect(),
+        };
+    }
+
+    /// The book as a snapshot, best levels first.
+    pub fn snapshot(&self) -> DepthSnapshot<P, Q> {
+        return DepthSnapshot {
+            sequence: self.sequence,
+            bids: self.top(Side::Bid, usize::MAX),
+            asks: self.top(Side::Ask, usize::MAX),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use crate::order_flow::{FlowConfig, OrderFlow};
+    use crate::OrderBook;
+    use rust_decimal_macros::*;
+
+    #[test]
+    fn test_apply_detects_gaps() {
+        let mut order_book = OrderBook::new();
+        order_book.set_depth_recording(true);
+        order_book.submit_limit_order(Side::Bid, dec!(1), dec!(10));
+        let mut depth_book = DepthBook::from_snapshot(&order_book.depth_snapshot());
+
+        order_book.submit_limit_order(Side::Bid, dec!(2), dec!(10));
+        order_book.submit_limit_order(Side::Ask, dec!(5), dec!(11));
+        let deltas = order_book.take_deltas();
+        assert_eq!(
+            deltas,
+            vec![
+                LevelDelta {
+                    sequence: 1,
+                    side: Side::Bid,
+                    price: dec!(10),
+                    volume: dec!(1),
+                    order_count: 1,
+                },
+                LevelDelta {
+                    sequence: 2,
+                    side: Side::Bid,
+                    price: dec!(10),
+                    volume: dec!(3),
+                    order_count: 2,
+                },
+                LevelDelta {
+                    sequence: 3,
+                    side: Side::Ask,
+                    price: dec!(11),
+                    volume: dec!(5),
+                    order_count: 1,
+                },
+            ]
+        );
+
+        assert_eq!(
+            depth_book.apply(&deltas[2]),
+            Err(SequenceGap {
+                expected: 2,
+                received: 3,
+            })
+        );
+        for delta in deltas.iter() {
+            depth_book.apply(delta).unwrap();
+        }
+        assert_eq!(depth_book.snapshot(), order_book.depth_snapshot());
+    }
+
+    #[test]
+    fn test_deltas_follow_order_flow() {
+        let mut order_book =
+            OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        order_book.set_depth_recording(true);
+        let mut depth_book = DepthBook::from_snapshot(&order_book.depth_snapshot());
+
+        for command in OrderFlow::new(FlowConfig::default()).take(5_000) {
+            order_book.execute(command);
+            for delta in order_book.take_deltas().iter() {
+                depth_book.apply(delta).unwrap();
+            }
+        }
+
+        assert!(depth_book.sequence() > 5_000);
+        assert_eq!(depth_book.snapshot(), order_book.depth_snapshot());
+    }
+}
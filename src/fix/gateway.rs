@@ -0,0 +1,566 @@
+use rust_decimal::prelude::*;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::fix::message::{msg_type, tag, Message};
+use crate::fix::session::Rejection;
+use crate::order::Side;
+use crate::{Fill, FillStatus, OrderBook};
+
+/// ExecType and OrdStatus values.
+mod exec {
+    pub const NEW: char = '0';
+    pub const PARTIALLY_FILLED: char = '1';
+    pub const FILLED: char = '2';
+    pub const CANCELED: char = '4';
+    pub const REPLACED: char = '5';
+    pub const REJECTED: char = '8';
+    pub const TRADE: char = 'F';
+}
+
+/// The largest OrderQty or Price an order can have. Keeping both this far
+/// inside `Decimal`'s range means a fill's price times quantity, and the sum
+/// of them, can't overflow.
+const MAX_VALUE: i64 = 1_000_000_000_000;
+
+/// A FIX order: what the client asked for and how much of it has filled.
+#[derive(Clone, Debug)]
+struct OrderState {
+    /// The SenderCompID of the session that owns the order.
+    owner: String,
+    order_id: String,
+    cl_ord_id: String,
+    side: Side,
+    /// `None` for market orders.
+    price: Option<Decimal>,
+    quantity: Decimal,
+    cum_qty: Decimal,
+    /// Sum of price times quantity over the fills, for the average price.
+    turnover: Decimal,
+}
+
+impl OrderState {
+    fn leaves_qty(&self) -> Decimal {
+        return self.quantity - self.cum_qty;
+    }
+
+    fn ord_status(&self) -> char {
+        if self.leaves_qty().is_zero() {
+            return exec::FILLED;
+        }
+        if self.cum_qty.is_zero() {
+            return exec::NEW;
+        }
+        return exec::PARTIALLY_FILLED;
+    }
+
+    fn fill(&mut self, price: Decimal, quantity: Decimal) {
+        self.cum_qty += quantity;
+        self.turnover += price * quantity;
+    }
+}
+
+/// Maps FIX orders onto an `OrderBook` for a single symbol.
+///
+/// NewOrderSingle submits a limit or market order, OrderCancelRequest
+/// removes it, and OrderCancelReplaceRequest amends it. Each returns the
+/// ExecutionReports and OrderCancelRejects it leads to, addressed to the
+/// sessions they're for: an order that trades also sends a report to the
+/// owner of each resting order it filled. Market orders never rest; what
+/// can't be filled is canceled straight away.
+#[derive(Debug)]
+pub struct Gateway {
+    symbol: String,
+    order_book: OrderBook,
+    /// Resting orders, by their id in the book.
+    orders: HashMap<Uuid, OrderState>,
+    /// Resting orders, by owner and current ClOrdID.
+    cl_ord_ids: HashMap<(String, String), Uuid>,
+    next_order_id: u64,
+    next_exec_id: u64,
+}
+
+impl Gateway {
+    pub fn new(symbol: &str, order_book: OrderBook) -> Self {
+        return Gateway {
+            symbol: symbol.to_string(),
+            order_book,
+            orders: HashMap::new(),
+            cl_ord_ids: HashMap::new(),
+            next_order_id: 1,
+            next_exec_id: 1,
+        };
+    }
+
+    pub fn symbol(&self) -> &str {
+        return &self.symbol;
+    }
+
+    pub fn order_book(&self) -> &OrderBook {
+        return &self.order_book;
+    }
+
+    /// Handles an application message from the session whose counterparty
+    /// is `sender`. Returns the messages to send and who to send them to, or
+    /// the reason to reject the message at the session level.
+    pub fn handle(
+        &mut self,
+        sender: &str,
+        message: &Message,
+    ) -> Result<Vec<(String, Message)>, Rejection> {
+        match message.msg_type() {
+            msg_type::NEW_ORDER_SINGLE => return self.new_order(sender, message),
+            msg_type::ORDER_CANCEL_REQUEST => return self.cancel(sender, message),
+            msg_type::ORDER_CANCEL_REPLACE_REQUEST => return self.replace(sender, message),
+            _ => {
+                let reject = Message::new(msg_type::BUSINESS_MESSAGE_REJECT)
+                    .with(
+                        tag::REF_SEQ_NUM,
+                        message.get(tag::MSG_SEQ_NUM).unwrap_or("0"),
+                    )
+                    .with(tag::REF_MSG_TYPE, message.msg_type())
+                    .with(tag::BUSINESS_REJECT_REASON, 3)
+                    .with(tag::TEXT, "unsupported message type");
+                return Ok(vec![(sender.to_string(), reject)]);
+            }
+        }
+    }
+
+    fn new_order(
+        &mut self,
+        sender: &str,
+        message: &Message,
+    ) -> Result<Vec<(String, Message)>, Rejection> {
+        let cl_ord_id = required(message, tag::CL_ORD_ID)?;
+        let symbol = required(message, tag::SYMBOL)?;
+        let side = side(message)?;
+        let quantity = decimal(message, tag::ORDER_QTY)?;
+        let price = match required(message, tag::ORD_TYPE)? {
+            "1" => None,
+            "2" => Some(decimal(message, tag::PRICE)?),
+            ord_type => return Err(Rejection::incorrect(tag::ORD_TYPE, ord_type)),
+        };
+
+        let mut order = OrderState {
+            owner: sender.to_string(),
+            order_id: String::from("NONE"),
+            cl_ord_id: cl_ord_id.to_string(),
+            side,
+            price,
+            quantity,
+            cum_qty: Decimal::zero(),
+            turnover: Decimal::zero(),
+        };
+
+        let reason = if symbol != self.symbol {
+            Some((1, "unknown symbol"))
+        } else if quantity <= Decimal::zero() {
+            Some((13, "quantity must be positive"))
+        } else if quantity > Decimal::from(MAX_VALUE) {
+            Some((13, "quantity too large"))
+        } else if price.is_some_and(|price| price <= Decimal::zero()) {
+            Some((99, "price must be positive"))
+        } else if price.is_some_and(|price| price > Decimal::from(MAX_VALUE)) {
+            Some((99, "price too large"))
+        } else if self
+            .cl_ord_ids
+            .contains_key(&(order.owner.clone(), order.cl_ord_id.clone()))
+        {
+            Some((6, "duplicate ClOrdID"))
+        } else {
+            None
+        };
+        if let Some((reason, text)) = reason {
+            let report = self
+                .execution_report(&order, exec::REJECTED, exec::REJECTED)
+                .with(tag::ORD_REJ_REASON, reason)
+                .with(tag::TEXT, text);
+            return Ok(vec![(order.owner, report)]);
+        }
+
+        order.order_id = self.next_order_id.to_string();
+        self.next_order_id += 1;
+        let mut reports = vec![(
+            order.owner.clone(),
+            self.execution_report(&order, exec::NEW, exec::NEW),
+        )];
+
+        let result = match price {
+            Some(price) => self.order_book.submit_limit_order(side, quantity, price),
+            None => self.order_book.submit_market_order(side, quantity),
+        };
+        self.fills(&mut order, &result.done, &mut reports);
+
+        match result.partial {
+            Some(resting) => self.rest(resting.id, order),
+            None if !order.leaves_qty().is_zero() => {
+                let report = self
+                    .execution_report(&order, exec::CANCELED, exec::CANCELED)
+                    .with(tag::LEAVES_QTY, 0)
+                    .with(tag::TEXT, "no liquidity");
+                reports.push((order.owner, report));
+            }
+            None => {}
+        }
+        return Ok(reports);
+    }
+
+    fn cancel(
+        &mut self,
+        sender: &str,
+        message: &Message,
+    ) -> Result<Vec<(String, Message)>, Rejection> {
+        let cl_ord_id = required(message, tag::CL_ORD_ID)?;
+        let orig_cl_ord_id = required(message, tag::ORIG_CL_ORD_ID)?;
+
+        let id = match self.lookup(sender, orig_cl_ord_id) {
+            Some(id) => id,
+            None => {
+                let reject = cancel_reject(cl_ord_id, orig_cl_ord_id, 1, "unknown order");
+                return Ok(vec![(sender.to_string(), reject)]);
+            }
+        };
+
+        self.order_book.remove(id);
+        let mut order = self.orders.remove(&id).unwrap();
+        self.cl_ord_ids
+            .remove(&(order.owner.clone(), order.cl_ord_id.clone()));
+
+        order.cl_ord_id = cl_ord_id.to_string();
+        let report = self
+            .execution_report(&order, exec::CANCELED, exec::CANCELED)
+            .with(tag::ORIG_CL_ORD_ID, orig_cl_ord_id)
+            .with(tag::LEAVES_QTY, 0);
+        return Ok(vec![(order.owner, report)]);
+    }
+
+    fn replace(
+        &mut self,
+        sender: &str,
+        message: &Message,
+    ) -> Result<Vec<(String, Message)>, Rejection> {
+        let cl_ord_id = required(message, tag::CL_ORD_ID)?;
+        let orig_cl_ord_id = required(message, tag::ORIG_CL_ORD_ID)?;
+        let side = side(message)?;
+        let quantity = decimal(message, tag::ORDER_QTY)?;
+        match required(message, tag::ORD_TYPE)? {
+            "2" => {}
+            ord_type => return Err(Rejection::incorrect(tag::ORD_TYPE, ord_type)),
+        }
+        let price = decimal(message, tag::PRICE)?;
+
+        let id = match self.lookup(sender, orig_cl_ord_id) {
+            Some(id) => id,
+            None => {
+                let reject = cancel_reject(cl_ord_id, orig_cl_ord_id, 1, "unknown order")
+                    .with(tag::CXL_REJ_RESPONSE_TO, 2);
+                return Ok(vec![(sender.to_string(), reject)]);
+            }
+        };
+
+        let order = &self.orders[&id];
+        let reason = if side != order.side {
+            Some("side can't be changed")
+        } else if price <= Decimal::zero() {
+            Some("price must be positive")
+        } else if price > Decimal::from(MAX_VALUE) {
+            Some("price too large")
+        } else if quantity <= order.cum_qty {
+            Some("quantity must be more than has filled")
+        } else if quantity > Decimal::from(MAX_VALUE) {
+            Some("quantity too large")
+        } else {
+            None
+        };
+        if let Some(text) = reason {
+            let reject = cancel_reject(cl_ord_id, orig_cl_ord_id, 99, text)
+                .with(tag::ORDER_ID, &order.order_id)
+                .with(tag::ORD_STATUS, order.ord_status())
+                .with(tag::CXL_REJ_RESPONSE_TO, 2);
+            return Ok(vec![(sender.to_string(), reject)]);
+        }
+
+        let mut order = self.orders.remove(&id).unwrap();
+        self.cl_ord_ids
+            .remove(&(order.owner.clone(), order.cl_ord_id.clone()));
+        order.cl_ord_id = cl_ord_id.to_string();
+        order.quantity = quantity;
+        order.price = Some(price);
+
+        let result = self
+            .order_book
+            .amend(id, order.leaves_qty(), price)
+            .unwrap();
+        let status = order.ord_status();
+        let report = self
+            .execution_report(&order, exec::REPLACED, status)
+            .with(tag::ORIG_CL_ORD_ID, orig_cl_ord_id);
+        let mut reports = vec![(order.owner.clone(), report)];
+
+        self.fills(&mut order, &result.done, &mut reports);
+        if result.partial.is_some() {
+            self.rest(id, order);
+        }
+        return Ok(reports);
+    }
+
+    fn lookup(&self, sender: &str, cl_ord_id: &str) -> Option<Uuid> {
+        return self
+            .cl_ord_ids
+            .get(&(sender.to_string(), cl_ord_id.to_string()))
+            .copied();
+    }
+
+    fn rest(&mut self, id: Uuid, order: OrderState) {
+        self.cl_ord_ids
+            .insert((order.owner.clone(), order.cl_ord_id.clone()), id);
+        self.orders.insert(id, order);
+    }
+
+    /// Sends a trade ExecutionReport per fill to both sides of it, and
+    /// forgets the ClOrdIDs of resting orders that filled completely.
+    fn fills(
+        &mut self,
+        order: &mut OrderState,
+        fills: &[Fill],
+        reports: &mut Vec<(String, Message)>,
+    ) {
+        for fill in fills.iter() {
+            order.fill(fill.price, fill.quantity);
+            let report = self.trade_report(order, fill);
+            reports.push((order.owner.clone(), report));
+
+            let mut maker = match self.orders.remove(&fill.order_id) {
+                Some(maker) => maker,
+                None => continue,
+            };
+            maker.fill(fill.price, fill.quantity);
+            let report = self.trade_report(&maker, fill);
+            reports.push((maker.owner.clone(), report));
+
+            match fill.status {
+                FillStatus::Full => {
+                    self.cl_ord_ids.remove(&(maker.owner, maker.cl_ord_id));
+                }
+                FillStatus::Partial => {
+                    self.orders.insert(fill.order_id, maker);
+                }
+            }
+        }
+    }
+
+    fn trade_report(&mut self, order: &OrderState, fill: &Fill) -> Message {
+        return self
+            .execution_report(order, exec::TRADE, order.ord_status())
+            .with(tag::LAST_QTY, fill.quantity)
+            .with(tag::LAST_PX, fill.price);
+    }
+
+    fn execution_report(&mut self, order: &OrderState, exec_type: char, status: char) -> Message {
+        let avg_px = if order.cum_qty.is_zero() {
+            Decimal::zero()
+        } else {
+            (order.turnover / order.cum_qty).round_dp(8).normalize()
+        };
+        let leaves_qty = if status == exec::REJECTED {
+            Decimal::zero()
+        } else {
+            order.leaves_qty()
+        };
+
+        let mut report = Message::new(msg_type::EXECUTION_REPORT)
+            .with(tag::ORDER_ID, &order.order_id)
+            .with(tag::CL_ORD_ID, &order.cl_ord_id)
+            .with(tag::EXEC_ID, self.next_exec_id)
+            .with(tag::EXEC_TYPE, exec_type)
+            .with(tag::ORD_STATUS, status)
+            .with(tag::SYMBOL, &self.symbol)
+            .with(
+                tag::SIDE,
+                match order.side {
+                    Side::Bid => "1",
+                    Side::Ask => "2",
+                },
+            )
+            .with(tag::ORDER_QTY, order.quantity)
+            .with(tag::ORD_TYPE, if order.price.is_some() { "2" } else { "1" });
+        if let Some(price) = order.price {
+            report.set(tag::PRICE, price);
+        }
+        report.set(tag::LEAVES_QTY, leaves_qty);
+        report.set(tag::CUM_QTY, order.cum_qty);
+        report.set(tag::AVG_PX, avg_px);
+
+        self.next_exec_id += 1;
+        return report;
+    }
+}
+
+fn cancel_reject(cl_ord_id: &str, orig_cl_ord_id: &str, reason: u32, text: &str) -> Message {
+    return Message::new(msg_type::ORDER_CANCEL_REJECT)
+        .with(tag::ORDER_ID, "NONE")
+        .with(tag::CL_ORD_ID, cl_ord_id)
+        .with(tag::ORIG_CL_ORD_ID, orig_cl_ord_id)
+        .with(tag::ORD_STATUS, exec::REJECTED)
+        .with(tag::CXL_REJ_RESPONSE_TO, 1)
+        .with(tag::CXL_REJ_REASON, reason)
+        .with(tag::TEXT, text);
+}
+
+fn required(message: &Message, tag: u32) -> Result<&str, Rejection> {
+    return message.get(tag).ok_or_else(|| Rejection::missing(tag));
+}
+
+fn decimal(message: &Message, tag: u32) -> Result<Decimal, Rejection> {
+    let value = required(message, tag)?;
+    return Decimal::from_str(value).map_err(|_| Rejection {
+        tag,
+        reason: 6,
+        text: format!("invalid number '{}' for tag {}", value, tag),
+    });
+}
+
+fn side(message: &Message) -> Result<Side, Rejection> {
+    match required(message, tag::SIDE)? {
+        "1" => return Ok(Side::Bid),
+        "2" => return Ok(Side::Ask),
+        side => return Err(Rejection::incorrect(tag::SIDE, side)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+
+    fn gateway() -> Gateway {
+        let order_book = OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        return Gateway::new("ABC", order_book);
+    }
+
+    fn order(cl_ord_id: &str, side: &str, quantity: &str, price: &str) -> Message {
+        return Message::new(msg_type::NEW_ORDER_SINGLE)
+            .with(tag::CL_ORD_ID, cl_ord_id)
+            .with(tag::SYMBOL, "ABC")
+            .with(tag::SIDE, side)
+            .with(tag::ORDER_QTY, quantity)
+            .with(tag::ORD_TYPE, "2")
+            .with(tag::PRICE, price);
+    }
+
+    /// Each report as "session: ExecType OrdStatus ClOrdID cum/leaves".
+    fn summary(reports: &[(String, Message)]) -> Vec<String> {
+        return reports
+            .iter()
+            .map(|(session, m)| match m.msg_type() {
+                msg_type::EXECUTION_REPORT => format!(
+                    "{}: {} {} {} {}/{}",
+                    session,
+                    m.get(tag::EXEC_TYPE).unwrap(),
+                    m.get(tag::ORD_STATUS).unwrap(),
+                    m.get(tag::CL_ORD_ID).unwrap(),
+                    m.get(tag::CUM_QTY).unwrap(),
+                    m.get(tag::LEAVES_QTY).unwrap()
+                ),
+                msg_type => format!("{}: {} {}", session, msg_type, m.get(tag::TEXT).unwrap()),
+            })
+            .collect();
+    }
+
+    #[test]
+    fn test_orders_trade() {
+        let mut gateway = gateway();
+
+        let reports = gateway.handle("A", &order("a1", "2", "5", "10")).unwrap();
+        assert_eq!(summary(&reports), ["A: 0 0 a1 0/5"]);
+
+        let reports = gateway.handle("B", &order("b1", "1", "8", "11")).unwrap();
+        assert_eq!(
+            summary(&reports),
+            ["B: 0 0 b1 0/8", "B: F 1 b1 5/3", "A: F 2 a1 5/0"]
+        );
+        assert_eq!(reports[1].1.get(tag::LAST_PX), Some("10"));
+        assert_eq!(reports[1].1.get(tag::AVG_PX), Some("10"));
+        assert_eq!(gateway.order_book().best_bid(), Some(Decimal::from(11)));
+
+        // A market order that runs out of liquidity is canceled
+        let market = order("a2", "2", "4", "0").with(tag::ORD_TYPE, "1");
+        let reports = gateway.handle("A", &market).unwrap();
+        assert_eq!(
+            summary(&reports),
+            [
+                "A: 0 0 a2 0/4",
+                "A: F 1 a2 3/1",
+                "B: F 2 b1 8/0",
+                "A: 4 4 a2 3/0"
+            ]
+        );
+        assert_eq!(reports[2].1.get(tag::AVG_PX), Some("10.375"));
+    }
+
+    #[test]
+    fn test_cancel_and_replace() {
+        let mut gateway = gateway();
+        gateway.handle("A", &order("a1", "1", "5", "10")).unwrap();
+
+        let replace = Message::new(msg_type::ORDER_CANCEL_REPLACE_REQUEST)
+            .with(tag::ORIG_CL_ORD_ID, "a1")
+            .with(tag::CL_ORD_ID, "a2")
+            .with(tag::SIDE, "1")
+            .with(tag::ORDER_QTY, "7")
+            .with(tag::ORD_TYPE, "2")
+            .with(tag::PRICE, "9");
+        let reports = gateway.handle("A", &replace).unwrap();
+        assert_eq!(summary(&reports), ["A: 5 0 a2 0/7"]);
+        assert_eq!(gateway.order_book().best_bid(), Some(Decimal::from(9)));
+
+        // Only the owner can cancel, and only by the current ClOrdID
+        let cancel = |orig| {
+            return Message::new(msg_type::ORDER_CANCEL_REQUEST)
+                .with(tag::ORIG_CL_ORD_ID, orig)
+                .with(tag::CL_ORD_ID, "a3")
+                .with(tag::SIDE, "1");
+        };
+        let reports = gateway.handle("B", &cancel("a2")).unwrap();
+        assert_eq!(summary(&reports), ["B: 9 unknown order"]);
+        let reports = gateway.handle("A", &cancel("a1")).unwrap();
+        assert_eq!(summary(&reports), ["A: 9 unknown order"]);
+
+        let reports = gateway.handle("A", &cancel("a2")).unwrap();
+        assert_eq!(summary(&reports), ["A: 4 4 a3 0/0"]);
+        assert_eq!(gateway.order_book().best_bid(), None);
+    }
+
+    #[test]
+    fn test_rejects() {
+        let mut gateway = gateway();
+
+        let reports = gateway
+            .handle("A", &order("a1", "1", "5", "10").with(tag::SYMBOL, "XYZ"))
+            .unwrap();
+        assert_eq!(summary(&reports), ["A: 8 8 a1 0/0"]);
+        assert_eq!(reports[0].1.get(tag::ORD_REJ_REASON), Some("1"));
+
+        let error = gateway
+            .handle("A", &order("a1", "3", "5", "10"))
+            .unwrap_err();
+        assert_eq!(error, Rejection::incorrect(tag::SIDE, "3"));
+
+        let error = gateway
+            .handle("A", &Message::new(msg_type::NEW_ORDER_SINGLE))
+            .unwrap_err();
+        assert_eq!(error, Rejection::missing(tag::CL_ORD_ID));
+
+        let reports = gateway.handle("A", &Message::new("R")).unwrap();
+        assert_eq!(summary(&reports), ["A: j unsupported message type"]);
+
+        // Values that could overflow a fill's notional never reach the book
+        let huge = "79228162514264337593543950335";
+        let reports = gateway.handle("A", &order("a2", "1", huge, "10")).unwrap();
+        assert_eq!(reports[0].1.get(tag::TEXT), Some("quantity too large"));
+        let reports = gateway.handle("A", &order("a3", "1", "5", huge)).unwrap();
+        assert_eq!(reports[0].1.get(tag::TEXT), Some("price too large"));
+        assert_eq!(gateway.order_book.best_bid(), None);
+    }
+}
@@ -0,0 +1,408 @@
+use std::error::Error;
+use std::fmt;
+
+pub const BEGIN_STRING: &str = "FIX.4.4";
+
+const SOH: u8 = 1;
+
+/// Tags used by the gateway.
+pub mod tag {
+    pub const AVG_PX: u32 = 6;
+    pub const BEGIN_SEQ_NO: u32 = 7;
+    pub const BEGIN_STRING: u32 = 8;
+    pub const BODY_LENGTH: u32 = 9;
+    pub const CHECK_SUM: u32 = 10;
+    pub const CL_ORD_ID: u32 = 11;
+    pub const CUM_QTY: u32 = 14;
+    pub const END_SEQ_NO: u32 = 16;
+    pub const EXEC_ID: u32 = 17;
+    pub const LAST_PX: u32 = 31;
+    pub const LAST_QTY: u32 = 32;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const NEW_SEQ_NO: u32 = 36;
+    pub const ORDER_ID: u32 = 37;
+    pub const ORDER_QTY: u32 = 38;
+    pub const ORD_STATUS: u32 = 39;
+    pub const ORD_TYPE: u32 = 40;
+    pub const ORIG_CL_ORD_ID: u32 = 41;
+    pub const POSS_DUP_FLAG: u32 = 43;
+    pub const PRICE: u32 = 44;
+    pub const REF_SEQ_NUM: u32 = 45;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const SIDE: u32 = 54;
+    pub const SYMBOL: u32 = 55;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const TRANSACT_TIME: u32 = 60;
+    pub const ENCRYPT_METHOD: u32 = 98;
+    pub const CXL_REJ_REASON: u32 = 102;
+    pub const ORD_REJ_REASON: u32 = 103;
+    pub const HEART_BT_INT: u32 = 108;
+    pub const TEST_REQ_ID: u32 = 112;
+    pub const ORIG_SENDING_TIME: u32 = 122;
+    pub const GAP_FILL_FLAG: u32 = 123;
+    pub const RESET_SEQ_NUM_FLAG: u32 = 141;
+    pub const EXEC_TYPE: u32 = 150;
+    pub const LEAVES_QTY: u32 = 151;
+    pub const REF_TAG_ID: u32 = 371;
+    pub const REF_MSG_TYPE: u32 = 372;
+    pub const SESSION_REJECT_REASON: u32 = 373;
+    pub const BUSINESS_REJECT_REASON: u32 = 380;
+    pub const CXL_REJ_RESPONSE_TO: u32 = 434;
+}
+
+/// Message types used by the gateway.
+pub mod msg_type {
+    pub const HEARTBEAT: &str = "0";
+    pub const TEST_REQUEST: &str = "1";
+    pub const RESEND_REQUEST: &str = "2";
+    pub const REJECT: &str = "3";
+    pub const SEQUENCE_RESET: &str = "4";
+    pub const LOGOUT: &str = "5";
+    pub const EXECUTION_REPORT: &str = "8";
+    pub const ORDER_CANCEL_REJECT: &str = "9";
+    pub const LOGON: &str = "A";
+    pub const NEW_ORDER_SINGLE: &str = "D";
+    pub const ORDER_CANCEL_REQUEST: &str = "F";
+    pub const ORDER_CANCEL_REPLACE_REQUEST: &str = "G";
+    pub const BUSINESS_MESSAGE_REJECT: &str = "j";
+
+    /// Whether `msg_type` is a session-level message, which is never resent.
+    pub fn is_admin(msg_type: &str) -> bool {
+        return matches!(msg_type, "0" | "1" | "2" | "3" | "4" | "5" | "A");
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FixError(pub String);
+
+impl fmt::Display for FixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.0);
+    }
+}
+
+impl Error for FixError {}
+
+/// A FIX message: its fields in order, without the BeginString, BodyLength
+/// and CheckSum, which are only added when it's encoded. The first field is
+/// always the MsgType.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Message {
+    fields: Vec<(u32, String)>,
+}
+
+impl Message {
+    pub fn new(msg_type: &str) -> Self {
+        return Message {
+            fields: vec![(tag::MSG_TYPE, msg_type.to_string())],
+        };
+    }
+
+    pub fn msg_type(&self) -> &str {
+        return &self.fields[0].1;
+    }
+
+    pub fn fields(&self) -> &[(u32, String)] {
+        return &self.fields;
+    }
+
+    /// The value of the first field with `tag`.
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        return self
+            .fields
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, value)| value.as_str());
+    }
+
+    /// Sets `tag` to `value`, replacing the first field with that tag or
+    /// adding one at the end.
+    pub fn set(&mut self, tag: u32, value: impl fmt::Display) {
+        let value = value.to_string();
+        match self.fields.iter_mut().find(|(t, _)| *t == tag) {
+            Some(field) => field.1 = value,
+            None => self.fields.push((tag, value)),
+        }
+    }
+
+    pub fn with(mut self, tag: u32, value: impl fmt::Display) -> Self {
+        self.set(tag, value);
+        return self;
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (tag, value) in self.fields.iter() {
+            body.extend(format!("{}={}", tag, value).bytes());
+            body.push(SOH);
+        }
+
+        let mut bytes = format!("8={}\x019={}\x01", BEGIN_STRING, body.len()).into_bytes();
+        bytes.extend(body);
+        let checksum = checksum(&bytes);
+        bytes.extend(format!("10={:03}\x01", checksum).bytes());
+        return bytes;
+    }
+}
+
+/// Shows the message with `|` between fields, as FIX logs usually do.
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (tag, value) in self.fields.iter() {
+            write!(f, "{}={}|", tag, value)?;
+        }
+        return Ok(());
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    return bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+}
+
+/// Decodes one complete message, checking its BeginString, BodyLength and
+/// CheckSum.
+pub fn decode(bytes: &[u8]) -> Result<Message, FixError> {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(_) => return Err(FixError(String::from("message isn't valid UTF-8"))),
+    };
+    let text = match text.strip_suffix('\x01') {
+        Some(text) => text,
+        None => return Err(FixError(String::from("message doesn't end with SOH"))),
+    };
+
+    let mut fields = Vec::new();
+    for field in text.split('\x01') {
+        let (tag, value) = match field.split_once('=') {
+            Some(field) => field,
+            None => return Err(FixError(format!("field '{}' has no '='", field))),
+        };
+        match tag.parse::<u32>() {
+            Ok(tag) => fields.push((tag, value.to_string())),
+            Err(_) => return Err(FixError(format!("invalid tag '{}'", tag))),
+        }
+    }
+
+    if fields.len() < 4 {
+        return Err(FixError(String::from("message is too short")));
+    }
+    if fields[0] != (tag::BEGIN_STRING, BEGIN_STRING.to_string()) {
+        return Err(FixError(format!("expected BeginString {}", BEGIN_STRING)));
+    }
+    if fields[1].0 != tag::BODY_LENGTH || fields[2].0 != tag::MSG_TYPE {
+        return Err(FixError(String::from("expected BodyLength then MsgType")));
+    }
+    let (last_tag, last_value) = fields.pop().unwrap();
+    if last_tag != tag::CHECK_SUM {
+        return Err(FixError(String::from("expected CheckSum last")));
+    }
+
+    let trailer = format!("10={}\x01", last_value).len();
+    let header = format!("8={}\x019={}\x01", BEGIN_STRING, fields[1].1).len();
+    if fields[1].1.parse::<usize>().ok() != Some(bytes.len() - header - trailer) {
+        return Err(FixError(String::from("wrong BodyLength")));
+    }
+    let expected = checksum(&bytes[..bytes.len() - trailer]);
+    if last_value.parse::<u8>().ok() != Some(expected) {
+        return Err(FixError(format!(
+            "wrong CheckSum, expected {:03}",
+            expected
+        )));
+    }
+
+    fields.drain(..2);
+    return Ok(Message { fields });
+}
+
+/// The longest body the decoder will buffer. Anything claiming to be longer
+/// is treated as garbled.
+const MAX_BODY_LENGTH: usize = 64 * 1024;
+
+/// How many digits `MAX_BODY_LENGTH` has.
+const MAX_LENGTH_DIGITS: usize = 5;
+
+/// Splits a byte stream into messages.
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        return Decoder { buffer: Vec::new() };
+    }
+
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+}
+
+impl Iterator for Decoder {
+    type Item = Result<Message, FixError>;
+
+    /// Takes the next message out of the buffer. Returns `None` until a whole
+    /// message has arrived. A garbled message is returned as an error and
+    /// skipped, along with anything before the next BeginString.
+    fn next(&mut self) -> Option<Result<Message, FixError>> {
+        let start = format!("8={}\x019=", BEGIN_STRING).into_bytes();
+
+        match find(&self.buffer, &start) {
+            Some(0) => {}
+            Some(i) => {
+                self.buffer.drain(..i);
+            }
+            None => {
+                // Keep what could be the start of a BeginString
+                let keep = self.buffer.len().min(start.len() - 1);
+                self.buffer.drain(..self.buffer.len() - keep);
+                return None;
+            }
+        }
+
+        let length_end = match self.buffer[start.len()..].iter().position(|b| *b == SOH) {
+            Some(i) => start.len() + i,
+            // A BodyLength can't run on longer than MAX_BODY_LENGTH's digits
+            None if self.buffer.len() - start.len() > MAX_LENGTH_DIGITS => self.buffer.len(),
+            None => return None,
+        };
+        let length = std::str::from_utf8(&self.buffer[start.len()..length_end])
+            .ok()
+            .and_then(|length| length.parse::<usize>().ok())
+            .filter(|length| *length <= MAX_BODY_LENGTH);
+        // The body is followed by "10=nnn<SOH>"
+        let end = length.and_then(|length| (length_end + 1).checked_add(length)?.checked_add(7));
+        let end = match end {
+            Some(end) => end,
+            None => {
+                self.buffer.drain(..length_end);
+                return Some(Err(FixError(String::from("invalid BodyLength"))));
+            }
+        };
+        if self.buffer.len() < end {
+            return None;
+        }
+        let message: Vec<u8> = self.buffer.drain(..end).collect();
+        return Some(decode(&message));
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    return haystack
+        .windows(needle.len())
+        .position(|window| window == needle);
+}
+
+/// Formats nanoseconds since the Unix epoch as a UTCTimestamp with
+/// milliseconds, `YYYYMMDD-HH:MM:SS.sss`.
+pub fn format_timestamp(nanos: u64) -> String {
+    let millis = nanos / 1_000_000;
+    let secs = millis / 1000;
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time = secs % 86400;
+
+    return format!(
+        "{:04}{:02}{:02}-{:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        millis % 1000
+    );
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date, using Howard
+/// Hinnant's algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    return (year, month, day);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat() -> Message {
+        return Message::new(msg_type::HEARTBEAT)
+            .with(tag::SENDER_COMP_ID, "CLIENT")
+            .with(tag::TARGET_COMP_ID, "EXCHANGE")
+            .with(tag::MSG_SEQ_NUM, 1)
+            .with(tag::SENDING_TIME, "20200101-00:00:00.000");
+    }
+
+    #[test]
+    fn test_encode_and_decode() {
+        let message = heartbeat();
+        let bytes = message.encode();
+
+        assert_eq!(
+            String::from_utf8(bytes.clone())
+                .unwrap()
+                .replace('\x01', "|"),
+            "8=FIX.4.4|9=57|35=0|49=CLIENT|56=EXCHANGE|34=1|52=20200101-00:00:00.000|10=176|"
+        );
+        assert_eq!(decode(&bytes), Ok(message));
+        assert_eq!(heartbeat().get(tag::MSG_SEQ_NUM), Some("1"));
+
+        let mut garbled = bytes.clone();
+        garbled[20] = b'1';
+        assert!(decode(&garbled).is_err());
+    }
+
+    #[test]
+    fn test_decoder_splits_stream() {
+        let bytes = heartbeat().encode();
+        let mut decoder = Decoder::new();
+
+        decoder.extend(b"noise");
+        decoder.extend(&bytes[..30]);
+        assert_eq!(decoder.next(), None);
+
+        decoder.extend(&bytes[30..]);
+        decoder.extend(&bytes);
+        assert_eq!(decoder.next(), Some(Ok(heartbeat())));
+        assert_eq!(decoder.next(), Some(Ok(heartbeat())));
+        assert_eq!(decoder.next(), None);
+    }
+
+    #[test]
+    fn test_decoder_skips_bad_body_lengths() {
+        let bytes = heartbeat().encode();
+        let mut decoder = Decoder::new();
+
+        decoder.extend(b"8=FIX.4.4\x019=18446744073709551615\x0135=0\x01");
+        decoder.extend(b"8=FIX.4.4\x019=1000000\x0135=0\x01");
+        decoder.extend(&bytes);
+        let invalid = Some(Err(FixError(String::from("invalid BodyLength"))));
+        assert_eq!(decoder.next(), invalid);
+        assert_eq!(decoder.next(), invalid);
+        assert_eq!(decoder.next(), Some(Ok(heartbeat())));
+
+        // Nor does it wait forever for a BodyLength that never ends
+        decoder.extend(b"8=FIX.4.4\x019=");
+        decoder.extend(&[b'9'; 100]);
+        assert_eq!(decoder.next(), invalid);
+        assert_eq!(decoder.next(), None);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "19700101-00:00:00.000");
+        assert_eq!(
+            format_timestamp(1_582_978_271_123_456_789),
+            "20200229-12:11:11.123"
+        );
+    }
+}
@@ -0,0 +1,9 @@
+pub mod gateway;
+pub mod message;
+pub mod server;
+pub mod session;
+
+pub use gateway::Gateway;
+pub use message::{FixError, Message};
+pub use server::Acceptor;
+pub use session::{Rejection, Session};
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::fix::gateway::Gateway;
+use crate::fix::message::{msg_type, tag, Decoder, Message};
+use crate::fix::session::Session;
+
+/// How often an idle connection wakes up to send heartbeats.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Accepts FIX sessions over TCP and routes their orders to a `Gateway`.
+///
+/// Any SenderCompID may log on, as long as the Logon is addressed to the
+/// acceptor's own CompID. Each connection gets a thread, and the gateway and
+/// sessions are shared between them behind a lock, so a trade reports to the
+/// resting order's owner over its own connection.
+#[derive(Debug)]
+pub struct Acceptor {
+    listener: TcpListener,
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    comp_id: String,
+    gateway: Gateway,
+    /// Sessions by the counterparty's CompID.
+    sessions: HashMap<String, Session>,
+    connections: HashMap<String, TcpStream>,
+}
+
+impl Acceptor {
+    /// Listens on `addr` as `comp_id`. A Logon isn't checked against any
+    /// credentials, so anyone who can reach `addr` can trade as any CompID.
+    pub fn bind(addr: impl ToSocketAddrs, comp_id: &str, gateway: Gateway) -> io::Result<Self> {
+        let shared = Shared {
+            comp_id: comp_id.to_string(),
+            gateway,
+            sessions: HashMap::new(),
+            connections: HashMap::new(),
+        };
+        return Ok(Acceptor {
+            listener: TcpListener::bind(addr)?,
+            shared: Arc::new(Mutex::new(shared)),
+        });
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        return self.listener.local_addr();
+    }
+
+    /// Starts a session thread for each connection, until the listener
+    /// fails.
+    pub fn run(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || serve(stream, shared));
+        }
+        return Ok(());
+    }
+}
+
+/// Runs one connection until either side logs out or it drops.
+fn serve(mut stream: TcpStream, shared: Arc<Mutex<Shared>>) {
+    let _ = stream.set_read_timeout(Some(POLL_INTERVAL));
+    let _ = stream.set_nodelay(true);
+    let mut decoder = Decoder::new();
+    let mut counterparty: Option<String> = None;
+    let mut buffer = [0; 4096];
+
+    loop {
+        let mut close = match stream.read(&mut buffer) {
+            Ok(0) => true,
+            Ok(n) => {
+                decoder.extend(&buffer[..n]);
+                false
+            }
+            Err(e) => !matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ),
+        };
+
+        let mut shared = shared.lock().unwrap();
+        let now = SystemClock.now();
+
+        // Garbled messages are dropped: the counterparty will ask for them
+        // again when it sees the gap.
+        for result in decoder.by_ref() {
+            let message = match result {
+                Ok(message) => message,
+                Err(_) => continue,
+            };
+            match counterparty.clone() {
+                Some(target) => shared.receive(&target, message, now),
+                None => match shared.logon(&stream, message, now) {
+                    Some(target) => counterparty = Some(target),
+                    None => {
+                        close = true;
+                        break;
+                    }
+                },
+            }
+        }
+
+        if let Some(target) = &counterparty {
+            let session = shared.sessions.get_mut(target).unwrap();
+            session.tick(now);
+            close |= session.should_disconnect();
+        }
+        shared.flush();
+
+        if close {
+            if let Some(target) = &counterparty {
+                shared.connections.remove(target);
+                shared.sessions.get_mut(target).unwrap().disconnected();
+            }
+            break;
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+impl Shared {
+    /// Handles the first message on a connection, which must be a Logon.
+    /// Returns the counterparty's CompID if it logged on; otherwise a Logout
+    /// has been written and the connection should be closed.
+    fn logon(&mut self, stream: &TcpStream, message: Message, now: u64) -> Option<String> {
+        let target = message
+            .get(tag::SENDER_COMP_ID)
+            .unwrap_or_default()
+            .to_string();
+
+        let refusal = if message.msg_type() != msg_type::LOGON {
+            Some("expected Logon")
+        } else if target.is_empty() || message.get(tag::TARGET_COMP_ID) != Some(&self.comp_id) {
+            Some("CompID problem")
+        } else if self.connections.contains_key(&target) {
+            Some("already logged on")
+        } else {
+            None
+        };
+        if let Some(text) = refusal {
+            // Outside of any session, so it doesn't use up a sequence number
+            let logout = Message::new(msg_type::LOGOUT)
+                .with(tag::SENDER_COMP_ID, &self.comp_id)
+                .with(tag::TARGET_COMP_ID, &target)
+                .with(tag::MSG_SEQ_NUM, 1)
+                .with(tag::TEXT, text);
+            let _ = (&*stream).write_all(&logout.encode());
+            return None;
+        }
+
+        let comp_id = &self.comp_id;
+        let session = self
+            .sessions
+            .entry(target.clone())
+            .or_insert_with(|| Session::new(comp_id, &target));
+        session.receive(message, now);
+
+        if !session.is_logged_on() {
+            for message in session.take_outbox() {
+                let _ = (&*stream).write_all(&message.encode());
+            }
+            session.disconnected();
+            return None;
+        }
+        match stream.try_clone() {
+            Ok(stream) => self.connections.insert(target.clone(), stream),
+            Err(_) => return None,
+        };
+        return Some(target);
+    }
+
+    fn receive(&mut self, target: &str, message: Message, now: u64) {
+        let session = self.sessions.get_mut(target).unwrap();
+        let message = match session.receive(message, now) {
+            Some(message) => message,
+            None => return,
+        };
+
+        match self.gateway.handle(target, &message) {
+            Ok(reports) => {
+                for (to, report) in reports {
+                    if let Some(session) = self.sessions.get_mut(&to) {
+                        session.send(report, now);
+                    }
+                }
+            }
+            Err(rejection) => {
+                let session = self.sessions.get_mut(target).unwrap();
+                session.reject(&message, &rejection, now);
+            }
+        }
+    }
+
+    /// Writes out every session's outbox. A connection that fails is shut
+    /// down, and its thread cleans up when it next wakes.
+    fn flush(&mut self) {
+        for (target, session) in self.sessions.iter_mut() {
+            let outbox = session.take_outbox();
+            let stream = match self.connections.get_mut(target) {
+                Some(stream) => stream,
+                None => continue,
+            };
+            for message in outbox {
+                if stream.write_all(&message.encode()).is_err() {
+                    let _ = stream.shutdown(Shutdown::Both);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::fix::message::format_timestamp;
+    use crate::id_generator::SequentialIdGenerator;
+    use crate::OrderBook;
+
+    struct Client {
+        comp_id: &'static str,
+        stream: TcpStream,
+        decoder: Decoder,
+        seq: u64,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr, comp_id: &'static str) -> Self {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            return Client {
+                comp_id,
+                stream,
+                decoder: Decoder::new(),
+                seq: 1,
+            };
+        }
+
+        fn send(&mut self, message: Message) {
+            let mut out = Message::new(message.msg_type())
+                .with(tag::SENDER_COMP_ID, self.comp_id)
+                .with(tag::TARGET_COMP_ID, "EXCHANGE")
+                .with(tag::MSG_SEQ_NUM, self.seq)
+                .with(tag::SENDING_TIME, format_timestamp(0));
+            for (tag, value) in message.fields().iter().skip(1) {
+                out.set(*tag, value);
+            }
+            self.seq += 1;
+            self.stream.write_all(&out.encode()).unwrap();
+        }
+
+        fn receive(&mut self) -> Message {
+            let mut buffer = [0; 4096];
+            loop {
+                if let Some(message) = self.decoder.next() {
+                    return message.unwrap();
+                }
+                let n = self.stream.read(&mut buffer).unwrap();
+                assert!(n > 0, "connection closed");
+                self.decoder.extend(&buffer[..n]);
+            }
+        }
+    }
+
+    fn order(cl_ord_id: &str, side: &str) -> Message {
+        return Message::new(msg_type::NEW_ORDER_SINGLE)
+            .with(tag::CL_ORD_ID, cl_ord_id)
+            .with(tag::SYMBOL, "ABC")
+            .with(tag::SIDE, side)
+            .with(tag::ORDER_QTY, 10)
+            .with(tag::ORD_TYPE, "2")
+            .with(tag::PRICE, "100.5");
+    }
+
+    #[test]
+    fn test_orders_over_tcp() {
+        let order_book = OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        let gateway = Gateway::new("ABC", order_book);
+        let acceptor = Acceptor::bind("127.0.0.1:0", "EXCHANGE", gateway).unwrap();
+        let addr = acceptor.local_addr().unwrap();
+        thread::spawn(move || acceptor.run());
+
+        let logon = Message::new(msg_type::LOGON)
+            .with(tag::ENCRYPT_METHOD, 0)
+            .with(tag::HEART_BT_INT, 30);
+
+        let mut seller = Client::connect(addr, "SELLER");
+        seller.send(logon.clone());
+        assert_eq!(seller.receive().msg_type(), msg_type::LOGON);
+        seller.send(order("s1", "2"));
+        assert_eq!(seller.receive().get(tag::ORD_STATUS), Some("0"));
+
+        let mut buyer = Client::connect(addr, "BUYER");
+        buyer.send(logon.clone());
+        assert_eq!(buyer.receive().msg_type(), msg_type::LOGON);
+        buyer.send(order("b1", "1"));
+        assert_eq!(buyer.receive().get(tag::EXEC_TYPE), Some("0"));
+        let fill = buyer.receive();
+        assert_eq!(fill.get(tag::EXEC_TYPE), Some("F"));
+        assert_eq!(fill.get(tag::LAST_PX), Some("100.5"));
+
+        // The seller hears about the trade on its own connection
+        let fill = seller.receive();
+        assert_eq!(fill.get(tag::CL_ORD_ID), Some("s1"));
+        assert_eq!(fill.get(tag::ORD_STATUS), Some("2"));
+        assert_eq!(fill.get(tag::MSG_SEQ_NUM), Some("3"));
+
+        // A second connection for the same CompID is turned away
+        let mut imposter = Client::connect(addr, "SELLER");
+        imposter.send(logon);
+        let logout = imposter.receive();
+        assert_eq!(logout.msg_type(), msg_type::LOGOUT);
+        assert_eq!(logout.get(tag::TEXT), Some("already logged on"));
+
+        seller.send(Message::new(msg_type::LOGOUT));
+        assert_eq!(seller.receive().msg_type(), msg_type::LOGOUT);
+    }
+}
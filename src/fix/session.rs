@@ -0,0 +1,592 @@
+use crate::fix::message::{format_timestamp, msg_type, tag, Message};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Why a message was rejected at the session level, sent back in a Reject.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Rejection {
+    /// The tag at fault.
+    pub tag: u32,
+    /// A SessionRejectReason, such as 1 for a missing tag.
+    pub reason: u32,
+    pub text: String,
+}
+
+impl Rejection {
+    pub fn missing(tag: u32) -> Self {
+        return Rejection {
+            tag,
+            reason: 1,
+            text: format!("missing tag {}", tag),
+        };
+    }
+
+    pub fn incorrect(tag: u32, value: &str) -> Self {
+        return Rejection {
+            tag,
+            reason: 5,
+            text: format!("invalid value '{}' for tag {}", value, tag),
+        };
+    }
+}
+
+/// One side of a FIX session, as the acceptor: logon, sequence numbers,
+/// resends and heartbeats.
+///
+/// A session doesn't do any I/O. Messages received go through `receive`,
+/// which hands back the application messages among them, and messages to
+/// send queue up in an outbox to be written out with `take_outbox`. Sessions
+/// outlive connections: sequence numbers carry over when the counterparty
+/// logs on again, and what was sent while it was away can be resent.
+#[derive(Clone, Debug)]
+pub struct Session {
+    sender_comp_id: String,
+    target_comp_id: String,
+    next_sender_seq: u64,
+    next_target_seq: u64,
+    logged_on: bool,
+    disconnect: bool,
+    /// Zero if the counterparty doesn't want heartbeats.
+    heartbeat_interval: u64,
+    last_sent: u64,
+    last_received: u64,
+    /// When a TestRequest went out that hasn't been answered.
+    test_request: Option<u64>,
+    /// The highest sequence number seen past a gap we asked to be resent.
+    resend_until: Option<u64>,
+    /// Everything sent, indexed by sequence number minus one.
+    sent: Vec<Message>,
+    outbox: Vec<Message>,
+}
+
+impl Session {
+    pub fn new(sender_comp_id: &str, target_comp_id: &str) -> Self {
+        return Session {
+            sender_comp_id: sender_comp_id.to_string(),
+            target_comp_id: target_comp_id.to_string(),
+            next_sender_seq: 1,
+            next_target_seq: 1,
+            logged_on: false,
+            disconnect: false,
+            heartbeat_interval: 0,
+            last_sent: 0,
+            last_received: 0,
+            test_request: None,
+            resend_until: None,
+            sent: Vec::new(),
+            outbox: Vec::new(),
+        };
+    }
+
+    pub fn target_comp_id(&self) -> &str {
+        return &self.target_comp_id;
+    }
+
+    pub fn is_logged_on(&self) -> bool {
+        return self.logged_on;
+    }
+
+    /// Whether the connection should be closed once the outbox is written.
+    pub fn should_disconnect(&self) -> bool {
+        return self.disconnect;
+    }
+
+    pub fn next_sender_seq(&self) -> u64 {
+        return self.next_sender_seq;
+    }
+
+    pub fn next_target_seq(&self) -> u64 {
+        return self.next_target_seq;
+    }
+
+    /// Handles a message from the counterparty. Returns it if it's an
+    /// application message to be processed.
+    pub fn receive(&mut self, message: Message, now: u64) -> Option<Message> {
+        let seq = match message.get(tag::MSG_SEQ_NUM).map(str::parse::<u64>) {
+            Some(Ok(seq)) => seq,
+            _ => {
+                self.logout("missing or invalid MsgSeqNum", now);
+                return None;
+            }
+        };
+
+        if !self.logged_on {
+            if message.msg_type() == msg_type::LOGON {
+                self.logon(&message, seq, now);
+            } else {
+                self.logout("expected Logon", now);
+            }
+            return None;
+        }
+
+        self.last_received = now;
+        self.test_request = None;
+
+        if message.get(tag::SENDER_COMP_ID) != Some(self.target_comp_id.as_str())
+            || message.get(tag::TARGET_COMP_ID) != Some(self.sender_comp_id.as_str())
+        {
+            let rejection = Rejection {
+                tag: tag::SENDER_COMP_ID,
+                reason: 9,
+                text: String::from("CompID problem"),
+            };
+            self.reject(&message, &rejection, now);
+            self.logout(&rejection.text, now);
+            return None;
+        }
+
+        // These are handled whatever their sequence number
+        match message.msg_type() {
+            msg_type::SEQUENCE_RESET if message.get(tag::GAP_FILL_FLAG) != Some("Y") => {
+                self.reset_sequence(&message, now);
+                return None;
+            }
+            msg_type::RESEND_REQUEST if seq != self.next_target_seq => {
+                self.resend(&message, now);
+            }
+            msg_type::LOGOUT if seq != self.next_target_seq => {
+                self.logout("", now);
+                return None;
+            }
+            _ => {}
+        }
+
+        if seq > self.next_target_seq {
+            if self.resend_until.is_none() {
+                self.send(
+                    Message::new(msg_type::RESEND_REQUEST)
+                        .with(tag::BEGIN_SEQ_NO, self.next_target_seq)
+                        .with(tag::END_SEQ_NO, 0),
+                    now,
+                );
+            }
+            self.resend_until = Some(self.resend_until.unwrap_or(0).max(seq));
+            return None;
+        }
+        if seq < self.next_target_seq {
+            if message.get(tag::POSS_DUP_FLAG) != Some("Y") {
+                let text = format!(
+                    "MsgSeqNum too low, expecting {} but received {}",
+                    self.next_target_seq, seq
+                );
+                self.logout(&text, now);
+            }
+            return None;
+        }
+
+        self.next_target_seq += 1;
+        if self
+            .resend_until
+            .is_some_and(|until| self.next_target_seq > until)
+        {
+            self.resend_until = None;
+        }
+
+        match message.msg_type() {
+            msg_type::HEARTBEAT | msg_type::REJECT | msg_type::LOGON => {}
+            msg_type::TEST_REQUEST => {
+                let mut heartbeat = Message::new(msg_type::HEARTBEAT);
+                if let Some(id) = message.get(tag::TEST_REQ_ID) {
+                    heartbeat.set(tag::TEST_REQ_ID, id);
+                }
+                self.send(heartbeat, now);
+            }
+            msg_type::RESEND_REQUEST => self.resend(&message, now),
+            msg_type::SEQUENCE_RESET => match message.get(tag::NEW_SEQ_NO).map(str::parse::<u64>) {
+                Some(Ok(new_seq)) => {
+                    self.next_target_seq = self.next_target_seq.max(new_seq);
+                }
+                _ => self.reject(&message, &Rejection::missing(tag::NEW_SEQ_NO), now),
+            },
+            msg_type::LOGOUT => self.logout("", now),
+            _ => return Some(message),
+        }
+        return None;
+    }
+
+    /// Sends an application or session message, filling in the header.
+    /// While the counterparty is logged off, the message is only stored so
+    /// it can be resent later.
+    pub fn send(&mut self, message: Message, now: u64) {
+        let mut out = Message::new(message.msg_type())
+            .with(tag::SENDER_COMP_ID, &self.sender_comp_id)
+            .with(tag::TARGET_COMP_ID, &self.target_comp_id)
+            .with(tag::MSG_SEQ_NUM, self.next_sender_seq)
+            .with(tag::SENDING_TIME, format_timestamp(now));
+        for (tag, value) in message.fields().iter().skip(1) {
+            out.set(*tag, value);
+        }
+
+        self.next_sender_seq += 1;
+        self.sent.push(out.clone());
+        if self.logged_on {
+            self.outbox.push(out);
+            self.last_sent = now;
+        }
+    }
+
+    /// Sends a Reject for a message that couldn't be processed.
+    pub fn reject(&mut self, message: &Message, rejection: &Rejection, now: u64) {
+        let reject = Message::new(msg_type::REJECT)
+            .with(
+                tag::REF_SEQ_NUM,
+                message.get(tag::MSG_SEQ_NUM).unwrap_or("0"),
+            )
+            .with(tag::REF_TAG_ID, rejection.tag)
+            .with(tag::REF_MSG_TYPE, message.msg_type())
+            .with(tag::SESSION_REJECT_REASON, rejection.reason)
+            .with(tag::TEXT, &rejection.text);
+        self.send(reject, now);
+    }
+
+    /// Sends heartbeats and test requests when the connection has been
+    /// quiet, and gives up on a counterparty that doesn't answer.
+    pub fn tick(&mut self, now: u64) {
+        if !self.logged_on || self.disconnect || self.heartbeat_interval == 0 {
+            return;
+        }
+        let interval = self.heartbeat_interval;
+
+        if let Some(sent) = self.test_request {
+            if now.saturating_sub(sent) >= interval {
+                self.logout("heartbeat timeout", now);
+            }
+            return;
+        }
+        if now.saturating_sub(self.last_received) >= interval + interval / 5 {
+            self.send(
+                Message::new(msg_type::TEST_REQUEST).with(tag::TEST_REQ_ID, now),
+                now,
+            );
+            self.test_request = Some(now);
+        } else if now.saturating_sub(self.last_sent) >= interval {
+            self.send(Message::new(msg_type::HEARTBEAT), now);
+        }
+    }
+
+    pub fn take_outbox(&mut self) -> Vec<Message> {
+        return std::mem::take(&mut self.outbox);
+    }
+
+    /// Marks the session logged off after its connection closes.
+    pub fn disconnected(&mut self) {
+        self.logged_on = false;
+        self.disconnect = false;
+        self.test_request = None;
+        self.resend_until = None;
+        self.outbox.clear();
+    }
+
+    fn logon(&mut self, logon: &Message, seq: u64, now: u64) {
+        let heartbeat_interval = match logon.get(tag::HEART_BT_INT).map(str::parse::<u64>) {
+            Some(Ok(interval)) => interval,
+            _ => {
+                self.logout("missing or invalid HeartBtInt", now);
+                return;
+            }
+        };
+        let heartbeat_nanos = match heartbeat_interval.checked_mul(NANOS_PER_SECOND) {
+            Some(nanos) => nanos,
+            None => {
+                self.logout("HeartBtInt too large", now);
+                return;
+            }
+        };
+
+        let reset = logon.get(tag::RESET_SEQ_NUM_FLAG) == Some("Y");
+        if reset {
+            self.next_sender_seq = 1;
+            self.next_target_seq = 1;
+            self.sent.clear();
+        }
+        if seq < self.next_target_seq {
+            let text = format!(
+                "MsgSeqNum too low, expecting {} but received {}",
+                self.next_target_seq, seq
+            );
+            self.logout(&text, now);
+            return;
+        }
+
+        self.logged_on = true;
+        self.heartbeat_interval = heartbeat_nanos;
+        self.last_received = now;
+
+        let mut reply = Message::new(msg_type::LOGON)
+            .with(tag::ENCRYPT_METHOD, 0)
+            .with(tag::HEART_BT_INT, heartbeat_interval);
+        if reset {
+            reply.set(tag::RESET_SEQ_NUM_FLAG, "Y");
+        }
+        self.send(reply, now);
+
+        if seq > self.next_target_seq {
+            self.send(
+                Message::new(msg_type::RESEND_REQUEST)
+                    .with(tag::BEGIN_SEQ_NO, self.next_target_seq)
+                    .with(tag::END_SEQ_NO, 0),
+                now,
+            );
+            self.resend_until = Some(seq);
+        } else {
+            self.next_target_seq += 1;
+        }
+    }
+
+    /// Sends a Logout and closes the connection. The counterparty's Logout,
+    /// if it sends one, isn't waited for.
+    fn logout(&mut self, text: &str, now: u64) {
+        let mut logout = Message::new(msg_type::LOGOUT);
+        if !text.is_empty() {
+            logout.set(tag::TEXT, text);
+        }
+
+        // A Logout for a failed logon goes out even though the session never
+        // logged on.
+        let logged_on = self.logged_on;
+        self.logged_on = true;
+        self.send(logout, now);
+        self.logged_on = logged_on;
+        self.disconnect = true;
+    }
+
+    fn reset_sequence(&mut self, message: &Message, now: u64) {
+        match message.get(tag::NEW_SEQ_NO).map(str::parse::<u64>) {
+            Some(Ok(new_seq)) if new_seq >= self.next_target_seq => {
+                self.next_target_seq = new_seq;
+                self.resend_until = None;
+            }
+            Some(Ok(_)) => {
+                let value = message.get(tag::NEW_SEQ_NO).unwrap_or_default();
+                self.reject(message, &Rejection::incorrect(tag::NEW_SEQ_NO, value), now);
+            }
+            _ => self.reject(message, &Rejection::missing(tag::NEW_SEQ_NO), now),
+        }
+    }
+
+    /// Answers a ResendRequest: application messages are sent again as
+    /// possible duplicates, and runs of session messages are skipped over
+    /// with a gap fill.
+    fn resend(&mut self, request: &Message, now: u64) {
+        let parse = |tag| request.get(tag).and_then(|value| value.parse::<u64>().ok());
+        let (begin, end) = match (parse(tag::BEGIN_SEQ_NO), parse(tag::END_SEQ_NO)) {
+            (Some(begin), Some(end)) => (begin.max(1), end),
+            _ => {
+                self.reject(request, &Rejection::missing(tag::BEGIN_SEQ_NO), now);
+                return;
+            }
+        };
+        let last = self.next_sender_seq - 1;
+        let end = if end == 0 || end > last { last } else { end };
+
+        let mut gap_start = None;
+        for seq in begin..=end {
+            let mut message = self.sent[seq as usize - 1].clone();
+            if msg_type::is_admin(message.msg_type()) {
+                gap_start = gap_start.or(Some(seq));
+                continue;
+            }
+            if let Some(start) = gap_start.take() {
+                self.gap_fill(start, seq, now);
+            }
+
+            let original = message
+                .get(tag::SENDING_TIME)
+                .unwrap_or_default()
+                .to_string();
+            message.set(tag::POSS_DUP_FLAG, "Y");
+            message.set(tag::SENDING_TIME, format_timestamp(now));
+            message.set(tag::ORIG_SENDING_TIME, original);
+            self.outbox.push(message);
+        }
+        if let Some(start) = gap_start {
+            self.gap_fill(start, end + 1, now);
+        }
+        self.last_sent = now;
+    }
+
+    fn gap_fill(&mut self, seq: u64, new_seq: u64, now: u64) {
+        let gap_fill = Message::new(msg_type::SEQUENCE_RESET)
+            .with(tag::SENDER_COMP_ID, &self.sender_comp_id)
+            .with(tag::TARGET_COMP_ID, &self.target_comp_id)
+            .with(tag::MSG_SEQ_NUM, seq)
+            .with(tag::POSS_DUP_FLAG, "Y")
+            .with(tag::SENDING_TIME, format_timestamp(now))
+            .with(tag::GAP_FILL_FLAG, "Y")
+            .with(tag::NEW_SEQ_NO, new_seq);
+        self.outbox.push(gap_fill);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = NANOS_PER_SECOND;
+
+    fn message(msg_type: &str, seq: u64) -> Message {
+        return Message::new(msg_type)
+            .with(tag::SENDER_COMP_ID, "CLIENT")
+            .with(tag::TARGET_COMP_ID, "EXCHANGE")
+            .with(tag::MSG_SEQ_NUM, seq)
+            .with(tag::SENDING_TIME, format_timestamp(0));
+    }
+
+    fn logon(seq: u64) -> Message {
+        return message(msg_type::LOGON, seq)
+            .with(tag::ENCRYPT_METHOD, 0)
+            .with(tag::HEART_BT_INT, 30);
+    }
+
+    fn types(messages: &[Message]) -> Vec<String> {
+        return messages
+            .iter()
+            .map(|m| format!("{}:{}", m.msg_type(), m.get(tag::MSG_SEQ_NUM).unwrap()))
+            .collect();
+    }
+
+    #[test]
+    fn test_logon_and_logout() {
+        let mut session = Session::new("EXCHANGE", "CLIENT");
+
+        assert_eq!(session.receive(message(msg_type::HEARTBEAT, 1), 0), None);
+        assert!(session.should_disconnect());
+        assert_eq!(types(&session.take_outbox()), ["5:1"]);
+        session.disconnected();
+
+        session.receive(logon(1), 0);
+        assert!(session.is_logged_on());
+        let reply = session.take_outbox();
+        assert_eq!(types(&reply), ["A:2"]);
+        assert_eq!(reply[0].get(tag::HEART_BT_INT), Some("30"));
+
+        let order = message(msg_type::NEW_ORDER_SINGLE, 2);
+        assert_eq!(session.receive(order.clone(), 0), Some(order));
+
+        session.receive(message(msg_type::LOGOUT, 3), 0);
+        assert_eq!(types(&session.take_outbox()), ["5:3"]);
+        assert!(session.should_disconnect());
+        assert_eq!(session.next_target_seq(), 4);
+    }
+
+    #[test]
+    fn test_rejects_heartbeat_interval_too_large() {
+        let mut session = Session::new("EXCHANGE", "CLIENT");
+        let logon = message(msg_type::LOGON, 1)
+            .with(tag::ENCRYPT_METHOD, 0)
+            .with(tag::HEART_BT_INT, u64::MAX);
+
+        assert_eq!(session.receive(logon, 0), None);
+        assert!(!session.is_logged_on());
+        assert!(session.should_disconnect());
+        let outbox = session.take_outbox();
+        assert_eq!(types(&outbox), ["5:1"]);
+        assert_eq!(outbox[0].get(tag::TEXT), Some("HeartBtInt too large"));
+    }
+
+    #[test]
+    fn test_gap_is_resent() {
+        let mut session = Session::new("EXCHANGE", "CLIENT");
+        session.receive(logon(1), 0);
+        session.take_outbox();
+
+        // Messages 2 and 3 went missing
+        assert_eq!(
+            session.receive(message(msg_type::NEW_ORDER_SINGLE, 4), 0),
+            None
+        );
+        let outbox = session.take_outbox();
+        assert_eq!(types(&outbox), ["2:2"]);
+        assert_eq!(outbox[0].get(tag::BEGIN_SEQ_NO), Some("2"));
+
+        // Only one ResendRequest goes out for the gap
+        session.receive(message(msg_type::NEW_ORDER_SINGLE, 5), 0);
+        assert!(session.take_outbox().is_empty());
+
+        let gap_fill = message(msg_type::SEQUENCE_RESET, 2)
+            .with(tag::POSS_DUP_FLAG, "Y")
+            .with(tag::GAP_FILL_FLAG, "Y")
+            .with(tag::NEW_SEQ_NO, 3);
+        assert_eq!(session.receive(gap_fill, 0), None);
+        let resent = message(msg_type::NEW_ORDER_SINGLE, 3).with(tag::POSS_DUP_FLAG, "Y");
+        assert!(session.receive(resent, 0).is_some());
+        assert!(session
+            .receive(message(msg_type::NEW_ORDER_SINGLE, 4), 0)
+            .is_some());
+        assert_eq!(session.next_target_seq(), 5);
+
+        // A duplicate of something already processed is dropped
+        let duplicate = message(msg_type::NEW_ORDER_SINGLE, 2).with(tag::POSS_DUP_FLAG, "Y");
+        assert_eq!(session.receive(duplicate, 0), None);
+        assert!(!session.should_disconnect());
+
+        // Without PossDupFlag a low sequence number is fatal
+        session.receive(message(msg_type::HEARTBEAT, 2), 0);
+        assert!(session.should_disconnect());
+    }
+
+    #[test]
+    fn test_resend_request() {
+        let mut session = Session::new("EXCHANGE", "CLIENT");
+        session.receive(logon(1), 0);
+        session.send(
+            Message::new(msg_type::EXECUTION_REPORT).with(tag::EXEC_ID, 1),
+            0,
+        );
+        session.send(Message::new(msg_type::HEARTBEAT), 0);
+        session.send(Message::new(msg_type::HEARTBEAT), 0);
+        session.send(
+            Message::new(msg_type::EXECUTION_REPORT).with(tag::EXEC_ID, 2),
+            0,
+        );
+        session.take_outbox();
+
+        let request = message(msg_type::RESEND_REQUEST, 2)
+            .with(tag::BEGIN_SEQ_NO, 1)
+            .with(tag::END_SEQ_NO, 0);
+        session.receive(request, SECOND);
+        let outbox = session.take_outbox();
+
+        // The Logon at 1 and the heartbeats at 3 and 4 are gap filled
+        assert_eq!(types(&outbox), ["4:1", "8:2", "4:3", "8:5"]);
+        assert_eq!(outbox[0].get(tag::NEW_SEQ_NO), Some("2"));
+        assert_eq!(outbox[2].get(tag::NEW_SEQ_NO), Some("5"));
+        assert_eq!(outbox[1].get(tag::POSS_DUP_FLAG), Some("Y"));
+        assert_eq!(
+            outbox[1].get(tag::ORIG_SENDING_TIME),
+            Some("19700101-00:00:00.000")
+        );
+        assert_eq!(
+            outbox[1].get(tag::SENDING_TIME),
+            Some("19700101-00:00:01.000")
+        );
+        assert_eq!(session.next_sender_seq(), 6);
+    }
+
+    #[test]
+    fn test_heartbeats() {
+        let mut session = Session::new("EXCHANGE", "CLIENT");
+        session.receive(logon(1), 0);
+        session.take_outbox();
+
+        session.tick(29 * SECOND);
+        assert!(session.take_outbox().is_empty());
+        session.receive(message(msg_type::HEARTBEAT, 2), 29 * SECOND);
+        session.tick(30 * SECOND);
+        assert_eq!(types(&session.take_outbox()), ["0:2"]);
+
+        let test_request = message(msg_type::TEST_REQUEST, 3).with(tag::TEST_REQ_ID, "abc");
+        session.receive(test_request, 31 * SECOND);
+        let outbox = session.take_outbox();
+        assert_eq!(outbox[0].get(tag::TEST_REQ_ID), Some("abc"));
+
+        // Nothing heard for 1.2 intervals, then for another interval
+        session.tick(67 * SECOND);
+        assert_eq!(types(&session.take_outbox()), ["1:4"]);
+        session.tick(96 * SECOND);
+        assert!(!session.should_disconnect());
+        session.tick(97 * SECOND);
+        assert_eq!(types(&session.take_outbox()), ["5:5"]);
+        assert!(session.should_disconnect());
+    }
+}
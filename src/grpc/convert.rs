@@ -0,0 +1,279 @@
+use rust_decimal::prelude::*;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use tonic::Status;
+use uuid::Uuid;
+
+use crate::grpc::proto;
+use crate::order::{Order, Side};
+use crate::order_manager::{OrderError, OrderStatus, OrderType, Report, ReportKind, TimeInForce};
+use crate::trade::Trade;
+use crate::{Fill, FillStatus};
+
+/// A message that doesn't describe a valid value of the crate's own type.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct WireError(pub String);
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.0);
+    }
+}
+
+impl Error for WireError {}
+
+impl From<WireError> for Status {
+    fn from(e: WireError) -> Self {
+        return Status::invalid_argument(e.0);
+    }
+}
+
+/// Parses the decimal string in `field`.
+pub fn parse_decimal(field: &str, value: &str) -> Result<Decimal, WireError> {
+    return Decimal::from_str(value)
+        .map_err(|_| WireError(format!("{} isn't a decimal: '{}'", field, value)));
+}
+
+/// Reads an enum field holding a `Side`.
+pub fn parse_side(value: i32) -> Result<Side, WireError> {
+    match proto::Side::try_from(value) {
+        Ok(side) => return Side::try_from(side),
+        Err(_) => return Err(WireError(format!("unknown side: {}", value))),
+    }
+}
+
+fn parse_id(field: &str, value: &str) -> Result<Uuid, WireError> {
+    return Uuid::parse_str(value)
+        .map_err(|_| WireError(format!("{} isn't a UUID: '{}'", field, value)));
+}
+
+impl From<Side> for proto::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Bid => return proto::Side::Bid,
+            Side::Ask => return proto::Side::Ask,
+        }
+    }
+}
+
+impl TryFrom<proto::Side> for Side {
+    type Error = WireError;
+
+    fn try_from(side: proto::Side) -> Result<Self, WireError> {
+        match side {
+            proto::Side::Bid => return Ok(Side::Bid),
+            proto::Side::Ask => return Ok(Side::Ask),
+            proto::Side::Unspecified => return Err(WireError(String::from("side is missing"))),
+        }
+    }
+}
+
+impl From<Order> for proto::Order {
+    fn from(order: Order) -> Self {
+        return proto::Order {
+            id: order.id.to_string(),
+            side: proto::Side::from(order.side).into(),
+            timestamp: order.timestamp,
+            price: order.price.to_string(),
+            quantity: order.quantity.to_string(),
+        };
+    }
+}
+
+impl TryFrom<proto::Order> for Order {
+    type Error = WireError;
+
+    fn try_from(order: proto::Order) -> Result<Self, WireError> {
+        return Ok(Order {
+            id: parse_id("id", &order.id)?,
+            side: parse_side(order.side)?,
+            timestamp: order.timestamp,
+            price: parse_decimal("price", &order.price)?,
+            quantity: parse_decimal("quantity", &order.quantity)?,
+        });
+    }
+}
+
+impl From<Fill> for proto::Fill {
+    fn from(fill: Fill) -> Self {
+        let status = match fill.status {
+            FillStatus::Full => proto::FillStatus::Full,
+            FillStatus::Partial => proto::FillStatus::Partial,
+        };
+        return proto::Fill {
+            order_id: fill.order_id.to_string(),
+            status: status.into(),
+            price: fill.price.to_string(),
+            quantity: fill.quantity.to_string(),
+        };
+    }
+}
+
+impl TryFrom<proto::Fill> for Fill {
+    type Error = WireError;
+
+    fn try_from(fill: proto::Fill) -> Result<Self, WireError> {
+        let status = match proto::FillStatus::try_from(fill.status) {
+            Ok(proto::FillStatus::Full) => FillStatus::Full,
+            Ok(proto::FillStatus::Partial) => FillStatus::Partial,
+            _ => return Err(WireError(format!("unknown fill status: {}", fill.status))),
+        };
+        return Ok(Fill {
+            order_id: parse_id("order_id", &fill.order_id)?,
+            status,
+            price: parse_decimal("price", &fill.price)?,
+            quantity: parse_decimal("quantity", &fill.quantity)?,
+        });
+    }
+}
+
+impl From<Trade> for proto::Trade {
+    fn from(trade: Trade) -> Self {
+        return proto::Trade {
+            aggressor: proto::Side::from(trade.aggressor).into(),
+            maker_id: trade.maker_id.to_string(),
+            price: trade.price.to_string(),
+            quantity: trade.quantity.to_string(),
+        };
+    }
+}
+
+impl TryFrom<proto::Trade> for Trade {
+    type Error = WireError;
+
+    fn try_from(trade: proto::Trade) -> Result<Self, WireError> {
+        return Ok(Trade {
+            aggressor: parse_side(trade.aggressor)?,
+            maker_id: parse_id("maker_id", &trade.maker_id)?,
+            price: parse_decimal("price", &trade.price)?,
+            quantity: parse_decimal("quantity", &trade.quantity)?,
+        });
+    }
+}
+
+impl From<OrderError> for Status {
+    fn from(e: OrderError) -> Self {
+        match e {
+            OrderError::Unknown(_) => return Status::not_found(e.to_string()),
+            OrderError::Closed(_) => return Status::failed_precondition(e.to_string()),
+            OrderError::Invalid(message) => return Status::invalid_argument(message),
+        }
+    }
+}
+
+impl From<OrderType> for proto::OrderType {
+    fn from(order_type: OrderType) -> Self {
+        match order_type {
+            OrderType::Limit => return proto::OrderType::Limit,
+            OrderType::Market => return proto::OrderType::Market,
+        }
+    }
+}
+
+impl From<TimeInForce> for proto::TimeInForce {
+    fn from(time_in_force: TimeInForce) -> Self {
+        match time_in_force {
+            TimeInForce::Gtc => return proto::TimeInForce::Gtc,
+            TimeInForce::Ioc => return proto::TimeInForce::Ioc,
+        }
+    }
+}
+
+impl From<OrderStatus> for proto::OrderStatus {
+    fn from(status: OrderStatus) -> Self {
+        match status {
+            OrderStatus::New => return proto::OrderStatus::New,
+            OrderStatus::PartiallyFilled => return proto::OrderStatus::PartiallyFilled,
+            OrderStatus::Filled => return proto::OrderStatus::Filled,
+            OrderStatus::Canceled => return proto::OrderStatus::Canceled,
+        }
+    }
+}
+
+impl From<&Report> for proto::ExecutionReport {
+    fn from(report: &Report) -> Self {
+        let order = &report.order;
+        let (execution_type, last_price, last_quantity) = match report.kind {
+            ReportKind::New => (proto::ExecutionType::New, None, None),
+            ReportKind::Trade { price, quantity } => {
+                (proto::ExecutionType::Trade, Some(price), Some(quantity))
+            }
+            ReportKind::Amended => (proto::ExecutionType::Amended, None, None),
+            ReportKind::Canceled => (proto::ExecutionType::Canceled, None, None),
+        };
+        let text = |value: Option<Decimal>| value.map(|v| v.to_string()).unwrap_or_default();
+        return proto::ExecutionReport {
+            order_id: order.id,
+            client_order_id: order.client_order_id.clone().unwrap_or_default(),
+            execution_type: execution_type.into(),
+            status: proto::OrderStatus::from(order.status).into(),
+            side: proto::Side::from(order.side).into(),
+            r#type: proto::OrderType::from(order.order_type).into(),
+            time_in_force: proto::TimeInForce::from(order.time_in_force).into(),
+            price: text(order.price),
+            quantity: order.quantity.to_string(),
+            filled: order.filled.to_string(),
+            open: order.open.to_string(),
+            last_price: text(last_price),
+            last_quantity: text(last_quantity),
+            timestamp: report.timestamp,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OrderBook;
+    use prost::Message;
+    use rust_decimal_macros::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut order_book = OrderBook::new();
+        let resting = order_book
+            .submit_limit_order(Side::Ask, dec!(2.5), dec!(10.25))
+            .partial
+            .unwrap();
+        let result = order_book.submit_limit_order(Side::Bid, dec!(1), dec!(11));
+        let fill = result.done[0];
+        let trade = Trade::from_result(Side::Bid, &result)[0];
+
+        let bytes = proto::Order::from(resting).encode_to_vec();
+        let decoded = proto::Order::decode(bytes.as_slice()).unwrap();
+        assert_eq!(decoded.price, "10.25");
+        assert_eq!(Order::try_from(decoded).unwrap(), resting);
+
+        let bytes = proto::Fill::from(fill).encode_to_vec();
+        let decoded = proto::Fill::decode(bytes.as_slice()).unwrap();
+        assert_eq!(Fill::try_from(decoded).unwrap(), fill);
+
+        let bytes = proto::Trade::from(trade).encode_to_vec();
+        let decoded = proto::Trade::decode(bytes.as_slice()).unwrap();
+        assert_eq!(Trade::try_from(decoded).unwrap(), trade);
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        let order = proto::Order {
+            id: Uuid::new_v4().to_string(),
+            side: proto::Side::Bid.into(),
+            timestamp: 0,
+            price: String::from("ten"),
+            quantity: String::from("1"),
+        };
+        assert_eq!(
+            Order::try_from(order.clone()),
+            Err(WireError(String::from("price isn't a decimal: 'ten'")))
+        );
+        let order = proto::Order {
+            side: proto::Side::Unspecified.into(),
+            ..order
+        };
+        assert_eq!(
+            Order::try_from(order),
+            Err(WireError(String::from("side is missing")))
+        );
+    }
+}
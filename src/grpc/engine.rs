@@ -0,0 +1,311 @@
+use std::convert::TryFrom;
+use tokio::sync::broadcast;
+use tonic::Status;
+
+use crate::grpc::convert::{parse_decimal, parse_side};
+use crate::grpc::proto::market_data_update::Update;
+use crate::grpc::proto::{
+    self, AmendOrderRequest, AmendOrderResponse, BookSnapshot, CancelOrderRequest,
+    CancelOrderResponse, ExecutionReport, Level, MarketDataUpdate, SubmitOrderRequest,
+    SubmitOrderResponse, TradeEvent,
+};
+use crate::order::Side;
+use crate::order_manager::{NewOrder, OrderChange, OrderManager, OrderType, Report, TimeInForce};
+use crate::OrderBook;
+
+/// How many messages a stream can fall behind by before it misses some.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Runs an `OrderManager` for the gRPC service, in terms of its messages.
+///
+/// Every execution report is broadcast to the execution report streams,
+/// and each change to the book sends a snapshot and the trades behind it to
+/// the market data streams.
+#[derive(Debug)]
+pub struct Engine {
+    orders: OrderManager,
+    reports: broadcast::Sender<ExecutionReport>,
+    market_data: broadcast::Sender<MarketDataUpdate>,
+}
+
+impl Engine {
+    /// Runs `order_book`, turning on its event recording.
+    pub fn new(order_book: OrderBook) -> Self {
+        return Engine {
+            orders: OrderManager::new(order_book),
+            reports: broadcast::channel(CHANNEL_CAPACITY).0,
+            market_data: broadcast::channel(CHANNEL_CAPACITY).0,
+        };
+    }
+
+    pub fn order_book(&self) -> &OrderBook {
+        return self.orders.order_book();
+    }
+
+    /// The execution reports sent from now on.
+    pub fn subscribe_reports(&self) -> broadcast::Receiver<ExecutionReport> {
+        return self.reports.subscribe();
+    }
+
+    /// A snapshot of the whole book, and the market data sent after it.
+    pub fn subscribe_market_data(&self) -> (BookSnapshot, broadcast::Receiver<MarketDataUpdate>) {
+        return (self.snapshot(), self.market_data.subscribe());
+    }
+
+    pub fn snapshot(&self) -> BookSnapshot {
+        let order_book = self.order_book();
+        let levels = |side| {
+            return order_book
+                .levels(side)
+                .map(|level| Level {
+                    price: level.price.to_string(),
+                    quantity: level.volume.to_string(),
+                    order_count: order_book.orders_at(side, level.price).count() as u32,
+                })
+                .collect();
+        };
+        return BookSnapshot {
+            timestamp: order_book.now(),
+            bids: levels(Side::Bid),
+            asks: levels(Side::Ask),
+        };
+    }
+
+    pub fn submit(&mut self, request: SubmitOrderRequest) -> Result<SubmitOrderResponse, Status> {
+        let side = parse_side(request.side)?;
+        let quantity = parse_decimal("quantity", &request.quantity)?;
+        let price = match request.price.as_str() {
+            "" => None,
+            price => Some(parse_decimal("price", price)?),
+        };
+        let order_type = match proto::OrderType::try_from(request.r#type) {
+            Ok(proto::OrderType::Market) => OrderType::Market,
+            Ok(_) => OrderType::Limit,
+            Err(_) => return Err(Status::invalid_argument("unknown order type")),
+        };
+        let time_in_force = match proto::TimeInForce::try_from(request.time_in_force) {
+            Ok(proto::TimeInForce::Unspecified) => None,
+            Ok(proto::TimeInForce::Gtc) => Some(TimeInForce::Gtc),
+            Ok(proto::TimeInForce::Ioc) => Some(TimeInForce::Ioc),
+            Err(_) => return Err(Status::invalid_argument("unknown time in force")),
+        };
+        let order = NewOrder {
+            side,
+            order_type,
+            quantity,
+            price,
+            time_in_force,
+            client_order_id: Some(request.client_order_id).filter(|id| !id.is_empty()),
+        };
+
+        let reports = self.orders.submit(order)?;
+        let id = reports[0].order.id;
+        let reports = self.publish(id, &reports);
+        return Ok(SubmitOrderResponse { reports });
+    }
+
+    pub fn cancel(&mut self, request: CancelOrderRequest) -> Result<CancelOrderResponse, Status> {
+        let report = self.orders.cancel(request.order_id)?;
+        let report = self.publish(request.order_id, &[report]).pop();
+        return Ok(CancelOrderResponse { report });
+    }
+
+    pub fn amend(&mut self, request: AmendOrderRequest) -> Result<AmendOrderResponse, Status> {
+        let change = OrderChange {
+            quantity: match request.quantity.as_str() {
+                "" => None,
+                quantity => Some(parse_decimal("quantity", quantity)?),
+            },
+            price: match request.price.as_str() {
+                "" => None,
+                price => Some(parse_decimal("price", price)?),
+            },
+        };
+        let reports = self.orders.amend(request.order_id, change)?;
+        let reports = self.publish(request.order_id, &reports);
+        return Ok(AmendOrderResponse { reports });
+    }
+
+    /// Broadcasts the reports, the trades behind them and the book they
+    /// left, and returns the execution reports of order `id`.
+    fn publish(&mut self, id: u64, reports: &[Report]) -> Vec<ExecutionReport> {
+        let mut own = Vec::new();
+        for report in reports.iter() {
+            let report = ExecutionReport::from(report);
+            if report.order_id == id {
+                own.push(report.clone());
+            }
+            let _ = self.reports.send(report);
+        }
+        for (timestamp, trade) in self.orders.take_trades() {
+            let update = Update::Trade(TradeEvent {
+                timestamp,
+                trade: Some(trade.into()),
+            });
+            let _ = self.market_data.send(MarketDataUpdate {
+                update: Some(update),
+            });
+        }
+        let update = MarketDataUpdate {
+            update: Some(Update::Book(self.snapshot())),
+        };
+        let _ = self.market_data.send(update);
+        return own;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::grpc::proto::{ExecutionType, OrderStatus, OrderType, TimeInForce};
+    use crate::id_generator::SequentialIdGenerator;
+
+    fn limit(side: proto::Side, quantity: &str, price: &str) -> SubmitOrderRequest {
+        return SubmitOrderRequest {
+            side: side.into(),
+            r#type: OrderType::Limit.into(),
+            quantity: quantity.to_string(),
+            price: price.to_string(),
+            time_in_force: TimeInForce::Unspecified.into(),
+            client_order_id: String::new(),
+        };
+    }
+
+    fn summary(report: &ExecutionReport) -> (u64, ExecutionType, OrderStatus, &str, &str) {
+        return (
+            report.order_id,
+            report.execution_type(),
+            report.status(),
+            report.filled.as_str(),
+            report.open.as_str(),
+        );
+    }
+
+    #[test]
+    fn test_reports() {
+        let clock = ManualClock::new(5);
+        let mut engine = Engine::new(OrderBook::with_clock(
+            clock.clone(),
+            SequentialIdGenerator::new(),
+        ));
+        let mut reports = engine.subscribe_reports();
+
+        let response = engine.submit(limit(proto::Side::Ask, "10", "100")).unwrap();
+        assert_eq!(
+            response.reports.iter().map(summary).collect::<Vec<_>>(),
+            vec![(1, ExecutionType::New, OrderStatus::New, "0", "10")]
+        );
+
+        clock.advance(1);
+        let mut request = limit(proto::Side::Bid, "4", "101");
+        request.time_in_force = TimeInForce::Ioc.into();
+        request.client_order_id = String::from("b");
+        let response = engine.submit(request).unwrap();
+        assert_eq!(
+            response.reports.iter().map(summary).collect::<Vec<_>>(),
+            vec![
+                (2, ExecutionType::New, OrderStatus::New, "0", "4"),
+                (2, ExecutionType::Trade, OrderStatus::Filled, "4", "0"),
+            ]
+        );
+        assert_eq!(response.reports[1].client_order_id, "b");
+        assert_eq!(response.reports[1].last_price, "100");
+        assert_eq!(response.reports[1].timestamp, 6);
+
+        // The stream has every report, the maker's included
+        let streamed: Vec<ExecutionReport> =
+            std::iter::from_fn(|| reports.try_recv().ok()).collect();
+        assert_eq!(
+            streamed.iter().map(summary).collect::<Vec<_>>(),
+            vec![
+                (1, ExecutionType::New, OrderStatus::New, "0", "10"),
+                (2, ExecutionType::New, OrderStatus::New, "0", "4"),
+                (2, ExecutionType::Trade, OrderStatus::Filled, "4", "0"),
+                (
+                    1,
+                    ExecutionType::Trade,
+                    OrderStatus::PartiallyFilled,
+                    "4",
+                    "6"
+                ),
+            ]
+        );
+
+        let request = AmendOrderRequest {
+            order_id: 1,
+            quantity: String::from("12"),
+            price: String::new(),
+        };
+        let response = engine.amend(request).unwrap();
+        assert_eq!(
+            summary(&response.reports[0]),
+            (
+                1,
+                ExecutionType::Amended,
+                OrderStatus::PartiallyFilled,
+                "4",
+                "8"
+            )
+        );
+
+        let response = engine.cancel(CancelOrderRequest { order_id: 1 }).unwrap();
+        assert_eq!(
+            summary(&response.report.unwrap()),
+            (1, ExecutionType::Canceled, OrderStatus::Canceled, "4", "0")
+        );
+        assert_eq!(engine.order_book().best_ask(), None);
+
+        let error = engine
+            .cancel(CancelOrderRequest { order_id: 1 })
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::FailedPrecondition);
+        let error = engine
+            .cancel(CancelOrderRequest { order_id: 9 })
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::NotFound);
+    }
+
+    #[test]
+    fn test_market_orders() {
+        let mut engine = Engine::new(OrderBook::with_clock(
+            ManualClock::new(0),
+            SequentialIdGenerator::new(),
+        ));
+        engine.submit(limit(proto::Side::Bid, "2", "9")).unwrap();
+        engine.submit(limit(proto::Side::Bid, "1", "9")).unwrap();
+        let (snapshot, mut updates) = engine.subscribe_market_data();
+        assert_eq!(
+            snapshot.bids,
+            vec![Level {
+                price: String::from("9"),
+                quantity: String::from("3"),
+                order_count: 2,
+            }]
+        );
+
+        let mut request = limit(proto::Side::Ask, "5", "");
+        request.r#type = OrderType::Market.into();
+        let response = engine.submit(request.clone()).unwrap();
+        let last = response.reports.last().unwrap();
+        assert_eq!(
+            summary(last),
+            (3, ExecutionType::Canceled, OrderStatus::Canceled, "3", "0")
+        );
+        assert_eq!(last.r#type(), OrderType::Market);
+        assert_eq!(last.time_in_force(), TimeInForce::Ioc);
+
+        let updates: Vec<Update> = std::iter::from_fn(|| updates.try_recv().ok())
+            .map(|update| update.update.unwrap())
+            .collect();
+        assert_eq!(updates.len(), 3);
+        assert!(matches!(updates[0], Update::Trade(_)));
+        assert!(matches!(updates[1], Update::Trade(_)));
+        assert_eq!(updates[2], Update::Book(engine.snapshot()));
+
+        request.price = String::from("8");
+        let error = engine.submit(request).unwrap_err();
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+        assert_eq!(error.message(), "market orders can't have a price");
+    }
+}
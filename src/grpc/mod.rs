@@ -0,0 +1,12 @@
+pub mod convert;
+pub mod engine;
+pub mod service;
+
+/// The messages and service generated from `proto/orderbook.proto`.
+pub mod proto {
+    tonic::include_proto!("orderbook.v1");
+}
+
+pub use convert::WireError;
+pub use engine::Engine;
+pub use service::{serve, Service};
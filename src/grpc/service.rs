@@ -0,0 +1,191 @@
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, TcpListenerStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+use crate::grpc::engine::Engine;
+use crate::grpc::proto::market_data_update::Update;
+use crate::grpc::proto::matching_engine_server::{MatchingEngine, MatchingEngineServer};
+use crate::grpc::proto::{
+    AmendOrderRequest, AmendOrderResponse, CancelOrderRequest, CancelOrderResponse,
+    ExecutionReport, ExecutionReportsRequest, MarketDataRequest, MarketDataUpdate,
+    SubmitOrderRequest, SubmitOrderResponse,
+};
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+/// The `MatchingEngine` gRPC service, handling calls one at a time on an
+/// `Engine`.
+///
+/// A stream that falls too far behind ends with `DATA_LOSS`, and can be
+/// opened again to pick up from a fresh snapshot.
+#[derive(Clone, Debug)]
+pub struct Service {
+    engine: Arc<Mutex<Engine>>,
+}
+
+impl Service {
+    pub fn new(engine: Engine) -> Self {
+        return Service {
+            engine: Arc::new(Mutex::new(engine)),
+        };
+    }
+
+    pub fn into_server(self) -> MatchingEngineServer<Self> {
+        return MatchingEngineServer::new(self);
+    }
+}
+
+/// Serves `engine` to connections on `listener`. There's no
+/// authentication, so keep it on localhost.
+pub async fn serve(listener: TcpListener, engine: Engine) -> Result<(), tonic::transport::Error> {
+    return tonic::transport::Server::builder()
+        .add_service(Service::new(engine).into_server())
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await;
+}
+
+#[tonic::async_trait]
+impl MatchingEngine for Service {
+    async fn submit_order(
+        &self,
+        request: Request<SubmitOrderRequest>,
+    ) -> Result<Response<SubmitOrderResponse>, Status> {
+        let response = self.engine.lock().unwrap().submit(request.into_inner())?;
+        return Ok(Response::new(response));
+    }
+
+    async fn cancel_order(
+        &self,
+        request: Request<CancelOrderRequest>,
+    ) -> Result<Response<CancelOrderResponse>, Status> {
+        let response = self.engine.lock().unwrap().cancel(request.into_inner())?;
+        return Ok(Response::new(response));
+    }
+
+    async fn amend_order(
+        &self,
+        request: Request<AmendOrderRequest>,
+    ) -> Result<Response<AmendOrderResponse>, Status> {
+        let response = self.engine.lock().unwrap().amend(request.into_inner())?;
+        return Ok(Response::new(response));
+    }
+
+    type StreamMarketDataStream = ResponseStream<MarketDataUpdate>;
+
+    async fn stream_market_data(
+        &self,
+        request: Request<MarketDataRequest>,
+    ) -> Result<Response<Self::StreamMarketDataStream>, Status> {
+        let depth = match request.into_inner().depth {
+            0 => usize::MAX,
+            depth => depth as usize,
+        };
+        let (snapshot, receiver) = self.engine.lock().unwrap().subscribe_market_data();
+        let snapshot = MarketDataUpdate {
+            update: Some(Update::Book(snapshot)),
+        };
+        let updates = tokio_stream::once(Ok(snapshot))
+            .chain(BroadcastStream::new(receiver).map(missed))
+            .map(move |update| update.map(|update| limit_depth(update, depth)));
+        return Ok(Response::new(Box::pin(updates)));
+    }
+
+    type StreamExecutionReportsStream = ResponseStream<ExecutionReport>;
+
+    async fn stream_execution_reports(
+        &self,
+        _: Request<ExecutionReportsRequest>,
+    ) -> Result<Response<Self::StreamExecutionReportsStream>, Status> {
+        let receiver = self.engine.lock().unwrap().subscribe_reports();
+        let reports = BroadcastStream::new(receiver).map(missed);
+        return Ok(Response::new(Box::pin(reports)));
+    }
+}
+
+fn missed<T>(received: Result<T, BroadcastStreamRecvError>) -> Result<T, Status> {
+    return received.map_err(|BroadcastStreamRecvError::Lagged(count)| {
+        return Status::data_loss(format!("fell behind and missed {} messages", count));
+    });
+}
+
+fn limit_depth(mut update: MarketDataUpdate, depth: usize) -> MarketDataUpdate {
+    if let Some(Update::Book(book)) = update.update.as_mut() {
+        book.bids.truncate(depth);
+        book.asks.truncate(depth);
+    }
+    return update;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::grpc::proto::matching_engine_client::MatchingEngineClient;
+    use crate::grpc::proto::{ExecutionType, OrderStatus, Side};
+    use crate::id_generator::SequentialIdGenerator;
+    use crate::OrderBook;
+    use tonic::transport::Channel;
+
+    #[tokio::test]
+    async fn test_grpc() {
+        let order_book = OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Engine::new(order_book)));
+
+        let channel = Channel::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = MatchingEngineClient::new(channel);
+        let mut reports = client
+            .stream_execution_reports(ExecutionReportsRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        let mut market_data = client
+            .stream_market_data(MarketDataRequest { depth: 1 })
+            .await
+            .unwrap()
+            .into_inner();
+        let snapshot = market_data.message().await.unwrap().unwrap();
+        match snapshot.update {
+            Some(Update::Book(book)) => assert!(book.bids.is_empty() && book.asks.is_empty()),
+            update => panic!("expected a snapshot, got {:?}", update),
+        }
+
+        for price in ["10", "11"].iter() {
+            let request = SubmitOrderRequest {
+                side: Side::Ask.into(),
+                quantity: String::from("5"),
+                price: price.to_string(),
+                ..SubmitOrderRequest::default()
+            };
+            let response = client.submit_order(request).await.unwrap().into_inner();
+            assert_eq!(response.reports[0].status(), OrderStatus::New);
+        }
+
+        let report = reports.message().await.unwrap().unwrap();
+        assert_eq!(report.order_id, 1);
+        assert_eq!(report.execution_type(), ExecutionType::New);
+        market_data.message().await.unwrap();
+        match market_data.message().await.unwrap().unwrap().update {
+            Some(Update::Book(book)) => {
+                assert_eq!(book.asks.len(), 1);
+                assert_eq!(book.asks[0].price, "10");
+            }
+            update => panic!("expected a snapshot, got {:?}", update),
+        }
+
+        let error = client
+            .cancel_order(CancelOrderRequest { order_id: 3 })
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::NotFound);
+    }
+}
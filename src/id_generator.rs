@@ -0,0 +1,81 @@
+use std::fmt;
+use uuid::Uuid;
+
+/// A source of ids for new orders.
+pub trait IdGenerator: fmt::Debug + Send {
+    fn next_id(&mut self) -> Uuid;
+}
+
+/// Generates random (v4) UUIDs.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UuidGenerator;
+
+impl IdGenerator for UuidGenerator {
+    fn next_id(&mut self) -> Uuid {
+        return Uuid::new_v4();
+    }
+}
+
+/// Generates predictable ids from a counter, starting at 1.
+///
+/// The counter is stored big-endian in the last eight bytes of the UUID, so
+/// ids sort in the order they were generated.
+#[derive(Clone, Debug)]
+pub struct SequentialIdGenerator {
+    next: u64,
+}
+
+impl SequentialIdGenerator {
+    pub fn new() -> Self {
+        return SequentialIdGenerator::starting_at(1);
+    }
+
+    pub fn starting_at(next: u64) -> Self {
+        return SequentialIdGenerator { next };
+    }
+}
+
+impl Default for SequentialIdGenerator {
+    fn default() -> Self {
+        return SequentialIdGenerator::new();
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn next_id(&mut self) -> Uuid {
+        let id = sequential_id(self.next);
+        self.next += 1;
+        return id;
+    }
+}
+
+/// Returns the id `SequentialIdGenerator` produces for `n`.
+pub fn sequential_id(n: u64) -> Uuid {
+    let mut bytes = [0u8; 16];
+    bytes[8..].copy_from_slice(&n.to_be_bytes());
+    return Uuid::from_bytes(&bytes).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential_ids() {
+        let mut ids = SequentialIdGenerator::new();
+
+        let first = ids.next_id();
+        let second = ids.next_id();
+
+        assert_eq!(first, sequential_id(1));
+        assert_eq!(second, sequential_id(2));
+        assert!(first < second);
+    }
+
+    #[test]
+    fn test_uuid_ids_are_unique() {
+        let mut ids = UuidGenerator;
+
+        assert_ne!(ids.next_id(), ids.next_id());
+    }
+}
@@ -0,0 +1,86 @@
+use rust_decimal::prelude::*;
+
+use crate::numeric::{Lots, Ticks};
+
+/// The tick and lot size of a traded instrument, used to convert between
+/// decimal prices and quantities and their `Ticks` and `Lots` equivalents.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Instrument {
+    pub tick_size: Decimal,
+    pub lot_size: Decimal,
+}
+
+impl Instrument {
+    pub fn new(tick_size: Decimal, lot_size: Decimal) -> Self {
+        return Instrument {
+            tick_size,
+            lot_size,
+        };
+    }
+
+    /// Returns `price` in ticks, or `None` if it isn't a whole number of ticks.
+    pub fn to_ticks(&self, price: Decimal) -> Option<Ticks> {
+        return whole_multiple(price, self.tick_size)
+            .and_then(|n| n.to_i64())
+            .map(Ticks);
+    }
+
+    pub fn from_ticks(&self, price: Ticks) -> Decimal {
+        return Decimal::from(price.0) * self.tick_size;
+    }
+
+    /// Returns `quantity` in lots, or `None` if it isn't a whole, non-negative
+    /// number of lots.
+    pub fn to_lots(&self, quantity: Decimal) -> Option<Lots> {
+        return whole_multiple(quantity, self.lot_size)
+            .and_then(|n| n.to_u64())
+            .map(Lots);
+    }
+
+    pub fn from_lots(&self, quantity: Lots) -> Decimal {
+        return Decimal::from(quantity.0) * self.lot_size;
+    }
+}
+
+fn whole_multiple(value: Decimal, unit: Decimal) -> Option<Decimal> {
+    if unit <= Decimal::zero() {
+        return None;
+    }
+
+    let n = value / unit;
+    if n.fract() != Decimal::zero() {
+        return None;
+    }
+
+    return Some(n);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::*;
+
+    #[test]
+    fn test_ticks() {
+        let instrument = Instrument::new(dec!(0.05), dec!(100));
+
+        assert_eq!(instrument.to_ticks(dec!(10.25)), Some(Ticks(205)));
+        assert_eq!(instrument.to_ticks(dec!(-0.10)), Some(Ticks(-2)));
+        assert_eq!(instrument.from_ticks(Ticks(205)), dec!(10.25));
+
+        // Off-tick prices are rejected
+        assert_eq!(instrument.to_ticks(dec!(10.26)), None);
+    }
+
+    #[test]
+    fn test_lots() {
+        let instrument = Instrument::new(dec!(0.05), dec!(100));
+
+        assert_eq!(instrument.to_lots(dec!(2500)), Some(Lots(25)));
+        assert_eq!(instrument.from_lots(Lots(25)), dec!(2500));
+
+        // Odd lots and negative quantities are rejected
+        assert_eq!(instrument.to_lots(dec!(150)), None);
+        assert_eq!(instrument.to_lots(dec!(-100)), None);
+    }
+}
@@ -0,0 +1,26 @@
+use std::error::Error;
+use std::fmt;
+
+/// A broken internal invariant, found by `OrderBook::check_invariants`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvariantViolation(pub String);
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "invariant violated: {}", self.0);
+    }
+}
+
+impl Error for InvariantViolation {}
+
+/// Returns an `InvariantViolation` from the enclosing function unless `$cond`
+/// holds. The remaining arguments are a `format!` message.
+macro_rules! ensure {
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            return Err($crate::invariants::InvariantViolation(format!($($arg)+)));
+        }
+    };
+}
+
+pub(crate) use ensure;
@@ -0,0 +1,324 @@
+use rust_decimal::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::itch::message::{Body, ItchError, Message};
+use crate::order::Side;
+use crate::OrderBook;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct ItchOrder {
+    side: Side,
+    price: u32,
+    shares: u32,
+}
+
+/// An order-by-order book rebuilt from an ITCH feed.
+#[derive(Clone, Debug, Default)]
+pub struct L3Book {
+    orders: HashMap<u64, ItchOrder>,
+    /// Order reference numbers at each price, in time priority.
+    bids: BTreeMap<u32, Vec<u64>>,
+    asks: BTreeMap<u32, Vec<u64>>,
+    /// Shares executed so far.
+    volume: u64,
+}
+
+impl L3Book {
+    pub fn new() -> Self {
+        return L3Book::default();
+    }
+
+    /// Applies a message to the book. Messages that don't change it, such as
+    /// System Events and Trades, are accepted and ignored.
+    pub fn apply(&mut self, message: &Message) -> Result<(), ItchError> {
+        match message.body {
+            Body::AddOrder {
+                reference,
+                side,
+                shares,
+                price,
+                ..
+            } => self.add(reference, side, shares, price)?,
+            Body::OrderExecuted {
+                reference,
+                executed_shares,
+                ..
+            } => {
+                self.reduce(reference, executed_shares)?;
+                self.volume += executed_shares as u64;
+            }
+            Body::OrderCancel {
+                reference,
+                canceled_shares,
+            } => self.reduce(reference, canceled_shares)?,
+            Body::OrderDelete { reference } => {
+                self.delete(reference)?;
+            }
+            Body::OrderReplace {
+                original_reference,
+                new_reference,
+                shares,
+                price,
+            } => {
+                let order = self.delete(original_reference)?;
+                self.add(new_reference, order.side, shares, price)?;
+            }
+            Body::SystemEvent { .. } | Body::Trade { .. } => {}
+        }
+        return Ok(());
+    }
+
+    /// Shares executed against the book so far.
+    pub fn volume(&self) -> u64 {
+        return self.volume;
+    }
+
+    pub fn num_orders(&self) -> usize {
+        return self.orders.len();
+    }
+
+    /// The price levels on `side`, best first, each as its price, volume
+    /// and number of orders.
+    pub fn levels(&self, side: Side) -> Vec<(Decimal, Decimal, usize)> {
+        let level = |(&price, queue): (&u32, &Vec<u64>)| {
+            let volume: u64 = queue.iter().map(|r| self.orders[r].shares as u64).sum();
+            return (to_price(price), Decimal::from(volume), queue.len());
+        };
+        match side {
+            Side::Bid => return self.bids.iter().rev().map(level).collect(),
+            Side::Ask => return self.asks.iter().map(level).collect(),
+        }
+    }
+
+    /// The orders at `price` on `side` in time priority, as their reference
+    /// numbers and shares.
+    pub fn orders_at(&self, side: Side, price: Decimal) -> Vec<(u64, Decimal)> {
+        let queue = from_price(price).and_then(|price| self.side(side).get(&price));
+        return queue
+            .map(|queue| {
+                queue
+                    .iter()
+                    .map(|r| (*r, Decimal::from(self.orders[r].shares)))
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    /// Checks that this book has the same orders as `order_book`, with the
+    /// same quantities and in the same order at every price.
+    pub fn compare<I>(&self, order_book: &OrderBook<Decimal, Decimal, I>) -> Result<(), ItchError>
+    where
+        I: crate::price_index::PriceIndex<Decimal>,
+    {
+        for &side in [Side::Bid, Side::Ask].iter() {
+            let ours = self.levels(side);
+            let theirs: Vec<Decimal> = order_book.levels(side).map(|l| l.price).collect();
+            if ours.len() != theirs.len() {
+                return Err(ItchError(format!(
+                    "{:?} side has {} levels on the feed but {} on the book",
+                    side,
+                    ours.len(),
+                    theirs.len()
+                )));
+            }
+
+            for (&(price, _, _), &book_price) in ours.iter().zip(theirs.iter()) {
+                if price != book_price {
+                    return Err(ItchError(format!(
+                        "{:?} side has a level at {} on the feed but {} on the book",
+                        side, price, book_price
+                    )));
+                }
+                let ours: Vec<Decimal> = self
+                    .orders_at(side, price)
+                    .iter()
+                    .map(|(_, shares)| *shares)
+                    .collect();
+                let theirs: Vec<Decimal> = order_book
+                    .orders_at(side, price)
+                    .map(|order| order.quantity)
+                    .collect();
+                if ours != theirs {
+                    return Err(ItchError(format!(
+                        "{:?} orders at {} are {:?} on the feed but {:?} on the book",
+                        side, price, ours, theirs
+                    )));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn side(&self, side: Side) -> &BTreeMap<u32, Vec<u64>> {
+        match side {
+            Side::Bid => return &self.bids,
+            Side::Ask => return &self.asks,
+        }
+    }
+
+    fn side_mut(&mut self, side: Side) -> &mut BTreeMap<u32, Vec<u64>> {
+        match side {
+            Side::Bid => return &mut self.bids,
+            Side::Ask => return &mut self.asks,
+        }
+    }
+
+    fn add(
+        &mut self,
+        reference: u64,
+        side: Side,
+        shares: u32,
+        price: u32,
+    ) -> Result<(), ItchError> {
+        if self.orders.contains_key(&reference) {
+            return Err(ItchError(format!("order {} was added twice", reference)));
+        }
+        self.orders.insert(
+            reference,
+            ItchOrder {
+                side,
+                price,
+                shares,
+            },
+        );
+        self.side_mut(side)
+            .entry(price)
+            .or_default()
+            .push(reference);
+        return Ok(());
+    }
+
+    /// Takes shares off an order, deleting it once none are left.
+    fn reduce(&mut self, reference: u64, shares: u32) -> Result<(), ItchError> {
+        let order = self.get(reference)?;
+        if shares > order.shares {
+            return Err(ItchError(format!(
+                "{} shares taken off order {} with {} left",
+                shares, reference, order.shares
+            )));
+        }
+        if shares == order.shares {
+            self.delete(reference)?;
+        } else {
+            self.orders.get_mut(&reference).unwrap().shares -= shares;
+        }
+        return Ok(());
+    }
+
+    fn delete(&mut self, reference: u64) -> Result<ItchOrder, ItchError> {
+        let order = self.get(reference)?;
+        self.orders.remove(&reference);
+
+        let levels = self.side_mut(order.side);
+        let queue = levels.get_mut(&order.price).unwrap();
+        queue.retain(|r| *r != reference);
+        if queue.is_empty() {
+            levels.remove(&order.price);
+        }
+        return Ok(order);
+    }
+
+    fn get(&self, reference: u64) -> Result<ItchOrder, ItchError> {
+        return self
+            .orders
+            .get(&reference)
+            .copied()
+            .ok_or_else(|| ItchError(format!("unknown order {}", reference)));
+    }
+}
+
+fn to_price(price: u32) -> Decimal {
+    return Decimal::new(price as i64, 4).normalize();
+}
+
+fn from_price(price: Decimal) -> Option<u32> {
+    return (price * Decimal::from(10_000)).to_u32();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use crate::itch::feed::Feed;
+    use crate::itch::message::read_message;
+    use crate::order_flow::{FlowConfig, OrderFlow};
+    use rust_decimal_macros::*;
+
+    #[test]
+    fn test_rebuilds_book_from_feed() {
+        let mut order_book =
+            OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        order_book.set_event_recording(true);
+        let mut feed = Feed::new("ABC", Vec::new());
+
+        for (n, command) in OrderFlow::new(FlowConfig::default())
+            .take(5_000)
+            .enumerate()
+        {
+            order_book.execute(command);
+
+            // Move an order now and then, for Order Replace messages
+            if n % 20 == 0 {
+                let best = order_book.levels(Side::Ask).next().map(|l| l.price);
+                let order = best.and_then(|price| order_book.orders_at(Side::Ask, price).next());
+                if let Some(order) = order.copied() {
+                    order_book.amend(order.id, order.quantity, order.price + dec!(0.01));
+                }
+            }
+            feed.publish(&order_book.take_events()).unwrap();
+        }
+
+        let file = feed.into_sink();
+        let mut reader = file.as_slice();
+        let mut book = L3Book::new();
+        let mut replaces = 0;
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            if let Body::OrderReplace { .. } = message.body {
+                replaces += 1;
+            }
+            book.apply(&message).unwrap();
+        }
+
+        assert!(replaces > 0);
+        assert!(book.num_orders() > 0);
+        assert_eq!(book.compare(&order_book), Ok(()));
+    }
+
+    #[test]
+    fn test_compare_finds_differences() {
+        let mut order_book = OrderBook::new();
+        order_book.set_event_recording(true);
+        let mut feed = Feed::new("ABC", Vec::new());
+        order_book.submit_limit_order(Side::Bid, dec!(5), dec!(10));
+        order_book.submit_limit_order(Side::Bid, dec!(3), dec!(10));
+        feed.publish(&order_book.take_events()).unwrap();
+
+        let mut book = L3Book::new();
+        let mut reader = feed.sink().as_slice();
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            book.apply(&message).unwrap();
+        }
+        assert_eq!(book.levels(Side::Bid), [(dec!(10), dec!(8), 2)]);
+        assert_eq!(
+            book.orders_at(Side::Bid, dec!(10)),
+            [(1, dec!(5)), (2, dec!(3))]
+        );
+        assert_eq!(book.compare(&order_book), Ok(()));
+
+        // Same volume at the price, but in a different order
+        let mut other = OrderBook::new();
+        other.submit_limit_order(Side::Bid, dec!(3), dec!(10));
+        other.submit_limit_order(Side::Bid, dec!(5), dec!(10));
+        assert!(book.compare(&other).is_err());
+
+        assert!(book
+            .apply(&Message {
+                stock_locate: 1,
+                tracking_number: 0,
+                timestamp: 0,
+                body: Body::OrderDelete { reference: 9 },
+            })
+            .is_err());
+    }
+}
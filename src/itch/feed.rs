@@ -0,0 +1,380 @@
+use rust_decimal::prelude::*;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::{SocketAddr, UdpSocket};
+use uuid::Uuid;
+
+use crate::book_event::BookEvent;
+use crate::itch::message::{event_code, stock, write_message, Body, ItchError, Message, Packet};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Where a `Feed` sends its messages.
+pub trait Sink {
+    fn send(&mut self, messages: &[Message]) -> io::Result<()>;
+}
+
+/// Writes length-prefixed messages, as in an ITCH file.
+impl<W: Write> Sink for W {
+    fn send(&mut self, messages: &[Message]) -> io::Result<()> {
+        for message in messages.iter() {
+            write_message(self, message)?;
+        }
+        return self.flush();
+    }
+}
+
+/// Sends messages over UDP in MoldUDP64 packets, to a unicast or multicast
+/// address.
+#[derive(Debug)]
+pub struct UdpSink {
+    socket: UdpSocket,
+    target: SocketAddr,
+    session: [u8; 10],
+    next_sequence_number: u64,
+}
+
+/// Keeps packets under a typical Ethernet MTU.
+const MAX_PACKET_SIZE: usize = 1400;
+
+impl UdpSink {
+    pub fn new(socket: UdpSocket, target: SocketAddr, session: [u8; 10]) -> Self {
+        return UdpSink {
+            socket,
+            target,
+            session,
+            next_sequence_number: 1,
+        };
+    }
+
+    fn send_packet(&mut self, messages: Vec<Message>) -> io::Result<()> {
+        let count = messages.len() as u64;
+        let packet = Packet {
+            session: self.session,
+            sequence_number: self.next_sequence_number,
+            messages,
+        };
+        self.socket.send_to(&packet.encode(), self.target)?;
+        self.next_sequence_number += count;
+        return Ok(());
+    }
+}
+
+impl Sink for UdpSink {
+    fn send(&mut self, messages: &[Message]) -> io::Result<()> {
+        let mut packet = Vec::new();
+        let mut size = 20;
+        for message in messages.iter() {
+            let length = 2 + message.encode().len();
+            if size + length > MAX_PACKET_SIZE {
+                self.send_packet(std::mem::take(&mut packet))?;
+                size = 20;
+            }
+            packet.push(*message);
+            size += length;
+        }
+        if !packet.is_empty() {
+            self.send_packet(packet)?;
+        }
+        return Ok(());
+    }
+}
+
+/// Publishes an ITCH 5.0 feed for one instrument from the `BookEvent`s its
+/// `OrderBook` records.
+///
+/// Each order on the book gets an order reference number. Adds, executions,
+/// partial cancels and deletes go out as the matching ITCH messages, and an
+/// amend that moves an order without trading becomes an Order Replace under
+/// a new reference number. Every execution is against a displayed order, so
+/// trades are all Order Executed messages and the feed never sends a Trade.
+///
+/// Prices go out in units of 1/10000 and quantities as whole shares, so the
+/// book's prices and quantities must fit: publishing fails otherwise. Event
+/// recording should start while the book is empty, so every order on it is
+/// known to the feed.
+#[derive(Debug)]
+pub struct Feed<S> {
+    sink: S,
+    stock: [u8; 8],
+    stock_locate: u16,
+    /// Order reference numbers and shares left, by order id.
+    references: HashMap<Uuid, (u64, u32)>,
+    next_reference: u64,
+    next_match_number: u64,
+}
+
+impl<S: Sink> Feed<S> {
+    pub fn new(symbol: &str, sink: S) -> Self {
+        return Feed {
+            sink,
+            stock: stock(symbol),
+            stock_locate: 1,
+            references: HashMap::new(),
+            next_reference: 1,
+            next_match_number: 1,
+        };
+    }
+
+    pub fn sink(&self) -> &S {
+        return &self.sink;
+    }
+
+    pub fn into_sink(self) -> S {
+        return self.sink;
+    }
+
+    /// Sends the System Events that open the feed and the market.
+    pub fn start(&mut self, timestamp: u64) -> Result<(), ItchError> {
+        let events = [
+            event_code::START_OF_MESSAGES,
+            event_code::START_OF_SYSTEM_HOURS,
+            event_code::START_OF_MARKET_HOURS,
+        ];
+        return self.system_events(timestamp, &events);
+    }
+
+    /// Sends the System Events that close the market and the feed.
+    pub fn finish(&mut self, timestamp: u64) -> Result<(), ItchError> {
+        let events = [
+            event_code::END_OF_MARKET_HOURS,
+            event_code::END_OF_SYSTEM_HOURS,
+            event_code::END_OF_MESSAGES,
+        ];
+        return self.system_events(timestamp, &events);
+    }
+
+    fn system_events(&mut self, timestamp: u64, events: &[u8]) -> Result<(), ItchError> {
+        let messages: Vec<Message> = events
+            .iter()
+            .map(|&event_code| self.message(timestamp, Body::SystemEvent { event_code }))
+            .collect();
+        self.sink.send(&messages)?;
+        return Ok(());
+    }
+
+    /// Sends the messages for a batch of book events, as returned by
+    /// `OrderBook::take_events`.
+    pub fn publish(&mut self, events: &[(u64, BookEvent)]) -> Result<(), ItchError> {
+        let mut messages = Vec::new();
+        let mut i = 0;
+
+        while i < events.len() {
+            let (timestamp, event) = events[i];
+            i += 1;
+
+            let body = match event {
+                BookEvent::Delete { id, .. } => match events.get(i) {
+                    Some((_, BookEvent::Add(order))) if order.id == id => {
+                        i += 1;
+                        let (original_reference, _) = self.reference(id)?;
+                        let (shares, price) = (shares(order.quantity)?, price(order.price)?);
+                        Body::OrderReplace {
+                            original_reference,
+                            new_reference: self.add_reference(id, shares),
+                            shares,
+                            price,
+                        }
+                    }
+                    _ => {
+                        let (reference, _) = self.reference(id)?;
+                        self.references.remove(&id);
+                        Body::OrderDelete { reference }
+                    }
+                },
+                BookEvent::Add(order) => {
+                    let (shares, price) = (shares(order.quantity)?, price(order.price)?);
+                    Body::AddOrder {
+                        reference: self.add_reference(order.id, shares),
+                        side: order.side,
+                        shares,
+                        stock: self.stock,
+                        price,
+                    }
+                }
+                BookEvent::Execute { id, quantity, .. } => {
+                    let executed_shares = shares(quantity)?;
+                    let reference = self.take_shares(id, executed_shares)?;
+                    let match_number = self.next_match_number;
+                    self.next_match_number += 1;
+                    Body::OrderExecuted {
+                        reference,
+                        executed_shares,
+                        match_number,
+                    }
+                }
+                BookEvent::Reduce { id, quantity, .. } => {
+                    let canceled_shares = shares(quantity)?;
+                    Body::OrderCancel {
+                        reference: self.take_shares(id, canceled_shares)?,
+                        canceled_shares,
+                    }
+                }
+            };
+            messages.push(self.message(timestamp, body));
+        }
+
+        self.sink.send(&messages)?;
+        return Ok(());
+    }
+
+    fn message(&self, timestamp: u64, body: Body) -> Message {
+        return Message {
+            stock_locate: self.stock_locate,
+            tracking_number: 0,
+            timestamp: timestamp % NANOS_PER_DAY,
+            body,
+        };
+    }
+
+    fn reference(&self, id: Uuid) -> Result<(u64, u32), ItchError> {
+        return self
+            .references
+            .get(&id)
+            .copied()
+            .ok_or_else(|| ItchError(format!("order {} isn't on the feed", id)));
+    }
+
+    fn add_reference(&mut self, id: Uuid, shares: u32) -> u64 {
+        let reference = self.next_reference;
+        self.next_reference += 1;
+        self.references.insert(id, (reference, shares));
+        return reference;
+    }
+
+    /// Takes shares off an order, forgetting it once none are left.
+    fn take_shares(&mut self, id: Uuid, shares: u32) -> Result<u64, ItchError> {
+        let (reference, left) = self.reference(id)?;
+        if shares >= left {
+            self.references.remove(&id);
+        } else {
+            self.references.insert(id, (reference, left - shares));
+        }
+        return Ok(reference);
+    }
+}
+
+fn price(price: Decimal) -> Result<u32, ItchError> {
+    let scaled = price * Decimal::from(10_000);
+    if scaled.fract().is_zero() {
+        if let Some(price) = scaled.to_u32() {
+            return Ok(price);
+        }
+    }
+    return Err(ItchError(format!(
+        "price {} doesn't fit in an ITCH price",
+        price
+    )));
+}
+
+fn shares(quantity: Decimal) -> Result<u32, ItchError> {
+    if quantity.fract().is_zero() {
+        if let Some(shares) = quantity.to_u32() {
+            return Ok(shares);
+        }
+    }
+    return Err(ItchError(format!(
+        "quantity {} isn't a whole number of shares",
+        quantity
+    )));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use crate::itch::message::read_message;
+    use crate::order::Side;
+    use crate::OrderBook;
+    use rust_decimal_macros::*;
+
+    fn bodies(file: &[u8]) -> Vec<Body> {
+        let mut reader = file;
+        let mut bodies = Vec::new();
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            bodies.push(message.body);
+        }
+        return bodies;
+    }
+
+    #[test]
+    fn test_publish() {
+        let mut order_book =
+            OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        order_book.set_event_recording(true);
+        let mut feed = Feed::new("ABC", Vec::new());
+        feed.start(0).unwrap();
+
+        let ask = order_book.submit_limit_order(Side::Ask, dec!(10), dec!(100.25));
+        let ask = ask.partial.unwrap().id;
+        order_book.submit_limit_order(Side::Bid, dec!(4), dec!(101));
+        order_book.amend(ask, dec!(5), dec!(100.25));
+        order_book.amend(ask, dec!(5), dec!(100.5));
+        order_book.remove(ask);
+        feed.publish(&order_book.take_events()).unwrap();
+
+        let stock = stock("ABC");
+        assert_eq!(
+            bodies(feed.sink())[3..],
+            [
+                Body::AddOrder {
+                    reference: 1,
+                    side: Side::Ask,
+                    shares: 10,
+                    stock,
+                    price: 1_002_500,
+                },
+                Body::OrderExecuted {
+                    reference: 1,
+                    executed_shares: 4,
+                    match_number: 1,
+                },
+                Body::OrderCancel {
+                    reference: 1,
+                    canceled_shares: 1,
+                },
+                Body::OrderReplace {
+                    original_reference: 1,
+                    new_reference: 2,
+                    shares: 5,
+                    price: 1_005_000,
+                },
+                Body::OrderDelete { reference: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_publish_errors() {
+        let mut order_book = OrderBook::new();
+        order_book.set_event_recording(true);
+        let mut feed = Feed::new("ABC", Vec::new());
+
+        order_book.submit_limit_order(Side::Ask, dec!(1.5), dec!(10));
+        assert!(feed.publish(&order_book.take_events()).is_err());
+
+        order_book.submit_limit_order(Side::Bid, dec!(1), dec!(0.00001));
+        assert!(feed.publish(&order_book.take_events()).is_err());
+        assert!(feed.sink().is_empty());
+    }
+
+    #[test]
+    fn test_udp_sink() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = UdpSink::new(socket, receiver.local_addr().unwrap(), *b"SESSION001");
+        let mut feed = Feed::new("ABC", sink);
+
+        feed.start(0).unwrap();
+        feed.finish(0).unwrap();
+
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        let n = receiver.recv(&mut buffer).unwrap();
+        let packet = Packet::decode(&buffer[..n]).unwrap();
+        assert_eq!(packet.sequence_number, 1);
+        assert_eq!(packet.messages.len(), 3);
+        let n = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(Packet::decode(&buffer[..n]).unwrap().sequence_number, 4);
+    }
+}
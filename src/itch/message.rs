@@ -0,0 +1,460 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+// ITCH pads its Stock fields the same way OUCH does.
+pub use ouch_codec::stock;
+
+use crate::order::Side;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ItchError(pub String);
+
+impl fmt::Display for ItchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.0);
+    }
+}
+
+impl Error for ItchError {}
+
+impl From<io::Error> for ItchError {
+    fn from(e: io::Error) -> Self {
+        return ItchError(e.to_string());
+    }
+}
+
+/// System Event codes.
+pub mod event_code {
+    pub const START_OF_MESSAGES: u8 = b'O';
+    pub const START_OF_SYSTEM_HOURS: u8 = b'S';
+    pub const START_OF_MARKET_HOURS: u8 = b'Q';
+    pub const END_OF_MARKET_HOURS: u8 = b'M';
+    pub const END_OF_SYSTEM_HOURS: u8 = b'E';
+    pub const END_OF_MESSAGES: u8 = b'C';
+}
+
+/// An ITCH 5.0 message, limited to the types needed to follow a book.
+///
+/// Every message starts with the same header: a stock locate code for the
+/// instrument, a tracking number, and a timestamp in nanoseconds since
+/// midnight. Prices are in units of 1/10000 and quantities in whole shares.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Message {
+    pub stock_locate: u16,
+    pub tracking_number: u16,
+    pub timestamp: u64,
+    pub body: Body,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Body {
+    /// `S`: see `event_code`.
+    SystemEvent { event_code: u8 },
+    /// `A`: a new order on the book.
+    AddOrder {
+        reference: u64,
+        side: Side,
+        shares: u32,
+        stock: [u8; 8],
+        price: u32,
+    },
+    /// `E`: part or all of a resting order traded at its own price.
+    OrderExecuted {
+        reference: u64,
+        executed_shares: u32,
+        match_number: u64,
+    },
+    /// `X`: part of a resting order was canceled.
+    OrderCancel {
+        reference: u64,
+        canceled_shares: u32,
+    },
+    /// `D`: a resting order was canceled.
+    OrderDelete { reference: u64 },
+    /// `U`: a resting order was replaced by a new one, at the back of the
+    /// queue at its price.
+    OrderReplace {
+        original_reference: u64,
+        new_reference: u64,
+        shares: u32,
+        price: u32,
+    },
+    /// `P`: a trade that doesn't involve an order on the book.
+    Trade {
+        reference: u64,
+        side: Side,
+        shares: u32,
+        stock: [u8; 8],
+        price: u32,
+        match_number: u64,
+    },
+}
+
+impl Body {
+    pub fn message_type(&self) -> u8 {
+        match self {
+            Body::SystemEvent { .. } => return b'S',
+            Body::AddOrder { .. } => return b'A',
+            Body::OrderExecuted { .. } => return b'E',
+            Body::OrderCancel { .. } => return b'X',
+            Body::OrderDelete { .. } => return b'D',
+            Body::OrderReplace { .. } => return b'U',
+            Body::Trade { .. } => return b'P',
+        }
+    }
+}
+
+fn side_code(side: Side) -> u8 {
+    match side {
+        Side::Bid => return b'B',
+        Side::Ask => return b'S',
+    }
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.body.message_type()];
+        bytes.extend(&self.stock_locate.to_be_bytes());
+        bytes.extend(&self.tracking_number.to_be_bytes());
+        bytes.extend(&self.timestamp.to_be_bytes()[2..]);
+
+        match self.body {
+            Body::SystemEvent { event_code } => bytes.push(event_code),
+            Body::AddOrder {
+                reference,
+                side,
+                shares,
+                stock,
+                price,
+            } => {
+                bytes.extend(&reference.to_be_bytes());
+                bytes.push(side_code(side));
+                bytes.extend(&shares.to_be_bytes());
+                bytes.extend(&stock);
+                bytes.extend(&price.to_be_bytes());
+            }
+            Body::OrderExecuted {
+                reference,
+                executed_shares,
+                match_number,
+            } => {
+                bytes.extend(&reference.to_be_bytes());
+                bytes.extend(&executed_shares.to_be_bytes());
+                bytes.extend(&match_number.to_be_bytes());
+            }
+            Body::OrderCancel {
+                reference,
+                canceled_shares,
+            } => {
+                bytes.extend(&reference.to_be_bytes());
+                bytes.extend(&canceled_shares.to_be_bytes());
+            }
+            Body::OrderDelete { reference } => bytes.extend(&reference.to_be_bytes()),
+            Body::OrderReplace {
+                original_reference,
+                new_reference,
+                shares,
+                price,
+            } => {
+                bytes.extend(&original_reference.to_be_bytes());
+                bytes.extend(&new_reference.to_be_bytes());
+                bytes.extend(&shares.to_be_bytes());
+                bytes.extend(&price.to_be_bytes());
+            }
+            Body::Trade {
+                reference,
+                side,
+                shares,
+                stock,
+                price,
+                match_number,
+            } => {
+                bytes.extend(&reference.to_be_bytes());
+                bytes.push(side_code(side));
+                bytes.extend(&shares.to_be_bytes());
+                bytes.extend(&stock);
+                bytes.extend(&price.to_be_bytes());
+                bytes.extend(&match_number.to_be_bytes());
+            }
+        }
+        return bytes;
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Message, ItchError> {
+        let mut reader = Reader { bytes, position: 0 };
+        let message_type = reader.u8()?;
+        let stock_locate = reader.u16()?;
+        let tracking_number = reader.u16()?;
+        let timestamp = reader.u48()?;
+
+        let body = match message_type {
+            b'S' => Body::SystemEvent {
+                event_code: reader.u8()?,
+            },
+            b'A' => Body::AddOrder {
+                reference: reader.u64()?,
+                side: reader.side()?,
+                shares: reader.u32()?,
+                stock: reader.stock()?,
+                price: reader.u32()?,
+            },
+            b'E' => Body::OrderExecuted {
+                reference: reader.u64()?,
+                executed_shares: reader.u32()?,
+                match_number: reader.u64()?,
+            },
+            b'X' => Body::OrderCancel {
+                reference: reader.u64()?,
+                canceled_shares: reader.u32()?,
+            },
+            b'D' => Body::OrderDelete {
+                reference: reader.u64()?,
+            },
+            b'U' => Body::OrderReplace {
+                original_reference: reader.u64()?,
+                new_reference: reader.u64()?,
+                shares: reader.u32()?,
+                price: reader.u32()?,
+            },
+            b'P' => Body::Trade {
+                reference: reader.u64()?,
+                side: reader.side()?,
+                shares: reader.u32()?,
+                stock: reader.stock()?,
+                price: reader.u32()?,
+                match_number: reader.u64()?,
+            },
+            t => return Err(ItchError(format!("unknown message type {:?}", t as char))),
+        };
+
+        if reader.position != bytes.len() {
+            return Err(ItchError(format!(
+                "{} bytes left over after a {:?} message",
+                bytes.len() - reader.position,
+                message_type as char
+            )));
+        }
+        return Ok(Message {
+            stock_locate,
+            tracking_number,
+            timestamp,
+            body,
+        });
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ItchError> {
+        let end = self.position + n;
+        if end > self.bytes.len() {
+            return Err(ItchError(String::from("message is too short")));
+        }
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        return Ok(bytes);
+    }
+
+    fn u8(&mut self) -> Result<u8, ItchError> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u16(&mut self) -> Result<u16, ItchError> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.take(2)?);
+        return Ok(u16::from_be_bytes(bytes));
+    }
+
+    fn u32(&mut self) -> Result<u32, ItchError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        return Ok(u32::from_be_bytes(bytes));
+    }
+
+    fn u48(&mut self) -> Result<u64, ItchError> {
+        let mut bytes = [0; 8];
+        bytes[2..].copy_from_slice(self.take(6)?);
+        return Ok(u64::from_be_bytes(bytes));
+    }
+
+    fn u64(&mut self) -> Result<u64, ItchError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        return Ok(u64::from_be_bytes(bytes));
+    }
+
+    fn side(&mut self) -> Result<Side, ItchError> {
+        match self.u8()? {
+            b'B' => return Ok(Side::Bid),
+            b'S' => return Ok(Side::Ask),
+            c => return Err(ItchError(format!("invalid side {:?}", c as char))),
+        }
+    }
+
+    fn stock(&mut self) -> Result<[u8; 8], ItchError> {
+        let mut stock = [0; 8];
+        stock.copy_from_slice(self.take(8)?);
+        return Ok(stock);
+    }
+}
+
+/// Writes a message with the two-byte length prefix used in ITCH files.
+pub fn write_message(writer: &mut impl Write, message: &Message) -> io::Result<()> {
+    let bytes = message.encode();
+    writer.write_all(&(bytes.len() as u16).to_be_bytes())?;
+    return writer.write_all(&bytes);
+}
+
+/// Reads a length-prefixed message. Returns `None` at the end of the input.
+pub fn read_message(reader: &mut impl Read) -> Result<Option<Message>, ItchError> {
+    let mut length = [0; 2];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut bytes = vec![0; u16::from_be_bytes(length) as usize];
+    reader.read_exact(&mut bytes)?;
+    return Message::decode(&bytes).map(Some);
+}
+
+/// A MoldUDP64 packet: a block of consecutive messages, numbered from
+/// `sequence_number`, for sending over UDP.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Packet {
+    pub session: [u8; 10],
+    pub sequence_number: u64,
+    pub messages: Vec<Message>,
+}
+
+impl Packet {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = self.session.to_vec();
+        bytes.extend(&self.sequence_number.to_be_bytes());
+        bytes.extend(&(self.messages.len() as u16).to_be_bytes());
+        for message in self.messages.iter() {
+            write_message(&mut bytes, message).unwrap();
+        }
+        return bytes;
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Packet, ItchError> {
+        if bytes.len() < 20 {
+            return Err(ItchError(String::from("packet is too short")));
+        }
+        let mut session = [0; 10];
+        session.copy_from_slice(&bytes[..10]);
+        let mut sequence_number = [0; 8];
+        sequence_number.copy_from_slice(&bytes[10..18]);
+        let count = u16::from_be_bytes([bytes[18], bytes[19]]);
+
+        let mut reader = &bytes[20..];
+        let mut messages = Vec::new();
+        for _ in 0..count {
+            match read_message(&mut reader)? {
+                Some(message) => messages.push(message),
+                None => return Err(ItchError(String::from("packet is missing messages"))),
+            }
+        }
+        return Ok(Packet {
+            session,
+            sequence_number: u64::from_be_bytes(sequence_number),
+            messages,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(body: Body) -> Message {
+        return Message {
+            stock_locate: 1,
+            tracking_number: 0,
+            timestamp: 34_200_000_000_123,
+            body,
+        };
+    }
+
+    #[test]
+    fn test_encode_and_decode() {
+        let messages = vec![
+            message(Body::SystemEvent {
+                event_code: event_code::START_OF_MESSAGES,
+            }),
+            message(Body::AddOrder {
+                reference: 7,
+                side: Side::Ask,
+                shares: 100,
+                stock: stock("ABC"),
+                price: 1_005_000,
+            }),
+            message(Body::OrderExecuted {
+                reference: 7,
+                executed_shares: 40,
+                match_number: 1,
+            }),
+            message(Body::OrderCancel {
+                reference: 7,
+                canceled_shares: 10,
+            }),
+            message(Body::OrderReplace {
+                original_reference: 7,
+                new_reference: 8,
+                shares: 30,
+                price: 1_004_000,
+            }),
+            message(Body::OrderDelete { reference: 8 }),
+            message(Body::Trade {
+                reference: 0,
+                side: Side::Bid,
+                shares: 5,
+                stock: stock("ABC"),
+                price: 1_004_000,
+                match_number: 2,
+            }),
+        ];
+
+        // The lengths from the ITCH 5.0 specification
+        let lengths: Vec<usize> = messages.iter().map(|m| m.encode().len()).collect();
+        assert_eq!(lengths, [12, 36, 31, 23, 35, 19, 44]);
+
+        let mut file = Vec::new();
+        for message in messages.iter() {
+            write_message(&mut file, message).unwrap();
+        }
+        let mut reader = file.as_slice();
+        for message in messages.iter() {
+            assert_eq!(read_message(&mut reader), Ok(Some(*message)));
+        }
+        assert_eq!(read_message(&mut reader), Ok(None));
+
+        let packet = Packet {
+            session: *b"SESSION001",
+            sequence_number: 42,
+            messages,
+        };
+        assert_eq!(Packet::decode(&packet.encode()), Ok(packet));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let bytes = message(Body::OrderDelete { reference: 1 }).encode();
+        assert!(Message::decode(&bytes[..18]).is_err());
+
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(Message::decode(&longer).is_err());
+
+        let mut unknown = bytes;
+        unknown[0] = b'Z';
+        assert!(Message::decode(&unknown).is_err());
+    }
+}
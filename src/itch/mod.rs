@@ -0,0 +1,7 @@
+pub mod book;
+pub mod feed;
+pub mod message;
+
+pub use book::L3Book;
+pub use feed::{Feed, Sink, UdpSink};
+pub use message::{read_message, write_message, Body, ItchError, Message, Packet};
@@ -1,6 +1,44 @@
+#![allow(clippy::needless_return)]
+
+pub mod arena;
+pub mod backtest;
+pub mod book_event;
 pub mod book_side;
+pub mod clock;
+pub mod command;
+pub mod conflation;
+pub mod consolidated;
+pub mod depth;
+pub mod fix;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod id_generator;
+pub mod instrument;
+pub mod invariants;
+pub mod itch;
+pub mod market_stats;
+pub mod numeric;
 pub mod order;
 pub mod order_book;
+pub mod order_flow;
+pub mod order_manager;
+pub mod ouch;
+pub mod price_index;
+pub mod price_ladder;
 pub mod price_level;
+#[cfg(test)]
+mod reference;
+pub mod replay;
+#[cfg(feature = "rest")]
+pub mod rest;
+pub mod router;
+mod schedule;
+#[cfg(feature = "schemars")]
+mod schema;
+pub mod script;
+pub mod simulation;
+pub mod trade;
+#[cfg(feature = "websocket")]
+pub mod ws;
 
 pub use order_book::*;
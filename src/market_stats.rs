@@ -0,0 +1,534 @@
+use rust_decimal::prelude::*;
+use std::collections::VecDeque;
+
+use crate::trade::Trade;
+
+/// Nanoseconds in a day, the default window for the rolling statistics.
+pub const DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// When a bar closes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BarInterval {
+    /// After this many trades.
+    Trades(u64),
+    /// Once this much has traded. A trade that would overfill a bar is split
+    /// across it and the next.
+    Volume(Decimal),
+    /// At every multiple of this many nanoseconds. Intervals without trades
+    /// have no bar.
+    Time(u64),
+}
+
+/// An OHLCV bar.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Bar {
+    /// When the bar started: the start of its interval for time bars, the
+    /// time of its first trade otherwise.
+    pub open_time: u64,
+    /// The time of the bar's last trade.
+    pub close_time: u64,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+    /// Sum of price times quantity.
+    pub turnover: Decimal,
+    pub trades: u64,
+}
+
+impl Bar {
+    fn new(open_time: u64, timestamp: u64, price: Decimal) -> Self {
+        return Bar {
+            open_time,
+            close_time: timestamp,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: Decimal::zero(),
+            turnover: Decimal::zero(),
+            trades: 0,
+        };
+    }
+
+    fn add(&mut self, timestamp: u64, price: Decimal, quantity: Decimal) {
+        self.close_time = timestamp;
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.volume += quantity;
+        self.turnover += price * quantity;
+        self.trades += 1;
+    }
+
+    pub fn vwap(&self) -> Option<Decimal> {
+        if self.volume.is_zero() {
+            return None;
+        }
+        return Some(self.turnover / self.volume);
+    }
+}
+
+/// Bars at one interval, built up from trades.
+#[derive(Clone, Debug)]
+pub struct BarSeries {
+    interval: BarInterval,
+    bars: Vec<Bar>,
+    current: Option<Bar>,
+}
+
+impl BarSeries {
+    /// Panics if `interval` isn't positive.
+    pub fn new(interval: BarInterval) -> Self {
+        let positive = match interval {
+            BarInterval::Trades(trades) => trades > 0,
+            BarInterval::Volume(volume) => volume > Decimal::zero(),
+            BarInterval::Time(nanos) => nanos > 0,
+        };
+        assert!(
+            positive,
+            "bar interval must be positive, not {:?}",
+            interval
+        );
+        return BarSeries {
+            interval,
+            bars: Vec::new(),
+            current: None,
+        };
+    }
+
+    pub fn interval(&self) -> BarInterval {
+        return self.interval;
+    }
+
+    /// The bars that have closed, oldest first.
+    pub fn bars(&self) -> &[Bar] {
+        return &self.bars;
+    }
+
+    /// The bar still being built, if any.
+    pub fn current(&self) -> Option<&Bar> {
+        return self.current.as_ref();
+    }
+
+    pub fn add(&mut self, timestamp: u64, price: Decimal, quantity: Decimal) {
+        self.close_until(timestamp);
+
+        match self.interval {
+            BarInterval::Trades(trades) => {
+                self.current_bar(timestamp, price)
+                    .add(timestamp, price, quantity);
+                if self.current.is_some_and(|bar| bar.trades >= trades) {
+                    self.close();
+                }
+            }
+            BarInterval::Volume(volume) => {
+                let mut left = quantity;
+                loop {
+                    let bar = self.current_bar(timestamp, price);
+                    let room = volume - bar.volume;
+                    bar.add(timestamp, price, left.min(room));
+                    if left < room {
+                        break;
+                    }
+                    self.close();
+                    left -= room;
+                    if left.is_zero() {
+                        break;
+                    }
+                }
+            }
+            BarInterval::Time(_) => {
+                self.current_bar(timestamp, price)
+                    .add(timestamp, price, quantity);
+            }
+        }
+    }
+
+    /// Closes a time bar whose interval ended by `now`. Other kinds of bars
+    /// only close on trades.
+    pub fn close_until(&mut self, now: u64) {
+        if let (BarInterval::Time(interval), Some(bar)) = (self.interval, self.current) {
+            if now >= bar.open_time + interval {
+                self.close();
+            }
+        }
+    }
+
+    fn current_bar(&mut self, timestamp: u64, price: Decimal) -> &mut Bar {
+        let open_time = match self.interval {
+            BarInterval::Time(interval) => timestamp - timestamp % interval,
+            _ => timestamp,
+        };
+        return self
+            .current
+            .get_or_insert_with(|| Bar::new(open_time, timestamp, price));
+    }
+
+    fn close(&mut self) {
+        if let Some(bar) = self.current.take() {
+            self.bars.push(bar);
+        }
+    }
+}
+
+/// A reference price other features can key off.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ReferencePrice {
+    /// The last trade.
+    Last,
+    /// Halfway between the best bid and offer.
+    Mid,
+    /// The median of the best bid, best offer and last trade, which follows
+    /// the market without jumping on a single odd trade or a thin side.
+    Mark,
+}
+
+/// Statistics about a market, kept up to date from its trades and its best
+/// bid and offer.
+///
+/// Session figures cover every trade since the stats were created or
+/// `start_session` was last called. Rolling figures cover a window of time,
+/// a day unless created `with_window`, ending at the latest trade or
+/// `advance`.
+#[derive(Clone, Debug)]
+pub struct MarketStats {
+    window: u64,
+    series: Vec<BarSeries>,
+    last_price: Option<Decimal>,
+    best_bid: Option<Decimal>,
+    best_ask: Option<Decimal>,
+    session_volume: Decimal,
+    session_turnover: Decimal,
+    /// Trades in the window, oldest first.
+    window_trades: VecDeque<(u64, Decimal, Decimal)>,
+    window_volume: Decimal,
+    window_turnover: Decimal,
+    /// Candidates for the window's high and low: prices in decreasing and
+    /// increasing order respectively, each with the time it traded.
+    highs: VecDeque<(u64, Decimal)>,
+    lows: VecDeque<(u64, Decimal)>,
+}
+
+impl MarketStats {
+    pub fn new() -> Self {
+        return MarketStats::with_window(DAY);
+    }
+
+    /// Creates stats whose rolling figures cover `window` nanoseconds.
+    pub fn with_window(window: u64) -> Self {
+        return MarketStats {
+            window,
+            series: Vec::new(),
+            last_price: None,
+            best_bid: None,
+            best_ask: None,
+            session_volume: Decimal::zero(),
+            session_turnover: Decimal::zero(),
+            window_trades: VecDeque::new(),
+            window_volume: Decimal::zero(),
+            window_turnover: Decimal::zero(),
+            highs: VecDeque::new(),
+            lows: VecDeque::new(),
+        };
+    }
+
+    /// Starts building bars at `interval`, returning the index to look them
+    /// up with. Panics if `interval` isn't positive.
+    pub fn add_bars(&mut self, interval: BarInterval) -> usize {
+        self.series.push(BarSeries::new(interval));
+        return self.series.len() - 1;
+    }
+
+    pub fn bars(&self, index: usize) -> &BarSeries {
+        return &self.series[index];
+    }
+
+    /// Records a trade that happened at `timestamp`. Trades must be recorded
+    /// in time order.
+    pub fn on_trade(&mut self, timestamp: u64, trade: &Trade) {
+        let (price, quantity) = (trade.price, trade.quantity);
+
+        for series in self.series.iter_mut() {
+            series.add(timestamp, price, quantity);
+        }
+
+        self.last_price = Some(price);
+        self.session_volume += quantity;
+        self.session_turnover += price * quantity;
+
+        self.window_trades.push_back((timestamp, price, quantity));
+        self.window_volume += quantity;
+        self.window_turnover += price * quantity;
+
+        while self.highs.back().is_some_and(|&(_, high)| high <= price) {
+            self.highs.pop_back();
+        }
+        self.highs.push_back((timestamp, price));
+        while self.lows.back().is_some_and(|&(_, low)| low >= price) {
+            self.lows.pop_back();
+        }
+        self.lows.push_back((timestamp, price));
+
+        self.advance(timestamp);
+    }
+
+    /// Records the best bid and offer, for the mid and mark.
+    pub fn on_book(&mut self, best_bid: Option<Decimal>, best_ask: Option<Decimal>) {
+        self.best_bid = best_bid;
+        self.best_ask = best_ask;
+    }
+
+    /// Moves time forward to `now` without a trade: trades older than the
+    /// window drop out of the rolling figures and time bars that have ended
+    /// close.
+    pub fn advance(&mut self, now: u64) {
+        for series in self.series.iter_mut() {
+            series.close_until(now);
+        }
+
+        let start = now.saturating_sub(self.window);
+        while let Some(&(timestamp, price, quantity)) = self.window_trades.front() {
+            if timestamp > start {
+                break;
+            }
+            self.window_trades.pop_front();
+            self.window_volume -= quantity;
+            self.window_turnover -= price * quantity;
+        }
+        while self.highs.front().is_some_and(|&(t, _)| t <= start) {
+            self.highs.pop_front();
+        }
+        while self.lows.front().is_some_and(|&(t, _)| t <= start) {
+            self.lows.pop_front();
+        }
+    }
+
+    /// Resets the session figures, such as at the start of a trading day.
+    pub fn start_session(&mut self) {
+        self.session_volume = Decimal::zero();
+        self.session_turnover = Decimal::zero();
+    }
+
+    pub fn last_price(&self) -> Option<Decimal> {
+        return self.last_price;
+    }
+
+    pub fn mid(&self) -> Option<Decimal> {
+        let (bid, ask) = (self.best_bid?, self.best_ask?);
+        return Some((bid + ask) / Decimal::from(2));
+    }
+
+    /// The median of the best bid, best offer and last trade. With one of
+    /// them missing, it's the mid if both sides are there, or whichever
+    /// single price there is.
+    pub fn mark(&self) -> Option<Decimal> {
+        match (self.best_bid, self.best_ask, self.last_price) {
+            (Some(bid), Some(ask), Some(last)) => return Some(last.max(bid).min(ask).max(bid)),
+            (Some(_), Some(_), None) => return self.mid(),
+            (bid, ask, last) => return last.or(bid).or(ask),
+        }
+    }
+
+    pub fn reference_price(&self, reference: ReferencePrice) -> Option<Decimal> {
+        match reference {
+            ReferencePrice::Last => return self.last_price(),
+            ReferencePrice::Mid => return self.mid(),
+            ReferencePrice::Mark => return self.mark(),
+        }
+    }
+
+    /// Volume-weighted average price over the session.
+    pub fn vwap(&self) -> Option<Decimal> {
+        if self.session_volume.is_zero() {
+            return None;
+        }
+        return Some(self.session_turnover / self.session_volume);
+    }
+
+    pub fn session_volume(&self) -> Decimal {
+        return self.session_volume;
+    }
+
+    pub fn session_turnover(&self) -> Decimal {
+        return self.session_turnover;
+    }
+
+    /// The highest price traded in the window.
+    pub fn high(&self) -> Option<Decimal> {
+        return self.highs.front().map(|&(_, price)| price);
+    }
+
+    /// The lowest price traded in the window.
+    pub fn low(&self) -> Option<Decimal> {
+        return self.lows.front().map(|&(_, price)| price);
+    }
+
+    /// Quantity traded in the window.
+    pub fn volume(&self) -> Decimal {
+        return self.window_volume;
+    }
+
+    /// Price times quantity traded in the window.
+    pub fn turnover(&self) -> Decimal {
+        return self.window_turnover;
+    }
+}
+
+impl Default for MarketStats {
+    fn default() -> Self {
+        return MarketStats::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::Side;
+    use rust_decimal_macros::*;
+    use uuid::Uuid;
+
+    fn trade(price: Decimal, quantity: Decimal) -> Trade {
+        return Trade {
+            aggressor: Side::Bid,
+            maker_id: Uuid::nil(),
+            price,
+            quantity,
+        };
+    }
+
+    #[test]
+    #[should_panic(expected = "bar interval must be positive")]
+    fn test_rejects_zero_volume_interval() {
+        BarSeries::new(BarInterval::Volume(Decimal::zero()));
+    }
+
+    #[test]
+    #[should_panic(expected = "bar interval must be positive")]
+    fn test_rejects_zero_time_interval() {
+        MarketStats::new().add_bars(BarInterval::Time(0));
+    }
+
+    #[test]
+    fn test_trade_count_bars() {
+        let mut series = BarSeries::new(BarInterval::Trades(2));
+
+        series.add(1, dec!(10), dec!(1));
+        series.add(2, dec!(12), dec!(2));
+        series.add(3, dec!(11), dec!(1));
+
+        assert_eq!(
+            series.bars(),
+            &[Bar {
+                open_time: 1,
+                close_time: 2,
+                open: dec!(10),
+                high: dec!(12),
+                low: dec!(10),
+                close: dec!(12),
+                volume: dec!(3),
+                turnover: dec!(34),
+                trades: 2,
+            }]
+        );
+        assert_eq!(series.current().unwrap().open, dec!(11));
+    }
+
+    #[test]
+    fn test_volume_bars_split_trades() {
+        let mut series = BarSeries::new(BarInterval::Volume(dec!(5)));
+
+        series.add(1, dec!(10), dec!(3));
+        series.add(2, dec!(11), dec!(9));
+
+        let bars = series.bars();
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0].volume, dec!(5));
+        assert_eq!(bars[0].close, dec!(11));
+        assert_eq!(bars[1].volume, dec!(5));
+        assert_eq!(bars[1].open, dec!(11));
+        assert_eq!(series.current().unwrap().volume, dec!(2));
+
+        // Filling a bar exactly closes it and opens nothing
+        series.add(3, dec!(12), dec!(3));
+        assert_eq!(series.bars().len(), 3);
+        assert!(series.current().is_none());
+    }
+
+    #[test]
+    fn test_time_bars() {
+        let mut series = BarSeries::new(BarInterval::Time(10));
+
+        series.add(3, dec!(10), dec!(1));
+        series.add(9, dec!(9), dec!(1));
+        series.add(25, dec!(11), dec!(1));
+
+        // Nothing traded between 10 and 20, so there's no bar for it
+        assert_eq!(series.bars().len(), 1);
+        assert_eq!(series.bars()[0].open_time, 0);
+        assert_eq!(series.bars()[0].low, dec!(9));
+        assert_eq!(series.current().unwrap().open_time, 20);
+
+        series.close_until(29);
+        assert_eq!(series.bars().len(), 1);
+        series.close_until(30);
+        assert_eq!(series.bars().len(), 2);
+    }
+
+    #[test]
+    fn test_session_and_window() {
+        let mut stats = MarketStats::with_window(100);
+        let minutes = stats.add_bars(BarInterval::Time(60));
+
+        stats.on_trade(10, &trade(dec!(10), dec!(1)));
+        stats.on_trade(20, &trade(dec!(14), dec!(3)));
+        stats.on_trade(50, &trade(dec!(8), dec!(1)));
+
+        assert_eq!(stats.last_price(), Some(dec!(8)));
+        assert_eq!(stats.vwap(), Some(dec!(12)));
+        assert_eq!(stats.high(), Some(dec!(14)));
+        assert_eq!(stats.low(), Some(dec!(8)));
+        assert_eq!(stats.volume(), dec!(5));
+        assert_eq!(stats.turnover(), dec!(60));
+
+        // The trades at 10 and 20 drop out of the window
+        stats.on_trade(125, &trade(dec!(9), dec!(1)));
+        assert_eq!(stats.high(), Some(dec!(9)));
+        assert_eq!(stats.low(), Some(dec!(8)));
+        assert_eq!(stats.volume(), dec!(2));
+        assert_eq!(stats.bars(minutes).bars().len(), 1);
+
+        stats.advance(300);
+        assert_eq!(stats.high(), None);
+        assert_eq!(stats.volume(), dec!(0));
+        assert_eq!(stats.bars(minutes).bars().len(), 2);
+
+        // Session figures aren't windowed
+        assert_eq!(stats.session_volume(), dec!(6));
+        stats.start_session();
+        assert_eq!(stats.vwap(), None);
+        assert_eq!(stats.last_price(), Some(dec!(9)));
+    }
+
+    #[test]
+    fn test_reference_prices() {
+        let mut stats = MarketStats::new();
+        assert_eq!(stats.mark(), None);
+
+        stats.on_book(Some(dec!(10)), Some(dec!(12)));
+        assert_eq!(stats.reference_price(ReferencePrice::Mid), Some(dec!(11)));
+        assert_eq!(stats.reference_price(ReferencePrice::Mark), Some(dec!(11)));
+
+        stats.on_trade(1, &trade(dec!(15), dec!(1)));
+        assert_eq!(stats.reference_price(ReferencePrice::Last), Some(dec!(15)));
+        assert_eq!(stats.reference_price(ReferencePrice::Mark), Some(dec!(12)));
+
+        stats.on_trade(2, &trade(dec!(10.5), dec!(1)));
+        assert_eq!(stats.mark(), Some(dec!(10.5)));
+
+        stats.on_book(None, Some(dec!(12)));
+        assert_eq!(stats.mid(), None);
+        assert_eq!(stats.mark(), Some(dec!(10.5)));
+    }
+}
@@ -0,0 +1,125 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+
+/// The arithmetic the book needs from its price and quantity types.
+///
+/// Prices only need to be ordered and hashed; quantities are also added and
+/// subtracted as orders fill. `Decimal` implements this for general use, and
+/// `Ticks` and `Lots` are cheaper integer representations for instruments
+/// with a fixed tick and lot size (see `Instrument`).
+pub trait Numeric:
+    Copy
+    + Ord
+    + Hash
+    + Default
+    + fmt::Debug
+    + fmt::Display
+    + Send
+    + Sync
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + AddAssign
+    + SubAssign
+    + 'static
+{
+    const ZERO: Self;
+}
+
+impl Numeric for Decimal {
+    const ZERO: Self = Decimal::from_parts(0, 0, 0, false, 0);
+}
+
+/// A price as a whole number of ticks.
+#[derive(
+    Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Ticks(pub i64);
+
+/// A quantity as a whole number of lots.
+#[derive(
+    Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Lots(pub u64);
+
+macro_rules! integer_numeric {
+    ($name:ident) => {
+        impl Numeric for $name {
+            const ZERO: Self = $name(0);
+        }
+
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, other: $name) -> $name {
+                return $name(self.0 + other.0);
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, other: $name) -> $name {
+                return $name(self.0 - other.0);
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: $name) {
+                self.0 += other.0;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                return self.0.fmt(f);
+            }
+        }
+
+        /// The bare count, for sums such as notionals that need more than
+        /// addition. Scaling it by the tick or lot size is up to
+        /// `Instrument`.
+        impl From<$name> for Decimal {
+            fn from(value: $name) -> Decimal {
+                return Decimal::from(value.0);
+            }
+        }
+    };
+}
+
+integer_numeric!(Ticks);
+integer_numeric!(Lots);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::*;
+
+    #[test]
+    fn test_zero() {
+        assert_eq!(<Decimal as Numeric>::ZERO, dec!(0));
+        assert_eq!(Ticks::ZERO, Ticks(0));
+        assert_eq!(Lots::ZERO, Lots(0));
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let mut lots = Lots(5) + Lots(3);
+        lots -= Lots(2);
+
+        assert_eq!(lots, Lots(6));
+        assert_eq!(Ticks(-2) - Ticks(3), Ticks(-5));
+        assert!(Ticks(-1) < Ticks(0));
+        assert_eq!(Decimal::from(Ticks(-2)), dec!(-2));
+    }
+}
@@ -1,26 +1,30 @@
 use rust_decimal::prelude::*;
-use std::time;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+use crate::numeric::Numeric;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum Side {
     Bid,
     Ask,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub struct Order {
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Order<P = Decimal, Q = Decimal> {
     pub id: Uuid,
     pub side: Side,
-    pub timestamp: time::Instant,
-    pub price: Decimal,
-    pub quantity: Decimal,
+    /// Nanoseconds since the Unix epoch, as reported by the book's `Clock`.
+    pub timestamp: u64,
+    pub price: P,
+    pub quantity: Q,
 }
 
-impl Order {
-    pub fn new(side: Side, quantity: Decimal, price: Decimal, timestamp: time::Instant) -> Order {
+impl<P: Numeric, Q: Numeric> Order<P, Q> {
+    pub fn new(id: Uuid, side: Side, quantity: Q, price: P, timestamp: u64) -> Self {
         return Order {
-            id: Uuid::new_v4(),
+            id,
             side,
             price,
             quantity,
@@ -36,13 +40,15 @@ mod tests {
 
     #[test]
     fn test_new_returns_order() {
+        let id = Uuid::new_v4();
         let side = Side::Ask;
         let quantity = dec!(1.0);
         let price = dec!(10.0);
-        let time = time::Instant::now();
+        let time = 1_600_000_000_000_000_000;
 
-        let order = Order::new(side, quantity, price, time);
+        let order = Order::new(id, side, quantity, price, time);
 
+        assert_eq!(order.id, id);
         assert_eq!(order.side, side);
         assert_eq!(order.quantity, quantity);
         assert_eq!(order.price, price);
@@ -1,19 +1,46 @@
 use rust_decimal::prelude::*;
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
-use std::time;
 use uuid::Uuid;
 
+use crate::book_event::BookEvent;
 use crate::book_side::BookSide;
+use crate::clock::{Clock, SystemClock};
+use crate::command::{Command, CommandResult};
+use crate::depth::{DepthLevel, DepthSnapshot, LevelDelta};
+use crate::id_generator::{IdGenerator, UuidGenerator};
+use crate::invariants::{ensure, InvariantViolation};
+use crate::numeric::Numeric;
 use crate::order::{Order, Side};
+use crate::price_index::{PriceIndex, PriceTree};
 use crate::price_level::PriceLevel;
 
+/// A limit order book for a single instrument.
+///
+/// Prices and quantities are `Decimal` by default. Any `Numeric` types can be
+/// used instead, such as `Ticks` and `Lots` for instruments with a fixed tick
+/// and lot size. Each side looks up its price levels through a `PriceIndex`,
+/// which is a `PriceTree` unless the sides are built with `with_book_sides`.
 #[derive(Debug)]
-pub struct OrderBook {
-    orders: HashMap<Uuid, Order>,
-    bids: BookSide,
-    asks: BookSide,
+pub struct OrderBook<P = Decimal, Q = Decimal, I = PriceTree<P>> {
+    orders: HashMap<Uuid, OrderHandle>,
+    bids: BookSide<P, Q, I>,
+    asks: BookSide<P, Q, I>,
+    clock: Box<dyn Clock>,
+    id_generator: Box<dyn IdGenerator>,
+    invariant_checks: bool,
+    record_events: bool,
+    events: Vec<(u64, BookEvent<P, Q>)>,
+    /// How many times a price level has changed, which numbers the deltas.
+    depth_sequence: u64,
+    record_depth: bool,
+    deltas: Vec<LevelDelta<P, Q>>,
+}
+
+/// Where a resting order lives: its side and its key in that `BookSide`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct OrderHandle {
+    side: Side,
+    key: usize,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -23,73 +50,252 @@ pub enum FillStatus {
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub struct Fill {
-    order_id: Uuid,
-    status: FillStatus,
-    price: Decimal,
-    quantity: Decimal,
+pub struct Fill<P = Decimal, Q = Decimal> {
+    pub order_id: Uuid,
+    pub status: FillStatus,
+    pub price: P,
+    pub quantity: Q,
 }
 
 #[derive(Debug)]
-pub struct OrderResult {
-    done: Vec<Fill>,
-    partial: Option<Order>,
-    quantity_filled: Decimal,
+pub struct OrderResult<P = Decimal, Q = Decimal> {
+    pub done: Vec<Fill<P, Q>>,
+    pub partial: Option<Order<P, Q>>,
+    pub quantity_filled: Q,
+}
+
+impl<P, Q: Numeric> OrderResult<P, Q> {
+    fn new() -> Self {
+        return OrderResult {
+            done: Vec::new(),
+            partial: None,
+            quantity_filled: Q::ZERO,
+        };
+    }
 }
 
-fn iterate_min(side: &BookSide) -> Option<Rc<RefCell<PriceLevel>>> {
-    return side.min_price_level();
+fn iterate_min<P: Numeric, Q: Numeric, I: PriceIndex<P>>(side: &BookSide<P, Q, I>) -> Option<P> {
+    return side.min_price_level().map(|pl| pl.price);
 }
 
-fn iterate_max(side: &BookSide) -> Option<Rc<RefCell<PriceLevel>>> {
-    return side.max_price_level();
+fn iterate_max<P: Numeric, Q: Numeric, I: PriceIndex<P>>(side: &BookSide<P, Q, I>) -> Option<P> {
+    return side.max_price_level().map(|pl| pl.price);
 }
 
-fn greater_than_or_equal(left: Decimal, right: Decimal) -> bool {
+fn greater_than_or_equal<P: Numeric>(left: P, right: P) -> bool {
     left >= right
 }
 
-fn less_than_or_equal(left: Decimal, right: Decimal) -> bool {
+fn less_than_or_equal<P: Numeric>(left: P, right: P) -> bool {
     left <= right
 }
 
-impl OrderBook {
-    pub fn new() -> OrderBook {
+impl<P: Numeric, Q: Numeric> OrderBook<P, Q> {
+    pub fn new() -> Self {
+        return OrderBook::with_clock(SystemClock, UuidGenerator);
+    }
+
+    /// Creates a book that timestamps orders with `clock` and assigns ids from
+    /// `id_generator`. Use a `ManualClock` and `SequentialIdGenerator` to get
+    /// reproducible results.
+    pub fn with_clock<C, G>(clock: C, id_generator: G) -> Self
+    where
+        C: Clock + 'static,
+        G: IdGenerator + 'static,
+    {
+        return OrderBook::with_book_sides(BookSide::new(), BookSide::new(), clock, id_generator);
+    }
+}
+
+impl<P: Numeric, Q: Numeric, I: PriceIndex<P>> OrderBook<P, Q, I> {
+    /// Creates a book from empty sides, for when the sides need a price index
+    /// that can't be built with `Default`, such as a `PriceLadder`.
+    ///
+    /// Panics if either side already holds orders, since the book wouldn't
+    /// know their ids.
+    pub fn with_book_sides<C, G>(
+        bids: BookSide<P, Q, I>,
+        asks: BookSide<P, Q, I>,
+        clock: C,
+        id_generator: G,
+    ) -> Self
+    where
+        C: Clock + 'static,
+        G: IdGenerator + 'static,
+    {
+        assert!(
+            bids.num_orders == 0 && asks.num_orders == 0,
+            "book sides must be empty"
+        );
         return OrderBook {
             orders: HashMap::new(),
-            bids: BookSide::new(),
-            asks: BookSide::new(),
+            bids,
+            asks,
+            clock: Box::new(clock),
+            id_generator: Box::new(id_generator),
+            invariant_checks: false,
+            record_events: false,
+            events: Vec::new(),
+            depth_sequence: 0,
+            record_depth: false,
+            deltas: Vec::new(),
         };
     }
 
-    pub fn submit_market_order(&mut self, side: Side, quantity: Decimal) -> OrderResult {
-        let iter: fn(&BookSide) -> Option<Rc<RefCell<PriceLevel>>>;
+    /// Runs `check_invariants` after every operation that changes the book,
+    /// panicking on the first violation. The checks walk the whole book, so
+    /// they are meant for tests, and they only run in debug builds.
+    pub fn set_invariant_checks(&mut self, enabled: bool) {
+        self.invariant_checks = enabled;
+    }
 
-        let mut order_result = OrderResult {
-            done: Vec::new(),
-            partial: None,
-            quantity_filled: Decimal::zero(),
+    /// Keeps a log of every change made to the book, each with the time it
+    /// was made, to be collected with `take_events`. Off by default.
+    pub fn set_event_recording(&mut self, enabled: bool) {
+        self.record_events = enabled;
+    }
+
+    /// Takes the changes recorded since the last call, oldest first.
+    pub fn take_events(&mut self) -> Vec<(u64, BookEvent<P, Q>)> {
+        return std::mem::take(&mut self.events);
+    }
+
+    /// Keeps the new state of each price level whenever it changes, to be
+    /// collected with `take_deltas`. Off by default. The changes are counted
+    /// either way, so a snapshot's sequence number is always meaningful.
+    pub fn set_depth_recording(&mut self, enabled: bool) {
+        self.record_depth = enabled;
+    }
+
+    /// Takes the level deltas recorded since the last call, oldest first.
+    pub fn take_deltas(&mut self) -> Vec<LevelDelta<P, Q>> {
+        return std::mem::take(&mut self.deltas);
+    }
+
+    /// Every price level on the book, tagged with the sequence number of the
+    /// last change to one. Deltas numbered after it apply on top.
+    pub fn depth_snapshot(&self) -> DepthSnapshot<P, Q> {
+        let levels = |side| {
+            return self
+                .levels(side)
+                .map(|level| DepthLevel {
+                    price: level.price,
+                    volume: level.volume,
+                    order_count: level.len() as u32,
+                })
+                .collect();
         };
-        let mut quantity_left = quantity;
+        return DepthSnapshot {
+            sequence: self.depth_sequence,
+            bids: levels(Side::Bid),
+            asks: levels(Side::Ask),
+        };
+    }
 
-        match side {
-            Side::Bid => {
-                iter = iterate_min;
-            }
-            Side::Ask => {
-                iter = iterate_max;
+    /// The time on the book's clock, as it would timestamp an order now.
+    pub fn now(&self) -> u64 {
+        return self.clock.now();
+    }
+
+    /// Checks both sides' internal consistency (see
+    /// `BookSide::check_invariants`), that the book isn't crossed, and that
+    /// the order index refers to exactly the orders resting on the book.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        self.bids.check_invariants()?;
+        self.asks.check_invariants()?;
+
+        if let (Some(bid), Some(ask)) = (self.best_bid(), self.best_ask()) {
+            ensure!(bid < ask, "book is crossed: bid {} >= ask {}", bid, ask);
+        }
+
+        for (id, handle) in self.orders.iter() {
+            let order = match self.book_side(handle.side).get(handle.key) {
+                Some(order) => order,
+                None => {
+                    return Err(InvariantViolation(format!(
+                        "order {} is indexed but not on the book",
+                        id
+                    )))
+                }
+            };
+
+            ensure!(
+                order.id == *id,
+                "order {} is indexed under {}",
+                order.id,
+                id
+            );
+            ensure!(
+                order.side == handle.side,
+                "order {} is on the wrong side of the book",
+                id
+            );
+        }
+
+        let resting = self.bids.num_orders as usize + self.asks.num_orders as usize;
+        ensure!(
+            self.orders.len() == resting,
+            "{} orders are indexed but {} are resting",
+            self.orders.len(),
+            resting
+        );
+
+        return Ok(());
+    }
+
+    fn record(&mut self, event: BookEvent<P, Q>) {
+        if self.record_events {
+            self.events.push((self.clock.now(), event));
+        }
+    }
+
+    /// Counts a change to the level at `price` on `side`, and records its
+    /// new state if deltas are being kept.
+    fn level_changed(&mut self, side: Side, price: P) {
+        self.depth_sequence += 1;
+        if !self.record_depth {
+            return;
+        }
+
+        let (volume, order_count) = match self.book_side(side).price_level(price) {
+            Some(level) => (level.volume, level.len() as u32),
+            None => (Q::ZERO, 0),
+        };
+        self.deltas.push(LevelDelta {
+            sequence: self.depth_sequence,
+            side,
+            price,
+            volume,
+            order_count,
+        });
+    }
+
+    fn after_operation(&self) {
+        if cfg!(debug_assertions) && self.invariant_checks {
+            if let Err(violation) = self.check_invariants() {
+                panic!("{}", violation);
             }
         }
+    }
+
+    pub fn submit_market_order(&mut self, side: Side, quantity: Q) -> OrderResult<P, Q> {
+        let mut order_result = OrderResult::new();
+        let mut quantity_left = quantity;
+
+        let iter: fn(&BookSide<P, Q, I>) -> Option<P> = match side {
+            Side::Bid => iterate_min,
+            Side::Ask => iterate_max,
+        };
 
         loop {
-            if quantity_left <= Decimal::zero() || self.other_book_side(side).num_orders <= 0 {
+            if quantity_left <= Q::ZERO || self.other_book_side(side).num_orders == 0 {
                 break;
             }
 
             match iter(self.other_book_side(side)) {
                 None => break,
                 Some(best_price) => {
-                    let result = self.fill_at_price_level(best_price, quantity_left);
+                    let result = self.fill_at_price_level(side, best_price, quantity_left);
 
                     order_result.done.extend(&result.done);
                     order_result.quantity_filled += result.quantity_filled;
@@ -98,23 +304,177 @@ impl OrderBook {
             }
         }
 
+        self.after_operation();
         return order_result;
     }
 
-    pub fn submit_limit_order(
-        &mut self,
-        side: Side,
-        quantity: Decimal,
-        price: Decimal,
-    ) -> OrderResult {
-        let iter: fn(&BookSide) -> Option<Rc<RefCell<PriceLevel>>>;
-        let comparator: fn(Decimal, Decimal) -> bool;
+    pub fn submit_limit_order(&mut self, side: Side, quantity: Q, price: P) -> OrderResult<P, Q> {
+        let mut order_result = self.match_limit_order(side, quantity, price);
+        let quantity_left = quantity - order_result.quantity_filled;
 
-        let mut order_result = OrderResult {
-            done: Vec::new(),
-            partial: None,
-            quantity_filled: Decimal::zero(),
-        };
+        // Add the remaining quantity to the book.
+        // Note that we don't implement Time in Force, so the orders are effectively
+        // Good Till Canceled (GTC).
+        if quantity_left > Q::ZERO {
+            let resting_order = Order::new(
+                self.id_generator.next_id(),
+                side,
+                quantity_left,
+                price,
+                self.clock.now(),
+            );
+
+            self.append(resting_order);
+            order_result.partial = Some(resting_order);
+        }
+
+        self.after_operation();
+        order_result
+    }
+
+    pub fn best_bid(&self) -> Option<P> {
+        return iterate_max(&self.bids);
+    }
+
+    pub fn best_ask(&self) -> Option<P> {
+        return iterate_min(&self.asks);
+    }
+
+    /// Iterates over the price levels on `side`, best price first.
+    pub fn levels(&self, side: Side) -> Box<dyn Iterator<Item = &PriceLevel<P, Q>> + '_> {
+        match side {
+            Side::Bid => return Box::new(self.bids.levels_descending()),
+            Side::Ask => return Box::new(self.asks.levels_ascending()),
+        }
+    }
+
+    /// Iterates over the orders resting at `price` on `side` in time priority.
+    pub fn orders_at(&self, side: Side, price: P) -> impl Iterator<Item = &Order<P, Q>> {
+        return self.book_side(side).orders_at(price);
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&Order<P, Q>> {
+        let handle = self.orders.get(&id)?;
+        return self.book_side(handle.side).get(handle.key);
+    }
+
+    pub fn remove(&mut self, id: Uuid) -> Option<Order<P, Q>> {
+        let handle = self.orders.remove(&id)?;
+        let order = self.book_side_mut(handle.side).remove(handle.key);
+
+        if let Some(order) = order {
+            self.record(BookEvent::Delete {
+                id,
+                side: order.side,
+                price: order.price,
+                quantity: order.quantity,
+            });
+            self.level_changed(order.side, order.price);
+        }
+        self.after_operation();
+        return order;
+    }
+
+    /// Changes the quantity and price of a resting order.
+    ///
+    /// An amend that changes nothing leaves the order where it is, and
+    /// reducing the quantity at the same price is done in place; both keep
+    /// the order's time priority. Amending the quantity to zero cancels the
+    /// order, whatever the price. Any other change takes the order off the
+    /// book and resubmits it under the same id at the back of the queue,
+    /// where it may match. Returns `None` if there is no such order.
+    pub fn amend(&mut self, id: Uuid, quantity: Q, price: P) -> Option<OrderResult<P, Q>> {
+        let handle = *self.orders.get(&id)?;
+        let order = *self.book_side(handle.side).get(handle.key)?;
+
+        if price == order.price && quantity == order.quantity {
+            let mut order_result = OrderResult::new();
+            order_result.partial = Some(order);
+            return Some(order_result);
+        }
+        if quantity == Q::ZERO {
+            self.remove(id);
+            return Some(OrderResult::new());
+        }
+
+        if price == order.price && quantity < order.quantity {
+            let amended = self
+                .book_side_mut(handle.side)
+                .reduce(handle.key, order.quantity - quantity)?;
+            self.level_changed(order.side, order.price);
+
+            let mut order_result = OrderResult::new();
+            if amended.quantity == Q::ZERO {
+                self.orders.remove(&id);
+                self.record(BookEvent::Delete {
+                    id,
+                    side: order.side,
+                    price: order.price,
+                    quantity: order.quantity,
+                });
+            } else {
+                order_result.partial = Some(amended);
+                self.record(BookEvent::Reduce {
+                    id,
+                    side: order.side,
+                    price: order.price,
+                    quantity: order.quantity - quantity,
+                });
+            }
+
+            self.after_operation();
+            return Some(order_result);
+        }
+
+        self.remove(id);
+
+        let mut order_result = self.match_limit_order(order.side, quantity, price);
+        let quantity_left = quantity - order_result.quantity_filled;
+
+        if quantity_left > Q::ZERO {
+            let resting_order = Order::new(id, order.side, quantity_left, price, self.clock.now());
+
+            self.append(resting_order);
+            order_result.partial = Some(resting_order);
+        }
+
+        self.after_operation();
+        return Some(order_result);
+    }
+
+    /// Executes `command` with the method that handles it.
+    pub fn execute(&mut self, command: Command<P, Q>) -> CommandResult<P, Q> {
+        match command {
+            Command::Limit {
+                side,
+                quantity,
+                price,
+            } => return CommandResult::Matched(self.submit_limit_order(side, quantity, price)),
+            Command::Market { side, quantity } => {
+                return CommandResult::Matched(self.submit_market_order(side, quantity));
+            }
+            Command::Cancel { id } => match self.remove(id) {
+                Some(order) => return CommandResult::Canceled(order),
+                None => return CommandResult::NotFound,
+            },
+            Command::Amend {
+                id,
+                quantity,
+                price,
+            } => match self.amend(id, quantity, price) {
+                Some(result) => return CommandResult::Matched(result),
+                None => return CommandResult::NotFound,
+            },
+        }
+    }
+
+    /// Matches an incoming limit order against the other side of the book
+    /// without resting whatever is left over.
+    fn match_limit_order(&mut self, side: Side, quantity: Q, price: P) -> OrderResult<P, Q> {
+        let iter: fn(&BookSide<P, Q, I>) -> Option<P>;
+        let comparator: fn(P, P) -> bool;
+
+        let mut order_result = OrderResult::new();
         let mut quantity_left = quantity;
 
         match side {
@@ -132,14 +492,14 @@ impl OrderBook {
             match iter(self.other_book_side(side)) {
                 None => break,
                 Some(best_price) => {
-                    if quantity_left <= Decimal::zero()
-                        || self.other_book_side(side).num_orders <= 0
-                        || !comparator(price, best_price.borrow().price)
+                    if quantity_left <= Q::ZERO
+                        || self.other_book_side(side).num_orders == 0
+                        || !comparator(price, best_price)
                     {
                         break;
                     }
 
-                    let result = self.fill_at_price_level(best_price, quantity_left);
+                    let result = self.fill_at_price_level(side, best_price, quantity_left);
 
                     order_result.done.extend(&result.done);
                     order_result.quantity_filled += result.quantity_filled;
@@ -148,39 +508,32 @@ impl OrderBook {
             }
         }
 
-        // Add the remaining quantity to the book.
-        // Note that we don't implement Time in Force, so the orders are effectively
-        // Good Till Canceled (GTC).
-        if quantity_left > Decimal::zero() {
-            let resting_order = Order::new(side, quantity_left, price, time::Instant::now());
-
-            self.append(resting_order);
-            order_result.partial = Some(resting_order);
-        }
-
-        order_result
+        return order_result;
     }
 
-    pub fn get(&self, id: Uuid) -> Option<&Order> {
-        return self.orders.get(&id);
+    fn book_side(&self, side: Side) -> &BookSide<P, Q, I> {
+        match side {
+            Side::Ask => {
+                return &self.asks;
+            }
+            Side::Bid => {
+                return &self.bids;
+            }
+        }
     }
 
-    pub fn remove(&mut self, id: Uuid) -> Option<Order> {
-        if let Some(order) = self.orders.remove(&id) {
-            match order.side {
-                Side::Ask => {
-                    return self.asks.remove(order);
-                }
-                Side::Bid => {
-                    return self.bids.remove(order);
-                }
+    fn book_side_mut(&mut self, side: Side) -> &mut BookSide<P, Q, I> {
+        match side {
+            Side::Ask => {
+                return &mut self.asks;
+            }
+            Side::Bid => {
+                return &mut self.bids;
             }
         }
-
-        return None;
     }
 
-    fn other_book_side(&self, side: Side) -> &BookSide {
+    fn other_book_side(&self, side: Side) -> &BookSide<P, Q, I> {
         match side {
             Side::Ask => {
                 return &self.bids;
@@ -191,100 +544,91 @@ impl OrderBook {
         }
     }
 
-    fn fill_at_price_level(
-        &mut self,
-        price_level: Rc<RefCell<PriceLevel>>,
-        quantity: Decimal,
-    ) -> OrderResult {
-        let mut order_result = OrderResult {
-            done: Vec::new(),
-            partial: None,
-            quantity_filled: Decimal::zero(),
-        };
+    /// Fills up to `quantity` against the resting orders at `price` on the
+    /// side opposite to `side`, in time priority.
+    fn fill_at_price_level(&mut self, side: Side, price: P, quantity: Q) -> OrderResult<P, Q> {
+        let mut order_result = OrderResult::new();
         let mut quantity_left = quantity;
 
-        while quantity_left > Decimal::zero() && price_level.borrow().len() > 0 {
-            let mut remove_id: Option<Uuid> = None;
-
-            {
-                let mut price_level = price_level.borrow_mut();
-                if let Some(head) = price_level.front() {
-                    if quantity_left < head.quantity {
-                        let prev_quantity = head.quantity;
-
-                        let mut o = head.clone();
-                        o.quantity -= quantity_left;
-
-                        price_level.replace_front(o);
-                        self.orders.insert(o.id, o);
-                        match o.side {
-                            Side::Ask => {
-                                self.asks.volume -= prev_quantity;
-                                self.asks.volume += o.quantity;
-                            }
-                            Side::Bid => {
-                                self.bids.volume -= prev_quantity;
-                                self.bids.volume += o.quantity;
-                            }
-                        }
-
-                        order_result.done.push(Fill {
-                            order_id: o.id,
-                            status: FillStatus::Partial,
-                            price: o.price,
-                            quantity: quantity_left,
-                        });
-                        order_result.quantity_filled += quantity_left;
-
-                        quantity_left = Decimal::zero();
-                    } else {
-                        remove_id = Some(head.id);
-                    }
-                }
-            }
+        let resting_side = match side {
+            Side::Ask => Side::Bid,
+            Side::Bid => Side::Ask,
+        };
 
-            if let Some(id) = remove_id {
-                match self.remove(id) {
-                    Some(order) => {
-                        order_result.done.push(Fill {
-                            order_id: order.id,
-                            status: FillStatus::Full,
-                            price: order.price,
-                            quantity: order.quantity,
-                        });
-                        order_result.quantity_filled += order.quantity;
-
-                        quantity_left -= order.quantity;
-                    }
-                    None => {
-                        println!("this should never happen");
-                        // This should never happen
-                    }
-                }
+        while quantity_left > Q::ZERO {
+            let book_side = self.book_side_mut(resting_side);
+            let key = match book_side.front(price) {
+                Some(key) => key,
+                None => break,
+            };
+            let quantity = quantity_left.min(book_side.get(key).unwrap().quantity);
+
+            let order = match book_side.reduce(key, quantity) {
+                Some(order) => order,
+                None => break,
+            };
+            self.level_changed(resting_side, price);
+
+            let status;
+            if order.quantity > Q::ZERO {
+                status = FillStatus::Partial;
+            } else {
+                status = FillStatus::Full;
+                self.orders.remove(&order.id);
             }
+            if self.record_events {
+                let event = BookEvent::Execute {
+                    id: order.id,
+                    side: order.side,
+                    price: order.price,
+                    quantity,
+                };
+                self.events.push((self.clock.now(), event));
+            }
+
+            order_result.done.push(Fill {
+                order_id: order.id,
+                status,
+                price: order.price,
+                quantity,
+            });
+            order_result.quantity_filled += quantity;
+            quantity_left -= quantity;
         }
 
         return order_result;
     }
 
-    fn append(&mut self, order: Order) {
-        self.orders.insert(order.id, order);
+    fn append(&mut self, order: Order<P, Q>) {
+        self.record(BookEvent::Add(order));
+        let key = self.book_side_mut(order.side).append(order);
+        self.level_changed(order.side, order.price);
+        self.orders.insert(
+            order.id,
+            OrderHandle {
+                side: order.side,
+                key,
+            },
+        );
+    }
+}
 
-        match order.side {
-            Side::Ask => {
-                self.asks.append(order);
-            }
-            Side::Bid => {
-                self.bids.append(order);
-            }
-        }
+impl<P: Numeric, Q: Numeric> Default for OrderBook<P, Q> {
+    fn default() -> Self {
+        return OrderBook::new();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::{sequential_id, SequentialIdGenerator};
+    use crate::instrument::Instrument;
+    use crate::numeric::{Lots, Ticks};
     use crate::order::Side;
+    use crate::order_flow::{FlowConfig, OrderFlow};
+    use crate::price_ladder::PriceLadder;
     use rust_decimal_macros::*;
 
     #[test]
@@ -423,6 +767,134 @@ mod tests {
         assert_eq!(order_book.get(result.unwrap().id), None);
     }
 
+    #[test]
+    fn test_remove_deep_in_level() {
+        let mut order_book = OrderBook::new();
+
+        let ids: Vec<Uuid> = (0..5)
+            .map(|_| {
+                let result = order_book.submit_limit_order(Side::Ask, dec!(1.00), dec!(50.00));
+                result.partial.unwrap().id
+            })
+            .collect();
+
+        order_book.remove(ids[2]);
+
+        // Remaining orders fill in their original time priority
+        let result = order_book.submit_market_order(Side::Bid, dec!(4.00));
+        let order_ids: Vec<Uuid> = result.done.iter().map(|f| f.order_id).collect();
+        assert_eq!(order_ids, vec![ids[0], ids[1], ids[3], ids[4]]);
+    }
+
+    #[test]
+    fn test_amend_reduce_keeps_priority() {
+        let mut order_book = OrderBook::new();
+
+        let o1 = order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+        let o2 = order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+        let id1 = o1.partial.unwrap().id;
+
+        let result = order_book.amend(id1, dec!(2.00), dec!(50.00)).unwrap();
+
+        // Order was reduced in place
+        assert_eq!(result.done.len(), 0);
+        assert_eq!(result.partial.unwrap().quantity, dec!(2.00));
+        assert_eq!(order_book.get(id1).unwrap().quantity, dec!(2.00));
+
+        // It is still first in line
+        let result = order_book.submit_market_order(Side::Bid, dec!(3.00));
+        let mut order_ids = result.done.iter().map(|f| f.order_id);
+        assert_eq!(order_ids.next(), Some(id1));
+        assert_eq!(order_ids.next(), Some(o2.partial.unwrap().id));
+        assert_eq!(order_ids.next(), None);
+    }
+
+    #[test]
+    fn test_amend_increase_loses_priority() {
+        let mut order_book = OrderBook::new();
+
+        let o1 = order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+        let o2 = order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+        let id1 = o1.partial.unwrap().id;
+
+        order_book.amend(id1, dec!(6.00), dec!(50.00)).unwrap();
+
+        // Order keeps its id but moves to the back of the queue
+        assert_eq!(order_book.get(id1).unwrap().quantity, dec!(6.00));
+
+        let result = order_book.submit_market_order(Side::Bid, dec!(1.00));
+        assert_eq!(result.done[0].order_id, o2.partial.unwrap().id);
+    }
+
+    #[test]
+    fn test_amend_price_can_match() {
+        let mut order_book = OrderBook::new();
+
+        let bid = order_book.submit_limit_order(Side::Bid, dec!(5.00), dec!(40.00));
+        let ask = order_book.submit_limit_order(Side::Ask, dec!(3.00), dec!(50.00));
+        let bid_id = bid.partial.unwrap().id;
+
+        let result = order_book.amend(bid_id, dec!(5.00), dec!(50.00)).unwrap();
+
+        // Amended order crossed the spread and filled against the ask
+        assert_eq!(result.quantity_filled, dec!(3.00));
+        assert_eq!(result.done[0].order_id, ask.partial.unwrap().id);
+
+        // Remainder rests under the original id
+        assert_eq!(result.partial.unwrap().id, bid_id);
+        assert_eq!(order_book.get(bid_id).unwrap().quantity, dec!(2.00));
+        assert_eq!(order_book.get(bid_id).unwrap().price, dec!(50.00));
+    }
+
+    #[test]
+    fn test_amend_unchanged_keeps_priority() {
+        let mut order_book = OrderBook::new();
+        order_book.set_event_recording(true);
+
+        let o1 = order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+        let o2 = order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+        let id1 = o1.partial.unwrap().id;
+        order_book.take_events();
+
+        let result = order_book.amend(id1, dec!(5.00), dec!(50.00)).unwrap();
+        assert_eq!(result.partial.unwrap().quantity, dec!(5.00));
+        assert!(order_book.take_events().is_empty());
+
+        let result = order_book.submit_market_order(Side::Bid, dec!(1.00));
+        assert_eq!(result.done[0].order_id, id1);
+        assert_eq!(
+            order_book.get(o2.partial.unwrap().id).unwrap().quantity,
+            dec!(5.00)
+        );
+    }
+
+    #[test]
+    fn test_amend_to_zero_cancels() {
+        let mut order_book = OrderBook::new();
+
+        let bid = order_book.submit_limit_order(Side::Bid, dec!(5.00), dec!(40.00));
+        let ask = order_book.submit_limit_order(Side::Ask, dec!(3.00), dec!(50.00));
+        let bid_id = bid.partial.unwrap().id;
+
+        // Even at a price that would cross, nothing trades
+        let result = order_book.amend(bid_id, dec!(0.00), dec!(50.00)).unwrap();
+        assert!(result.done.is_empty());
+        assert!(result.partial.is_none());
+        assert!(order_book.get(bid_id).is_none());
+        assert_eq!(order_book.best_bid(), None);
+        let ask_id = ask.partial.unwrap().id;
+        assert_eq!(order_book.get(ask_id).unwrap().quantity, dec!(3.00));
+    }
+
+    #[test]
+    fn test_amend_no_order() {
+        let mut order_book = OrderBook::new();
+
+        assert!(order_book
+            .amend(Uuid::new_v4(), dec!(1.00), dec!(1.00))
+            .is_none());
+    }
+
     #[test]
     fn test_get() {
         let mut order_book = OrderBook::new();
@@ -431,14 +903,263 @@ mod tests {
 
         // Gets an order on the book
         assert_eq!(
-            order_book.get(result.partial.unwrap().id).map(|&o| o),
+            order_book.get(result.partial.unwrap().id).copied(),
             result.partial
         );
     }
 
+    #[test]
+    fn test_with_clock() {
+        let clock = ManualClock::new(1_000);
+        let mut order_book = OrderBook::with_clock(clock.clone(), SequentialIdGenerator::new());
+
+        let o1 = order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+        clock.advance(250);
+        let o2 = order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+
+        // Ids and timestamps come from the injected sources
+        let o1 = o1.partial.unwrap();
+        let o2 = o2.partial.unwrap();
+        assert_eq!(o1.id, sequential_id(1));
+        assert_eq!(o1.timestamp, 1_000);
+        assert_eq!(o2.id, sequential_id(2));
+        assert_eq!(o2.timestamp, 1_250);
+    }
+
+    #[test]
+    fn test_event_recording() {
+        let clock = ManualClock::new(0);
+        let mut order_book = OrderBook::with_clock(clock.clone(), SequentialIdGenerator::new());
+
+        order_book.submit_limit_order(Side::Ask, dec!(5), dec!(50));
+        assert!(order_book.take_events().is_empty());
+        order_book.set_event_recording(true);
+
+        clock.set(10);
+        let bid = order_book.submit_limit_order(Side::Bid, dec!(3), dec!(50));
+        let ask = order_book.submit_limit_order(Side::Ask, dec!(4), dec!(51));
+        let ask = ask.partial.unwrap();
+        order_book.amend(sequential_id(1), dec!(1), dec!(50));
+        order_book.amend(ask.id, dec!(4), dec!(52));
+        clock.set(20);
+        order_book.remove(ask.id);
+
+        assert!(bid.partial.is_none());
+        let ask_at_52 = Order {
+            price: dec!(52),
+            ..ask
+        };
+        assert_eq!(
+            order_book.take_events(),
+            vec![
+                (
+                    10,
+                    BookEvent::Execute {
+                        id: sequential_id(1),
+                        side: Side::Ask,
+                        price: dec!(50),
+                        quantity: dec!(3),
+                    }
+                ),
+                (10, BookEvent::Add(ask)),
+                (
+                    10,
+                    BookEvent::Reduce {
+                        id: sequential_id(1),
+                        side: Side::Ask,
+                        price: dec!(50),
+                        quantity: dec!(1),
+                    }
+                ),
+                (
+                    10,
+                    BookEvent::Delete {
+                        id: ask.id,
+                        side: Side::Ask,
+                        price: dec!(51),
+                        quantity: dec!(4),
+                    }
+                ),
+                (10, BookEvent::Add(ask_at_52)),
+                (
+                    20,
+                    BookEvent::Delete {
+                        id: ask.id,
+                        side: Side::Ask,
+                        price: dec!(52),
+                        quantity: dec!(4),
+                    }
+                ),
+            ]
+        );
+        assert!(order_book.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_integer_prices_and_quantities() {
+        let instrument = Instrument::new(dec!(0.01), dec!(100));
+        let mut order_book: OrderBook<Ticks, Lots> = OrderBook::new();
+
+        let price = instrument.to_ticks(dec!(50.25)).unwrap();
+        let o1 = order_book.submit_limit_order(Side::Ask, Lots(5), price);
+        let _o2 = order_book.submit_limit_order(Side::Ask, Lots(5), Ticks(5026));
+
+        let result = order_book.submit_limit_order(Side::Bid, Lots(7), Ticks(5025));
+
+        // Only the order at the limit price matched
+        assert_eq!(result.quantity_filled, Lots(5));
+        assert_eq!(result.done[0].order_id, o1.partial.unwrap().id);
+        assert_eq!(instrument.from_lots(result.quantity_filled), dec!(500));
+
+        // Remainder rests on the bid
+        let resting = result.partial.unwrap();
+        assert_eq!(resting.quantity, Lots(2));
+        assert_eq!(order_book.get(resting.id).unwrap().price, Ticks(5025));
+    }
+
+    #[test]
+    #[should_panic(expected = "book sides must be empty")]
+    fn test_book_sides_must_be_empty() {
+        let mut asks = BookSide::new();
+        asks.append(Order::new(Uuid::new_v4(), Side::Ask, dec!(1), dec!(10), 0));
+        OrderBook::with_book_sides(BookSide::new(), asks, SystemClock, UuidGenerator);
+    }
+
+    #[test]
+    fn test_price_ladder_book_sides() {
+        let ladder = PriceLadder::new(Ticks(4000), Ticks(1), 2000);
+        let mut order_book = OrderBook::with_book_sides(
+            BookSide::with_index(ladder.clone()),
+            BookSide::with_index(ladder),
+            SystemClock,
+            UuidGenerator,
+        );
+
+        let o1 = order_book.submit_limit_order(Side::Ask, Lots(5), Ticks(5025));
+        let o2 = order_book.submit_limit_order(Side::Ask, Lots(5), Ticks(5030));
+        let _o3 = order_book.submit_limit_order(Side::Ask, Lots(5), Ticks(9000));
+
+        let result = order_book.submit_limit_order(Side::Bid, Lots(7), Ticks(5030));
+
+        // Matched across levels in price priority
+        let mut order_ids = result.done.iter().map(|f| f.order_id);
+        assert_eq!(order_ids.next(), Some(o1.partial.unwrap().id));
+        assert_eq!(order_ids.next(), Some(o2.partial.unwrap().id));
+        assert_eq!(order_ids.next(), None);
+
+        // A market order walks into prices outside of the ladder's range
+        let result = order_book.submit_market_order(Side::Bid, Lots(8));
+        assert_eq!(result.quantity_filled, Lots(8));
+        assert_eq!(order_book.asks.volume, Lots(0));
+    }
+
+    #[test]
+    fn test_invariants_hold_under_random_flow() {
+        let mut order_book =
+            OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        order_book.set_invariant_checks(true);
+
+        for command in OrderFlow::new(FlowConfig::default()).take(3_000) {
+            order_book.execute(command);
+        }
+
+        assert_eq!(order_book.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut order_book = OrderBook::new();
+        let command = Command::Limit {
+            side: Side::Ask,
+            quantity: dec!(5),
+            price: dec!(10),
+        };
+        let id = match order_book.execute(command) {
+            CommandResult::Matched(result) => result.partial.unwrap().id,
+            result => panic!("expected a match, got {:?}", result),
+        };
+
+        let command = Command::Market {
+            side: Side::Bid,
+            quantity: dec!(2),
+        };
+        match order_book.execute(command) {
+            CommandResult::Matched(result) => assert_eq!(result.quantity_filled, dec!(2)),
+            result => panic!("expected a match, got {:?}", result),
+        }
+
+        match order_book.execute(Command::Cancel { id }) {
+            CommandResult::Canceled(order) => assert_eq!(order.quantity, dec!(3)),
+            result => panic!("expected a cancel, got {:?}", result),
+        }
+        let command = Command::Amend {
+            id,
+            quantity: dec!(1),
+            price: dec!(10),
+        };
+        assert!(matches!(
+            order_book.execute(command),
+            CommandResult::NotFound
+        ));
+    }
+
+    #[test]
+    fn test_check_invariants_finds_bad_counters() {
+        let mut order_book = OrderBook::new();
+        order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+
+        order_book.asks.volume += dec!(1.00);
+
+        assert!(order_book.check_invariants().is_err());
+    }
+
+    #[test]
+    fn test_check_invariants_finds_crossed_book() {
+        let mut order_book = OrderBook::new();
+        order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+
+        // Sneak a crossing bid onto the book without matching it
+        let order = Order::new(Uuid::new_v4(), Side::Bid, dec!(1.00), dec!(51.00), 0);
+        order_book.append(order);
+
+        let violation = order_book.check_invariants().unwrap_err();
+        assert!(violation.0.contains("crossed"), "{}", violation);
+    }
+
+    #[test]
+    fn test_check_invariants_finds_stale_index() {
+        let mut order_book = OrderBook::new();
+        let result = order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+        let id = result.partial.unwrap().id;
+
+        let handle = order_book.orders[&id];
+        order_book.asks.remove(handle.key);
+
+        assert!(order_book.check_invariants().is_err());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invariant violated")]
+    fn test_invariant_checks_panic_after_operation() {
+        let mut order_book = OrderBook::new();
+        order_book.set_invariant_checks(true);
+        order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+
+        order_book.asks.num_orders += 1;
+        order_book.submit_limit_order(Side::Ask, dec!(5.00), dec!(50.00));
+    }
+
+    #[test]
+    fn test_order_book_is_send() {
+        fn assert_send<T: Send>() {}
+
+        assert_send::<OrderBook>();
+    }
+
     #[test]
     fn test_get_no_order() {
-        let order_book = OrderBook::new();
+        let order_book: OrderBook = OrderBook::new();
 
         let id = Uuid::new_v4();
 
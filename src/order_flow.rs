@@ -0,0 +1,344 @@
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use uuid::Uuid;
+
+use crate::clock::ManualClock;
+use crate::command::{Command, CommandResult};
+use crate::id_generator::SequentialIdGenerator;
+use crate::order::Side;
+use crate::OrderBook;
+
+/// Shape of the order flow produced by `OrderFlow`.
+#[derive(Copy, Clone, Debug)]
+pub struct FlowConfig {
+    pub seed: u64,
+    /// Mid price to quote around until both sides of the book have orders.
+    pub mid: Decimal,
+    pub tick_size: Decimal,
+    pub lot_size: Decimal,
+    /// Mean distance from the mid, in ticks, of orders that don't cross.
+    /// Distances are exponentially distributed, so most orders cluster near
+    /// the touch with a long tail deeper into the book.
+    pub mean_depth: f64,
+    /// Mean order size in lots. Sizes are exponentially distributed and
+    /// capped at `max_lots`.
+    pub mean_lots: f64,
+    pub max_lots: u64,
+    /// Fraction of commands that cancel a resting order.
+    pub cancel_ratio: f64,
+    /// Fraction of commands that reduce the size of a resting order.
+    pub amend_ratio: f64,
+    /// Fraction of new orders that cross the spread.
+    pub marketable_ratio: f64,
+    /// Fraction of crossing orders sent as market rather than limit orders.
+    pub market_order_ratio: f64,
+}
+
+impl Default for FlowConfig {
+    fn default() -> Self {
+        return FlowConfig {
+            seed: 0,
+            mid: dec!(100.00),
+            tick_size: dec!(0.01),
+            lot_size: dec!(1),
+            mean_depth: 10.0,
+            mean_lots: 5.0,
+            max_lots: 100,
+            cancel_ratio: 0.4,
+            amend_ratio: 0.05,
+            marketable_ratio: 0.1,
+            market_order_ratio: 0.5,
+        };
+    }
+}
+
+/// A seeded generator of synthetic order flow.
+///
+/// The generator runs every command it produces against a book of its own,
+/// so cancels and amends always refer to orders that are resting at that
+/// point. That book assigns ids with a `SequentialIdGenerator`, which means
+/// the commands replay exactly against any fresh book built with
+/// `SequentialIdGenerator::new()`.
+#[derive(Debug)]
+pub struct OrderFlow {
+    config: FlowConfig,
+    rng: ChaCha8Rng,
+    book: OrderBook,
+    resting: Vec<Uuid>,
+}
+
+impl OrderFlow {
+    /// Panics if a ratio in `config` is outside [0, 1], or if its tick or lot
+    /// size isn't positive.
+    pub fn new(config: FlowConfig) -> Self {
+        let ratios = [
+            ("cancel_ratio", config.cancel_ratio),
+            ("amend_ratio", config.amend_ratio),
+            ("marketable_ratio", config.marketable_ratio),
+            ("market_order_ratio", config.market_order_ratio),
+        ];
+        for (name, ratio) in ratios.iter() {
+            assert!(
+                (0.0..=1.0).contains(ratio),
+                "{} must be between 0 and 1, not {}",
+                name,
+                ratio
+            );
+        }
+        assert!(
+            config.tick_size > Decimal::zero(),
+            "tick_size must be positive"
+        );
+        assert!(
+            config.lot_size > Decimal::zero(),
+            "lot_size must be positive"
+        );
+
+        return OrderFlow {
+            config,
+            rng: ChaCha8Rng::seed_from_u64(config.seed),
+            book: OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new()),
+            resting: Vec::new(),
+        };
+    }
+
+    /// The book the generated commands have been run against so far.
+    pub fn book(&self) -> &OrderBook {
+        return &self.book;
+    }
+
+    pub fn next_command(&mut self) -> Command {
+        let roll: f64 = self.rng.gen();
+        let command = if roll < self.config.cancel_ratio {
+            self.cancel()
+        } else if roll < self.config.cancel_ratio + self.config.amend_ratio {
+            self.amend()
+        } else {
+            None
+        };
+
+        let command = command.unwrap_or_else(|| self.new_order());
+        self.apply(command);
+        return command;
+    }
+
+    fn apply(&mut self, command: Command) {
+        if let CommandResult::Matched(result) = self.book.execute(command) {
+            if let Some(order) = result.partial {
+                self.resting.push(order.id);
+            }
+        }
+    }
+
+    fn cancel(&mut self) -> Option<Command> {
+        let id = self.pick_resting()?;
+        return Some(Command::Cancel { id });
+    }
+
+    fn amend(&mut self) -> Option<Command> {
+        let id = self.pick_resting()?;
+        let order = *self.book.get(id)?;
+        let lots = (order.quantity / self.config.lot_size)
+            .to_u64()
+            .unwrap_or(0);
+        if lots < 2 {
+            return None;
+        }
+
+        let quantity = Decimal::from(self.rng.gen_range(1..lots)) * self.config.lot_size;
+        return Some(Command::Amend {
+            id,
+            quantity,
+            price: order.price,
+        });
+    }
+
+    fn new_order(&mut self) -> Command {
+        let side = if self.rng.gen_bool(0.5) {
+            Side::Bid
+        } else {
+            Side::Ask
+        };
+        let quantity = Decimal::from(self.lots()) * self.config.lot_size;
+
+        if self.rng.gen_bool(self.config.marketable_ratio) {
+            let touch = match side {
+                Side::Bid => self.book.best_ask(),
+                Side::Ask => self.book.best_bid(),
+            };
+
+            if let Some(touch) = touch {
+                if self.rng.gen_bool(self.config.market_order_ratio) {
+                    return Command::Market { side, quantity };
+                }
+
+                // Cross by a few ticks so the order may take out several levels.
+                let through = Decimal::from(self.rng.gen_range(0..3)) * self.config.tick_size;
+                let price = match side {
+                    Side::Bid => touch + through,
+                    Side::Ask => touch - through,
+                };
+                return Command::Limit {
+                    side,
+                    quantity,
+                    price,
+                };
+            }
+        }
+
+        let depth = Decimal::from(1 + self.exponential(self.config.mean_depth) as u64);
+        let distance = depth * self.config.tick_size;
+        let mid = self.mid();
+        let price = match side {
+            Side::Bid => {
+                let price = mid - distance;
+                match self.book.best_ask() {
+                    Some(ask) => price.min(ask - self.config.tick_size),
+                    None => price,
+                }
+            }
+            Side::Ask => {
+                let price = mid + distance;
+                match self.book.best_bid() {
+                    Some(bid) => price.max(bid + self.config.tick_size),
+                    None => price,
+                }
+            }
+        };
+
+        return Command::Limit {
+            side,
+            quantity,
+            price,
+        };
+    }
+
+    /// Picks a random order that is still on the book, forgetting any that
+    /// have since been filled or canceled.
+    fn pick_resting(&mut self) -> Option<Uuid> {
+        while !self.resting.is_empty() {
+            let i = self.rng.gen_range(0..self.resting.len());
+            let id = self.resting[i];
+
+            if self.book.get(id).is_some() {
+                return Some(id);
+            }
+
+            self.resting.swap_remove(i);
+        }
+
+        return None;
+    }
+
+    fn mid(&self) -> Decimal {
+        let tick = self.config.tick_size;
+
+        match (self.book.best_bid(), self.book.best_ask()) {
+            (Some(bid), Some(ask)) => return ((bid + ask) / dec!(2) / tick).floor() * tick,
+            (Some(bid), None) => return bid + tick,
+            (None, Some(ask)) => return ask - tick,
+            (None, None) => return self.config.mid,
+        }
+    }
+
+    fn lots(&mut self) -> u64 {
+        let lots = 1 + self.exponential(self.config.mean_lots - 1.0) as u64;
+        return lots.min(self.config.max_lots.max(1));
+    }
+
+    fn exponential(&mut self, mean: f64) -> f64 {
+        if mean <= 0.0 {
+            return 0.0;
+        }
+
+        let u: f64 = self.rng.gen();
+        return -mean * (1.0 - u).ln();
+    }
+}
+
+impl Iterator for OrderFlow {
+    type Item = Command;
+
+    fn next(&mut self) -> Option<Command> {
+        return Some(self.next_command());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id_generator::SequentialIdGenerator;
+
+    #[test]
+    fn test_same_seed_same_flow() {
+        let config = FlowConfig {
+            seed: 7,
+            ..FlowConfig::default()
+        };
+
+        let a: Vec<Command> = OrderFlow::new(config).take(1_000).collect();
+        let b: Vec<Command> = OrderFlow::new(config).take(1_000).collect();
+
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_replays_against_fresh_book() {
+        let mut flow = OrderFlow::new(FlowConfig::default());
+        let commands: Vec<Command> = flow.by_ref().take(5_000).collect();
+
+        let mut order_book =
+            OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        for command in commands.iter() {
+            // Cancels and amends always refer to resting orders
+            let result = order_book.execute(*command);
+            assert!(!matches!(result, CommandResult::NotFound));
+        }
+
+        assert_eq!(order_book.best_bid(), flow.book().best_bid());
+        assert_eq!(order_book.best_ask(), flow.book().best_ask());
+    }
+
+    #[test]
+    fn test_ratios() {
+        let config = FlowConfig {
+            cancel_ratio: 0.3,
+            amend_ratio: 0.0,
+            marketable_ratio: 0.0,
+            ..FlowConfig::default()
+        };
+
+        let commands: Vec<Command> = OrderFlow::new(config).take(10_000).collect();
+        let cancels = commands
+            .iter()
+            .filter(|c| matches!(c, Command::Cancel { .. }))
+            .count();
+        let markets = commands
+            .iter()
+            .filter(|c| matches!(c, Command::Market { .. }))
+            .count();
+
+        assert!(cancels > 2_500 && cancels < 3_500, "{} cancels", cancels);
+        assert_eq!(markets, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "marketable_ratio must be between 0 and 1")]
+    fn test_rejects_ratio_above_one() {
+        OrderFlow::new(FlowConfig {
+            marketable_ratio: 1.5,
+            ..FlowConfig::default()
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "lot_size must be positive")]
+    fn test_rejects_zero_lot_size() {
+        OrderFlow::new(FlowConfig {
+            lot_size: Decimal::zero(),
+            ..FlowConfig::default()
+        });
+    }
+}
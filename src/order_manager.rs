@@ -0,0 +1,517 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::book_event::BookEvent;
+use crate::order::Side;
+use crate::trade::Trade;
+use crate::OrderBook;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum OrderType {
+    Limit,
+    /// Trades at whatever prices are on the book, and never rests.
+    Market,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TimeInForce {
+    /// Good till canceled: what doesn't trade straight away rests on the
+    /// book.
+    Gtc,
+    /// Immediate or cancel: what doesn't trade straight away is canceled.
+    Ioc,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    /// On the book, and hasn't traded.
+    New,
+    /// On the book, and has traded some of its quantity.
+    PartiallyFilled,
+    Filled,
+    /// Off the book before it completely filled, by request or because it
+    /// couldn't rest. It may still have traded some of its quantity.
+    Canceled,
+}
+
+/// An order to submit, as a client asks for it.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct NewOrder {
+    pub side: Side,
+    /// Limit unless given.
+    #[serde(rename = "type", default = "default_order_type")]
+    pub order_type: OrderType,
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::DecimalString"))]
+    pub quantity: Decimal,
+    /// Required for limit orders, and not allowed for market orders.
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Option<crate::schema::DecimalString>")
+    )]
+    pub price: Option<Decimal>,
+    /// Good till canceled for limit orders unless given. Market orders are
+    /// always immediate or cancel.
+    #[serde(default)]
+    pub time_in_force: Option<TimeInForce>,
+    /// Kept with the order and echoed back, to match it up.
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+}
+
+fn default_order_type() -> OrderType {
+    return OrderType::Limit;
+}
+
+/// A change to an order on the book. Either field can be left out to keep
+/// it as it is.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct OrderChange {
+    /// The new total quantity, including what's already filled.
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Option<crate::schema::DecimalString>")
+    )]
+    pub quantity: Option<Decimal>,
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Option<crate::schema::DecimalString>")
+    )]
+    pub price: Option<Decimal>,
+}
+
+/// An order and what's happened to it so far.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct OrderReport {
+    pub id: u64,
+    pub client_order_id: Option<String>,
+    pub side: Side,
+    #[serde(rename = "type")]
+    pub order_type: OrderType,
+    pub time_in_force: TimeInForce,
+    pub status: OrderStatus,
+    /// The limit price, or null for a market order.
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Option<crate::schema::DecimalString>")
+    )]
+    pub price: Option<Decimal>,
+    /// The total quantity, including what's filled.
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::DecimalString"))]
+    pub quantity: Decimal,
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::DecimalString"))]
+    pub filled: Decimal,
+    /// What's resting on the book, or still to be matched while the order
+    /// is being submitted.
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::DecimalString"))]
+    pub open: Decimal,
+    /// Oldest first.
+    pub fills: Vec<Execution>,
+}
+
+impl OrderReport {
+    fn update_status(&mut self) {
+        self.status = if self.open > Decimal::zero() {
+            if self.filled.is_zero() {
+                OrderStatus::New
+            } else {
+                OrderStatus::PartiallyFilled
+            }
+        } else if self.filled == self.quantity {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::Canceled
+        };
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Execution {
+    /// Nanoseconds since the Unix epoch, as reported by the book's `Clock`.
+    pub timestamp: u64,
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::DecimalString"))]
+    pub price: Decimal,
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::DecimalString"))]
+    pub quantity: Decimal,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ReportKind {
+    /// The order was accepted, and is about to be matched.
+    New,
+    Trade {
+        price: Decimal,
+        quantity: Decimal,
+    },
+    /// The order's quantity or price changed, and it's about to be matched
+    /// again.
+    Amended,
+    /// What was left of the order was taken off the book, or never rested.
+    Canceled,
+}
+
+/// Something that happened to an order, with the order as it was right
+/// after.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Report {
+    pub kind: ReportKind,
+    /// Nanoseconds since the Unix epoch, as reported by the book's `Clock`.
+    pub timestamp: u64,
+    pub order: OrderReport,
+}
+
+/// Why a request about an order was refused.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum OrderError {
+    /// No order has the id.
+    Unknown(u64),
+    /// The order has filled or been canceled, so it can't be changed.
+    Closed(u64),
+    /// The order can't be placed, or changed, as asked.
+    Invalid(String),
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderError::Unknown(id) => return write!(f, "no such order: {}", id),
+            OrderError::Closed(id) => return write!(f, "order {} is no longer on the book", id),
+            OrderError::Invalid(message) => return write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for OrderError {}
+
+/// Keeps track of the orders that clients place on an `OrderBook`, for the
+/// gateways that speak in whole orders rather than in book events.
+///
+/// Orders get their own ids, counting up from 1, and are remembered after
+/// they leave the book so their final status can still be looked up.
+/// Limit orders are good till canceled unless they say otherwise, and
+/// market orders are always immediate or cancel.
+#[derive(Debug)]
+pub struct OrderManager {
+    order_book: OrderBook,
+    orders: HashMap<u64, OrderReport>,
+    /// The book id of each order that's on the book, and the other way round.
+    book_ids: HashMap<u64, Uuid>,
+    ids: HashMap<Uuid, u64>,
+    trades: Vec<(u64, Trade)>,
+    next_id: u64,
+}
+
+impl OrderManager {
+    /// Manages orders on `order_book`, turning on its event recording.
+    pub fn new(mut order_book: OrderBook) -> Self {
+        order_book.set_event_recording(true);
+        return OrderManager {
+            order_book,
+            orders: HashMap::new(),
+            book_ids: HashMap::new(),
+            ids: HashMap::new(),
+            trades: Vec::new(),
+            next_id: 1,
+        };
+    }
+
+    pub fn order_book(&self) -> &OrderBook {
+        return &self.order_book;
+    }
+
+    pub fn order(&self, id: u64) -> Option<&OrderReport> {
+        return self.orders.get(&id);
+    }
+
+    /// Takes the trades made since they were last taken, with their
+    /// timestamps, oldest first.
+    pub fn take_trades(&mut self) -> Vec<(u64, Trade)> {
+        return std::mem::take(&mut self.trades);
+    }
+
+    /// Places `order` and matches it. The reports are of every order it
+    /// touched, the resting orders it traded with included, in the order
+    /// they happened.
+    pub fn submit(&mut self, order: NewOrder) -> Result<Vec<Report>, OrderError> {
+        if order.quantity <= Decimal::zero() {
+            return Err(invalid("quantity must be positive"));
+        }
+        let time_in_force = match (order.order_type, order.price, order.time_in_force) {
+            (OrderType::Limit, None, _) => return Err(invalid("limit orders need a price")),
+            (OrderType::Limit, Some(price), _) if price <= Decimal::zero() => {
+                return Err(invalid("price must be positive"));
+            }
+            (OrderType::Limit, Some(_), time_in_force) => time_in_force.unwrap_or(TimeInForce::Gtc),
+            (OrderType::Market, Some(_), _) => {
+                return Err(invalid("market orders can't have a price"));
+            }
+            (OrderType::Market, None, Some(TimeInForce::Gtc)) => {
+                return Err(invalid("market orders are immediate or cancel"));
+            }
+            (OrderType::Market, None, _) => TimeInForce::Ioc,
+        };
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let report = OrderReport {
+            id,
+            client_order_id: order.client_order_id,
+            side: order.side,
+            order_type: order.order_type,
+            time_in_force,
+            status: OrderStatus::New,
+            price: order.price,
+            quantity: order.quantity,
+            filled: Decimal::zero(),
+            open: order.quantity,
+            fills: Vec::new(),
+        };
+        self.orders.insert(id, report);
+        let mut reports = vec![self.report(id, ReportKind::New)];
+
+        let result = match order.price {
+            Some(price) => self
+                .order_book
+                .submit_limit_order(order.side, order.quantity, price),
+            None => self
+                .order_book
+                .submit_market_order(order.side, order.quantity),
+        };
+        self.record_events(id, &mut reports);
+        match result.partial {
+            Some(resting) if time_in_force == TimeInForce::Gtc => {
+                self.book_ids.insert(id, resting.id);
+                self.ids.insert(resting.id, id);
+            }
+            Some(resting) => {
+                self.order_book.remove(resting.id);
+                self.order_book.take_events();
+                reports.push(self.close(id));
+            }
+            None if self.orders[&id].open > Decimal::zero() => reports.push(self.close(id)),
+            None => {}
+        }
+        return Ok(reports);
+    }
+
+    /// Changes the total quantity or the price of an order on the book, and
+    /// matches it again if it now crosses.
+    pub fn amend(&mut self, id: u64, change: OrderChange) -> Result<Vec<Report>, OrderError> {
+        let book_id = self.book_id(id)?;
+        let order = &self.orders[&id];
+        let quantity = change.quantity.unwrap_or(order.quantity);
+        // Market orders never rest, so anything on the book has a price
+        let price = change.price.or(order.price).unwrap();
+        if quantity <= order.filled {
+            let message = format!(
+                "quantity must be more than the {} already filled",
+                order.filled
+            );
+            return Err(OrderError::Invalid(message));
+        }
+        if price <= Decimal::zero() {
+            return Err(invalid("price must be positive"));
+        }
+
+        let open = quantity - order.filled;
+        let order = self.orders.get_mut(&id).unwrap();
+        order.quantity = quantity;
+        order.price = Some(price);
+        order.open = open;
+        let mut reports = vec![self.report(id, ReportKind::Amended)];
+
+        let result = self.order_book.amend(book_id, open, price);
+        self.record_events(id, &mut reports);
+        if result.and_then(|result| result.partial).is_none() {
+            self.book_ids.remove(&id);
+            self.ids.remove(&book_id);
+        }
+        return Ok(reports);
+    }
+
+    /// Takes an order off the book.
+    pub fn cancel(&mut self, id: u64) -> Result<Report, OrderError> {
+        let book_id = self.book_id(id)?;
+        self.order_book.remove(book_id);
+        self.order_book.take_events();
+        return Ok(self.close(id));
+    }
+
+    /// The book id of an order that's on the book.
+    fn book_id(&self, id: u64) -> Result<Uuid, OrderError> {
+        if !self.orders.contains_key(&id) {
+            return Err(OrderError::Unknown(id));
+        }
+        match self.book_ids.get(&id) {
+            Some(book_id) => return Ok(*book_id),
+            None => return Err(OrderError::Closed(id)),
+        }
+    }
+
+    /// Applies the trades in the book's latest events to `taker`, the order
+    /// that made them, and to the resting orders it traded with.
+    fn record_events(&mut self, taker: u64, reports: &mut Vec<Report>) {
+        for (timestamp, event) in self.order_book.take_events() {
+            let (maker_id, price, quantity) = match event {
+                BookEvent::Execute {
+                    id,
+                    price,
+                    quantity,
+                    ..
+                } => (id, price, quantity),
+                _ => continue,
+            };
+            let trade = Trade {
+                aggressor: self.orders[&taker].side,
+                maker_id,
+                price,
+                quantity,
+            };
+            self.trades.push((timestamp, trade));
+
+            reports.push(self.fill(taker, price, quantity, timestamp));
+            if let Some(maker) = self.ids.get(&maker_id).copied() {
+                reports.push(self.fill(maker, price, quantity, timestamp));
+                if self.orders[&maker].open.is_zero() {
+                    self.book_ids.remove(&maker);
+                    self.ids.remove(&maker_id);
+                }
+            }
+        }
+    }
+
+    fn fill(&mut self, id: u64, price: Decimal, quantity: Decimal, timestamp: u64) -> Report {
+        let order = self.orders.get_mut(&id).unwrap();
+        order.filled += quantity;
+        order.open -= quantity;
+        order.fills.push(Execution {
+            timestamp,
+            price,
+            quantity,
+        });
+        order.update_status();
+        return Report {
+            kind: ReportKind::Trade { price, quantity },
+            timestamp,
+            order: order.clone(),
+        };
+    }
+
+    /// Cancels what's left of an order that's already off the book.
+    fn close(&mut self, id: u64) -> Report {
+        self.orders.get_mut(&id).unwrap().open = Decimal::zero();
+        if let Some(book_id) = self.book_ids.remove(&id) {
+            self.ids.remove(&book_id);
+        }
+        return self.report(id, ReportKind::Canceled);
+    }
+
+    fn report(&mut self, id: u64, kind: ReportKind) -> Report {
+        let order = self.orders.get_mut(&id).unwrap();
+        order.update_status();
+        return Report {
+            kind,
+            timestamp: self.order_book.now(),
+            order: order.clone(),
+        };
+    }
+}
+
+fn invalid(message: &str) -> OrderError {
+    return OrderError::Invalid(message.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use rust_decimal_macros::*;
+
+    fn limit(side: Side, quantity: Decimal, price: Decimal) -> NewOrder {
+        return NewOrder {
+            side,
+            order_type: OrderType::Limit,
+            quantity,
+            price: Some(price),
+            time_in_force: None,
+            client_order_id: None,
+        };
+    }
+
+    fn summary(report: &Report) -> (u64, ReportKind, OrderStatus, Decimal) {
+        return (
+            report.order.id,
+            report.kind,
+            report.order.status,
+            report.order.open,
+        );
+    }
+
+    #[test]
+    fn test_order_lifecycle() {
+        let mut manager = OrderManager::new(OrderBook::with_clock(
+            ManualClock::new(0),
+            SequentialIdGenerator::new(),
+        ));
+        manager
+            .submit(limit(Side::Ask, dec!(10), dec!(100)))
+            .unwrap();
+
+        let mut order = limit(Side::Bid, dec!(4), dec!(101));
+        order.time_in_force = Some(TimeInForce::Ioc);
+        let reports = manager.submit(order).unwrap();
+        let trade = ReportKind::Trade {
+            price: dec!(100),
+            quantity: dec!(4),
+        };
+        assert_eq!(
+            reports.iter().map(summary).collect::<Vec<_>>(),
+            vec![
+                (2, ReportKind::New, OrderStatus::New, dec!(4)),
+                (2, trade, OrderStatus::Filled, dec!(0)),
+                (1, trade, OrderStatus::PartiallyFilled, dec!(6)),
+            ]
+        );
+        assert_eq!(manager.take_trades().len(), 1);
+
+        let change = OrderChange {
+            quantity: Some(dec!(4)),
+            price: None,
+        };
+        assert_eq!(
+            manager.amend(1, change),
+            Err(OrderError::Invalid(String::from(
+                "quantity must be more than the 4 already filled"
+            )))
+        );
+
+        let report = manager.cancel(1).unwrap();
+        assert_eq!(
+            summary(&report),
+            (1, ReportKind::Canceled, OrderStatus::Canceled, dec!(0))
+        );
+        assert_eq!(manager.order(1).unwrap().filled, dec!(4));
+        assert_eq!(manager.cancel(1), Err(OrderError::Closed(1)));
+        assert_eq!(manager.cancel(9), Err(OrderError::Unknown(9)));
+        assert_eq!(manager.order_book().best_ask(), None);
+    }
+}
@@ -0,0 +1,525 @@
+use ouch_codec::message::{
+    cancel_reason, liquidity, reject_reason, stock, time_in_force, Inbound, Outbound, Token,
+    MARKET_PRICE,
+};
+use rust_decimal::prelude::*;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::order::Side;
+use crate::{Fill, FillStatus, OrderBook, OrderResult};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// An open order and who it belongs to.
+#[derive(Copy, Clone, Debug)]
+struct OrderState {
+    /// The connection that entered the order.
+    owner: u64,
+    token: Token,
+    side: ouch_codec::Side,
+    time_in_force: u32,
+    order_reference: u64,
+}
+
+/// Maps OUCH orders onto an `OrderBook` for a single symbol.
+///
+/// Enter Order submits a limit or market order, Replace Order amends it and
+/// Cancel Order reduces or removes it. Each returns the messages it leads
+/// to, addressed to the connections they're for: an order that trades also
+/// sends an execution to the owner of each resting order it filled.
+/// Immediate orders never rest, and what they can't fill is canceled
+/// straight away, as are the remains of market orders.
+///
+/// Tokens can't be reused on a connection. Orders belong to the connection
+/// that entered them, and are canceled when it's gone.
+#[derive(Debug)]
+pub struct Gateway {
+    symbol: String,
+    order_book: OrderBook,
+    /// Open orders, by their id in the book.
+    orders: HashMap<Uuid, OrderState>,
+    /// Open orders, by owner and current token.
+    tokens: HashMap<(u64, Token), Uuid>,
+    /// Every token each connection has used.
+    used_tokens: HashSet<(u64, Token)>,
+    next_order_reference: u64,
+    next_match_number: u64,
+}
+
+impl Gateway {
+    pub fn new(symbol: &str, order_book: OrderBook) -> Self {
+        return Gateway {
+            symbol: symbol.to_string(),
+            order_book,
+            orders: HashMap::new(),
+            tokens: HashMap::new(),
+            used_tokens: HashSet::new(),
+            next_order_reference: 1,
+            next_match_number: 1,
+        };
+    }
+
+    pub fn symbol(&self) -> &str {
+        return &self.symbol;
+    }
+
+    pub fn order_book(&self) -> &OrderBook {
+        return &self.order_book;
+    }
+
+    /// Handles a message from `connection` at `now`, in nanoseconds since
+    /// the epoch. Returns the messages to send and the connections to send
+    /// them to.
+    pub fn handle(&mut self, connection: u64, message: &Inbound, now: u64) -> Vec<(u64, Outbound)> {
+        let timestamp = now % NANOS_PER_DAY;
+        match *message {
+            Inbound::EnterOrder {
+                token,
+                side,
+                shares,
+                stock,
+                price,
+                time_in_force,
+            } => {
+                let reason = if stock != ouch_codec::stock(&self.symbol) {
+                    Some(reject_reason::INVALID_STOCK)
+                } else if shares == 0 {
+                    Some(reject_reason::INVALID_SHARES)
+                } else if price == 0 {
+                    Some(reject_reason::INVALID_PRICE)
+                } else if time_in_force != time_in_force::IMMEDIATE
+                    && time_in_force != time_in_force::DAY
+                {
+                    Some(reject_reason::INVALID_TIME_IN_FORCE)
+                } else if self.used_tokens.contains(&(connection, token)) {
+                    Some(reject_reason::DUPLICATE_TOKEN)
+                } else {
+                    None
+                };
+                if let Some(reason) = reason {
+                    return vec![(connection, rejected(timestamp, token, reason))];
+                }
+
+                let order = OrderState {
+                    owner: connection,
+                    token,
+                    side,
+                    time_in_force,
+                    order_reference: self.next_order_reference,
+                };
+                self.next_order_reference += 1;
+                self.used_tokens.insert((connection, token));
+                return self.enter(order, shares, price, timestamp);
+            }
+            Inbound::ReplaceOrder {
+                existing_token,
+                replacement_token,
+                shares,
+                price,
+            } => {
+                let id = self.tokens.get(&(connection, existing_token)).copied();
+                let reason = if id.is_none() {
+                    Some(reject_reason::UNKNOWN_TOKEN)
+                } else if self.used_tokens.contains(&(connection, replacement_token)) {
+                    Some(reject_reason::DUPLICATE_TOKEN)
+                } else if shares == 0 {
+                    Some(reject_reason::INVALID_SHARES)
+                } else if price == 0 || price == MARKET_PRICE {
+                    Some(reject_reason::INVALID_PRICE)
+                } else {
+                    None
+                };
+                match (id, reason) {
+                    (Some(id), None) => {
+                        self.used_tokens.insert((connection, replacement_token));
+                        return self.replace(id, replacement_token, shares, price, timestamp);
+                    }
+                    (_, reason) => {
+                        let reason = reason.unwrap_or(reject_reason::UNKNOWN_TOKEN);
+                        return vec![(connection, rejected(timestamp, replacement_token, reason))];
+                    }
+                }
+            }
+            Inbound::CancelOrder { token, shares } => {
+                match self.tokens.get(&(connection, token)).copied() {
+                    Some(id) => return self.cancel(id, shares, timestamp),
+                    None => {
+                        let reason = reject_reason::UNKNOWN_TOKEN;
+                        return vec![(connection, rejected(timestamp, token, reason))];
+                    }
+                }
+            }
+        }
+    }
+
+    /// Cancels the open orders of a connection that's gone, and forgets the
+    /// tokens it used.
+    pub fn disconnected(&mut self, connection: u64) {
+        let ids: Vec<Uuid> = self
+            .orders
+            .iter()
+            .filter(|(_, order)| order.owner == connection)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            self.order_book.remove(id);
+            let order = self.orders.remove(&id).unwrap();
+            self.tokens.remove(&(connection, order.token));
+        }
+        self.used_tokens.retain(|(owner, _)| *owner != connection);
+    }
+
+    fn enter(
+        &mut self,
+        order: OrderState,
+        shares: u32,
+        price: u32,
+        timestamp: u64,
+    ) -> Vec<(u64, Outbound)> {
+        let accepted = Outbound::Accepted {
+            timestamp,
+            token: order.token,
+            side: order.side,
+            shares,
+            stock: stock(&self.symbol),
+            price,
+            time_in_force: order.time_in_force,
+            order_reference: order.order_reference,
+        };
+        let mut messages = vec![(order.owner, accepted)];
+
+        let side = book_side(order.side);
+        let quantity = Decimal::from(shares);
+        let result = if price == MARKET_PRICE {
+            self.order_book.submit_market_order(side, quantity)
+        } else {
+            self.order_book
+                .submit_limit_order(side, quantity, to_decimal(price))
+        };
+        self.fills(&order, &result.done, timestamp, &mut messages);
+
+        let left = shares - to_shares(result.quantity_filled);
+        match result.partial {
+            Some(resting) if order.time_in_force == time_in_force::DAY => {
+                self.tokens.insert((order.owner, order.token), resting.id);
+                self.orders.insert(resting.id, order);
+            }
+            Some(resting) => {
+                self.order_book.remove(resting.id);
+                messages.push((order.owner, immediate_cancel(timestamp, order.token, left)));
+            }
+            None if left > 0 => {
+                messages.push((order.owner, immediate_cancel(timestamp, order.token, left)));
+            }
+            None => {}
+        }
+        return messages;
+    }
+
+    fn replace(
+        &mut self,
+        id: Uuid,
+        token: Token,
+        shares: u32,
+        price: u32,
+        timestamp: u64,
+    ) -> Vec<(u64, Outbound)> {
+        let mut order = self.orders.remove(&id).unwrap();
+        self.tokens.remove(&(order.owner, order.token));
+        let previous_token = order.token;
+        order.token = token;
+
+        let replaced = Outbound::Replaced {
+            timestamp,
+            token,
+            side: order.side,
+            shares,
+            stock: stock(&self.symbol),
+            price,
+            time_in_force: order.time_in_force,
+            order_reference: order.order_reference,
+            previous_token,
+        };
+        let mut messages = vec![(order.owner, replaced)];
+
+        let result: OrderResult = self
+            .order_book
+            .amend(id, Decimal::from(shares), to_decimal(price))
+            .unwrap();
+        self.fills(&order, &result.done, timestamp, &mut messages);
+        if result.partial.is_some() {
+            self.tokens.insert((order.owner, token), id);
+            self.orders.insert(id, order);
+        }
+        return messages;
+    }
+
+    fn cancel(&mut self, id: Uuid, shares: u32, timestamp: u64) -> Vec<(u64, Outbound)> {
+        let resting = *self.order_book.get(id).unwrap();
+        let open = to_shares(resting.quantity);
+        if shares >= open {
+            return Vec::new();
+        }
+
+        let order = self.orders[&id];
+        if shares == 0 {
+            self.order_book.remove(id);
+            self.orders.remove(&id);
+            self.tokens.remove(&(order.owner, order.token));
+        } else {
+            self.order_book
+                .amend(id, Decimal::from(shares), resting.price);
+        }
+        let canceled = Outbound::Canceled {
+            timestamp,
+            token: order.token,
+            decrement_shares: open - shares,
+            reason: cancel_reason::USER_REQUESTED,
+        };
+        return vec![(order.owner, canceled)];
+    }
+
+    /// Sends an Order Executed per fill to both sides of it, flagged as
+    /// removing or adding liquidity, under one match number per fill.
+    fn fills(
+        &mut self,
+        order: &OrderState,
+        fills: &[Fill],
+        timestamp: u64,
+        messages: &mut Vec<(u64, Outbound)>,
+    ) {
+        for fill in fills.iter() {
+            let match_number = self.next_match_number;
+            self.next_match_number += 1;
+            let execution = |token, liquidity_flag| {
+                return Outbound::Executed {
+                    timestamp,
+                    token,
+                    executed_shares: to_shares(fill.quantity),
+                    execution_price: to_price(fill.price),
+                    liquidity_flag,
+                    match_number,
+                };
+            };
+            messages.push((order.owner, execution(order.token, liquidity::REMOVED)));
+
+            let maker = match self.orders.get(&fill.order_id) {
+                Some(maker) => *maker,
+                None => continue,
+            };
+            messages.push((maker.owner, execution(maker.token, liquidity::ADDED)));
+            if let FillStatus::Full = fill.status {
+                self.orders.remove(&fill.order_id);
+                self.tokens.remove(&(maker.owner, maker.token));
+            }
+        }
+    }
+}
+
+fn rejected(timestamp: u64, token: Token, reason: u8) -> Outbound {
+    return Outbound::Rejected {
+        timestamp,
+        token,
+        reason,
+    };
+}
+
+fn immediate_cancel(timestamp: u64, token: Token, shares: u32) -> Outbound {
+    return Outbound::Canceled {
+        timestamp,
+        token,
+        decrement_shares: shares,
+        reason: cancel_reason::IMMEDIATE_OR_CANCEL,
+    };
+}
+
+fn book_side(side: ouch_codec::Side) -> Side {
+    match side {
+        ouch_codec::Side::Buy => return Side::Bid,
+        ouch_codec::Side::Sell => return Side::Ask,
+    }
+}
+
+fn to_decimal(price: u32) -> Decimal {
+    return Decimal::new(price as i64, 4).normalize();
+}
+
+/// Book prices all came in as OUCH prices, so they always fit.
+fn to_price(price: Decimal) -> u32 {
+    return (price * Decimal::from(10_000)).to_u32().unwrap();
+}
+
+fn to_shares(quantity: Decimal) -> u32 {
+    return quantity.to_u32().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use ouch_codec::Side::{Buy, Sell};
+
+    fn gateway() -> Gateway {
+        let order_book = OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        return Gateway::new("ABC", order_book);
+    }
+
+    fn enter(token: &str, side: ouch_codec::Side, shares: u32, price: u32) -> Inbound {
+        return Inbound::EnterOrder {
+            token: Token::new(token),
+            side,
+            shares,
+            stock: stock("ABC"),
+            price,
+            time_in_force: time_in_force::DAY,
+        };
+    }
+
+    /// Each message as "connection: type token shares".
+    fn summary(messages: &[(u64, Outbound)]) -> Vec<String> {
+        return messages
+            .iter()
+            .map(|(connection, m)| {
+                let detail = match *m {
+                    Outbound::Accepted { shares, .. } | Outbound::Replaced { shares, .. } => {
+                        shares.to_string()
+                    }
+                    Outbound::Executed {
+                        executed_shares,
+                        liquidity_flag,
+                        ..
+                    } => format!("{}{}", executed_shares, liquidity_flag as char),
+                    Outbound::Canceled {
+                        decrement_shares,
+                        reason,
+                        ..
+                    } => format!("{}{}", decrement_shares, reason as char),
+                    Outbound::Rejected { reason, .. } => (reason as char).to_string(),
+                };
+                return format!(
+                    "{}: {} {} {}",
+                    connection,
+                    m.message_type() as char,
+                    m.token(),
+                    detail
+                );
+            })
+            .collect();
+    }
+
+    #[test]
+    fn test_orders_trade() {
+        let mut gateway = gateway();
+
+        let messages = gateway.handle(1, &enter("a1", Sell, 5, 100_000), 0);
+        assert_eq!(summary(&messages), ["1: A a1 5"]);
+
+        let messages = gateway.handle(2, &enter("b1", Buy, 8, 110_000), 0);
+        assert_eq!(
+            summary(&messages),
+            ["2: A b1 8", "2: E b1 5R", "1: E a1 5A"]
+        );
+        match messages[1].1 {
+            Outbound::Executed {
+                execution_price,
+                match_number,
+                ..
+            } => assert_eq!((execution_price, match_number), (100_000, 1)),
+            _ => unreachable!(),
+        }
+        assert_eq!(gateway.order_book().best_bid(), Some(Decimal::from(11)));
+
+        // A market order that runs out of liquidity has the rest canceled
+        let messages = gateway.handle(1, &enter("a2", Sell, 4, MARKET_PRICE), 0);
+        assert_eq!(
+            summary(&messages),
+            ["1: A a2 4", "1: E a2 3R", "2: E b1 3A", "1: C a2 1I"]
+        );
+
+        // And so does an immediate order, without resting
+        let mut ioc = enter("b2", Buy, 2, 90_000);
+        if let Inbound::EnterOrder { time_in_force, .. } = &mut ioc {
+            *time_in_force = time_in_force::IMMEDIATE;
+        }
+        let messages = gateway.handle(2, &ioc, 0);
+        assert_eq!(summary(&messages), ["2: A b2 2", "2: C b2 2I"]);
+        assert_eq!(gateway.order_book().best_bid(), None);
+    }
+
+    #[test]
+    fn test_replace_and_cancel() {
+        let mut gateway = gateway();
+        gateway.handle(1, &enter("a1", Buy, 5, 100_000), 0);
+
+        let replace = |existing, replacement, shares, price| {
+            return Inbound::ReplaceOrder {
+                existing_token: Token::new(existing),
+                replacement_token: Token::new(replacement),
+                shares,
+                price,
+            };
+        };
+        let messages = gateway.handle(1, &replace("a1", "a2", 7, 90_000), 0);
+        assert_eq!(summary(&messages), ["1: U a2 7"]);
+        assert_eq!(gateway.order_book().best_bid(), Some(Decimal::from(9)));
+
+        // Only the owner can cancel, and only by the current token
+        let cancel = |token, shares| {
+            return Inbound::CancelOrder {
+                token: Token::new(token),
+                shares,
+            };
+        };
+        let messages = gateway.handle(2, &cancel("a2", 0), 0);
+        assert_eq!(summary(&messages), ["2: J a2 U"]);
+        let messages = gateway.handle(1, &cancel("a1", 0), 0);
+        assert_eq!(summary(&messages), ["1: J a1 U"]);
+
+        // Reducing keeps the order, asking for more does nothing
+        let messages = gateway.handle(1, &cancel("a2", 3), 0);
+        assert_eq!(summary(&messages), ["1: C a2 4U"]);
+        assert!(gateway.handle(1, &cancel("a2", 3), 0).is_empty());
+
+        // A replace that crosses trades straight away
+        gateway.handle(2, &enter("b1", Sell, 2, 95_000), 0);
+        let messages = gateway.handle(1, &replace("a2", "a3", 3, 95_000), 0);
+        assert_eq!(
+            summary(&messages),
+            ["1: U a3 3", "1: E a3 2R", "2: E b1 2A"]
+        );
+
+        let messages = gateway.handle(1, &cancel("a3", 0), 0);
+        assert_eq!(summary(&messages), ["1: C a3 1U"]);
+        assert_eq!(gateway.order_book().best_bid(), None);
+    }
+
+    #[test]
+    fn test_rejects_and_disconnects() {
+        let mut gateway = gateway();
+        let mut wrong_stock = enter("a1", Buy, 5, 100_000);
+        if let Inbound::EnterOrder { stock, .. } = &mut wrong_stock {
+            *stock = ouch_codec::stock("XYZ");
+        }
+        let messages = gateway.handle(1, &wrong_stock, 0);
+        assert_eq!(summary(&messages), ["1: J a1 S"]);
+        let messages = gateway.handle(1, &enter("a1", Buy, 0, 100_000), 0);
+        assert_eq!(summary(&messages), ["1: J a1 Z"]);
+
+        gateway.handle(1, &enter("a1", Buy, 5, 100_000), 0);
+        gateway.handle(1, &enter("a2", Buy, 5, 100_000), 0);
+        let messages = gateway.handle(1, &enter("a1", Buy, 5, 100_000), 0);
+        assert_eq!(summary(&messages), ["1: J a1 D"]);
+
+        // Other connections have their own tokens
+        let messages = gateway.handle(2, &enter("a1", Buy, 5, 90_000), 0);
+        assert_eq!(summary(&messages), ["2: A a1 5"]);
+
+        gateway.disconnected(1);
+        assert_eq!(gateway.order_book().best_bid(), Some(Decimal::from(9)));
+        let messages = gateway.handle(1, &enter("a1", Buy, 5, 100_000), 86_400_000_000_001);
+        assert_eq!(messages[0].1.timestamp(), 1);
+        assert_eq!(summary(&messages), ["1: A a1 5"]);
+    }
+}
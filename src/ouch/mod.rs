@@ -0,0 +1,5 @@
+pub mod gateway;
+pub mod server;
+
+pub use gateway::Gateway;
+pub use server::Server;
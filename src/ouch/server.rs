@@ -0,0 +1,194 @@
+use ouch_codec::message::{read_frame, write_frame, Inbound};
+use std::collections::HashMap;
+use std::io;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::clock::{Clock, SystemClock};
+use crate::ouch::gateway::Gateway;
+
+/// Accepts OUCH connections over TCP and routes their orders to a
+/// `Gateway`.
+///
+/// Messages are framed with a two-byte length, as in SoupBinTCP, but there's
+/// no login or heartbeating: each connection is its own account, and its
+/// orders are canceled when it closes. A message that can't be decoded
+/// closes the connection. The thread that enters an order also writes the
+/// Order Executed to the owner of whatever it traded with.
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    gateway: Gateway,
+    connections: HashMap<u64, TcpStream>,
+    next_connection: u64,
+}
+
+impl Server {
+    /// Listens on `addr`. Without a SoupBinTCP login, anyone who can reach
+    /// `addr` can enter orders.
+    pub fn bind(addr: impl ToSocketAddrs, gateway: Gateway) -> io::Result<Self> {
+        let shared = Shared {
+            gateway,
+            connections: HashMap::new(),
+            next_connection: 1,
+        };
+        return Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            shared: Arc::new(Mutex::new(shared)),
+        });
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        return self.listener.local_addr();
+    }
+
+    /// Gives each connection its own account and thread, until the listener
+    /// fails.
+    pub fn run(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let _ = stream.set_nodelay(true);
+            let connection = {
+                let mut shared = self.shared.lock().unwrap();
+                let connection = shared.next_connection;
+                shared.next_connection += 1;
+                shared.connections.insert(connection, stream.try_clone()?);
+                connection
+            };
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || serve(connection, stream, shared));
+        }
+        return Ok(());
+    }
+}
+
+/// Runs one connection until it drops or sends something undecodable.
+fn serve(connection: u64, mut stream: TcpStream, shared: Arc<Mutex<Shared>>) {
+    while let Ok(Some(bytes)) = read_frame(&mut stream) {
+        let message = match Inbound::decode(&bytes) {
+            Ok(message) => message,
+            Err(_) => break,
+        };
+
+        let mut shared = shared.lock().unwrap();
+        let messages = shared
+            .gateway
+            .handle(connection, &message, SystemClock.now());
+        for (to, message) in messages {
+            let stream = match shared.connections.get_mut(&to) {
+                Some(stream) => stream,
+                None => continue,
+            };
+            // A connection that fails is shut down, and its thread cleans up
+            // when its read fails.
+            if write_frame(stream, &message.encode()).is_err() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+
+    let mut shared = shared.lock().unwrap();
+    shared.connections.remove(&connection);
+    shared.gateway.disconnected(connection);
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use crate::OrderBook;
+    use ouch_codec::message::{cancel_reason, liquidity, time_in_force, Outbound, Side};
+    use ouch_codec::Client;
+    use std::io::Write;
+    use std::time::Duration;
+
+    fn connect(addr: SocketAddr) -> Client {
+        let client = Client::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        return client;
+    }
+
+    #[test]
+    fn test_orders_over_tcp() {
+        let order_book = OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        let server = Server::bind("127.0.0.1:0", Gateway::new("ABC", order_book)).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let mut seller = connect(addr);
+        seller
+            .enter_order("s1", Side::Sell, 10, "ABC", 1_005_000, time_in_force::DAY)
+            .unwrap();
+        match seller.receive().unwrap() {
+            Some(Outbound::Accepted { shares, .. }) => assert_eq!(shares, 10),
+            message => panic!("expected Accepted, got {:?}", message),
+        }
+
+        let mut buyer = connect(addr);
+        buyer
+            .enter_order("b1", Side::Buy, 4, "ABC", 1_010_000, time_in_force::DAY)
+            .unwrap();
+        assert_eq!(buyer.receive().unwrap().unwrap().message_type(), b'A');
+        match buyer.receive().unwrap() {
+            Some(Outbound::Executed {
+                executed_shares,
+                execution_price,
+                liquidity_flag,
+                ..
+            }) => {
+                assert_eq!(executed_shares, 4);
+                assert_eq!(execution_price, 1_005_000);
+                assert_eq!(liquidity_flag, liquidity::REMOVED);
+            }
+            message => panic!("expected Executed, got {:?}", message),
+        }
+
+        // The seller hears about the trade on its own connection
+        let fill = seller.receive().unwrap().unwrap();
+        assert_eq!(fill.message_type(), b'E');
+        assert_eq!(fill.token().to_string(), "s1");
+
+        seller.replace_order("s1", "s2", 6, 1_006_000).unwrap();
+        assert_eq!(seller.receive().unwrap().unwrap().message_type(), b'U');
+        seller.cancel_order("s2", 2).unwrap();
+        match seller.receive().unwrap() {
+            Some(Outbound::Canceled {
+                decrement_shares,
+                reason,
+                ..
+            }) => assert_eq!(
+                (decrement_shares, reason),
+                (4, cancel_reason::USER_REQUESTED)
+            ),
+            message => panic!("expected Canceled, got {:?}", message),
+        }
+
+        // Closing the connection cancels the seller's orders, so a market
+        // order finds nothing to trade with
+        seller.close().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        buyer
+            .enter_order("b2", Side::Buy, 1, "ABC", ouch_codec::MARKET_PRICE, 0)
+            .unwrap();
+        assert_eq!(buyer.receive().unwrap().unwrap().message_type(), b'A');
+        assert_eq!(buyer.receive().unwrap().unwrap().message_type(), b'C');
+
+        // Garbage closes the connection
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(&[0, 1, b'?']).unwrap();
+        let mut client = connect(addr);
+        client.cancel_order("x", 0).unwrap();
+        assert_eq!(client.receive().unwrap().unwrap().message_type(), b'J');
+        assert_eq!(read_frame(&mut stream).unwrap(), None);
+    }
+}
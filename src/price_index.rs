@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound::{Excluded, Unbounded};
+
+use crate::numeric::Numeric;
+
+/// Maps the prices on one side of the book to the keys of their levels.
+///
+/// This is the part of a `BookSide` that can be swapped out: the side keeps
+/// its orders and levels in arenas either way, and only asks the index which
+/// level sits at a price and which non-empty level comes next. `PriceTree`
+/// works for any price; `PriceLadder` is faster when prices fall on a known,
+/// bounded grid of ticks.
+pub trait PriceIndex<P>: fmt::Debug + Send {
+    fn get(&self, price: P) -> Option<usize>;
+
+    fn insert(&mut self, price: P, level: usize);
+
+    fn remove(&mut self, price: P) -> Option<usize>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// Returns the level with the lowest price.
+    fn first(&self) -> Option<usize>;
+
+    /// Returns the level with the highest price.
+    fn last(&self) -> Option<usize>;
+
+    /// Returns the level with the lowest price above `price`.
+    fn next_above(&self, price: P) -> Option<usize>;
+
+    /// Returns the level with the highest price below `price`.
+    fn next_below(&self, price: P) -> Option<usize>;
+}
+
+/// A `PriceIndex` backed by an ordered map.
+#[derive(Clone, Debug, Default)]
+pub struct PriceTree<P> {
+    tree: BTreeMap<P, usize>,
+}
+
+impl<P: Numeric> PriceTree<P> {
+    pub fn new() -> Self {
+        return PriceTree {
+            tree: BTreeMap::new(),
+        };
+    }
+}
+
+impl<P: Numeric> PriceIndex<P> for PriceTree<P> {
+    fn get(&self, price: P) -> Option<usize> {
+        return self.tree.get(&price).copied();
+    }
+
+    fn insert(&mut self, price: P, level: usize) {
+        self.tree.insert(price, level);
+    }
+
+    fn remove(&mut self, price: P) -> Option<usize> {
+        return self.tree.remove(&price);
+    }
+
+    fn len(&self) -> usize {
+        return self.tree.len();
+    }
+
+    fn first(&self) -> Option<usize> {
+        return self.tree.values().next().copied();
+    }
+
+    fn last(&self) -> Option<usize> {
+        return self.tree.values().next_back().copied();
+    }
+
+    fn next_above(&self, price: P) -> Option<usize> {
+        return self
+            .tree
+            .range((Excluded(price), Unbounded))
+            .next()
+            .map(|(_, &level)| level);
+    }
+
+    fn next_below(&self, price: P) -> Option<usize> {
+        return self
+            .tree
+            .range((Unbounded, Excluded(price)))
+            .next_back()
+            .map(|(_, &level)| level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numeric::Ticks;
+
+    #[test]
+    fn test_price_tree() {
+        let mut index = PriceTree::new();
+
+        index.insert(Ticks(10), 0);
+        index.insert(Ticks(30), 1);
+        index.insert(Ticks(20), 2);
+
+        assert_eq!(index.get(Ticks(20)), Some(2));
+        assert_eq!(index.first(), Some(0));
+        assert_eq!(index.last(), Some(1));
+        assert_eq!(index.next_above(Ticks(10)), Some(2));
+        assert_eq!(index.next_above(Ticks(15)), Some(2));
+        assert_eq!(index.next_below(Ticks(20)), Some(0));
+        assert_eq!(index.next_below(Ticks(10)), None);
+
+        assert_eq!(index.remove(Ticks(20)), Some(2));
+        assert_eq!(index.next_above(Ticks(10)), Some(1));
+        assert_eq!(index.len(), 2);
+    }
+}
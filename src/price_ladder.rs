@@ -0,0 +1,422 @@
+use rust_decimal::prelude::*;
+use std::collections::BTreeMap;
+use std::ops::Bound::{Excluded, Unbounded};
+
+use crate::numeric::{Numeric, Ticks};
+use crate::price_index::PriceIndex;
+
+/// Prices that can be laid out on a grid of ticks.
+pub trait TickPrice: Numeric {
+    /// Returns `(self - base) / tick_size` rounded down, and whether the
+    /// division was exact. An offset too far to compute is `i64::MIN` or
+    /// `i64::MAX`, and never exact.
+    fn tick_offset(self, base: Self, tick_size: Self) -> (i64, bool);
+}
+
+/// The offset of a price too far from the base to compute.
+fn out_of_range<P: Numeric>(price: P, base: P) -> (i64, bool) {
+    return (if price > base { i64::MAX } else { i64::MIN }, false);
+}
+
+impl TickPrice for Decimal {
+    fn tick_offset(self, base: Self, tick_size: Self) -> (i64, bool) {
+        let n = match self
+            .checked_sub(base)
+            .and_then(|distance| distance.checked_div(tick_size))
+        {
+            Some(n) => n,
+            None => return out_of_range(self, base),
+        };
+        let offset = match n.floor().to_i64() {
+            Some(offset) => offset,
+            None => return out_of_range(self, base),
+        };
+        return (offset, n.fract() == Decimal::ZERO);
+    }
+}
+
+impl TickPrice for Ticks {
+    fn tick_offset(self, base: Self, tick_size: Self) -> (i64, bool) {
+        let distance = match self.0.checked_sub(base.0) {
+            Some(distance) => distance,
+            None => return out_of_range(self, base),
+        };
+        return (
+            distance.div_euclid(tick_size.0),
+            distance.rem_euclid(tick_size.0) == 0,
+        );
+    }
+}
+
+/// A `PriceIndex` backed by a dense array with one slot per tick.
+///
+/// Slots cover `levels` ticks starting at `min_price`. A bitmap of occupied
+/// slots makes finding the next non-empty level a matter of scanning words
+/// rather than slots, and the lowest and highest occupied slots are tracked
+/// so the best price is always at hand. Prices outside the range, or off the
+/// tick grid, still work but fall back to an ordered map.
+#[derive(Clone, Debug)]
+pub struct PriceLadder<P> {
+    min_price: P,
+    tick_size: P,
+    slots: Vec<Option<(P, usize)>>,
+    occupied: Bitmap,
+    lowest: Option<usize>,
+    highest: Option<usize>,
+    overflow: BTreeMap<P, usize>,
+    len: usize,
+}
+
+impl<P: TickPrice> PriceLadder<P> {
+    pub fn new(min_price: P, tick_size: P, levels: usize) -> Self {
+        assert!(tick_size > P::ZERO, "tick size must be positive");
+
+        return PriceLadder {
+            min_price,
+            tick_size,
+            slots: vec![None; levels],
+            occupied: Bitmap::new(levels),
+            lowest: None,
+            highest: None,
+            overflow: BTreeMap::new(),
+            len: 0,
+        };
+    }
+
+    /// Returns whether `price` gets a slot in the array rather than going to
+    /// the overflow map.
+    pub fn contains(&self, price: P) -> bool {
+        return self.slot(price).is_some();
+    }
+
+    fn slot(&self, price: P) -> Option<usize> {
+        let (offset, exact) = price.tick_offset(self.min_price, self.tick_size);
+        if !exact || offset < 0 || offset as u64 >= self.slots.len() as u64 {
+            return None;
+        }
+
+        return Some(offset as usize);
+    }
+
+    fn slot_entry(&self, slot: Option<usize>) -> Option<(P, usize)> {
+        return slot.and_then(|slot| self.slots[slot]);
+    }
+
+    /// Returns the first slot strictly above `price`, which may be past the
+    /// end of the array.
+    fn slot_above(&self, price: P) -> usize {
+        let (offset, _) = price.tick_offset(self.min_price, self.tick_size);
+        return offset.saturating_add(1).clamp(0, self.slots.len() as i64) as usize;
+    }
+
+    /// Returns the last slot strictly below `price`, if there is one.
+    fn slot_below(&self, price: P) -> Option<usize> {
+        let (offset, exact) = price.tick_offset(self.min_price, self.tick_size);
+        let offset = if exact {
+            offset.saturating_sub(1)
+        } else {
+            offset
+        };
+        if offset < 0 || self.slots.is_empty() {
+            return None;
+        }
+
+        return Some((offset as u64).min(self.slots.len() as u64 - 1) as usize);
+    }
+}
+
+fn lower<P: Numeric>(a: Option<(P, usize)>, b: Option<(P, usize)>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => return Some(if a.0 <= b.0 { a.1 } else { b.1 }),
+        (a, b) => return a.or(b).map(|(_, level)| level),
+    }
+}
+
+fn higher<P: Numeric>(a: Option<(P, usize)>, b: Option<(P, usize)>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => return Some(if a.0 >= b.0 { a.1 } else { b.1 }),
+        (a, b) => return a.or(b).map(|(_, level)| level),
+    }
+}
+
+impl<P: TickPrice> PriceIndex<P> for PriceLadder<P> {
+    fn get(&self, price: P) -> Option<usize> {
+        match self.slot(price) {
+            Some(slot) => return self.slots[slot].map(|(_, level)| level),
+            None => return self.overflow.get(&price).copied(),
+        }
+    }
+
+    fn insert(&mut self, price: P, level: usize) {
+        let slot = match self.slot(price) {
+            Some(slot) => slot,
+            None => {
+                if self.overflow.insert(price, level).is_none() {
+                    self.len += 1;
+                }
+                return;
+            }
+        };
+
+        if self.slots[slot].replace((price, level)).is_none() {
+            self.len += 1;
+        }
+        self.occupied.set(slot);
+        self.lowest = Some(self.lowest.map_or(slot, |lowest| lowest.min(slot)));
+        self.highest = Some(self.highest.map_or(slot, |highest| highest.max(slot)));
+    }
+
+    fn remove(&mut self, price: P) -> Option<usize> {
+        let slot = match self.slot(price) {
+            Some(slot) => slot,
+            None => {
+                let level = self.overflow.remove(&price)?;
+                self.len -= 1;
+                return Some(level);
+            }
+        };
+
+        let (_, level) = self.slots[slot].take()?;
+        self.len -= 1;
+        self.occupied.clear(slot);
+
+        if self.lowest == Some(slot) {
+            self.lowest = self.occupied.next_set(slot);
+        }
+        if self.highest == Some(slot) {
+            self.highest = self.occupied.prev_set(slot);
+        }
+
+        return Some(level);
+    }
+
+    fn len(&self) -> usize {
+        return self.len;
+    }
+
+    fn first(&self) -> Option<usize> {
+        let overflow = self.overflow.iter().next().map(|(&p, &l)| (p, l));
+        return lower(self.slot_entry(self.lowest), overflow);
+    }
+
+    fn last(&self) -> Option<usize> {
+        let overflow = self.overflow.iter().next_back().map(|(&p, &l)| (p, l));
+        return higher(self.slot_entry(self.highest), overflow);
+    }
+
+    fn next_above(&self, price: P) -> Option<usize> {
+        let slot = self.occupied.next_set(self.slot_above(price));
+        let overflow = self
+            .overflow
+            .range((Excluded(price), Unbounded))
+            .next()
+            .map(|(&p, &l)| (p, l));
+        return lower(self.slot_entry(slot), overflow);
+    }
+
+    fn next_below(&self, price: P) -> Option<usize> {
+        let slot = self
+            .slot_below(price)
+            .and_then(|slot| self.occupied.prev_set(slot));
+        let overflow = self
+            .overflow
+            .range((Unbounded, Excluded(price)))
+            .next_back()
+            .map(|(&p, &l)| (p, l));
+        return higher(self.slot_entry(slot), overflow);
+    }
+}
+
+/// A fixed-size set of bits with fast scans for the next set bit.
+#[derive(Clone, Debug)]
+struct Bitmap {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitmap {
+    fn new(len: usize) -> Self {
+        return Bitmap {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        };
+    }
+
+    fn set(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    fn clear(&mut self, i: usize) {
+        self.words[i / 64] &= !(1 << (i % 64));
+    }
+
+    /// Returns the first set bit at or after `from`.
+    fn next_set(&self, from: usize) -> Option<usize> {
+        if from >= self.len {
+            return None;
+        }
+
+        let mut word_index = from / 64;
+        let mut word = self.words[word_index] & (u64::MAX << (from % 64));
+
+        loop {
+            if word != 0 {
+                return Some(word_index * 64 + word.trailing_zeros() as usize);
+            }
+
+            word_index += 1;
+            if word_index >= self.words.len() {
+                return None;
+            }
+            word = self.words[word_index];
+        }
+    }
+
+    /// Returns the last set bit at or before `to`.
+    fn prev_set(&self, to: usize) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let to = to.min(self.len - 1);
+        let mut word_index = to / 64;
+        let mut word = self.words[word_index] & (u64::MAX >> (63 - to % 64));
+
+        loop {
+            if word != 0 {
+                return Some(word_index * 64 + 63 - word.leading_zeros() as usize);
+            }
+
+            if word_index == 0 {
+                return None;
+            }
+            word_index -= 1;
+            word = self.words[word_index];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::*;
+
+    #[test]
+    fn test_bitmap() {
+        let mut bitmap = Bitmap::new(200);
+
+        bitmap.set(3);
+        bitmap.set(64);
+        bitmap.set(199);
+
+        assert_eq!(bitmap.next_set(0), Some(3));
+        assert_eq!(bitmap.next_set(4), Some(64));
+        assert_eq!(bitmap.next_set(65), Some(199));
+        assert_eq!(bitmap.prev_set(198), Some(64));
+        assert_eq!(bitmap.prev_set(63), Some(3));
+        assert_eq!(bitmap.prev_set(2), None);
+
+        bitmap.clear(64);
+        assert_eq!(bitmap.next_set(4), Some(199));
+    }
+
+    #[test]
+    fn test_tick_offset() {
+        assert_eq!(dec!(10.05).tick_offset(dec!(10.00), dec!(0.01)), (5, true));
+        assert_eq!(
+            dec!(10.055).tick_offset(dec!(10.00), dec!(0.01)),
+            (5, false)
+        );
+        assert_eq!(dec!(9.99).tick_offset(dec!(10.00), dec!(0.01)), (-1, true));
+        assert_eq!(Ticks(7).tick_offset(Ticks(0), Ticks(5)), (1, false));
+        assert_eq!(Ticks(-3).tick_offset(Ticks(0), Ticks(5)), (-1, false));
+
+        // Too far to compute, rather than overflowing
+        assert_eq!(
+            Decimal::max_value().tick_offset(dec!(-1), dec!(0.01)),
+            (i64::MAX, false)
+        );
+        assert_eq!(
+            Decimal::min_value().tick_offset(dec!(1), dec!(1)),
+            (i64::MIN, false)
+        );
+        assert_eq!(
+            Ticks(i64::MAX).tick_offset(Ticks(-1), Ticks(1)),
+            (i64::MAX, false)
+        );
+        assert_eq!(
+            Ticks(i64::MIN).tick_offset(Ticks(1), Ticks(1)),
+            (i64::MIN, false)
+        );
+    }
+
+    #[test]
+    fn test_best_price_tracking() {
+        let mut ladder = PriceLadder::new(Ticks(100), Ticks(1), 100);
+
+        ladder.insert(Ticks(150), 0);
+        ladder.insert(Ticks(120), 1);
+        ladder.insert(Ticks(180), 2);
+
+        assert_eq!(ladder.first(), Some(1));
+        assert_eq!(ladder.last(), Some(2));
+
+        ladder.remove(Ticks(120));
+        ladder.remove(Ticks(180));
+
+        assert_eq!(ladder.first(), Some(0));
+        assert_eq!(ladder.last(), Some(0));
+
+        ladder.remove(Ticks(150));
+
+        assert_eq!(ladder.first(), None);
+        assert!(ladder.is_empty());
+    }
+
+    #[test]
+    fn test_next_above_and_below() {
+        let mut ladder = PriceLadder::new(dec!(10.00), dec!(0.01), 1000);
+
+        ladder.insert(dec!(10.10), 0);
+        ladder.insert(dec!(10.50), 1);
+
+        assert_eq!(ladder.next_above(dec!(10.10)), Some(1));
+        assert_eq!(ladder.next_above(dec!(10.105)), Some(1));
+        assert_eq!(ladder.next_above(dec!(9.00)), Some(0));
+        assert_eq!(ladder.next_above(dec!(10.50)), None);
+        assert_eq!(ladder.next_below(dec!(10.50)), Some(0));
+        assert_eq!(ladder.next_below(dec!(99.00)), Some(1));
+        assert_eq!(ladder.next_below(dec!(10.10)), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut ladder = PriceLadder::new(Ticks(100), Ticks(1), 10);
+
+        ladder.insert(Ticks(105), 0);
+        ladder.insert(Ticks(50), 1);
+        ladder.insert(Ticks(500), 2);
+
+        assert!(ladder.contains(Ticks(105)));
+        assert!(!ladder.contains(Ticks(500)));
+        assert_eq!(ladder.get(Ticks(500)), Some(2));
+        assert_eq!(ladder.len(), 3);
+
+        // Out of range prices are ordered with the rest
+        assert_eq!(ladder.first(), Some(1));
+        assert_eq!(ladder.last(), Some(2));
+        assert_eq!(ladder.next_above(Ticks(50)), Some(0));
+        assert_eq!(ladder.next_above(Ticks(105)), Some(2));
+        assert_eq!(ladder.next_below(Ticks(500)), Some(0));
+
+        assert_eq!(ladder.remove(Ticks(50)), Some(1));
+        assert_eq!(ladder.first(), Some(0));
+
+        ladder.insert(Ticks(i64::MIN), 3);
+        ladder.insert(Ticks(i64::MAX), 4);
+        assert!(!ladder.contains(Ticks(i64::MIN)));
+        assert_eq!(ladder.first(), Some(3));
+        assert_eq!(ladder.last(), Some(4));
+        assert_eq!(ladder.next_above(Ticks(500)), Some(4));
+    }
+}
@@ -1,139 +1,334 @@
 use rust_decimal::prelude::*;
-use rust_decimal_macros::*;
-use std::collections::VecDeque;
 
+use crate::arena::Arena;
+use crate::invariants::{ensure, InvariantViolation};
+use crate::numeric::Numeric;
 use crate::order::Order;
 
-#[derive(Debug, Eq, PartialEq)]
-pub struct PriceLevel {
-    pub volume: Decimal,
-    pub price: Decimal,
-    orders: VecDeque<Order>,
+/// A resting order and its links to its neighbours in its level's queue.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OrderNode<P = Decimal, Q = Decimal> {
+    pub order: Order<P, Q>,
+    /// Key of the `PriceLevel` the order rests at.
+    pub level: usize,
+    prev: Option<usize>,
+    next: Option<usize>,
 }
 
-impl PriceLevel {
-    pub fn new(price: Decimal) -> Self {
+impl<P, Q> OrderNode<P, Q> {
+    pub fn new(order: Order<P, Q>, level: usize) -> Self {
+        return OrderNode {
+            order,
+            level,
+            prev: None,
+            next: None,
+        };
+    }
+}
+
+/// A FIFO queue of resting orders at a single price.
+///
+/// The queue is a doubly-linked list threaded through `OrderNode`s in the
+/// owning `BookSide`'s arena, so an order can be unlinked in constant time
+/// given its key.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PriceLevel<P = Decimal, Q = Decimal> {
+    pub volume: Q,
+    pub price: P,
+    head: Option<usize>,
+    tail: Option<usize>,
+    len: usize,
+}
+
+impl<P: Numeric, Q: Numeric> PriceLevel<P, Q> {
+    pub fn new(price: P) -> Self {
         return PriceLevel {
-            volume: dec!(0),
-            price: price,
-            orders: VecDeque::new(),
+            volume: Q::ZERO,
+            price,
+            head: None,
+            tail: None,
+            len: 0,
         };
     }
 
-    pub fn append(&mut self, order: Order) {
-        self.volume += order.quantity;
-        self.orders.push_back(order);
+    /// Links the node at `key` onto the back of the queue.
+    pub fn append(&mut self, nodes: &mut Arena<OrderNode<P, Q>>, key: usize) {
+        let node = &mut nodes[key];
+        node.prev = self.tail;
+        node.next = None;
+        self.volume += node.order.quantity;
+
+        match self.tail {
+            Some(tail) => nodes[tail].next = Some(key),
+            None => self.head = Some(key),
+        }
+
+        self.tail = Some(key);
+        self.len += 1;
     }
 
-    pub fn remove(&mut self, order: Order) -> Option<Order> {
-        self.volume -= order.quantity;
-        if let Some(pos) = self.orders.iter().position(|&o| o == order) {
-            return self.orders.remove(pos);
+    /// Unlinks the node at `key`, which must belong to this level. The node
+    /// itself is left in the arena for the caller to free.
+    pub fn remove(&mut self, nodes: &mut Arena<OrderNode<P, Q>>, key: usize) -> bool {
+        let (prev, next, quantity) = match nodes.get(key) {
+            Some(node) => (node.prev, node.next, node.order.quantity),
+            None => return false,
+        };
+
+        match prev {
+            Some(prev) => nodes[prev].next = next,
+            None => self.head = next,
+        }
+
+        match next {
+            Some(next) => nodes[next].prev = prev,
+            None => self.tail = prev,
         }
 
-        return None;
+        let node = &mut nodes[key];
+        node.prev = None;
+        node.next = None;
+
+        self.volume -= quantity;
+        self.len -= 1;
+        return true;
     }
 
     pub fn len(&self) -> usize {
-        return self.orders.len();
+        return self.len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    pub fn front(&self) -> Option<usize> {
+        return self.head;
     }
 
-    pub fn front(&self) -> Option<&Order> {
-        return self.orders.front();
+    /// Iterates over the keys of resting orders in time priority.
+    pub fn iter<'a>(&self, nodes: &'a Arena<OrderNode<P, Q>>) -> impl Iterator<Item = usize> + 'a {
+        return std::iter::successors(self.head, move |&key| nodes[key].next);
     }
 
-    pub fn replace_front(&mut self, order: Order) {
-        let mut quantity = dec!(0);
+    /// Walks the queue and checks that its links, length and volume agree
+    /// with the nodes in it. `key` is this level's own key.
+    pub fn check_invariants(
+        &self,
+        key: usize,
+        nodes: &Arena<OrderNode<P, Q>>,
+    ) -> Result<(), InvariantViolation> {
+        let mut prev = None;
+        let mut next = self.head;
+        let mut len = 0;
+        let mut volume = Q::ZERO;
 
-        if let Some(o) = self.front_mut() {
-            quantity = o.quantity;
+        while let Some(node_key) = next {
+            ensure!(
+                len < self.len,
+                "level {} has more than {} orders",
+                self.price,
+                self.len
+            );
 
-            o.id = order.id;
-            o.price = order.price;
-            o.quantity = order.quantity;
-            o.side = order.side;
-            o.timestamp = order.timestamp;
+            let node = match nodes.get(node_key) {
+                Some(node) => node,
+                None => {
+                    return Err(InvariantViolation(format!(
+                        "level {} links to freed order slot {}",
+                        self.price, node_key
+                    )))
+                }
+            };
+
+            ensure!(
+                node.level == key,
+                "order {} is linked into the wrong level",
+                node.order.id
+            );
+            ensure!(
+                node.prev == prev,
+                "order {} has a broken back link",
+                node.order.id
+            );
+            ensure!(
+                node.order.price == self.price,
+                "order {} at {} rests in level {}",
+                node.order.id,
+                node.order.price,
+                self.price
+            );
+            ensure!(
+                node.order.quantity > Q::ZERO,
+                "order {} has non-positive quantity {}",
+                node.order.id,
+                node.order.quantity
+            );
+
+            len += 1;
+            volume += node.order.quantity;
+            prev = Some(node_key);
+            next = node.next;
         }
 
-        self.volume -= quantity;
-        self.volume += order.quantity;
-    }
+        ensure!(self.tail == prev, "level {} has the wrong tail", self.price);
+        ensure!(
+            len == self.len,
+            "level {} has {} orders but counts {}",
+            self.price,
+            len,
+            self.len
+        );
+        ensure!(
+            volume == self.volume,
+            "level {} has volume {} but counts {}",
+            self.price,
+            volume,
+            self.volume
+        );
 
-    fn front_mut(&mut self) -> Option<&mut Order> {
-        return self.orders.front_mut();
+        return Ok(());
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::numeric::{Lots, Ticks};
     use crate::order::Side;
-    use std::time;
+    use rust_decimal_macros::*;
+    use uuid::Uuid;
+
+    fn node(nodes: &mut Arena<OrderNode>, quantity: Decimal) -> usize {
+        let order = Order::new(Uuid::new_v4(), Side::Ask, quantity, dec!(10.00), 0);
+        return nodes.insert(OrderNode::new(order, 0));
+    }
 
     #[test]
     fn test_append() {
+        let mut nodes = Arena::new();
         let mut price_level = PriceLevel::new(dec!(10.00));
-        let order = Order::new(Side::Ask, dec!(1.0), dec!(10.00), time::Instant::now());
+        let order = node(&mut nodes, dec!(1.0));
 
-        price_level.append(order);
+        price_level.append(&mut nodes, order);
 
-        assert_eq!(price_level.volume, order.quantity);
-        assert_eq!(*price_level.front().unwrap(), order);
+        assert_eq!(price_level.volume, dec!(1.0));
+        assert_eq!(price_level.front(), Some(order));
     }
 
     #[test]
     fn test_remove() {
+        let mut nodes = Arena::new();
         let mut price_level = PriceLevel::new(dec!(10.00));
-        let order = Order::new(Side::Ask, dec!(1.0), dec!(10.00), time::Instant::now());
-        let order2 = Order::new(Side::Ask, dec!(2.0), dec!(10.00), time::Instant::now());
+        let order = node(&mut nodes, dec!(1.0));
+        let order2 = node(&mut nodes, dec!(2.0));
 
-        price_level.append(order);
-        price_level.append(order2);
+        price_level.append(&mut nodes, order);
+        price_level.append(&mut nodes, order2);
 
-        price_level.remove(order);
+        assert!(price_level.remove(&mut nodes, order));
 
-        assert_eq!(price_level.volume, order2.quantity);
-        assert_eq!(*price_level.front().unwrap(), order2);
+        assert_eq!(price_level.volume, dec!(2.0));
+        assert_eq!(price_level.front(), Some(order2));
     }
 
     #[test]
-    fn test_len() {
+    fn test_remove_from_middle() {
+        let mut nodes = Arena::new();
         let mut price_level = PriceLevel::new(dec!(10.00));
-        let order = Order::new(Side::Ask, dec!(1.0), dec!(10.00), time::Instant::now());
-        let order2 = Order::new(Side::Ask, dec!(2.0), dec!(10.00), time::Instant::now());
+        let order = node(&mut nodes, dec!(1.0));
+        let order2 = node(&mut nodes, dec!(2.0));
+        let order3 = node(&mut nodes, dec!(3.0));
+
+        price_level.append(&mut nodes, order);
+        price_level.append(&mut nodes, order2);
+        price_level.append(&mut nodes, order3);
 
-        price_level.append(order);
-        price_level.append(order2);
+        assert!(price_level.remove(&mut nodes, order2));
 
+        assert_eq!(
+            price_level.iter(&nodes).collect::<Vec<_>>(),
+            vec![order, order3]
+        );
+        assert_eq!(price_level.volume, dec!(4.0));
         assert_eq!(price_level.len(), 2);
     }
 
     #[test]
-    fn test_front() {
+    fn test_remove_last() {
+        let mut nodes = Arena::new();
+        let mut price_level = PriceLevel::new(dec!(10.00));
+        let order = node(&mut nodes, dec!(1.0));
+        let order2 = node(&mut nodes, dec!(2.0));
+
+        price_level.append(&mut nodes, order);
+        price_level.append(&mut nodes, order2);
+        price_level.remove(&mut nodes, order2);
+
+        // The tail moves back so later appends still link correctly
+        let order3 = node(&mut nodes, dec!(3.0));
+        price_level.append(&mut nodes, order3);
+
+        assert_eq!(
+            price_level.iter(&nodes).collect::<Vec<_>>(),
+            vec![order, order3]
+        );
+    }
+
+    #[test]
+    fn test_remove_missing_order() {
+        let mut nodes = Arena::new();
+        let mut price_level = PriceLevel::new(dec!(10.00));
+        let order = node(&mut nodes, dec!(1.0));
+
+        price_level.append(&mut nodes, order);
+
+        assert!(!price_level.remove(&mut nodes, order + 1));
+
+        // Volume is untouched when nothing was removed
+        assert_eq!(price_level.volume, dec!(1.0));
+    }
+
+    #[test]
+    fn test_len() {
+        let mut nodes = Arena::new();
         let mut price_level = PriceLevel::new(dec!(10.00));
-        let order = Order::new(Side::Ask, dec!(1.0), dec!(10.00), time::Instant::now());
-        let order2 = Order::new(Side::Ask, dec!(2.0), dec!(10.00), time::Instant::now());
+        let order = node(&mut nodes, dec!(1.0));
+        let order2 = node(&mut nodes, dec!(2.0));
 
-        price_level.append(order);
-        price_level.append(order2);
+        price_level.append(&mut nodes, order);
+        price_level.append(&mut nodes, order2);
 
-        assert_eq!(*price_level.front().unwrap(), order);
+        assert_eq!(price_level.len(), 2);
     }
 
     #[test]
-    fn test_replace_front() {
+    fn test_front() {
+        let mut nodes = Arena::new();
         let mut price_level = PriceLevel::new(dec!(10.00));
-        let order = Order::new(Side::Ask, dec!(1.0), dec!(10.00), time::Instant::now());
-        let order2 = Order::new(Side::Ask, dec!(2.0), dec!(10.00), time::Instant::now());
+        let order = node(&mut nodes, dec!(1.0));
+        let order2 = node(&mut nodes, dec!(2.0));
+
+        price_level.append(&mut nodes, order);
+        price_level.append(&mut nodes, order2);
 
-        price_level.append(order);
-        price_level.append(order2);
+        assert_eq!(price_level.front(), Some(order));
+    }
+
+    #[test]
+    fn test_integer_quantities() {
+        let mut nodes = Arena::new();
+        let mut price_level = PriceLevel::new(Ticks(1000));
+        let order = Order::new(Uuid::new_v4(), Side::Bid, Lots(3), Ticks(1000), 0);
+        let order2 = Order::new(Uuid::new_v4(), Side::Bid, Lots(4), Ticks(1000), 0);
+        let key = nodes.insert(OrderNode::new(order, 0));
+        let key2 = nodes.insert(OrderNode::new(order2, 0));
 
-        let mut new_order = order.clone();
-        new_order.quantity = dec!(0.1);
+        price_level.append(&mut nodes, key);
+        price_level.append(&mut nodes, key2);
+        price_level.remove(&mut nodes, key);
 
-        price_level.replace_front(new_order);
-        assert_eq!(*price_level.front().unwrap(), new_order);
-        assert_eq!(price_level.volume, new_order.quantity + order2.quantity);
+        assert_eq!(price_level.volume, Lots(4));
+        assert_eq!(price_level.front(), Some(key2));
     }
 }
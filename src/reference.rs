@@ -0,0 +1,366 @@
+use rust_decimal::prelude::*;
+use uuid::Uuid;
+
+use crate::id_generator::sequential_id;
+use crate::numeric::Numeric;
+use crate::order::{Order, Side};
+use crate::{Fill, FillStatus, OrderResult};
+
+/// A deliberately naive order book to check `OrderBook` against.
+///
+/// Resting orders are kept in a single list in arrival order and every match
+/// scans all of them, so it is easy to convince yourself it's right and far
+/// too slow for anything but tests. Ids are assigned like a
+/// `SequentialIdGenerator` starting at 1, and timestamps are always 0, so
+/// results can be compared exactly with an `OrderBook` built with
+/// `ManualClock::new(0)` and `SequentialIdGenerator::new()`.
+#[derive(Clone, Debug)]
+pub struct ReferenceBook<P = Decimal, Q = Decimal> {
+    orders: Vec<Order<P, Q>>,
+    next_id: u64,
+}
+
+impl<P: Numeric, Q: Numeric> ReferenceBook<P, Q> {
+    pub fn new() -> Self {
+        return ReferenceBook {
+            orders: Vec::new(),
+            next_id: 1,
+        };
+    }
+
+    pub fn submit_limit_order(&mut self, side: Side, quantity: Q, price: P) -> OrderResult<P, Q> {
+        let mut result = self.match_order(side, quantity, Some(price));
+
+        let left = quantity - result.quantity_filled;
+        if left > Q::ZERO {
+            let order = Order::new(sequential_id(self.next_id), side, left, price, 0);
+            self.next_id += 1;
+            self.orders.push(order);
+            result.partial = Some(order);
+        }
+
+        return result;
+    }
+
+    pub fn submit_market_order(&mut self, side: Side, quantity: Q) -> OrderResult<P, Q> {
+        return self.match_order(side, quantity, None);
+    }
+
+    pub fn remove(&mut self, id: Uuid) -> Option<Order<P, Q>> {
+        let i = self.orders.iter().position(|o| o.id == id)?;
+        return Some(self.orders.remove(i));
+    }
+
+    pub fn amend(&mut self, id: Uuid, quantity: Q, price: P) -> Option<OrderResult<P, Q>> {
+        let i = self.orders.iter().position(|o| o.id == id)?;
+        let order = self.orders[i];
+        let mut result = OrderResult {
+            done: Vec::new(),
+            partial: None,
+            quantity_filled: Q::ZERO,
+        };
+
+        if quantity == Q::ZERO {
+            self.orders.remove(i);
+            return Some(result);
+        }
+        if price == order.price && quantity <= order.quantity {
+            self.orders[i].quantity = quantity;
+            result.partial = Some(self.orders[i]);
+            return Some(result);
+        }
+
+        self.orders.remove(i);
+
+        let mut result = self.match_order(order.side, quantity, Some(price));
+        let left = quantity - result.quantity_filled;
+        if left > Q::ZERO {
+            let order = Order::new(id, order.side, left, price, 0);
+            self.orders.push(order);
+            result.partial = Some(order);
+        }
+
+        return Some(result);
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&Order<P, Q>> {
+        return self.orders.iter().find(|o| o.id == id);
+    }
+
+    /// Returns `(price, volume, number of orders)` for each level on `side`,
+    /// best price first.
+    pub fn levels(&self, side: Side) -> Vec<(P, Q, usize)> {
+        let mut prices: Vec<P> = self
+            .orders
+            .iter()
+            .filter(|o| o.side == side)
+            .map(|o| o.price)
+            .collect();
+        prices.sort();
+        prices.dedup();
+        if side == Side::Bid {
+            prices.reverse();
+        }
+
+        return prices
+            .into_iter()
+            .map(|price| {
+                let orders = self.orders_at(side, price);
+                let mut volume = Q::ZERO;
+                for order in orders.iter() {
+                    volume += order.quantity;
+                }
+                (price, volume, orders.len())
+            })
+            .collect();
+    }
+
+    /// Returns the orders resting at `price` on `side` in time priority.
+    pub fn orders_at(&self, side: Side, price: P) -> Vec<Order<P, Q>> {
+        return self
+            .orders
+            .iter()
+            .filter(|o| o.side == side && o.price == price)
+            .copied()
+            .collect();
+    }
+
+    fn match_order(&mut self, side: Side, quantity: Q, limit: Option<P>) -> OrderResult<P, Q> {
+        let mut result = OrderResult {
+            done: Vec::new(),
+            partial: None,
+            quantity_filled: Q::ZERO,
+        };
+        let mut left = quantity;
+
+        while left > Q::ZERO {
+            // Best price on the other side, earliest arrival breaking ties.
+            let mut best: Option<usize> = None;
+            for (i, order) in self.orders.iter().enumerate() {
+                if order.side == side {
+                    continue;
+                }
+
+                let crosses = match (side, limit) {
+                    (_, None) => true,
+                    (Side::Bid, Some(limit)) => order.price <= limit,
+                    (Side::Ask, Some(limit)) => order.price >= limit,
+                };
+                if !crosses {
+                    continue;
+                }
+
+                let better = match best {
+                    None => true,
+                    Some(b) => match side {
+                        Side::Bid => order.price < self.orders[b].price,
+                        Side::Ask => order.price > self.orders[b].price,
+                    },
+                };
+                if better {
+                    best = Some(i);
+                }
+            }
+
+            let i = match best {
+                Some(i) => i,
+                None => break,
+            };
+
+            let resting = &mut self.orders[i];
+            let traded = left.min(resting.quantity);
+            resting.quantity -= traded;
+            left -= traded;
+
+            let status = if resting.quantity > Q::ZERO {
+                FillStatus::Partial
+            } else {
+                FillStatus::Full
+            };
+            result.done.push(Fill {
+                order_id: resting.id,
+                status,
+                price: resting.price,
+                quantity: traded,
+            });
+            result.quantity_filled += traded;
+
+            if status == FillStatus::Full {
+                self.orders.remove(i);
+            }
+        }
+
+        return result;
+    }
+}
+
+impl<P: Numeric, Q: Numeric> Default for ReferenceBook<P, Q> {
+    fn default() -> Self {
+        return ReferenceBook::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use crate::numeric::{Lots, Ticks};
+    use crate::OrderBook;
+    use proptest::prelude::*;
+    use rust_decimal_macros::*;
+
+    /// An operation on a book. Cancels and amends pick their target from the
+    /// ids seen so far by index, so shrinking keeps them meaningful.
+    #[derive(Clone, Debug)]
+    enum Op {
+        Limit(Side, u64, i64),
+        Market(Side, u64),
+        Cancel(usize),
+        Amend(usize, u64, i64),
+    }
+
+    fn side() -> impl Strategy<Value = Side> {
+        return prop_oneof![Just(Side::Bid), Just(Side::Ask)];
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        // A narrow range of prices and small sizes, so orders cross and
+        // levels are exhausted exactly a lot of the time.
+        return prop_oneof![
+            6 => (side(), 1..10u64, 95..105i64).prop_map(|(s, q, p)| Op::Limit(s, q, p)),
+            1 => (side(), 1..20u64).prop_map(|(s, q)| Op::Market(s, q)),
+            2 => any::<usize>().prop_map(Op::Cancel),
+            1 => (any::<usize>(), 0..10u64, 95..105i64).prop_map(|(i, q, p)| Op::Amend(i, q, p)),
+        ];
+    }
+
+    fn pick(ids: &[Uuid], i: usize) -> Uuid {
+        if ids.is_empty() {
+            return Uuid::nil();
+        }
+        return ids[i % ids.len()];
+    }
+
+    /// Runs `ops` against both books, comparing every result and the final
+    /// state of the book. `price` and `quantity` map the generated integers
+    /// to the books' types.
+    fn check<P: Numeric, Q: Numeric>(
+        ops: &[Op],
+        price: impl Fn(i64) -> P,
+        quantity: impl Fn(u64) -> Q,
+    ) -> Result<(), TestCaseError> {
+        let mut order_book: OrderBook<P, Q> =
+            OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        let mut reference: ReferenceBook<P, Q> = ReferenceBook::new();
+        let mut ids: Vec<Uuid> = Vec::new();
+
+        order_book.set_invariant_checks(true);
+
+        for op in ops.iter() {
+            match *op {
+                Op::Limit(side, q, p) => {
+                    let actual = order_book.submit_limit_order(side, quantity(q), price(p));
+                    let expected = reference.submit_limit_order(side, quantity(q), price(p));
+                    compare(&actual, &expected)?;
+                    ids.extend(actual.partial.map(|o| o.id));
+                }
+                Op::Market(side, q) => {
+                    let actual = order_book.submit_market_order(side, quantity(q));
+                    let expected = reference.submit_market_order(side, quantity(q));
+                    compare(&actual, &expected)?;
+                }
+                Op::Cancel(i) => {
+                    let id = pick(&ids, i);
+                    prop_assert_eq!(order_book.remove(id), reference.remove(id));
+                }
+                Op::Amend(i, q, p) => {
+                    let id = pick(&ids, i);
+                    let actual = order_book.amend(id, quantity(q), price(p));
+                    let expected = reference.amend(id, quantity(q), price(p));
+                    prop_assert_eq!(actual.is_some(), expected.is_some());
+                    if let (Some(actual), Some(expected)) = (actual, expected) {
+                        compare(&actual, &expected)?;
+                    }
+                }
+            }
+        }
+
+        for side in [Side::Bid, Side::Ask] {
+            let levels: Vec<(P, Q, usize)> = order_book
+                .levels(side)
+                .map(|l| (l.price, l.volume, l.len()))
+                .collect();
+            prop_assert_eq!(&levels, &reference.levels(side));
+
+            for &(price, _, _) in levels.iter() {
+                let orders: Vec<Order<P, Q>> = order_book.orders_at(side, price).copied().collect();
+                prop_assert_eq!(orders, reference.orders_at(side, price));
+            }
+        }
+
+        for id in ids.iter() {
+            prop_assert_eq!(order_book.get(*id), reference.get(*id));
+        }
+
+        return Ok(());
+    }
+
+    fn compare<P: Numeric, Q: Numeric>(
+        actual: &OrderResult<P, Q>,
+        expected: &OrderResult<P, Q>,
+    ) -> Result<(), TestCaseError> {
+        prop_assert_eq!(&actual.done, &expected.done);
+        prop_assert_eq!(actual.partial, expected.partial);
+        prop_assert_eq!(actual.quantity_filled, expected.quantity_filled);
+        return Ok(());
+    }
+
+    fn decimal_price(p: i64) -> Decimal {
+        return Decimal::new(p * 10, 1);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(500))]
+
+        #[test]
+        fn test_matches_reference(ops in prop::collection::vec(op(), 0..200)) {
+            check(&ops, decimal_price, Decimal::from)?;
+        }
+
+        #[test]
+        fn test_matches_reference_with_integer_types(ops in prop::collection::vec(op(), 0..200)) {
+            check(&ops, Ticks, Lots)?;
+        }
+    }
+
+    #[test]
+    fn test_exact_level_exhaustion() {
+        let ops = vec![
+            Op::Limit(Side::Ask, 3, 100),
+            Op::Limit(Side::Ask, 2, 100),
+            Op::Limit(Side::Ask, 4, 101),
+            // Takes out the whole first level and nothing more
+            Op::Limit(Side::Bid, 5, 101),
+            // Takes out the second level exactly with a market order
+            Op::Market(Side::Bid, 4),
+            Op::Limit(Side::Bid, 2, 99),
+            Op::Limit(Side::Bid, 2, 98),
+            // Exhausts both bid levels exactly and rests nothing
+            Op::Limit(Side::Ask, 4, 98),
+        ];
+
+        check(&ops, decimal_price, Decimal::from).unwrap();
+    }
+
+    #[test]
+    fn test_reference_book() {
+        let mut reference = ReferenceBook::new();
+
+        let ask = reference.submit_limit_order(Side::Ask, dec!(5), dec!(10));
+        let result = reference.submit_market_order(Side::Bid, dec!(2));
+
+        assert_eq!(result.done[0].order_id, ask.partial.unwrap().id);
+        assert_eq!(reference.levels(Side::Ask), vec![(dec!(10), dec!(3), 1)]);
+    }
+}
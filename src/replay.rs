@@ -0,0 +1,847 @@
+use rust_decimal::prelude::*;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
+use uuid::Uuid;
+
+use crate::clock::ManualClock;
+use crate::id_generator::SequentialIdGenerator;
+use crate::order::{Order, Side};
+use crate::{Fill, OrderBook};
+
+/// The layout of message and snapshot files.
+///
+/// `Lobster` is the LOBSTER format: messages are
+/// `time,type,order id,size,price,direction` with the time in seconds after
+/// midnight and prices in units of 1/10000, and each snapshot row is
+/// `ask price,ask size,bid price,bid size` per level, with empty levels
+/// priced at ±9999999999.
+///
+/// `Csv` is the same information spelled out: messages are
+/// `timestamp,type,order_id,side,price,quantity` with the timestamp in
+/// nanoseconds, `type` one of `add`, `cancel`, `delete`, `execute`, `hidden`,
+/// `cross` or `halt`, and `side` either `buy` or `sell`. Snapshot rows have
+/// the same columns as LOBSTER's with plain decimal prices, and empty levels
+/// left blank. Either file may start with a header line.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Format {
+    Lobster,
+    Csv,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MessageKind {
+    /// A new limit order.
+    Add,
+    /// Part of a resting order is canceled.
+    Cancel,
+    /// What's left of a resting order is canceled.
+    Delete,
+    /// A resting order is executed against.
+    Execute,
+    /// A hidden order is executed against. Doesn't change the visible book.
+    Hidden,
+    /// An auction cross. Doesn't change the visible book.
+    Cross,
+    /// Trading halts or resumes.
+    Halt,
+}
+
+/// One line of a message file.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Message {
+    /// Nanoseconds since midnight.
+    pub timestamp: u64,
+    pub kind: MessageKind,
+    /// The exchange's id for the order.
+    pub order_id: u64,
+    /// The side of the order the message is about. For executions, that's
+    /// the resting order's side.
+    pub side: Side,
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+/// The visible depth of the book, best price first on each side.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Snapshot {
+    /// How many levels per side the snapshot covers, including empty ones.
+    pub depth: usize,
+    pub bids: Vec<(Decimal, Decimal)>,
+    pub asks: Vec<(Decimal, Decimal)>,
+}
+
+/// A file that couldn't be read or parsed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReplayError(pub String);
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.0);
+    }
+}
+
+impl Error for ReplayError {}
+
+/// A way the book disagrees with the messages or snapshots.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Discrepancy {
+    /// The message refers to an order that isn't on the book.
+    UnknownOrder { order_id: u64 },
+    /// A new order reuses the id of an order still on the book.
+    DuplicateOrder { order_id: u64 },
+    /// A new order traded on arrival instead of resting.
+    UnexpectedTrade { order_id: u64, quantity: Decimal },
+    /// The message's price isn't the resting order's.
+    PriceMismatch {
+        order_id: u64,
+        book: Decimal,
+        message: Decimal,
+    },
+    /// The message cancels or executes more than is resting, or a delete
+    /// doesn't match what's resting.
+    QuantityMismatch {
+        order_id: u64,
+        book: Decimal,
+        message: Decimal,
+    },
+    /// Executing against the order's price level filled other orders than
+    /// the one in the message, which means priority differs. Orders missing
+    /// from the book have an id of 0.
+    WrongExecution {
+        order_id: u64,
+        filled: Vec<(u64, Decimal)>,
+    },
+    /// A level of the book differs from the snapshot. `None` means the
+    /// level is empty.
+    Depth {
+        side: Side,
+        level: usize,
+        book: Option<(Decimal, Decimal)>,
+        snapshot: Option<(Decimal, Decimal)>,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let level = |level: &Option<(Decimal, Decimal)>| match level {
+            Some((price, quantity)) => format!("{} @ {}", quantity, price),
+            None => String::from("nothing"),
+        };
+
+        match self {
+            Discrepancy::UnknownOrder { order_id } => {
+                return write!(f, "order {} is not on the book", order_id)
+            }
+            Discrepancy::DuplicateOrder { order_id } => {
+                return write!(f, "order {} is already on the book", order_id)
+            }
+            Discrepancy::UnexpectedTrade { order_id, quantity } => {
+                return write!(f, "order {} traded {} on arrival", order_id, quantity)
+            }
+            Discrepancy::PriceMismatch {
+                order_id,
+                book,
+                message,
+            } => {
+                return write!(
+                    f,
+                    "order {} rests at {} but the message has {}",
+                    order_id, book, message
+                )
+            }
+            Discrepancy::QuantityMismatch {
+                order_id,
+                book,
+                message,
+            } => {
+                return write!(
+                    f,
+                    "order {} has {} resting but the message has {}",
+                    order_id, book, message
+                )
+            }
+            Discrepancy::WrongExecution { order_id, filled } => {
+                let filled: Vec<String> = filled
+                    .iter()
+                    .map(|(id, quantity)| format!("{} of order {}", quantity, id))
+                    .collect();
+                return write!(
+                    f,
+                    "executing order {} filled {}",
+                    order_id,
+                    filled.join(", ")
+                );
+            }
+            Discrepancy::Depth {
+                side,
+                level: n,
+                book,
+                snapshot,
+            } => {
+                return write!(
+                    f,
+                    "{:?} level {} has {} but the snapshot has {}",
+                    side,
+                    n,
+                    level(book),
+                    level(snapshot)
+                )
+            }
+        }
+    }
+}
+
+/// What a replay found, by message number: 1 for the first message in the
+/// file, not counting a header or blank lines.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Report {
+    pub messages: usize,
+    pub snapshots: usize,
+    pub discrepancies: Vec<(usize, Discrepancy)>,
+}
+
+/// Rebuilds a book from exchange messages, keeping track of the exchange's
+/// order ids and checking each message against the book.
+///
+/// New orders are submitted as limit orders, cancels amend the resting order
+/// down in place, and executions are replayed as a limit order on the other
+/// side at the resting order's price, so they go through matching and show
+/// whether the book agrees on priority. Orders that were on the book before
+/// the first message are unknown, so files should start from an empty book.
+#[derive(Debug)]
+pub struct Replay {
+    order_book: OrderBook,
+    clock: ManualClock,
+    ids: HashMap<u64, Uuid>,
+    order_ids: HashMap<Uuid, u64>,
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        let clock = ManualClock::new(0);
+
+        return Replay {
+            order_book: OrderBook::with_clock(clock.clone(), SequentialIdGenerator::new()),
+            clock,
+            ids: HashMap::new(),
+            order_ids: HashMap::new(),
+        };
+    }
+
+    pub fn order_book(&self) -> &OrderBook {
+        return &self.order_book;
+    }
+
+    /// Returns the book's id for an exchange order id, if it's resting.
+    pub fn id(&self, order_id: u64) -> Option<Uuid> {
+        return self.ids.get(&order_id).copied();
+    }
+
+    /// Reads messages and, if given, a snapshot after each one, applying
+    /// them to the book and collecting every discrepancy.
+    pub fn run(
+        &mut self,
+        format: Format,
+        messages: impl BufRead,
+        snapshots: Option<impl BufRead>,
+    ) -> Result<Report, ReplayError> {
+        let mut report = Report::default();
+        let mut snapshots = snapshots.map(data_lines);
+
+        for line in data_lines(messages) {
+            let (n, line) = line?;
+            let message = parse_message(format, &line)
+                .map_err(|e| ReplayError(format!("message {}: {}", n, e)))?;
+
+            report.messages += 1;
+            let number = report.messages;
+            for discrepancy in self.apply(&message) {
+                report.discrepancies.push((number, discrepancy));
+            }
+
+            if let Some(snapshots) = snapshots.as_mut() {
+                let (n, line) = match snapshots.next() {
+                    Some(line) => line?,
+                    None => return Err(ReplayError(format!("no snapshot for message {}", number))),
+                };
+                let snapshot = parse_snapshot(format, &line)
+                    .map_err(|e| ReplayError(format!("snapshot {}: {}", n, e)))?;
+
+                report.snapshots += 1;
+                for discrepancy in self.verify(&snapshot) {
+                    report.discrepancies.push((number, discrepancy));
+                }
+            }
+        }
+
+        return Ok(report);
+    }
+
+    /// Applies a message to the book, returning anything about it that
+    /// doesn't agree with the book.
+    pub fn apply(&mut self, message: &Message) -> Vec<Discrepancy> {
+        self.clock.set(message.timestamp);
+
+        match message.kind {
+            MessageKind::Add => return self.add(message),
+            MessageKind::Cancel | MessageKind::Delete => return self.cancel(message),
+            MessageKind::Execute => return self.execute(message),
+            MessageKind::Hidden | MessageKind::Cross | MessageKind::Halt => return Vec::new(),
+        }
+    }
+
+    /// Compares the top of the book with `snapshot`, level by level, as deep
+    /// as the snapshot goes.
+    pub fn verify(&self, snapshot: &Snapshot) -> Vec<Discrepancy> {
+        let mut discrepancies = Vec::new();
+
+        for (side, expected) in [(Side::Bid, &snapshot.bids), (Side::Ask, &snapshot.asks)] {
+            let mut actual = self.order_book.levels(side).map(|l| (l.price, l.volume));
+
+            for level in 0..snapshot.depth {
+                let book = actual.next();
+                let snapshot = expected.get(level).copied();
+                if book != snapshot {
+                    discrepancies.push(Discrepancy::Depth {
+                        side,
+                        level: level + 1,
+                        book,
+                        snapshot,
+                    });
+                }
+            }
+        }
+
+        return discrepancies;
+    }
+
+    fn add(&mut self, message: &Message) -> Vec<Discrepancy> {
+        let mut discrepancies = Vec::new();
+
+        if self.ids.contains_key(&message.order_id) {
+            discrepancies.push(Discrepancy::DuplicateOrder {
+                order_id: message.order_id,
+            });
+            return discrepancies;
+        }
+
+        let result =
+            self.order_book
+                .submit_limit_order(message.side, message.quantity, message.price);
+        self.forget_filled(&result.done);
+
+        if !result.done.is_empty() {
+            discrepancies.push(Discrepancy::UnexpectedTrade {
+                order_id: message.order_id,
+                quantity: result.quantity_filled,
+            });
+        }
+
+        if let Some(order) = result.partial {
+            self.ids.insert(message.order_id, order.id);
+            self.order_ids.insert(order.id, message.order_id);
+        }
+
+        return discrepancies;
+    }
+
+    fn cancel(&mut self, message: &Message) -> Vec<Discrepancy> {
+        let (id, order) = match self.resting(message) {
+            Ok(resting) => resting,
+            Err(discrepancies) => return discrepancies,
+        };
+        let mut discrepancies = self.check_price(message, order.price);
+
+        let mismatch = match message.kind {
+            MessageKind::Delete => message.quantity != order.quantity,
+            _ => message.quantity > order.quantity,
+        };
+        if mismatch {
+            discrepancies.push(Discrepancy::QuantityMismatch {
+                order_id: message.order_id,
+                book: order.quantity,
+                message: message.quantity,
+            });
+        }
+
+        if message.kind == MessageKind::Delete || message.quantity >= order.quantity {
+            self.order_book.remove(id);
+            self.forget(message.order_id);
+        } else {
+            self.order_book
+                .amend(id, order.quantity - message.quantity, order.price);
+        }
+
+        return discrepancies;
+    }
+
+    fn execute(&mut self, message: &Message) -> Vec<Discrepancy> {
+        let (_, order) = match self.resting(message) {
+            Ok(resting) => resting,
+            Err(discrepancies) => return discrepancies,
+        };
+        let mut discrepancies = self.check_price(message, order.price);
+
+        if message.quantity > order.quantity {
+            discrepancies.push(Discrepancy::QuantityMismatch {
+                order_id: message.order_id,
+                book: order.quantity,
+                message: message.quantity,
+            });
+        }
+
+        let aggressor = match order.side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+        let result = self
+            .order_book
+            .submit_limit_order(aggressor, message.quantity, order.price);
+
+        let filled: Vec<(u64, Decimal)> = result
+            .done
+            .iter()
+            .map(|fill| {
+                let order_id = self.order_ids.get(&fill.order_id).copied().unwrap_or(0);
+                (order_id, fill.quantity)
+            })
+            .collect();
+        self.forget_filled(&result.done);
+
+        // Whatever didn't match shouldn't stay on the book.
+        if let Some(partial) = result.partial {
+            self.order_book.remove(partial.id);
+        }
+
+        let expected = [(message.order_id, message.quantity.min(order.quantity))];
+        if filled != expected {
+            discrepancies.push(Discrepancy::WrongExecution {
+                order_id: message.order_id,
+                filled,
+            });
+        }
+
+        return discrepancies;
+    }
+
+    fn resting(&self, message: &Message) -> Result<(Uuid, Order), Vec<Discrepancy>> {
+        let unknown = || {
+            vec![Discrepancy::UnknownOrder {
+                order_id: message.order_id,
+            }]
+        };
+
+        let id = self.id(message.order_id).ok_or_else(unknown)?;
+        let order = *self.order_book.get(id).ok_or_else(unknown)?;
+        return Ok((id, order));
+    }
+
+    fn check_price(&self, message: &Message, price: Decimal) -> Vec<Discrepancy> {
+        if message.price == price {
+            return Vec::new();
+        }
+
+        return vec![Discrepancy::PriceMismatch {
+            order_id: message.order_id,
+            book: price,
+            message: message.price,
+        }];
+    }
+
+    fn forget_filled(&mut self, fills: &[Fill]) {
+        for fill in fills.iter() {
+            if self.order_book.get(fill.order_id).is_none() {
+                if let Some(order_id) = self.order_ids.remove(&fill.order_id) {
+                    self.ids.remove(&order_id);
+                }
+            }
+        }
+    }
+
+    fn forget(&mut self, order_id: u64) {
+        if let Some(id) = self.ids.remove(&order_id) {
+            self.order_ids.remove(&id);
+        }
+    }
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        return Replay::new();
+    }
+}
+
+/// Iterates over the non-blank lines of a file with their line numbers,
+/// skipping a header: a first line that doesn't start with a digit.
+fn data_lines(input: impl BufRead) -> impl Iterator<Item = Result<(usize, String), ReplayError>> {
+    return input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| match line {
+            Ok(line) => return Ok((i + 1, line)),
+            Err(e) => return Err(ReplayError(format!("line {}: {}", i + 1, e))),
+        })
+        .filter(|line| match line {
+            Ok((n, line)) => {
+                let header =
+                    *n == 1 && !line.trim_start().starts_with(|c: char| c.is_ascii_digit());
+                return !header && !line.trim().is_empty();
+            }
+            Err(_) => return true,
+        });
+}
+
+fn fields(line: &str) -> Vec<&str> {
+    return line.split(',').map(|field| field.trim()).collect();
+}
+
+fn parse_decimal(field: &str) -> Result<Decimal, ReplayError> {
+    return Decimal::from_str(field)
+        .map_err(|_| ReplayError(format!("invalid number '{}'", field)));
+}
+
+fn parse_integer(field: &str) -> Result<i64, ReplayError> {
+    return field
+        .parse()
+        .map_err(|_| ReplayError(format!("invalid integer '{}'", field)));
+}
+
+/// Parses a line of a message file.
+pub fn parse_message(format: Format, line: &str) -> Result<Message, ReplayError> {
+    let fields = fields(line);
+    if fields.len() < 6 {
+        return Err(ReplayError(format!(
+            "expected 6 fields, got {}",
+            fields.len()
+        )));
+    }
+
+    match format {
+        Format::Lobster => {
+            let seconds = parse_decimal(fields[0])?;
+            let timestamp = (seconds * Decimal::from(1_000_000_000))
+                .to_u64()
+                .ok_or_else(|| ReplayError(format!("invalid time '{}'", fields[0])))?;
+
+            let kind = match parse_integer(fields[1])? {
+                1 => MessageKind::Add,
+                2 => MessageKind::Cancel,
+                3 => MessageKind::Delete,
+                4 => MessageKind::Execute,
+                5 => MessageKind::Hidden,
+                6 => MessageKind::Cross,
+                7 => MessageKind::Halt,
+                kind => return Err(ReplayError(format!("unknown message type {}", kind))),
+            };
+
+            let side = match parse_integer(fields[5])? {
+                1 => Side::Bid,
+                -1 => Side::Ask,
+                direction => return Err(ReplayError(format!("unknown direction {}", direction))),
+            };
+
+            return Ok(Message {
+                timestamp,
+                kind,
+                order_id: parse_order_id(fields[2])?,
+                side,
+                price: Decimal::new(parse_integer(fields[4])?, 4),
+                quantity: parse_decimal(fields[3])?,
+            });
+        }
+        Format::Csv => {
+            let kind = match fields[1] {
+                "add" => MessageKind::Add,
+                "cancel" => MessageKind::Cancel,
+                "delete" => MessageKind::Delete,
+                "execute" => MessageKind::Execute,
+                "hidden" => MessageKind::Hidden,
+                "cross" => MessageKind::Cross,
+                "halt" => MessageKind::Halt,
+                kind => return Err(ReplayError(format!("unknown message type '{}'", kind))),
+            };
+
+            let side = match fields[3] {
+                "buy" => Side::Bid,
+                "sell" => Side::Ask,
+                side => return Err(ReplayError(format!("unknown side '{}'", side))),
+            };
+
+            return Ok(Message {
+                timestamp: fields[0]
+                    .parse()
+                    .map_err(|_| ReplayError(format!("invalid timestamp '{}'", fields[0])))?,
+                kind,
+                order_id: parse_order_id(fields[2])?,
+                side,
+                price: parse_decimal(fields[4])?,
+                quantity: parse_decimal(fields[5])?,
+            });
+        }
+    }
+}
+
+fn parse_order_id(field: &str) -> Result<u64, ReplayError> {
+    return field
+        .parse()
+        .map_err(|_| ReplayError(format!("invalid order id '{}'", field)));
+}
+
+/// LOBSTER's price for an empty ask level. Empty bid levels are negative.
+const LOBSTER_EMPTY_PRICE: i64 = 9_999_999_999;
+
+/// Parses a line of a snapshot file.
+pub fn parse_snapshot(format: Format, line: &str) -> Result<Snapshot, ReplayError> {
+    let fields = fields(line);
+    if !fields.len().is_multiple_of(4) {
+        return Err(ReplayError(format!(
+            "expected 4 fields per level, got {}",
+            fields.len()
+        )));
+    }
+
+    let level = |price: &str, size: &str| -> Result<Option<(Decimal, Decimal)>, ReplayError> {
+        match format {
+            Format::Lobster => {
+                let price = parse_integer(price)?;
+                if price.abs() == LOBSTER_EMPTY_PRICE {
+                    return Ok(None);
+                }
+                return Ok(Some((Decimal::new(price, 4), parse_decimal(size)?)));
+            }
+            Format::Csv => {
+                if price.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some((parse_decimal(price)?, parse_decimal(size)?)));
+            }
+        }
+    };
+
+    let mut snapshot = Snapshot {
+        depth: fields.len() / 4,
+        ..Snapshot::default()
+    };
+    for chunk in fields.chunks(4) {
+        snapshot.asks.extend(level(chunk[0], chunk[1])?);
+        snapshot.bids.extend(level(chunk[2], chunk[3])?);
+    }
+
+    return Ok(snapshot);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::*;
+
+    const MESSAGES: &str = "\
+34200.000000001,1,1,100,1000000,-1
+34200.000000002,1,2,50,1000000,-1
+34200.000000003,1,3,200,999900,1
+34200.5,4,1,30,1000000,-1
+34201,2,3,50,999900,1
+34202,3,2,50,1000000,-1
+34202,5,0,10,999800,1
+";
+
+    const SNAPSHOTS: &str = "\
+1000000,100,-9999999999,0
+1000000,150,-9999999999,0
+1000000,150,999900,200
+1000000,120,999900,200
+1000000,120,999900,150
+1000000,70,999900,150
+1000000,70,999900,150
+";
+
+    #[test]
+    fn test_parse_lobster_message() {
+        let message =
+            parse_message(Format::Lobster, "34200.004241176,1,16113575,18,5853300,1").unwrap();
+
+        assert_eq!(
+            message,
+            Message {
+                timestamp: 34_200_004_241_176,
+                kind: MessageKind::Add,
+                order_id: 16113575,
+                side: Side::Bid,
+                price: dec!(585.33),
+                quantity: dec!(18),
+            }
+        );
+
+        assert!(parse_message(Format::Lobster, "34200,9,1,1,1,1").is_err());
+        assert!(parse_message(Format::Lobster, "34200,1,1,1,1,0").is_err());
+        assert!(parse_message(Format::Lobster, "34200,1,1,1").is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_message() {
+        let message = parse_message(Format::Csv, "1000, execute, 7, sell, 10.5, 3").unwrap();
+
+        assert_eq!(message.timestamp, 1000);
+        assert_eq!(message.kind, MessageKind::Execute);
+        assert_eq!(message.side, Side::Ask);
+        assert_eq!(message.price, dec!(10.5));
+    }
+
+    #[test]
+    fn test_parse_snapshot() {
+        let snapshot = parse_snapshot(
+            Format::Lobster,
+            "1000000,100,999900,200,1000100,5,-9999999999,0",
+        )
+        .unwrap();
+
+        assert_eq!(
+            snapshot.asks,
+            vec![(dec!(100), dec!(100)), (dec!(100.01), dec!(5))]
+        );
+        assert_eq!(snapshot.bids, vec![(dec!(99.99), dec!(200))]);
+
+        let snapshot = parse_snapshot(Format::Csv, "10.5,3,,").unwrap();
+        assert_eq!(snapshot.asks, vec![(dec!(10.5), dec!(3))]);
+        assert!(snapshot.bids.is_empty());
+    }
+
+    #[test]
+    fn test_replay_matches_snapshots() {
+        let mut replay = Replay::new();
+
+        let report = replay
+            .run(
+                Format::Lobster,
+                MESSAGES.as_bytes(),
+                Some(SNAPSHOTS.as_bytes()),
+            )
+            .unwrap();
+
+        assert_eq!(report.messages, 7);
+        assert_eq!(report.snapshots, 7);
+        assert_eq!(report.discrepancies, vec![]);
+
+        assert_eq!(replay.id(2), None);
+        assert_eq!(
+            replay
+                .order_book()
+                .get(replay.id(1).unwrap())
+                .unwrap()
+                .quantity,
+            dec!(70)
+        );
+    }
+
+    #[test]
+    fn test_replay_csv_with_header() {
+        let messages = "\
+timestamp,type,order_id,side,price,quantity
+1,add,1,sell,10,5
+2,add,2,buy,9,5
+";
+        let snapshots = "\
+ask_price_1,ask_size_1,bid_price_1,bid_size_1
+10,5,,
+10,5,9,5
+";
+
+        let report = Replay::new()
+            .run(Format::Csv, messages.as_bytes(), Some(snapshots.as_bytes()))
+            .unwrap();
+
+        assert_eq!(report.messages, 2);
+        assert_eq!(report.discrepancies, vec![]);
+    }
+
+    #[test]
+    fn test_replay_reports_discrepancies() {
+        let messages = "\
+1,add,1,sell,10,5
+2,add,2,sell,10,5
+3,execute,2,sell,10,3
+4,cancel,9,buy,9,1
+5,delete,1,sell,10,4
+6,add,3,buy,10,1
+";
+        let report = Replay::new()
+            .run(Format::Csv, messages.as_bytes(), None::<&[u8]>)
+            .unwrap();
+
+        assert_eq!(
+            report.discrepancies,
+            vec![
+                // Order 1 is ahead of order 2, so it takes the execution
+                (
+                    3,
+                    Discrepancy::WrongExecution {
+                        order_id: 2,
+                        filled: vec![(1, dec!(3))],
+                    }
+                ),
+                (4, Discrepancy::UnknownOrder { order_id: 9 }),
+                (
+                    5,
+                    Discrepancy::QuantityMismatch {
+                        order_id: 1,
+                        book: dec!(2),
+                        message: dec!(4),
+                    }
+                ),
+                (
+                    6,
+                    Discrepancy::UnexpectedTrade {
+                        order_id: 3,
+                        quantity: dec!(1),
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_reports_each_level() {
+        let mut replay = Replay::new();
+        replay.apply(&parse_message(Format::Csv, "1,add,1,buy,9,5").unwrap());
+
+        let snapshot = parse_snapshot(Format::Csv, "10,1,9,4,,,8,2").unwrap();
+
+        assert_eq!(
+            replay.verify(&snapshot),
+            vec![
+                Discrepancy::Depth {
+                    side: Side::Bid,
+                    level: 1,
+                    book: Some((dec!(9), dec!(5))),
+                    snapshot: Some((dec!(9), dec!(4))),
+                },
+                Discrepancy::Depth {
+                    side: Side::Bid,
+                    level: 2,
+                    book: None,
+                    snapshot: Some((dec!(8), dec!(2))),
+                },
+                Discrepancy::Depth {
+                    side: Side::Ask,
+                    level: 1,
+                    book: None,
+                    snapshot: Some((dec!(10), dec!(1))),
+                },
+            ]
+        );
+        assert_eq!(
+            replay.verify(&snapshot)[1].to_string(),
+            "Bid level 2 has nothing but the snapshot has 2 @ 8"
+        );
+
+        // Levels left empty in the snapshot are checked too
+        replay.apply(&parse_message(Format::Csv, "2,add,2,buy,8,2").unwrap());
+        replay.apply(&parse_message(Format::Csv, "3,add,3,buy,7,1").unwrap());
+        let snapshot = parse_snapshot(Format::Csv, ",,9,5,,,8,2").unwrap();
+        assert!(replay.verify(&snapshot).is_empty());
+        let snapshot = parse_snapshot(Format::Csv, ",,9,5,,,8,2,,,,").unwrap();
+        assert_eq!(replay.verify(&snapshot).len(), 1);
+    }
+}
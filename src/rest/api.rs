@@ -0,0 +1,433 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+
+use crate::order::Side;
+use crate::order_manager::{NewOrder, OrderChange, OrderError, OrderManager};
+use crate::rest::openapi::openapi;
+use crate::rest::types::{Book, ErrorReport, Level, TradeReport};
+use crate::OrderBook;
+
+/// How many trades `GET /trades` can go back.
+const TRADE_HISTORY: usize = 1000;
+
+/// What to send back for a request.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Value,
+}
+
+impl Response {
+    fn new(status: u16, body: &impl Serialize) -> Self {
+        return Response {
+            status,
+            headers: Vec::new(),
+            body: serde_json::to_value(body).unwrap(),
+        };
+    }
+}
+
+/// A request that can't be carried out, and the status it maps to.
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct Failure {
+    status: u16,
+    message: String,
+    allow: Option<&'static str>,
+}
+
+impl Failure {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        return Failure {
+            status,
+            message: message.into(),
+            allow: None,
+        };
+    }
+
+    fn into_response(self) -> Response {
+        let mut response = Response::new(
+            self.status,
+            &ErrorReport {
+                error: self.message,
+            },
+        );
+        if let Some(allow) = self.allow {
+            response.headers.push(("Allow", allow.to_string()));
+        }
+        return response;
+    }
+}
+
+/// Serves an `OrderManager` through JSON requests and responses, leaving
+/// HTTP itself to a server.
+///
+/// Errors map onto statuses as:
+///
+/// * 400 for a body or query that doesn't parse,
+/// * 404 for an unknown order or path,
+/// * 405 for a method the path doesn't support,
+/// * 409 for changing an order that's no longer on the book,
+/// * 422 for an order that parses but can't be placed, such as a limit
+///   order without a price.
+#[derive(Debug)]
+pub struct Api {
+    orders: OrderManager,
+    trades: VecDeque<TradeReport>,
+}
+
+impl Api {
+    /// Serves `order_book`, turning on its event recording.
+    pub fn new(order_book: OrderBook) -> Self {
+        return Api {
+            orders: OrderManager::new(order_book),
+            trades: VecDeque::new(),
+        };
+    }
+
+    pub fn order_book(&self) -> &OrderBook {
+        return self.orders.order_book();
+    }
+
+    /// Handles a request for `target`, a path with an optional query
+    /// string.
+    pub fn handle(&mut self, method: &str, target: &str, body: &str) -> Response {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        let outcome = match (method, segments.as_slice()) {
+            ("POST", ["orders"]) => self.submit(body),
+            ("GET", ["orders", id]) => self.order(id),
+            ("PATCH", ["orders", id]) => self.amend(id, body),
+            ("DELETE", ["orders", id]) => self.cancel(id),
+            ("GET", ["book"]) => self.book(query),
+            ("GET", ["trades"]) => self.trades(query),
+            ("GET", ["openapi.json"]) => Ok(Response::new(200, &openapi())),
+            (_, ["orders"]) => Err(method_not_allowed("POST")),
+            (_, ["orders", _]) => Err(method_not_allowed("GET, PATCH, DELETE")),
+            (_, ["book"]) | (_, ["trades"]) | (_, ["openapi.json"]) => {
+                Err(method_not_allowed("GET"))
+            }
+            _ => Err(Failure::new(404, format!("no such path: {}", path))),
+        };
+        return outcome.unwrap_or_else(Failure::into_response);
+    }
+
+    fn submit(&mut self, body: &str) -> Result<Response, Failure> {
+        let order: NewOrder = parse_body(body)?;
+        let reports = self.orders.submit(order)?;
+        self.record_trades();
+
+        let id = reports[0].order.id;
+        let mut response = Response::new(201, self.orders.order(id).unwrap());
+        response
+            .headers
+            .push(("Location", format!("/orders/{}", id)));
+        return Ok(response);
+    }
+
+    fn order(&self, id: &str) -> Result<Response, Failure> {
+        let id = parse_id(id)?;
+        match self.orders.order(id) {
+            Some(report) => return Ok(Response::new(200, report)),
+            None => return Err(OrderError::Unknown(id).into()),
+        }
+    }
+
+    fn amend(&mut self, id: &str, body: &str) -> Result<Response, Failure> {
+        let id = parse_id(id)?;
+        let change: OrderChange = parse_body(body)?;
+        self.orders.amend(id, change)?;
+        self.record_trades();
+        return Ok(Response::new(200, self.orders.order(id).unwrap()));
+    }
+
+    fn cancel(&mut self, id: &str) -> Result<Response, Failure> {
+        let id = parse_id(id)?;
+        let report = self.orders.cancel(id)?;
+        return Ok(Response::new(200, &report.order));
+    }
+
+    fn book(&self, query: &str) -> Result<Response, Failure> {
+        let depth = parse_count(query, "depth")?.unwrap_or(usize::MAX);
+        let levels = |side| {
+            return self
+                .order_book()
+                .levels(side)
+                .take(depth)
+                .map(|level| Level {
+                    price: level.price,
+                    quantity: level.volume,
+                })
+                .collect();
+        };
+        let book = Book {
+            bids: levels(Side::Bid),
+            asks: levels(Side::Ask),
+        };
+        return Ok(Response::new(200, &book));
+    }
+
+    /// The most recent trades, oldest first.
+    fn trades(&self, query: &str) -> Result<Response, Failure> {
+        let limit = parse_count(query, "limit")?.unwrap_or(TRADE_HISTORY);
+        let skip = self.trades.len().saturating_sub(limit);
+        let trades: Vec<&TradeReport> = self.trades.iter().skip(skip).collect();
+        return Ok(Response::new(200, &trades));
+    }
+
+    /// Adds the trades the last request made to the history.
+    fn record_trades(&mut self) {
+        for (timestamp, trade) in self.orders.take_trades() {
+            if self.trades.len() == TRADE_HISTORY {
+                self.trades.pop_front();
+            }
+            self.trades.push_back(TradeReport {
+                timestamp,
+                aggressor: trade.aggressor,
+                price: trade.price,
+                quantity: trade.quantity,
+            });
+        }
+    }
+}
+
+impl From<OrderError> for Failure {
+    fn from(e: OrderError) -> Self {
+        let status = match e {
+            OrderError::Unknown(_) => 404,
+            OrderError::Closed(_) => 409,
+            OrderError::Invalid(_) => 422,
+        };
+        return Failure::new(status, e.to_string());
+    }
+}
+
+fn parse_body<T: DeserializeOwned>(body: &str) -> Result<T, Failure> {
+    return serde_json::from_str(body)
+        .map_err(|e| Failure::new(400, format!("invalid body: {}", e)));
+}
+
+fn parse_id(id: &str) -> Result<u64, Failure> {
+    return id
+        .parse()
+        .map_err(|_| Failure::new(404, format!("no such order: {}", id)));
+}
+
+/// Reads a whole number from the query parameter `name`, if it's there.
+fn parse_count(query: &str, name: &str) -> Result<Option<usize>, Failure> {
+    let value = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value);
+    match value {
+        Some(value) => {
+            let count = value
+                .parse()
+                .map_err(|_| Failure::new(400, format!("{} must be a whole number", name)))?;
+            return Ok(Some(count));
+        }
+        None => return Ok(None),
+    }
+}
+
+fn method_not_allowed(allow: &'static str) -> Failure {
+    let mut failure = Failure::new(405, format!("allowed methods are {}", allow));
+    failure.allow = Some(allow);
+    return failure;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use rust_decimal::prelude::*;
+    use serde_json::json;
+
+    fn api() -> Api {
+        let order_book = OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        return Api::new(order_book);
+    }
+
+    fn post(api: &mut Api, order: Value) -> Response {
+        return api.handle("POST", "/orders", &order.to_string());
+    }
+
+    #[test]
+    fn test_order_lifecycle() {
+        let mut api = api();
+        let response = post(
+            &mut api,
+            json!({"side": "Ask", "quantity": "10", "price": "100", "client_order_id": "a"}),
+        );
+        assert_eq!(response.status, 201);
+        assert_eq!(
+            response.headers,
+            vec![("Location", String::from("/orders/1"))]
+        );
+        assert_eq!(response.body["status"], "new");
+        assert_eq!(response.body["type"], "limit");
+        assert_eq!(response.body["time_in_force"], "gtc");
+        assert_eq!(response.body["open"], "10");
+
+        let response = post(
+            &mut api,
+            json!({"side": "Bid", "type": "market", "quantity": "4"}),
+        );
+        assert_eq!(response.status, 201);
+        assert_eq!(response.body["status"], "filled");
+        assert_eq!(response.body["time_in_force"], "ioc");
+        assert_eq!(
+            response.body["fills"],
+            json!([{"timestamp": 0, "price": "100", "quantity": "4"}])
+        );
+
+        let response = api.handle("GET", "/orders/1", "");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["status"], "partially_filled");
+        assert_eq!(response.body["client_order_id"], "a");
+        assert_eq!(response.body["filled"], "4");
+        assert_eq!(response.body["open"], "6");
+
+        // Raising the total to 12 leaves 8 open, at a new price
+        let response = api.handle(
+            "PATCH",
+            "/orders/1",
+            r#"{"quantity": "12", "price": "101"}"#,
+        );
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["price"], "101");
+        assert_eq!(response.body["open"], "8");
+        assert_eq!(api.order_book().best_ask(), Some(Decimal::from(101)));
+
+        let response = api.handle("PATCH", "/orders/1", r#"{"quantity": "4"}"#);
+        assert_eq!(response.status, 422);
+
+        let response = api.handle("DELETE", "/orders/1", "");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["status"], "canceled");
+        assert_eq!(response.body["open"], "0");
+        assert_eq!(api.order_book().best_ask(), None);
+
+        let response = api.handle("DELETE", "/orders/1", "");
+        assert_eq!(response.status, 409);
+        assert_eq!(
+            response.body,
+            json!({"error": "order 1 is no longer on the book"})
+        );
+
+        let response = api.handle("GET", "/trades", "");
+        assert_eq!(
+            response.body,
+            json!([{"timestamp": 0, "aggressor": "Bid", "price": "100", "quantity": "4"}])
+        );
+    }
+
+    #[test]
+    fn test_immediate_or_cancel() {
+        let mut api = api();
+        post(
+            &mut api,
+            json!({"side": "Bid", "quantity": "3", "price": "99"}),
+        );
+        let response = post(
+            &mut api,
+            json!({"side": "Ask", "quantity": "5", "price": "99", "time_in_force": "ioc"}),
+        );
+        assert_eq!(response.body["status"], "canceled");
+        assert_eq!(response.body["filled"], "3");
+        assert_eq!(response.body["open"], "0");
+        assert_eq!(api.order_book().best_ask(), None);
+        assert_eq!(api.handle("GET", "/orders/1", "").body["status"], "filled");
+    }
+
+    #[test]
+    fn test_book_depth() {
+        let mut api = api();
+        for price in ["10", "11", "12"].iter() {
+            post(
+                &mut api,
+                json!({"side": "Ask", "quantity": "1", "price": price}),
+            );
+        }
+        post(
+            &mut api,
+            json!({"side": "Ask", "quantity": "2", "price": "10"}),
+        );
+        post(
+            &mut api,
+            json!({"side": "Bid", "quantity": "5", "price": "9"}),
+        );
+
+        let response = api.handle("GET", "/book?depth=2", "");
+        assert_eq!(
+            response.body,
+            json!({
+                "bids": [{"price": "9", "quantity": "5"}],
+                "asks": [{"price": "10", "quantity": "3"}, {"price": "11", "quantity": "1"}]
+            })
+        );
+        assert_eq!(
+            api.handle("GET", "/book", "").body["asks"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(api.handle("GET", "/book?depth=two", "").status, 400);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut api = api();
+        let cases = vec![
+            ("POST", "/orders", "{", 400),
+            (
+                "POST",
+                "/orders",
+                r#"{"side": "Bid", "quantity": "1", "size": "2"}"#,
+                400,
+            ),
+            (
+                "POST",
+                "/orders",
+                r#"{"side": "Bid", "quantity": "1"}"#,
+                422,
+            ),
+            (
+                "POST",
+                "/orders",
+                r#"{"side": "Bid", "quantity": "0", "price": "1"}"#,
+                422,
+            ),
+            (
+                "POST",
+                "/orders",
+                r#"{"side": "Bid", "type": "market", "quantity": "1", "price": "1"}"#,
+                422,
+            ),
+            (
+                "POST",
+                "/orders",
+                r#"{"side": "Bid", "type": "market", "quantity": "1", "time_in_force": "gtc"}"#,
+                422,
+            ),
+            ("GET", "/orders/7", "", 404),
+            ("PATCH", "/orders/x", "{}", 404),
+            ("GET", "/positions", "", 404),
+            ("PUT", "/orders/1", "", 405),
+            ("GET", "/orders", "", 405),
+        ];
+        for (method, target, body, status) in cases {
+            let response = api.handle(method, target, body);
+            assert_eq!(response.status, status, "{} {} {}", method, target, body);
+            assert!(response.body["error"].is_string());
+        }
+        let response = api.handle("POST", "/book", "");
+        assert_eq!(response.headers, vec![("Allow", String::from("GET"))]);
+    }
+}
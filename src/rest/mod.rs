@@ -0,0 +1,9 @@
+pub mod api;
+pub mod openapi;
+pub mod server;
+pub mod types;
+
+pub use api::{Api, Response};
+pub use openapi::openapi;
+pub use server::Server;
+pub use types::{NewOrder, OrderChange, OrderReport, OrderStatus};
@@ -0,0 +1,183 @@
+use schemars::generate::SchemaSettings;
+use schemars::{JsonSchema, SchemaGenerator};
+use serde_json::{json, Value};
+
+use crate::rest::types::{Book, ErrorReport, NewOrder, OrderChange, OrderReport, TradeReport};
+
+/// Describes the HTTP API as an OpenAPI 3.0 document, with the schemas of
+/// its bodies generated from the types they're read into and written from.
+pub fn openapi() -> Value {
+    let mut requests = SchemaSettings::openapi3()
+        .for_deserialize()
+        .into_generator();
+    let mut responses = SchemaSettings::openapi3().for_serialize().into_generator();
+
+    let new_order = request_body(reference::<NewOrder>(&mut requests));
+    let order_change = request_body(reference::<OrderChange>(&mut requests));
+    let order = reference::<OrderReport>(&mut responses);
+    let book = reference::<Book>(&mut responses);
+    let trades = json!({"type": "array", "items": reference::<TradeReport>(&mut responses)});
+    let error = reference::<ErrorReport>(&mut responses);
+
+    let success = |description: &str, schema: &Value| {
+        return json!({
+            "description": description,
+            "content": {"application/json": {"schema": schema}}
+        });
+    };
+    let failure = |description: &str| success(description, &error);
+    let id = json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": {"type": "integer", "format": "int64", "minimum": 1}
+    });
+    let count = |name: &str, description: &str| {
+        return json!({
+            "name": name,
+            "in": "query",
+            "required": false,
+            "description": description,
+            "schema": {"type": "integer", "minimum": 0}
+        });
+    };
+
+    let paths = json!({
+        "/orders": {
+            "post": {
+                "summary": "Submit a limit or market order",
+                "requestBody": new_order,
+                "responses": {
+                    "201": success("The order, after it's matched", &order),
+                    "400": failure("The body doesn't parse"),
+                    "422": failure("The order can't be placed")
+                }
+            }
+        },
+        "/orders/{id}": {
+            "parameters": [id],
+            "get": {
+                "summary": "Look up an order",
+                "responses": {
+                    "200": success("The order", &order),
+                    "404": failure("There's no such order")
+                }
+            },
+            "patch": {
+                "summary": "Change the quantity or price of an order on the book",
+                "description": "Reducing the quantity at the same price keeps the order's place in the queue. Any other change sends it to the back, where it may match.",
+                "requestBody": order_change,
+                "responses": {
+                    "200": success("The order, after it's matched", &order),
+                    "400": failure("The body doesn't parse"),
+                    "404": failure("There's no such order"),
+                    "409": failure("The order is no longer on the book"),
+                    "422": failure("The change can't be made")
+                }
+            },
+            "delete": {
+                "summary": "Cancel an order on the book",
+                "responses": {
+                    "200": success("The canceled order", &order),
+                    "404": failure("There's no such order"),
+                    "409": failure("The order is no longer on the book")
+                }
+            }
+        },
+        "/book": {
+            "get": {
+                "summary": "The price levels on each side of the book",
+                "parameters": [count("depth", "How many levels to show on each side, or all of them")],
+                "responses": {
+                    "200": success("The book", &book),
+                    "400": failure("The query doesn't parse")
+                }
+            }
+        },
+        "/trades": {
+            "get": {
+                "summary": "The most recent trades, oldest first",
+                "parameters": [count("limit", "How many trades to show, or as many as are kept")],
+                "responses": {
+                    "200": success("The trades", &trades),
+                    "400": failure("The query doesn't parse")
+                }
+            }
+        }
+    });
+
+    let mut schemas = requests.take_definitions(true);
+    schemas.extend(responses.take_definitions(true));
+    return json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "orderbook",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": paths,
+        "components": {"schemas": Value::Object(schemas)}
+    });
+}
+
+fn reference<T: JsonSchema>(generator: &mut SchemaGenerator) -> Value {
+    return generator.subschema_for::<T>().to_value();
+}
+
+fn request_body(schema: Value) -> Value {
+    return json!({
+        "required": true,
+        "content": {"application/json": {"schema": schema}}
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collects every `$ref` in `value`.
+    fn references<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter() {
+                    match (key.as_str(), value) {
+                        ("$ref", Value::String(reference)) => found.push(reference),
+                        _ => references(value, found),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|value| references(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_references_resolve() {
+        let document = openapi();
+        let schemas = &document["components"]["schemas"];
+        let mut found = Vec::new();
+        references(&document, &mut found);
+        assert!(found.contains(&"#/components/schemas/OrderReport"));
+        for reference in found {
+            let name = reference.strip_prefix("#/components/schemas/").unwrap();
+            assert!(schemas[name].is_object(), "{} is missing", reference);
+        }
+
+        let new_order = &schemas["NewOrder"];
+        let mut required: Vec<&str> = new_order["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|name| name.as_str().unwrap())
+            .collect();
+        required.sort();
+        assert_eq!(required, vec!["quantity", "side"]);
+        assert!(new_order["properties"]["type"].is_object());
+        assert!(schemas["OrderStatus"]
+            .to_string()
+            .contains(r#"["partially_filled"]"#));
+        assert_eq!(
+            new_order["properties"]["quantity"],
+            json!({"type": "string", "format": "decimal"})
+        );
+    }
+}
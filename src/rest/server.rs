@@ -0,0 +1,146 @@
+use std::io::{self, Read};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::Mutex;
+use tiny_http::{Header, Request};
+
+use crate::rest::api::{Api, Response};
+
+/// The most of a request body that's read.
+const MAX_BODY: u64 = 64 * 1024;
+
+/// Serves an `Api` over HTTP/1.1.
+///
+/// Connections are read on their own threads, but requests are handled one
+/// at a time, in the order they arrive.
+pub struct Server {
+    http: tiny_http::Server,
+    local_addr: SocketAddr,
+    api: Mutex<Api>,
+}
+
+impl Server {
+    /// Listens on `addr`. Requests carry no credentials, so anyone who can
+    /// reach `addr` can place and cancel orders.
+    pub fn bind(addr: impl ToSocketAddrs, api: Api) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let http = tiny_http::Server::from_listener(listener, None).map_err(io::Error::other)?;
+        return Ok(Server {
+            http,
+            local_addr,
+            api: Mutex::new(api),
+        });
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        return Ok(self.local_addr);
+    }
+
+    /// Answers requests until the listener fails.
+    pub fn run(&self) -> io::Result<()> {
+        loop {
+            let request = self.http.recv()?;
+            let _ = self.respond(request);
+        }
+    }
+
+    fn respond(&self, mut request: Request) -> io::Result<()> {
+        let mut body = String::new();
+        let read = request.as_reader().take(MAX_BODY).read_to_string(&mut body);
+        let response = match read {
+            Ok(_) => {
+                let method = request.method().to_string();
+                self.api
+                    .lock()
+                    .unwrap()
+                    .handle(&method, request.url(), &body)
+            }
+            Err(e) => Response {
+                status: 400,
+                headers: Vec::new(),
+                body: serde_json::json!({ "error": format!("can't read body: {}", e) }),
+            },
+        };
+
+        let mut http_response = tiny_http::Response::from_string(response.body.to_string())
+            .with_status_code(response.status)
+            .with_header(header("Content-Type", "application/json"));
+        for (name, value) in response.headers.iter() {
+            http_response.add_header(header(name, value));
+        }
+        return request.respond(http_response);
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    return Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use crate::OrderBook;
+    use serde_json::Value;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::thread;
+
+    /// Sends a request on its own connection, and returns the status line,
+    /// the headers and the body of the response.
+    fn request(
+        addr: SocketAddr,
+        method: &str,
+        target: &str,
+        body: &str,
+    ) -> (String, String, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            target,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let (status, headers) = head.split_once("\r\n").unwrap();
+        return (
+            status.to_string(),
+            headers.to_string(),
+            serde_json::from_str(body).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_http() {
+        let order_book = OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        let server = Server::bind("127.0.0.1:0", Api::new(order_book)).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let order = r#"{"side": "Bid", "quantity": "2", "price": "9.5"}"#;
+        let (status, headers, body) = request(addr, "POST", "/orders", order);
+        assert_eq!(status, "HTTP/1.1 201 Created");
+        assert!(headers.contains("Location: /orders/1"));
+        assert!(headers.contains("Content-Type: application/json"));
+        assert_eq!(body["status"], "new");
+
+        let (status, _, body) = request(addr, "GET", "/book?depth=1", "");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body["bids"][0]["price"], "9.5");
+
+        let (status, _, body) = request(addr, "DELETE", "/orders/2", "");
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        assert_eq!(body["error"], "no such order: 2");
+
+        let (status, _, body) = request(addr, "GET", "/openapi.json", "");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(body["openapi"], "3.0.3");
+    }
+}
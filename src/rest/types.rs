@@ -0,0 +1,32 @@
+use rust_decimal::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::schema::DecimalString;
+
+pub use crate::order_manager::{
+    Execution, NewOrder, OrderChange, OrderReport, OrderStatus, OrderType, TimeInForce,
+};
+pub use crate::trade::TradeReport;
+
+/// The price levels on each side of the book, best first.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Book {
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Level {
+    #[schemars(with = "DecimalString")]
+    pub price: Decimal,
+    /// The total quantity of the orders at the price.
+    #[schemars(with = "DecimalString")]
+    pub quantity: Decimal,
+}
+
+/// The body of every response that isn't a success.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorReport {
+    pub error: String,
+}
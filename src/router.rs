@@ -0,0 +1,656 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::consolidated::VenueId;
+use crate::numeric::Numeric;
+use crate::order::Side;
+use crate::trade::Trade;
+use crate::{OrderBook, OrderResult};
+
+/// What it costs to take liquidity on a venue.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct VenueCosts {
+    /// The taker fee, as a fraction of the notional traded.
+    pub fee_rate: Decimal,
+    /// How long an order takes to reach the venue, in nanoseconds. Between
+    /// venues with the same price after fees, the nearest is used first.
+    pub latency: u64,
+}
+
+/// An order to be split across venues.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ParentOrder<P = Decimal, Q = Decimal> {
+    pub side: Side,
+    pub quantity: Q,
+    /// The worst price to trade at, or none for a market order.
+    pub limit: Option<P>,
+}
+
+/// What to do with the part of a parent order that couldn't be filled
+/// straight away.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RemainderPolicy {
+    Cancel,
+    /// Plan and send child orders again, up to this many more times, while
+    /// there's liquidity within the limit, then cancel what's left.
+    Reroute {
+        attempts: u32,
+    },
+    /// Rest what's left at the limit on `venue`, or on the venue with the
+    /// lowest fee. Market orders are canceled instead.
+    Rest {
+        venue: Option<VenueId>,
+    },
+}
+
+/// A child order the router plans to send.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ChildOrder<P = Decimal, Q = Decimal> {
+    pub venue: VenueId,
+    pub quantity: Q,
+    /// The worst price the child is planned to trade at, or none for a market
+    /// order.
+    pub price: Option<P>,
+}
+
+/// A child order that was sent, and what came of it.
+///
+/// Notionals and fees are in `Decimal` whatever the book's types are. For
+/// `Ticks` and `Lots` they're in tick-lots, which `Instrument` can scale.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ChildReport<P = Decimal, Q = Decimal> {
+    pub order: ChildOrder<P, Q>,
+    pub trades: Vec<Trade<P, Q>>,
+    pub filled: Q,
+    pub notional: Decimal,
+    pub fees: Decimal,
+}
+
+impl<P: Numeric, Q: Numeric> ChildReport<P, Q>
+where
+    Decimal: From<P> + From<Q>,
+{
+    /// Reports what a venue charging `fee_rate` did with `order`.
+    fn new(
+        order: ChildOrder<P, Q>,
+        side: Side,
+        result: &OrderResult<P, Q>,
+        fee_rate: Decimal,
+    ) -> Self {
+        let trades = Trade::from_result(side, result);
+        let notional: Decimal = trades
+            .iter()
+            .map(|trade| Decimal::from(trade.price) * Decimal::from(trade.quantity))
+            .sum();
+        return ChildReport {
+            order,
+            trades,
+            filled: result.quantity_filled,
+            notional,
+            fees: notional * fee_rate,
+        };
+    }
+}
+
+/// The part of a parent order left resting on a venue.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RestingOrder<P = Decimal, Q = Decimal> {
+    pub venue: VenueId,
+    pub id: Uuid,
+    pub quantity: Q,
+    pub price: P,
+}
+
+/// Everything that happened to a parent order.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ParentReport<P = Decimal, Q = Decimal> {
+    pub order: ParentOrder<P, Q>,
+    pub children: Vec<ChildReport<P, Q>>,
+    pub filled: Q,
+    pub notional: Decimal,
+    pub fees: Decimal,
+    pub resting: Option<RestingOrder<P, Q>>,
+    pub canceled: Q,
+}
+
+impl<P: Numeric, Q: Numeric> ParentReport<P, Q>
+where
+    Decimal: From<Q>,
+{
+    fn new(order: ParentOrder<P, Q>) -> Self {
+        return ParentReport {
+            order,
+            children: Vec::new(),
+            filled: Q::ZERO,
+            notional: Decimal::zero(),
+            fees: Decimal::zero(),
+            resting: None,
+            canceled: Q::ZERO,
+        };
+    }
+
+    /// The average price filled at, before fees, in the units of `P`.
+    pub fn average_price(&self) -> Option<Decimal> {
+        if self.filled == Q::ZERO {
+            return None;
+        }
+        return Some(self.notional / Decimal::from(self.filled));
+    }
+
+    fn add_child(&mut self, child: ChildReport<P, Q>) {
+        self.filled += child.filled;
+        self.notional += child.notional;
+        self.fees += child.fees;
+        self.children.push(child);
+    }
+}
+
+#[derive(Debug)]
+struct Venue<P, Q> {
+    name: String,
+    order_book: OrderBook<P, Q>,
+    costs: VenueCosts,
+}
+
+/// Liquidity at one price on one venue.
+struct Quote<P, Q> {
+    venue: VenueId,
+    price: P,
+    volume: Q,
+    /// The price after fees.
+    cost: Decimal,
+    latency: u64,
+}
+
+/// A parent order the router can't take.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RouteError(pub String);
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.0);
+    }
+}
+
+impl Error for RouteError {}
+
+/// Splits parent orders across the books of several venues trading the same
+/// instrument.
+///
+/// Each parent is planned against the books as they stand: the levels within
+/// its limit on every venue are ranked by price after fees, nearest venue
+/// first on ties, and taken in turn until the parent is covered. Each venue
+/// then gets one child for its share, sent nearest venue first. A child of a
+/// limit order is a limit order at the worst price planned on its venue, and
+/// anything it doesn't fill is canceled before the remainder policy applies.
+///
+/// Prices are ranked after fees as `Decimal`s, so for `Ticks` every venue
+/// has to share a tick size.
+#[derive(Debug)]
+pub struct Router<P = Decimal, Q = Decimal> {
+    venues: Vec<Venue<P, Q>>,
+    policy: RemainderPolicy,
+}
+
+impl<P: Numeric, Q: Numeric> Router<P, Q>
+where
+    Decimal: From<P> + From<Q>,
+{
+    pub fn new(policy: RemainderPolicy) -> Self {
+        return Router {
+            venues: Vec::new(),
+            policy,
+        };
+    }
+
+    pub fn add_venue(
+        &mut self,
+        name: &str,
+        order_book: OrderBook<P, Q>,
+        costs: VenueCosts,
+    ) -> VenueId {
+        self.venues.push(Venue {
+            name: name.to_string(),
+            order_book,
+            costs,
+        });
+        return VenueId(self.venues.len() - 1);
+    }
+
+    /// Panics if `venue` is from another router.
+    pub fn venue_name(&self, venue: VenueId) -> &str {
+        return &self.venues[venue.0].name;
+    }
+
+    pub fn order_book(&self, venue: VenueId) -> &OrderBook<P, Q> {
+        return &self.venues[venue.0].order_book;
+    }
+
+    pub fn order_book_mut(&mut self, venue: VenueId) -> &mut OrderBook<P, Q> {
+        return &mut self.venues[venue.0].order_book;
+    }
+
+    /// The child orders that would be sent for `order` now, nearest venue
+    /// first.
+    pub fn plan(&self, order: &ParentOrder<P, Q>) -> Vec<ChildOrder<P, Q>> {
+        let opposite = match order.side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
+        };
+        let mut quotes = Vec::new();
+        for (index, venue) in self.venues.iter().enumerate() {
+            let mut volume = Q::ZERO;
+            let levels = venue.order_book.levels(opposite);
+            for level in levels {
+                if volume >= order.quantity || !within(order, level.price) {
+                    break;
+                }
+                volume += level.volume;
+                let price = Decimal::from(level.price);
+                let cost = match order.side {
+                    Side::Bid => price * (Decimal::one() + venue.costs.fee_rate),
+                    Side::Ask => price * (Decimal::one() - venue.costs.fee_rate),
+                };
+                quotes.push(Quote {
+                    venue: VenueId(index),
+                    price: level.price,
+                    volume: level.volume,
+                    cost,
+                    latency: venue.costs.latency,
+                });
+            }
+        }
+        quotes.sort_by(|left, right| {
+            let by_cost = match order.side {
+                Side::Bid => left.cost.cmp(&right.cost),
+                Side::Ask => right.cost.cmp(&left.cost),
+            };
+            return by_cost
+                .then(left.latency.cmp(&right.latency))
+                .then(left.venue.0.cmp(&right.venue.0));
+        });
+
+        let mut children: Vec<ChildOrder<P, Q>> = Vec::new();
+        let mut remaining = order.quantity;
+        for quote in quotes.iter() {
+            if remaining == Q::ZERO {
+                break;
+            }
+            let quantity = remaining.min(quote.volume);
+            remaining -= quantity;
+            let price = order.limit.map(|_| quote.price);
+            // A venue's quotes are ranked in price order, so the last one
+            // taken is the worst price its child has to reach
+            match children.iter_mut().find(|child| child.venue == quote.venue) {
+                Some(child) => {
+                    child.quantity += quantity;
+                    child.price = price;
+                }
+                None => children.push(ChildOrder {
+                    venue: quote.venue,
+                    quantity,
+                    price,
+                }),
+            }
+        }
+        children.sort_by_key(|child| (self.venues[child.venue.0].costs.latency, child.venue.0));
+        return children;
+    }
+
+    /// Routes `order`, then deals with whatever couldn't be filled by the
+    /// remainder policy. Fails if the order's quantity isn't positive, or the
+    /// policy rests on a venue the router doesn't have.
+    pub fn route(&mut self, order: &ParentOrder<P, Q>) -> Result<ParentReport<P, Q>, RouteError> {
+        let children = self.plan(order);
+        return self.execute(order, &children);
+    }
+
+    /// Sends `children`, planned for `order` earlier, then deals with
+    /// whatever they didn't fill by the remainder policy. The books may have
+    /// moved since the plan was made, so a child can fill less than planned;
+    /// rerouting plans the shortfall against the books as they are now.
+    /// Fails, before anything is sent, if the order's quantity isn't
+    /// positive, a child isn't for one of the router's venues, the children
+    /// add up to more than the order or the policy rests on a venue the
+    /// router doesn't have.
+    pub fn execute(
+        &mut self,
+        order: &ParentOrder<P, Q>,
+        children: &[ChildOrder<P, Q>],
+    ) -> Result<ParentReport<P, Q>, RouteError> {
+        if order.quantity <= Q::ZERO {
+            let message = format!("quantity must be positive, not {}", order.quantity);
+            return Err(RouteError(message));
+        }
+        let mut planned = Q::ZERO;
+        for child in children.iter() {
+            self.check_venue(child.venue)?;
+            if child.quantity <= Q::ZERO {
+                let message = format!("child quantity must be positive, not {}", child.quantity);
+                return Err(RouteError(message));
+            }
+            planned += child.quantity;
+        }
+        if planned > order.quantity {
+            let message = format!(
+                "children add up to {}, more than the order's {}",
+                planned, order.quantity
+            );
+            return Err(RouteError(message));
+        }
+        if let RemainderPolicy::Rest { venue: Some(venue) } = self.policy {
+            self.check_venue(venue)?;
+        }
+
+        let mut report = ParentReport::new(*order);
+        for child in children.iter() {
+            let child = self.send(order.side, child);
+            report.add_child(child);
+        }
+
+        let mut reroutes = match self.policy {
+            RemainderPolicy::Reroute { attempts } => attempts,
+            _ => 0,
+        };
+        while reroutes > 0 && report.filled < order.quantity {
+            let remaining = ParentOrder {
+                quantity: order.quantity - report.filled,
+                ..*order
+            };
+            let children = self.plan(&remaining);
+            if children.is_empty() {
+                break;
+            }
+            for child in children.iter() {
+                let child = self.send(order.side, child);
+                report.add_child(child);
+            }
+            reroutes -= 1;
+        }
+
+        let remaining = order.quantity - report.filled;
+        if remaining == Q::ZERO {
+            return Ok(report);
+        }
+        match (self.policy, order.limit) {
+            (RemainderPolicy::Rest { venue }, Some(limit)) if !self.venues.is_empty() => {
+                let venue = venue.unwrap_or_else(|| self.cheapest_venue());
+                self.rest(&mut report, venue, remaining, limit);
+            }
+            _ => report.canceled = remaining,
+        }
+        return Ok(report);
+    }
+
+    fn check_venue(&self, venue: VenueId) -> Result<(), RouteError> {
+        if venue.0 >= self.venues.len() {
+            return Err(RouteError(format!("unknown venue {}", venue.0)));
+        }
+        return Ok(());
+    }
+
+    fn send(&mut self, side: Side, child: &ChildOrder<P, Q>) -> ChildReport<P, Q> {
+        let venue = &mut self.venues[child.venue.0];
+        let result = match child.price {
+            Some(price) => {
+                let result = venue
+                    .order_book
+                    .submit_limit_order(side, child.quantity, price);
+                if let Some(partial) = result.partial {
+                    venue.order_book.remove(partial.id);
+                }
+                result
+            }
+            None => venue.order_book.submit_market_order(side, child.quantity),
+        };
+        return ChildReport::new(*child, side, &result, venue.costs.fee_rate);
+    }
+
+    fn rest(&mut self, report: &mut ParentReport<P, Q>, venue: VenueId, quantity: Q, price: P) {
+        let side = report.order.side;
+        let order_book = &mut self.venues[venue.0].order_book;
+        let result = order_book.submit_limit_order(side, quantity, price);
+        let fee_rate = self.venues[venue.0].costs.fee_rate;
+        if result.quantity_filled != Q::ZERO {
+            let order = ChildOrder {
+                venue,
+                quantity,
+                price: Some(price),
+            };
+            report.add_child(ChildReport::new(order, side, &result, fee_rate));
+        }
+        report.resting = result.partial.map(|order| RestingOrder {
+            venue,
+            id: order.id,
+            quantity: order.quantity,
+            price: order.price,
+        });
+    }
+
+    fn cheapest_venue(&self) -> VenueId {
+        let (index, _) = self
+            .venues
+            .iter()
+            .enumerate()
+            .min_by(|(_, left), (_, right)| {
+                return left
+                    .costs
+                    .fee_rate
+                    .cmp(&right.costs.fee_rate)
+                    .then(left.costs.latency.cmp(&right.costs.latency));
+            })
+            .unwrap();
+        return VenueId(index);
+    }
+}
+
+fn within<P: Numeric, Q>(order: &ParentOrder<P, Q>, price: P) -> bool {
+    return match (order.side, order.limit) {
+        (_, None) => true,
+        (Side::Bid, Some(limit)) => price <= limit,
+        (Side::Ask, Some(limit)) => price >= limit,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numeric::{Lots, Ticks};
+    use rust_decimal_macros::*;
+
+    fn venue(asks: &[(Decimal, Decimal)]) -> OrderBook {
+        let mut order_book = OrderBook::new();
+        for (quantity, price) in asks.iter() {
+            order_book.submit_limit_order(Side::Ask, *quantity, *price);
+        }
+        return order_book;
+    }
+
+    fn costs(fee_rate: Decimal, latency: u64) -> VenueCosts {
+        return VenueCosts { fee_rate, latency };
+    }
+
+    #[test]
+    fn test_splits_by_price_after_fees() {
+        let mut router = Router::new(RemainderPolicy::Cancel);
+        let a = router.add_venue("A", venue(&[(dec!(5), dec!(100))]), costs(dec!(0.01), 100));
+        let b = router.add_venue("B", venue(&[(dec!(5), dec!(100.5))]), costs(dec!(0), 500));
+        let c = router.add_venue(
+            "C",
+            venue(&[(dec!(5), dec!(100.5)), (dec!(5), dec!(102))]),
+            costs(dec!(0), 50),
+        );
+        let order = ParentOrder {
+            side: Side::Bid,
+            quantity: dec!(12),
+            limit: Some(dec!(101)),
+        };
+
+        let child = |venue, quantity, price| ChildOrder {
+            venue,
+            quantity,
+            price: Some(price),
+        };
+        assert_eq!(
+            router.plan(&order),
+            vec![
+                child(c, dec!(5), dec!(100.5)),
+                child(a, dec!(2), dec!(100)),
+                child(b, dec!(5), dec!(100.5)),
+            ]
+        );
+
+        let report = router.route(&order).unwrap();
+        assert_eq!(report.filled, dec!(12));
+        assert_eq!(report.notional, dec!(1205));
+        assert_eq!(report.fees, dec!(2));
+        assert_eq!(report.canceled, dec!(0));
+        assert_eq!(report.children.len(), 3);
+        assert_eq!(router.order_book(a).best_ask(), Some(dec!(100)));
+        assert_eq!(router.order_book(b).best_ask(), None);
+        assert_eq!(router.order_book(c).best_ask(), Some(dec!(102)));
+    }
+
+    #[test]
+    fn test_remainder_policy() {
+        let venues = |router: &mut Router| {
+            let a = router.add_venue("A", venue(&[(dec!(5), dec!(100))]), costs(dec!(0.001), 100));
+            router.add_venue("B", venue(&[(dec!(5), dec!(101))]), costs(dec!(0.002), 50));
+            return a;
+        };
+
+        let mut router = Router::new(RemainderPolicy::Rest { venue: None });
+        let a = venues(&mut router);
+        let report = router
+            .route(&ParentOrder {
+                side: Side::Bid,
+                quantity: dec!(15),
+                limit: Some(dec!(101)),
+            })
+            .unwrap();
+        assert_eq!(report.filled, dec!(10));
+        assert_eq!(report.average_price(), Some(dec!(100.5)));
+        let resting = report.resting.unwrap();
+        assert_eq!((resting.venue, resting.quantity), (a, dec!(5)));
+        assert_eq!(
+            router.order_book(a).get(resting.id).unwrap().price,
+            dec!(101)
+        );
+
+        let mut router = Router::new(RemainderPolicy::Rest { venue: None });
+        venues(&mut router);
+        let report = router
+            .route(&ParentOrder {
+                side: Side::Bid,
+                quantity: dec!(15),
+                limit: None,
+            })
+            .unwrap();
+        assert_eq!(report.children[0].order.price, None);
+        assert_eq!(report.filled, dec!(10));
+        assert_eq!(report.resting, None);
+        assert_eq!(report.canceled, dec!(5));
+    }
+
+    #[test]
+    fn test_reroutes_what_a_child_leaves() {
+        let mut router = Router::new(RemainderPolicy::Reroute { attempts: u32::MAX });
+        let a = router.add_venue("A", venue(&[(dec!(5), dec!(100))]), costs(dec!(0), 50));
+        let b = router.add_venue("B", venue(&[(dec!(5), dec!(101))]), costs(dec!(0), 100));
+        let order = ParentOrder {
+            side: Side::Bid,
+            quantity: dec!(8),
+            limit: Some(dec!(101)),
+        };
+        let children = router.plan(&order);
+
+        // Someone else gets to A first, so its child only fills 3 of 5
+        router
+            .order_book_mut(a)
+            .submit_market_order(Side::Bid, dec!(2));
+        let report = router.execute(&order, &children).unwrap();
+        let filled: Vec<(VenueId, Decimal)> = report
+            .children
+            .iter()
+            .map(|child| (child.order.venue, child.filled))
+            .collect();
+        assert_eq!(filled, vec![(a, dec!(3)), (b, dec!(3)), (b, dec!(2))]);
+        assert_eq!(report.filled, dec!(8));
+        assert_eq!(report.canceled, dec!(0));
+
+        // Rerouting stops once nothing's left within the limit
+        let report = router.route(&order).unwrap();
+        assert!(report.children.is_empty());
+        assert_eq!(report.canceled, dec!(8));
+    }
+
+    #[test]
+    fn test_ticks_and_lots() {
+        let mut order_book = OrderBook::new();
+        order_book.submit_limit_order(Side::Bid, Lots(4), Ticks(1000));
+        let mut router = Router::new(RemainderPolicy::Cancel);
+        let a = router.add_venue("A", order_book, costs(dec!(0.001), 0));
+
+        let order = ParentOrder {
+            side: Side::Ask,
+            quantity: Lots(6),
+            limit: Some(Ticks(999)),
+        };
+        let report = router.route(&order).unwrap();
+        assert_eq!(report.children[0].order.venue, a);
+        assert_eq!((report.filled, report.canceled), (Lots(4), Lots(2)));
+        assert_eq!(report.notional, dec!(4000));
+        assert_eq!(report.fees, dec!(4));
+        assert_eq!(report.average_price(), Some(dec!(1000)));
+
+        let order = ParentOrder {
+            quantity: Lots(0),
+            ..order
+        };
+        assert_eq!(
+            router.route(&order),
+            Err(RouteError(String::from("quantity must be positive, not 0")))
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_children_and_venues() {
+        let mut router = Router::new(RemainderPolicy::Cancel);
+        let a = router.add_venue("A", OrderBook::new(), costs(dec!(0), 0));
+        let order = ParentOrder {
+            side: Side::Bid,
+            quantity: Lots(5),
+            limit: Some(Ticks(100)),
+        };
+        let child = |venue, quantity| ChildOrder {
+            venue,
+            quantity: Lots(quantity),
+            price: Some(Ticks(100)),
+        };
+        let error = |message: &str| Err(RouteError(String::from(message)));
+
+        assert_eq!(
+            router.execute(&order, &[child(a, 3), child(a, 3)]),
+            error("children add up to 6, more than the order's 5")
+        );
+        assert_eq!(
+            router.execute(&order, &[child(VenueId(1), 1)]),
+            error("unknown venue 1")
+        );
+        assert_eq!(
+            router.execute(&order, &[child(a, 0)]),
+            error("child quantity must be positive, not 0")
+        );
+        // Nothing was sent
+        assert_eq!(router.order_book(a).best_bid(), None);
+
+        let mut router = Router::new(RemainderPolicy::Rest {
+            venue: Some(VenueId(2)),
+        });
+        router.add_venue("A", OrderBook::new(), costs(dec!(0), 0));
+        assert_eq!(router.route(&order), error("unknown venue 2"));
+    }
+}
@@ -0,0 +1,92 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Events waiting for points in simulated time, earliest first. Events at
+/// the same time come out in the order they were scheduled.
+#[derive(Debug)]
+pub(crate) struct Schedule<E> {
+    events: BinaryHeap<Scheduled<E>>,
+    seq: u64,
+}
+
+#[derive(Debug)]
+struct Scheduled<E> {
+    time: u64,
+    seq: u64,
+    event: E,
+}
+
+impl<E> PartialEq for Scheduled<E> {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl<E> Eq for Scheduled<E> {}
+
+impl<E> PartialOrd for Scheduled<E> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl<E> Ord for Scheduled<E> {
+    // Reversed, so the heap pops the earliest event first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        return (other.time, other.seq).cmp(&(self.time, self.seq));
+    }
+}
+
+impl<E> Schedule<E> {
+    pub(crate) fn new() -> Self {
+        return Schedule {
+            events: BinaryHeap::new(),
+            seq: 0,
+        };
+    }
+
+    pub(crate) fn push(&mut self, time: u64, event: E) {
+        self.seq += 1;
+        self.events.push(Scheduled {
+            time,
+            seq: self.seq,
+            event,
+        });
+    }
+
+    /// The time of the earliest event.
+    pub(crate) fn next_time(&self) -> Option<u64> {
+        return self.events.peek().map(|scheduled| scheduled.time);
+    }
+
+    /// Takes the earliest event, with its time.
+    pub(crate) fn pop(&mut self) -> Option<(u64, E)> {
+        return self
+            .events
+            .pop()
+            .map(|scheduled| (scheduled.time, scheduled.event));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_earliest_first_then_in_order_scheduled() {
+        let mut schedule = Schedule::new();
+        schedule.push(20, "c");
+        schedule.push(10, "a");
+        schedule.push(10, "b");
+
+        assert_eq!(schedule.next_time(), Some(10));
+        assert_eq!(schedule.pop(), Some((10, "a")));
+        assert_eq!(schedule.pop(), Some((10, "b")));
+        assert_eq!(schedule.pop(), Some((20, "c")));
+        assert_eq!(schedule.pop(), None);
+    }
+}
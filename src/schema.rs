@@ -0,0 +1,20 @@
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use std::borrow::Cow;
+
+/// The schema of a `Decimal`, which serializes as a string to keep its
+/// precision.
+pub(crate) struct DecimalString;
+
+impl JsonSchema for DecimalString {
+    fn inline_schema() -> bool {
+        return true;
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        return Cow::Borrowed("Decimal");
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        return json_schema!({"type": "string", "format": "decimal"});
+    }
+}
@@ -0,0 +1,507 @@
+use rust_decimal::prelude::*;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::clock::SystemClock;
+use crate::command::{Command, CommandResult};
+use crate::id_generator::SequentialIdGenerator;
+use crate::numeric::Numeric;
+use crate::order::{Order, Side};
+use crate::price_index::PriceIndex;
+use crate::trade::Trade;
+use crate::{OrderBook, OrderResult};
+
+/// How many levels per side `book` shows when no depth is given.
+pub const DEFAULT_DEPTH: usize = 10;
+
+pub const HELP: &str = "\
+buy <quantity> @ <price>             submit a limit order
+sell <quantity> @ <price>
+buy mkt <quantity>                   submit a market order
+sell mkt <quantity>
+cancel <id>                          cancel a resting order
+amend <id> <quantity> @ <price>      change a resting order's quantity and price
+book [depth]                         show the ladder, 10 levels per side by default
+trades                               list the trades so far
+help                                 show this message
+quit
+
+Order ids are the numbers shown as #<id>. Lines starting with # are ignored.";
+
+/// One line of a script.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Statement {
+    Order(Command),
+    Book(usize),
+    Trades,
+    Help,
+    Quit,
+}
+
+/// A line that couldn't be parsed or executed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScriptError(pub String);
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.0);
+    }
+}
+
+impl Error for ScriptError {}
+
+/// Parses a line of a script. Returns `None` for blank lines and comments.
+pub fn parse(line: &str) -> Result<Option<Statement>, ScriptError> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let statement = match words.as_slice() {
+        ["buy", rest @ ..] => parse_order(Side::Bid, rest)?,
+        ["sell", rest @ ..] => parse_order(Side::Ask, rest)?,
+        ["cancel", id] => Statement::Order(Command::Cancel { id: parse_id(id)? }),
+        ["amend", id, rest @ ..] => {
+            let (quantity, price) = parse_quantity_and_price(rest)?;
+            Statement::Order(Command::Amend {
+                id: parse_id(id)?,
+                quantity,
+                price,
+            })
+        }
+        ["book"] => Statement::Book(DEFAULT_DEPTH),
+        ["book", depth] => match depth.parse() {
+            Ok(depth) => Statement::Book(depth),
+            Err(_) => return Err(ScriptError(format!("invalid depth '{}'", depth))),
+        },
+        ["trades"] => Statement::Trades,
+        ["help"] => Statement::Help,
+        ["quit"] | ["exit"] => Statement::Quit,
+        _ => return Err(ScriptError(format!("can't parse '{}', try 'help'", line))),
+    };
+
+    return Ok(Some(statement));
+}
+
+fn parse_order(side: Side, words: &[&str]) -> Result<Statement, ScriptError> {
+    if let ["mkt", quantity] = words {
+        return Ok(Statement::Order(Command::Market {
+            side,
+            quantity: parse_decimal(quantity)?,
+        }));
+    }
+
+    let (quantity, price) = parse_quantity_and_price(words)?;
+    return Ok(Statement::Order(Command::Limit {
+        side,
+        quantity,
+        price,
+    }));
+}
+
+fn parse_quantity_and_price(words: &[&str]) -> Result<(Decimal, Decimal), ScriptError> {
+    match words {
+        [quantity, "@", price] => return Ok((parse_decimal(quantity)?, parse_decimal(price)?)),
+        _ => {
+            return Err(ScriptError(format!(
+                "expected '<quantity> @ <price>', got '{}'",
+                words.join(" ")
+            )))
+        }
+    }
+}
+
+fn parse_decimal(word: &str) -> Result<Decimal, ScriptError> {
+    match Decimal::from_str(word) {
+        Ok(value) if value.is_sign_negative() => {
+            return Err(ScriptError(format!("'{}' is negative", word)))
+        }
+        Ok(value) => return Ok(value),
+        Err(_) => return Err(ScriptError(format!("invalid number '{}'", word))),
+    }
+}
+
+/// Parses an id as printed by `format_id`: the number of a sequential id,
+/// optionally prefixed with `#`, or a full UUID.
+pub fn parse_id(word: &str) -> Result<Uuid, ScriptError> {
+    let word = word.trim_start_matches('#');
+
+    if let Ok(n) = word.parse::<u64>() {
+        return Ok(crate::id_generator::sequential_id(n));
+    }
+
+    return Uuid::parse_str(word).map_err(|_| ScriptError(format!("invalid order id '{}'", word)));
+}
+
+/// Formats an id from a `SequentialIdGenerator` as `#<n>`, and any other id
+/// as a full UUID.
+pub fn format_id(id: Uuid) -> String {
+    let bytes = id.as_bytes();
+    if bytes[..8].iter().all(|b| *b == 0) {
+        let mut n = [0u8; 8];
+        n.copy_from_slice(&bytes[8..]);
+        return format!("#{}", u64::from_be_bytes(n));
+    }
+
+    return id.to_string();
+}
+
+fn format_side(side: Side) -> &'static str {
+    match side {
+        Side::Bid => return "buy",
+        Side::Ask => return "sell",
+    }
+}
+
+fn format_order<P: Numeric, Q: Numeric>(order: &Order<P, Q>) -> String {
+    return format!(
+        "{} {} {} @ {}",
+        format_id(order.id),
+        format_side(order.side),
+        order.quantity,
+        order.price
+    );
+}
+
+/// Formats up to `depth` levels of each side of `order_book` as a price
+/// ladder: asks above bids, highest price first, with each level's volume
+/// and number of orders.
+pub fn format_ladder<P, Q, I>(order_book: &OrderBook<P, Q, I>, depth: usize) -> String
+where
+    P: Numeric,
+    Q: Numeric,
+    I: PriceIndex<P>,
+{
+    let level = |volume: Q, orders: usize| format!("{} ({})", volume, orders);
+
+    let mut asks: Vec<(String, String)> = order_book
+        .levels(Side::Ask)
+        .take(depth)
+        .map(|l| (l.price.to_string(), level(l.volume, l.len())))
+        .collect();
+    asks.reverse();
+    let bids: Vec<(String, String)> = order_book
+        .levels(Side::Bid)
+        .take(depth)
+        .map(|l| (l.price.to_string(), level(l.volume, l.len())))
+        .collect();
+
+    if asks.is_empty() && bids.is_empty() {
+        return String::from("book is empty\n");
+    }
+
+    let width = |cells: &[(String, String)], i: usize| {
+        cells
+            .iter()
+            .map(|c| if i == 0 { c.0.len() } else { c.1.len() })
+            .max()
+            .unwrap_or(0)
+    };
+    let price_width = width(&asks, 0).max(width(&bids, 0)).max("price".len());
+    let bid_width = width(&bids, 1).max("bids".len());
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:>bw$}  {:>pw$}  asks",
+        "bids",
+        "price",
+        bw = bid_width,
+        pw = price_width
+    )
+    .unwrap();
+
+    for (price, ask) in asks.iter() {
+        writeln!(
+            out,
+            "{:>bw$}  {:>pw$}  {}",
+            "",
+            price,
+            ask,
+            bw = bid_width,
+            pw = price_width
+        )
+        .unwrap();
+    }
+
+    if let (Some(bid), Some(ask)) = (order_book.best_bid(), order_book.best_ask()) {
+        writeln!(
+            out,
+            "{:>bw$}  {:>pw$}  spread {}",
+            "",
+            "",
+            ask - bid,
+            bw = bid_width,
+            pw = price_width
+        )
+        .unwrap();
+    }
+
+    for (price, bid) in bids.iter() {
+        writeln!(
+            out,
+            "{:>bw$}  {:>pw$}",
+            bid,
+            price,
+            bw = bid_width,
+            pw = price_width
+        )
+        .unwrap();
+    }
+
+    return out;
+}
+
+/// An order book driven by script statements, keeping a log of the trades
+/// it made. Order ids are sequential so scripts can refer to them.
+#[derive(Debug)]
+pub struct Session {
+    order_book: OrderBook,
+    trades: Vec<Trade>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        return Session {
+            order_book: OrderBook::with_clock(SystemClock, SequentialIdGenerator::new()),
+            trades: Vec::new(),
+        };
+    }
+
+    pub fn order_book(&self) -> &OrderBook {
+        return &self.order_book;
+    }
+
+    pub fn trades(&self) -> &[Trade] {
+        return &self.trades;
+    }
+
+    /// Parses and executes a line, returning what to print.
+    pub fn run(&mut self, line: &str) -> Result<String, ScriptError> {
+        match parse(line)? {
+            Some(statement) => return self.execute(statement),
+            None => return Ok(String::new()),
+        }
+    }
+
+    /// Executes a statement, returning what to print. `Quit` is up to the
+    /// caller and does nothing here.
+    pub fn execute(&mut self, statement: Statement) -> Result<String, ScriptError> {
+        match statement {
+            Statement::Order(command) => return self.execute_command(command),
+            Statement::Book(depth) => return Ok(format_ladder(&self.order_book, depth)),
+            Statement::Trades => return Ok(self.format_trades()),
+            Statement::Help => return Ok(format!("{}\n", HELP)),
+            Statement::Quit => return Ok(String::new()),
+        }
+    }
+
+    fn execute_command(&mut self, command: Command) -> Result<String, ScriptError> {
+        let side = match command {
+            Command::Limit { side, .. } | Command::Market { side, .. } => side,
+            Command::Cancel { id } | Command::Amend { id, .. } => match self.order_book.get(id) {
+                Some(order) => order.side,
+                None => return Err(ScriptError(format!("no order {}", format_id(id)))),
+            },
+        };
+
+        let result = match self.order_book.execute(command) {
+            CommandResult::Matched(result) => result,
+            CommandResult::Canceled(order) => {
+                return Ok(format!("canceled {}\n", format_order(&order)));
+            }
+            CommandResult::NotFound => unreachable!("the order was just found"),
+        };
+        let mut out = self.record(side, &result);
+        match command {
+            Command::Market { quantity, .. } if result.quantity_filled < quantity => {
+                writeln!(out, "{} unfilled", quantity - result.quantity_filled).unwrap();
+            }
+            Command::Amend { id, .. } if result.partial.is_none() && result.done.is_empty() => {
+                writeln!(out, "removed {}", format_id(id)).unwrap();
+            }
+            _ => {}
+        }
+        return Ok(out);
+    }
+
+    /// Logs the trades in `result` and describes them and the resting order.
+    fn record(&mut self, side: Side, result: &OrderResult) -> String {
+        let mut out = String::new();
+
+        for trade in Trade::from_result(side, result) {
+            writeln!(
+                out,
+                "traded {} @ {} with {}",
+                trade.quantity,
+                trade.price,
+                format_id(trade.maker_id)
+            )
+            .unwrap();
+            self.trades.push(trade);
+        }
+
+        if let Some(order) = result.partial {
+            writeln!(out, "resting {}", format_order(&order)).unwrap();
+        }
+
+        return out;
+    }
+
+    fn format_trades(&self) -> String {
+        if self.trades.is_empty() {
+            return String::from("no trades\n");
+        }
+
+        let mut out = String::new();
+        for (i, trade) in self.trades.iter().enumerate() {
+            writeln!(
+                out,
+                "{:>4}  {} {} @ {} against {}",
+                i + 1,
+                format_side(trade.aggressor),
+                trade.quantity,
+                trade.price,
+                format_id(trade.maker_id)
+            )
+            .unwrap();
+        }
+        return out;
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        return Session::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id_generator::sequential_id;
+    use rust_decimal_macros::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("buy 10 @ 50.00"),
+            Ok(Some(Statement::Order(Command::Limit {
+                side: Side::Bid,
+                quantity: dec!(10),
+                price: dec!(50.00),
+            })))
+        );
+        assert_eq!(
+            parse("  sell mkt 5 "),
+            Ok(Some(Statement::Order(Command::Market {
+                side: Side::Ask,
+                quantity: dec!(5),
+            })))
+        );
+        assert_eq!(
+            parse("cancel #3"),
+            Ok(Some(Statement::Order(Command::Cancel {
+                id: sequential_id(3)
+            })))
+        );
+        assert_eq!(
+            parse("amend 3 2 @ 49.5"),
+            Ok(Some(Statement::Order(Command::Amend {
+                id: sequential_id(3),
+                quantity: dec!(2),
+                price: dec!(49.5),
+            })))
+        );
+        assert_eq!(parse("book"), Ok(Some(Statement::Book(DEFAULT_DEPTH))));
+        assert_eq!(parse("book 3"), Ok(Some(Statement::Book(3))));
+        assert_eq!(parse("trades"), Ok(Some(Statement::Trades)));
+        assert_eq!(parse(""), Ok(None));
+        assert_eq!(parse("# a comment"), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("buy 10 50").is_err());
+        assert!(parse("buy ten @ 50").is_err());
+        assert!(parse("sell -1 @ 50").is_err());
+        assert!(parse("cancel abc").is_err());
+        assert!(parse("book many").is_err());
+        assert!(parse("hold").is_err());
+    }
+
+    #[test]
+    fn test_ids_round_trip() {
+        let id = sequential_id(42);
+        assert_eq!(format_id(id), "#42");
+        assert_eq!(parse_id(&format_id(id)), Ok(id));
+
+        let id = Uuid::new_v4();
+        assert_eq!(parse_id(&format_id(id)), Ok(id));
+    }
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::new();
+
+        assert_eq!(
+            session.run("sell 10 @ 50.00").unwrap(),
+            "resting #1 sell 10 @ 50.00\n"
+        );
+        assert_eq!(
+            session.run("sell 5 @ 51.00").unwrap(),
+            "resting #2 sell 5 @ 51.00\n"
+        );
+        assert_eq!(
+            session.run("buy 12 @ 51").unwrap(),
+            "traded 10 @ 50.00 with #1\ntraded 2 @ 51.00 with #2\n"
+        );
+        assert_eq!(
+            session.run("buy mkt 5").unwrap(),
+            "traded 3 @ 51.00 with #2\n2 unfilled\n"
+        );
+        assert_eq!(session.trades().len(), 3);
+
+        assert_eq!(
+            session.run("buy 1 @ 49").unwrap(),
+            "resting #3 buy 1 @ 49\n"
+        );
+        assert_eq!(
+            session.run("amend 3 4 @ 48").unwrap(),
+            "resting #3 buy 4 @ 48\n"
+        );
+        assert_eq!(session.run("amend 3 0 @ 48").unwrap(), "removed #3\n");
+        assert!(session.run("cancel 3").is_err());
+    }
+
+    #[test]
+    fn test_format_ladder() {
+        let mut session = Session::new();
+        session.run("sell 10 @ 50.00").unwrap();
+        session.run("sell 5 @ 50.00").unwrap();
+        session.run("sell 1 @ 52.00").unwrap();
+        session.run("buy 2 @ 49.50").unwrap();
+
+        let expected = concat!(
+            " bids  price  asks\n",
+            "       52.00  1 (1)\n",
+            "       50.00  15 (2)\n",
+            "              spread 0.50\n",
+            "2 (1)  49.50\n",
+        );
+        assert_eq!(session.run("book").unwrap(), expected);
+
+        let expected = concat!(
+            " bids  price  asks\n",
+            "       50.00  15 (2)\n",
+            "              spread 0.50\n",
+            "2 (1)  49.50\n",
+        );
+        assert_eq!(session.run("book 1").unwrap(), expected);
+
+        assert_eq!(Session::new().run("book").unwrap(), "book is empty\n");
+    }
+}
@@ -0,0 +1,999 @@
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::clock::{Clock, ManualClock};
+use crate::id_generator::SequentialIdGenerator;
+use crate::instrument::Instrument;
+use crate::numeric::{Lots, Ticks};
+use crate::order::Side;
+use crate::schedule::Schedule;
+use crate::trade::Trade;
+use crate::{OrderBook, OrderResult};
+
+const NANOS_PER_SECOND: f64 = 1_000_000_000.0;
+
+/// Everything about a simulation run. Times are in seconds, distances from
+/// the mid or the fundamental value in ticks, and sizes in lots.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    pub duration: f64,
+    pub tick_size: Decimal,
+    pub lot_size: Decimal,
+    /// Where the fundamental value starts, and the price quoted around until
+    /// there's a market.
+    pub initial_price: Decimal,
+    /// How often to take an L2 snapshot.
+    pub snapshot_interval: f64,
+    /// Levels per side in each snapshot.
+    pub snapshot_depth: usize,
+    pub fundamental: FundamentalConfig,
+    pub noise: Vec<NoiseConfig>,
+    pub market_makers: Vec<MarketMakerConfig>,
+    pub momentum: Vec<MomentumConfig>,
+    pub informed: Vec<InformedConfig>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        return SimulationConfig {
+            seed: 0,
+            duration: 3600.0,
+            tick_size: dec!(0.01),
+            lot_size: dec!(1),
+            initial_price: dec!(100.00),
+            snapshot_interval: 1.0,
+            snapshot_depth: 5,
+            fundamental: FundamentalConfig::default(),
+            noise: Vec::new(),
+            market_makers: Vec::new(),
+            momentum: Vec::new(),
+            informed: Vec::new(),
+        };
+    }
+}
+
+impl SimulationConfig {
+    /// Checks that sizes, intervals and rates are positive, distances aren't
+    /// negative, every number is finite and ratios are between 0 and 1, so a
+    /// run can't divide by zero or stall.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (name, value) in [
+            ("tick_size", self.tick_size),
+            ("lot_size", self.lot_size),
+            ("initial_price", self.initial_price),
+        ]
+        .iter()
+        {
+            if *value <= Decimal::zero() {
+                return Err(ConfigError(format!(
+                    "{} must be positive, not {}",
+                    name, value
+                )));
+            }
+        }
+
+        let mut positive = vec![
+            ("snapshot_interval", self.snapshot_interval),
+            ("fundamental.interval", self.fundamental.interval),
+        ];
+        let mut non_negative = vec![
+            ("duration", self.duration),
+            ("fundamental.volatility", self.fundamental.volatility),
+        ];
+        let mut ratios = Vec::new();
+        for noise in self.noise.iter() {
+            positive.push(("noise.rate", noise.rate));
+            positive.push(("noise.mean_lots", noise.mean_lots));
+            non_negative.push(("noise.mean_offset", noise.mean_offset));
+            non_negative.push(("noise.lifetime", noise.lifetime));
+            ratios.push(("noise.market_ratio", noise.market_ratio));
+        }
+        for market_maker in self.market_makers.iter() {
+            positive.push(("market_makers.interval", market_maker.interval));
+            positive.push(("market_makers.lots", market_maker.lots as f64));
+            non_negative.push(("market_makers.half_spread", market_maker.half_spread as f64));
+            non_negative.push(("market_makers.skew", market_maker.skew));
+            non_negative.push((
+                "market_makers.max_inventory",
+                market_maker.max_inventory as f64,
+            ));
+        }
+        for momentum in self.momentum.iter() {
+            positive.push(("momentum.interval", momentum.interval));
+            positive.push(("momentum.lots", momentum.lots as f64));
+            non_negative.push(("momentum.threshold", momentum.threshold));
+        }
+        for informed in self.informed.iter() {
+            positive.push(("informed.rate", informed.rate));
+            positive.push(("informed.lots", informed.lots as f64));
+            non_negative.push(("informed.edge", informed.edge));
+        }
+
+        for (name, value) in positive.iter().chain(non_negative.iter()) {
+            if !value.is_finite() {
+                return Err(ConfigError(format!(
+                    "{} must be finite, not {}",
+                    name, value
+                )));
+            }
+        }
+        for (name, value) in positive {
+            if value <= 0.0 {
+                return Err(ConfigError(format!(
+                    "{} must be positive, not {}",
+                    name, value
+                )));
+            }
+        }
+        for (name, value) in non_negative {
+            if value < 0.0 {
+                return Err(ConfigError(format!(
+                    "{} can't be negative, not {}",
+                    name, value
+                )));
+            }
+        }
+        for (name, value) in ratios {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(ConfigError(format!(
+                    "{} must be between 0 and 1, not {}",
+                    name, value
+                )));
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// A `SimulationConfig` setting that's out of range.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.0);
+    }
+}
+
+impl Error for ConfigError {}
+
+/// The value informed traders know, a random walk.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FundamentalConfig {
+    /// Standard deviation of the value's change over a second, in ticks.
+    pub volatility: f64,
+    /// How often the value moves.
+    pub interval: f64,
+}
+
+impl Default for FundamentalConfig {
+    fn default() -> Self {
+        return FundamentalConfig {
+            volatility: 1.0,
+            interval: 1.0,
+        };
+    }
+}
+
+/// Traders who send orders at random, around the mid.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoiseConfig {
+    pub count: usize,
+    /// Orders per second, per trader.
+    pub rate: f64,
+    pub mean_lots: f64,
+    /// Fraction of orders sent as market orders.
+    pub market_ratio: f64,
+    /// Mean distance of limit orders from the mid.
+    pub mean_offset: f64,
+    /// How long limit orders rest before being canceled.
+    pub lifetime: f64,
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        return NoiseConfig {
+            count: 10,
+            rate: 1.0,
+            mean_lots: 5.0,
+            market_ratio: 0.1,
+            mean_offset: 5.0,
+            lifetime: 30.0,
+        };
+    }
+}
+
+/// Traders who keep a quote on both sides of the mid, leaning it against
+/// their inventory.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarketMakerConfig {
+    pub count: usize,
+    /// How often quotes are replaced.
+    pub interval: f64,
+    pub half_spread: i64,
+    pub lots: u64,
+    /// Ticks to shift the quotes by per lot of inventory.
+    pub skew: f64,
+    /// Stops quoting the side that would grow a position past this.
+    pub max_inventory: i64,
+}
+
+impl Default for MarketMakerConfig {
+    fn default() -> Self {
+        return MarketMakerConfig {
+            count: 2,
+            interval: 1.0,
+            half_spread: 2,
+            lots: 10,
+            skew: 0.1,
+            max_inventory: 100,
+        };
+    }
+}
+
+/// Traders who follow moves in the mid with market orders.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MomentumConfig {
+    pub count: usize,
+    /// How often the mid is looked at.
+    pub interval: f64,
+    /// How many looks back a move is measured over.
+    pub lookback: usize,
+    /// Smallest move that's followed.
+    pub threshold: f64,
+    pub lots: u64,
+}
+
+impl Default for MomentumConfig {
+    fn default() -> Self {
+        return MomentumConfig {
+            count: 2,
+            interval: 5.0,
+            lookback: 6,
+            threshold: 5.0,
+            lots: 5,
+        };
+    }
+}
+
+/// Traders who know the fundamental value and take prices far enough from
+/// it.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InformedConfig {
+    pub count: usize,
+    /// Looks per second, per trader.
+    pub rate: f64,
+    /// How far the best price has to be from the value to trade.
+    pub edge: f64,
+    pub lots: u64,
+}
+
+impl Default for InformedConfig {
+    fn default() -> Self {
+        return InformedConfig {
+            count: 1,
+            rate: 0.2,
+            edge: 3.0,
+            lots: 5,
+        };
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AgentKind {
+    Noise,
+    MarketMaker,
+    Momentum,
+    Informed,
+}
+
+/// The book's depth at a point in time, best price first on each side.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Snapshot {
+    pub timestamp: u64,
+    pub bids: Vec<(Ticks, Lots)>,
+    pub asks: Vec<(Ticks, Lots)>,
+}
+
+/// What a population of agents did over the run.
+#[derive(Clone, Debug)]
+pub struct PopulationSummary {
+    pub kind: AgentKind,
+    pub agents: usize,
+    pub volume: Decimal,
+    pub position: Decimal,
+    /// Marked at the last trade.
+    pub pnl: Decimal,
+}
+
+#[derive(Clone, Debug)]
+pub struct Summary {
+    pub trades: usize,
+    pub volume: Decimal,
+    pub first_price: Option<Decimal>,
+    pub last_price: Option<Decimal>,
+    pub fundamental: Decimal,
+    /// Standard deviation of the log return of the mid between snapshots.
+    pub volatility: f64,
+    /// Mean spread over the snapshots with both sides.
+    pub mean_spread: Option<Decimal>,
+    /// Mean quantity in each side's snapshot levels.
+    pub mean_bid_depth: Decimal,
+    pub mean_ask_depth: Decimal,
+    pub populations: Vec<PopulationSummary>,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Account {
+    /// Lots bought minus lots sold.
+    position: i64,
+    /// In tick-lots: received for sales minus paid for purchases.
+    cash: i128,
+    volume: u64,
+}
+
+/// The book and everything agents can see or do to it.
+#[derive(Debug)]
+struct Market {
+    order_book: OrderBook<Ticks, Lots>,
+    clock: ManualClock,
+    owners: HashMap<Uuid, usize>,
+    accounts: Vec<Account>,
+    trades: Vec<(u64, Trade<Ticks, Lots>)>,
+    last_price: Ticks,
+    /// In ticks.
+    fundamental: f64,
+}
+
+impl Market {
+    fn submit_limit_order(
+        &mut self,
+        agent: usize,
+        side: Side,
+        lots: u64,
+        price: i64,
+    ) -> Option<Uuid> {
+        let result = self
+            .order_book
+            .submit_limit_order(side, Lots(lots), Ticks(price));
+        self.record(agent, side, &result);
+
+        let id = result.partial?.id;
+        self.owners.insert(id, agent);
+        return Some(id);
+    }
+
+    fn submit_market_order(&mut self, agent: usize, side: Side, lots: u64) {
+        let result = self.order_book.submit_market_order(side, Lots(lots));
+        self.record(agent, side, &result);
+    }
+
+    fn cancel(&mut self, id: Uuid) {
+        if self.order_book.remove(id).is_some() {
+            self.owners.remove(&id);
+        }
+    }
+
+    fn best_bid(&self) -> Option<i64> {
+        return self.order_book.best_bid().map(|p| p.0);
+    }
+
+    fn best_ask(&self) -> Option<i64> {
+        return self.order_book.best_ask().map(|p| p.0);
+    }
+
+    /// The mid in ticks, or the last trade if a side is empty.
+    fn mid(&self) -> f64 {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => return (bid + ask) as f64 / 2.0,
+            _ => return self.last_price.0 as f64,
+        }
+    }
+
+    fn position(&self, agent: usize) -> i64 {
+        return self.accounts[agent].position;
+    }
+
+    fn record(&mut self, agent: usize, side: Side, result: &OrderResult<Ticks, Lots>) {
+        let now = self.clock.now();
+
+        for trade in Trade::from_result(side, result) {
+            let maker = match self.order_book.get(trade.maker_id) {
+                Some(_) => self.owners.get(&trade.maker_id).copied(),
+                None => self.owners.remove(&trade.maker_id),
+            };
+
+            let quantity = trade.quantity.0 as i64;
+            let notional = trade.price.0 as i128 * quantity as i128;
+            let (buyer, seller) = match side {
+                Side::Bid => (Some(agent), maker),
+                Side::Ask => (maker, Some(agent)),
+            };
+            if let Some(buyer) = buyer {
+                self.accounts[buyer].position += quantity;
+                self.accounts[buyer].cash -= notional;
+                self.accounts[buyer].volume += trade.quantity.0;
+            }
+            if let Some(seller) = seller {
+                self.accounts[seller].position -= quantity;
+                self.accounts[seller].cash += notional;
+                self.accounts[seller].volume += trade.quantity.0;
+            }
+
+            self.last_price = trade.price;
+            self.trades.push((now, trade));
+        }
+    }
+}
+
+trait Agent {
+    /// Acts on the market, returning how many nanoseconds until it wants to
+    /// act again.
+    fn act(&mut self, id: usize, market: &mut Market, rng: &mut ChaCha8Rng) -> u64;
+}
+
+fn exponential(rng: &mut ChaCha8Rng, mean: f64) -> f64 {
+    return -mean * (1.0 - rng.gen::<f64>()).ln();
+}
+
+fn seconds(seconds: f64) -> u64 {
+    return (seconds * NANOS_PER_SECOND).max(1.0) as u64;
+}
+
+/// A fixed interval, jittered by up to half of it either way so agents with
+/// the same interval don't act in lockstep.
+fn jittered(rng: &mut ChaCha8Rng, interval: f64) -> u64 {
+    return seconds(interval * rng.gen_range(0.5..1.5));
+}
+
+fn random_side(rng: &mut ChaCha8Rng) -> Side {
+    if rng.gen_bool(0.5) {
+        return Side::Bid;
+    }
+    return Side::Ask;
+}
+
+struct NoiseTrader {
+    config: NoiseConfig,
+    /// Resting orders and when to cancel them, oldest first.
+    orders: VecDeque<(u64, Uuid)>,
+}
+
+impl Agent for NoiseTrader {
+    fn act(&mut self, id: usize, market: &mut Market, rng: &mut ChaCha8Rng) -> u64 {
+        let now = market.clock.now();
+        while let Some(&(expiry, order)) = self.orders.front() {
+            if expiry > now {
+                break;
+            }
+            market.cancel(order);
+            self.orders.pop_front();
+        }
+
+        let side = random_side(rng);
+        let lots = 1 + exponential(rng, (self.config.mean_lots - 1.0).max(0.0)).round() as u64;
+
+        if rng.gen_bool(self.config.market_ratio) {
+            market.submit_market_order(id, side, lots);
+        } else {
+            let offset = exponential(rng, self.config.mean_offset);
+            let price = match side {
+                Side::Bid => (market.mid() - offset).floor(),
+                Side::Ask => (market.mid() + offset).ceil(),
+            };
+            let expiry = now + seconds(self.config.lifetime);
+            if let Some(order) = market.submit_limit_order(id, side, lots, price as i64) {
+                self.orders.push_back((expiry, order));
+            }
+        }
+
+        return seconds(exponential(rng, 1.0 / self.config.rate));
+    }
+}
+
+struct MarketMaker {
+    config: MarketMakerConfig,
+    quotes: Vec<Uuid>,
+}
+
+impl Agent for MarketMaker {
+    fn act(&mut self, id: usize, market: &mut Market, rng: &mut ChaCha8Rng) -> u64 {
+        for quote in self.quotes.drain(..) {
+            market.cancel(quote);
+        }
+
+        let position = market.position(id);
+        let center = market.mid() - position as f64 * self.config.skew;
+        let bid = (center - self.config.half_spread as f64).floor() as i64;
+        let ask = ((center + self.config.half_spread as f64).ceil() as i64).max(bid + 1);
+
+        if position < self.config.max_inventory {
+            self.quotes
+                .extend(market.submit_limit_order(id, Side::Bid, self.config.lots, bid));
+        }
+        if position > -self.config.max_inventory {
+            self.quotes
+                .extend(market.submit_limit_order(id, Side::Ask, self.config.lots, ask));
+        }
+
+        return jittered(rng, self.config.interval);
+    }
+}
+
+struct MomentumTrader {
+    config: MomentumConfig,
+    mids: VecDeque<f64>,
+}
+
+impl Agent for MomentumTrader {
+    fn act(&mut self, id: usize, market: &mut Market, rng: &mut ChaCha8Rng) -> u64 {
+        let mid = market.mid();
+        self.mids.push_back(mid);
+
+        if self.mids.len() > self.config.lookback {
+            let change = mid - self.mids.pop_front().unwrap();
+            if change >= self.config.threshold {
+                market.submit_market_order(id, Side::Bid, self.config.lots);
+            } else if change <= -self.config.threshold {
+                market.submit_market_order(id, Side::Ask, self.config.lots);
+            }
+        }
+
+        return jittered(rng, self.config.interval);
+    }
+}
+
+struct InformedTrader {
+    config: InformedConfig,
+}
+
+impl Agent for InformedTrader {
+    fn act(&mut self, id: usize, market: &mut Market, rng: &mut ChaCha8Rng) -> u64 {
+        let value = market.fundamental;
+
+        // Takes what's at the best price and no more: anything left over is
+        // canceled rather than left resting.
+        if let Some(ask) = market.best_ask() {
+            if (ask as f64) < value - self.config.edge {
+                if let Some(order) = market.submit_limit_order(id, Side::Bid, self.config.lots, ask)
+                {
+                    market.cancel(order);
+                }
+            }
+        }
+        if let Some(bid) = market.best_bid() {
+            if (bid as f64) > value + self.config.edge {
+                if let Some(order) = market.submit_limit_order(id, Side::Ask, self.config.lots, bid)
+                {
+                    market.cancel(order);
+                }
+            }
+        }
+
+        return seconds(exponential(rng, 1.0 / self.config.rate));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Wakeup {
+    Agent(usize),
+    Fundamental,
+    Snapshot,
+}
+
+/// Populations of agents trading on one book on a simulated clock.
+///
+/// All randomness comes from generators seeded from `SimulationConfig::seed`,
+/// and the book assigns sequential ids, so a run is reproducible from its
+/// config.
+pub struct Simulation {
+    config: SimulationConfig,
+    instrument: Instrument,
+    market: Market,
+    agents: Vec<(AgentKind, Box<dyn Agent>, ChaCha8Rng)>,
+    rng: ChaCha8Rng,
+    wakeups: Schedule<Wakeup>,
+    snapshots: Vec<Snapshot>,
+}
+
+impl Simulation {
+    pub fn new(config: SimulationConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        let instrument = Instrument::new(config.tick_size, config.lot_size);
+        let initial_price = (config.initial_price / config.tick_size)
+            .round()
+            .to_i64()
+            .unwrap_or(0);
+        let clock = ManualClock::new(0);
+        let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+
+        let mut agents: Vec<(AgentKind, Box<dyn Agent>, ChaCha8Rng)> = Vec::new();
+        let mut add = |kind, agent: Box<dyn Agent>| {
+            let agent_rng = ChaCha8Rng::seed_from_u64(rng.gen());
+            agents.push((kind, agent, agent_rng));
+        };
+        for population in config.market_makers.iter() {
+            for _ in 0..population.count {
+                add(
+                    AgentKind::MarketMaker,
+                    Box::new(MarketMaker {
+                        config: *population,
+                        quotes: Vec::new(),
+                    }),
+                );
+            }
+        }
+        for population in config.noise.iter() {
+            for _ in 0..population.count {
+                add(
+                    AgentKind::Noise,
+                    Box::new(NoiseTrader {
+                        config: *population,
+                        orders: VecDeque::new(),
+                    }),
+                );
+            }
+        }
+        for population in config.momentum.iter() {
+            for _ in 0..population.count {
+                add(
+                    AgentKind::Momentum,
+                    Box::new(MomentumTrader {
+                        config: *population,
+                        mids: VecDeque::new(),
+                    }),
+                );
+            }
+        }
+        for population in config.informed.iter() {
+            for _ in 0..population.count {
+                add(
+                    AgentKind::Informed,
+                    Box::new(InformedTrader {
+                        config: *population,
+                    }),
+                );
+            }
+        }
+
+        let market = Market {
+            order_book: OrderBook::with_clock(clock.clone(), SequentialIdGenerator::new()),
+            clock,
+            owners: HashMap::new(),
+            accounts: vec![Account::default(); agents.len()],
+            trades: Vec::new(),
+            last_price: Ticks(initial_price),
+            fundamental: initial_price as f64,
+        };
+
+        let mut simulation = Simulation {
+            config,
+            instrument,
+            market,
+            agents,
+            rng,
+            wakeups: Schedule::new(),
+            snapshots: Vec::new(),
+        };
+
+        for agent in 0..simulation.agents.len() {
+            simulation.schedule(0, Wakeup::Agent(agent));
+        }
+        simulation.schedule(0, Wakeup::Fundamental);
+        simulation.schedule(0, Wakeup::Snapshot);
+
+        return Ok(simulation);
+    }
+
+    pub fn instrument(&self) -> Instrument {
+        return self.instrument;
+    }
+
+    pub fn order_book(&self) -> &OrderBook<Ticks, Lots> {
+        return &self.market.order_book;
+    }
+
+    /// Every trade so far with the time it happened.
+    pub fn trades(&self) -> &[(u64, Trade<Ticks, Lots>)] {
+        return &self.market.trades;
+    }
+
+    pub fn snapshots(&self) -> &[Snapshot] {
+        return &self.snapshots;
+    }
+
+    /// Runs until the configured duration has passed.
+    pub fn run(&mut self) {
+        let end = seconds(self.config.duration);
+
+        while let Some(next) = self.wakeups.next_time() {
+            if next > end {
+                break;
+            }
+
+            let (next, wakeup) = self.wakeups.pop().unwrap();
+            self.market.clock.set(next);
+
+            match wakeup {
+                Wakeup::Agent(agent) => {
+                    let (_, agent_impl, rng) = &mut self.agents[agent];
+                    let delay = agent_impl.act(agent, &mut self.market, rng);
+                    self.schedule(delay, Wakeup::Agent(agent));
+                }
+                Wakeup::Fundamental => {
+                    let interval = self.config.fundamental.interval;
+                    // Uniform steps scaled to the configured standard deviation.
+                    let step = self.config.fundamental.volatility
+                        * interval.sqrt()
+                        * 3f64.sqrt()
+                        * self.rng.gen_range(-1.0..1.0);
+                    self.market.fundamental += step;
+                    self.schedule(seconds(interval), Wakeup::Fundamental);
+                }
+                Wakeup::Snapshot => {
+                    self.snapshot();
+                    self.schedule(seconds(self.config.snapshot_interval), Wakeup::Snapshot);
+                }
+            }
+        }
+    }
+
+    fn schedule(&mut self, delay: u64, wakeup: Wakeup) {
+        let time = self.market.clock.now().saturating_add(delay);
+        self.wakeups.push(time, wakeup);
+    }
+
+    fn snapshot(&mut self) {
+        let depth = self.config.snapshot_depth;
+        let order_book = &self.market.order_book;
+        let levels = |side| {
+            order_book
+                .levels(side)
+                .take(depth)
+                .map(|l| (l.price, l.volume))
+                .collect()
+        };
+
+        self.snapshots.push(Snapshot {
+            timestamp: self.market.clock.now(),
+            bids: levels(Side::Bid),
+            asks: levels(Side::Ask),
+        });
+    }
+
+    pub fn summary(&self) -> Summary {
+        let instrument = self.instrument;
+        let price = |ticks: i64| instrument.from_ticks(Ticks(ticks));
+        let mut volume = 0;
+        for (_, trade) in self.market.trades.iter() {
+            volume += trade.quantity.0;
+        }
+
+        let mut returns = Vec::new();
+        let mut spreads = Vec::new();
+        let mut bid_depth = 0;
+        let mut ask_depth = 0;
+        let mut previous_mid: Option<f64> = None;
+        for snapshot in self.snapshots.iter() {
+            bid_depth += snapshot.bids.iter().map(|l| l.1 .0).sum::<u64>();
+            ask_depth += snapshot.asks.iter().map(|l| l.1 .0).sum::<u64>();
+
+            if let (Some(bid), Some(ask)) = (snapshot.bids.first(), snapshot.asks.first()) {
+                spreads.push(ask.0 .0 - bid.0 .0);
+
+                let mid = (bid.0 .0 + ask.0 .0) as f64 / 2.0;
+                if let Some(previous) = previous_mid {
+                    returns.push((mid / previous).ln());
+                }
+                previous_mid = Some(mid);
+            }
+        }
+
+        let volatility = if returns.len() > 1 {
+            let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+            let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>()
+                / (returns.len() - 1) as f64;
+            variance.sqrt()
+        } else {
+            0.0
+        };
+
+        let snapshots = Decimal::from(self.snapshots.len().max(1) as u64);
+        let mark = self.market.last_price.0 as i128;
+        let tick_lot = instrument.tick_size * instrument.lot_size;
+
+        let mut populations: Vec<PopulationSummary> = Vec::new();
+        for (agent, (kind, _, _)) in self.agents.iter().enumerate() {
+            let account = self.market.accounts[agent];
+            let pnl = tick_lot
+                * Decimal::from_i128(account.cash + account.position as i128 * mark).unwrap();
+
+            let population = match populations.iter_mut().find(|p| p.kind == *kind) {
+                Some(population) => population,
+                None => {
+                    populations.push(PopulationSummary {
+                        kind: *kind,
+                        agents: 0,
+                        volume: Decimal::zero(),
+                        position: Decimal::zero(),
+                        pnl: Decimal::zero(),
+                    });
+                    populations.last_mut().unwrap()
+                }
+            };
+            population.agents += 1;
+            population.volume += instrument.from_lots(Lots(account.volume));
+            population.position += Decimal::from(account.position) * instrument.lot_size;
+            population.pnl += pnl;
+        }
+
+        return Summary {
+            trades: self.market.trades.len(),
+            volume: instrument.from_lots(Lots(volume)),
+            first_price: self.market.trades.first().map(|(_, t)| price(t.price.0)),
+            last_price: self.market.trades.last().map(|(_, t)| price(t.price.0)),
+            fundamental: price(self.market.fundamental.round() as i64),
+            volatility,
+            mean_spread: match spreads.len() {
+                0 => None,
+                n => Some(price(spreads.iter().sum::<i64>()) / Decimal::from(n as u64)),
+            },
+            mean_bid_depth: instrument.from_lots(Lots(bid_depth)) / snapshots,
+            mean_ask_depth: instrument.from_lots(Lots(ask_depth)) / snapshots,
+            populations,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(seed: u64) -> SimulationConfig {
+        return SimulationConfig {
+            seed,
+            duration: 120.0,
+            noise: vec![NoiseConfig::default()],
+            market_makers: vec![MarketMakerConfig::default()],
+            momentum: vec![MomentumConfig::default()],
+            informed: vec![InformedConfig::default()],
+            ..SimulationConfig::default()
+        };
+    }
+
+    #[test]
+    fn test_rejects_invalid_config() {
+        let zero_tick = SimulationConfig {
+            tick_size: Decimal::zero(),
+            ..config(1)
+        };
+        assert!(Simulation::new(zero_tick).is_err());
+
+        let mut bad_ratio = config(1);
+        bad_ratio.noise[0].market_ratio = 1.5;
+        let error = Simulation::new(bad_ratio).err().unwrap();
+        assert_eq!(
+            error.0,
+            "noise.market_ratio must be between 0 and 1, not 1.5"
+        );
+
+        let negative_lots = SimulationConfig {
+            lot_size: dec!(-1),
+            ..config(1)
+        };
+        assert!(Simulation::new(negative_lots).is_err());
+
+        let mut negative_spread = config(1);
+        negative_spread.market_makers[0].half_spread = -1;
+        let error = Simulation::new(negative_spread).err().unwrap();
+        assert_eq!(
+            error.0,
+            "market_makers.half_spread can't be negative, not -1"
+        );
+
+        let mut no_lots = config(1);
+        no_lots.market_makers[0].lots = 0;
+        let error = Simulation::new(no_lots).err().unwrap();
+        assert_eq!(error.0, "market_makers.lots must be positive, not 0");
+
+        let mut no_edge = config(1);
+        no_edge.informed[0].edge = -0.5;
+        assert!(Simulation::new(no_edge).is_err());
+
+        let mut no_momentum_lots = config(1);
+        no_momentum_lots.momentum[0].lots = 0;
+        assert!(Simulation::new(no_momentum_lots).is_err());
+
+        for duration in [f64::NAN, f64::INFINITY] {
+            let forever = SimulationConfig {
+                duration,
+                ..config(1)
+            };
+            let error = Simulation::new(forever).err().unwrap();
+            assert_eq!(
+                error.0,
+                format!("duration must be finite, not {}", duration)
+            );
+        }
+    }
+
+    #[test]
+    fn test_runs_are_reproducible() {
+        let mut first = Simulation::new(config(7)).unwrap();
+        let mut second = Simulation::new(config(7)).unwrap();
+        let mut other = Simulation::new(config(8)).unwrap();
+
+        first.run();
+        second.run();
+        other.run();
+
+        assert!(!first.trades().is_empty());
+        assert_eq!(first.trades(), second.trades());
+        assert_eq!(first.snapshots(), second.snapshots());
+        assert_ne!(first.trades(), other.trades());
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut simulation = Simulation::new(config(1)).unwrap();
+        simulation.market.order_book.set_invariant_checks(true);
+        simulation.run();
+
+        let summary = simulation.summary();
+
+        // One snapshot a second, including one at the start
+        assert_eq!(simulation.snapshots().len(), 121);
+        assert_eq!(summary.trades, simulation.trades().len());
+        assert!(summary.volume > Decimal::zero());
+        assert!(summary.mean_spread.unwrap() > Decimal::zero());
+
+        // Every trade has a buyer and a seller among the agents
+        let position: Decimal = summary.populations.iter().map(|p| p.position).sum();
+        let pnl: Decimal = summary.populations.iter().map(|p| p.pnl).sum();
+        assert_eq!(position, Decimal::zero());
+        assert_eq!(pnl, Decimal::zero());
+        assert_eq!(summary.populations.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config: SimulationConfig = toml::from_str(
+            r#"
+            seed = 3
+            duration = 60
+            tick_size = "0.05"
+
+            [[noise]]
+            count = 4
+            rate = 2.0
+
+            [[market_makers]]
+            half_spread = 1
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.seed, 3);
+        assert_eq!(config.tick_size, dec!(0.05));
+        assert_eq!(config.noise[0].count, 4);
+        assert_eq!(config.noise[0].lifetime, NoiseConfig::default().lifetime);
+        assert_eq!(config.market_makers[0].half_spread, 1);
+        assert!(config.momentum.is_empty());
+
+        assert!(toml::from_str::<SimulationConfig>("speed = 1").is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::numeric::Numeric;
+use crate::order::Side;
+use crate::OrderResult;
+
+/// A match between an incoming order and an order resting on the book.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Trade<P = Decimal, Q = Decimal> {
+    /// The side of the incoming order.
+    pub aggressor: Side,
+    /// The id of the resting order that was filled.
+    pub maker_id: Uuid,
+    pub price: P,
+    pub quantity: Q,
+}
+
+/// A trade as the market data and order gateways publish it, stamped with
+/// when it happened instead of which order it filled.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct TradeReport {
+    /// Nanoseconds since the Unix epoch, as reported by the book's `Clock`.
+    pub timestamp: u64,
+    /// The side of the incoming order.
+    pub aggressor: Side,
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::DecimalString"))]
+    pub price: Decimal,
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::schema::DecimalString"))]
+    pub quantity: Decimal,
+}
+
+impl<P: Numeric, Q: Numeric> Trade<P, Q> {
+    /// Returns the trades in `result` for an incoming order on `side`, in the
+    /// order they happened.
+    pub fn from_result(side: Side, result: &OrderResult<P, Q>) -> Vec<Self> {
+        return result
+            .done
+            .iter()
+            .map(|fill| Trade {
+                aggressor: side,
+                maker_id: fill.order_id,
+                price: fill.price,
+                quantity: fill.quantity,
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OrderBook;
+    use rust_decimal_macros::*;
+
+    #[test]
+    fn test_from_result() {
+        let mut order_book = OrderBook::new();
+
+        let first = order_book.submit_limit_order(Side::Ask, dec!(2), dec!(10));
+        let second = order_book.submit_limit_order(Side::Ask, dec!(2), dec!(11));
+        let result = order_book.submit_market_order(Side::Bid, dec!(3));
+
+        let trades = Trade::from_result(Side::Bid, &result);
+
+        assert_eq!(
+            trades,
+            vec![
+                Trade {
+                    aggressor: Side::Bid,
+                    maker_id: first.partial.unwrap().id,
+                    price: dec!(10),
+                    quantity: dec!(2),
+                },
+                Trade {
+                    aggressor: Side::Bid,
+                    maker_id: second.partial.unwrap().id,
+                    price: dec!(11),
+                    quantity: dec!(1),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,594 @@
+use rust_decimal::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use uuid::Uuid;
+
+use crate::book_event::BookEvent;
+use crate::order::Side;
+use crate::ws::protocol::{
+    Bbo, Channel, Execution, Level, Request, Response, Snapshot, TradeReport, Update,
+};
+use crate::{FillStatus, OrderBook};
+
+/// How many trades a Trades snapshot goes back.
+const RECENT_TRADES: usize = 100;
+
+const CHANNELS: [Channel; 4] = [Channel::L2, Channel::L3, Channel::Trades, Channel::Bbo];
+
+#[derive(Debug)]
+struct Market {
+    order_book: OrderBook,
+    /// The connection that submitted each resting order.
+    owners: HashMap<Uuid, u64>,
+    subscribers: BTreeMap<Channel, BTreeSet<u64>>,
+    /// The sequence number of each channel's last update.
+    sequences: BTreeMap<Channel, u64>,
+    trades: VecDeque<TradeReport>,
+    bbo: Bbo,
+}
+
+/// Serves market data and takes orders for a set of symbols, each with its
+/// own `OrderBook`, on behalf of numbered client connections.
+///
+/// Subscribing to a channel sends a snapshot, followed by an update each
+/// time a request changes what the channel shows. Orders belong to the
+/// connection that submitted them: only it can cancel them, it hears when
+/// they trade, and they're canceled when it's gone.
+#[derive(Debug, Default)]
+pub struct Hub {
+    markets: BTreeMap<String, Market>,
+}
+
+impl Hub {
+    pub fn new() -> Self {
+        return Hub::default();
+    }
+
+    /// Adds a symbol to trade on `order_book`, turning on its event
+    /// recording.
+    pub fn add_market(&mut self, symbol: &str, mut order_book: OrderBook) {
+        order_book.set_event_recording(true);
+        order_book.take_events();
+        let bbo = bbo(&order_book);
+        let market = Market {
+            order_book,
+            owners: HashMap::new(),
+            subscribers: BTreeMap::new(),
+            sequences: CHANNELS.iter().map(|&channel| (channel, 0)).collect(),
+            trades: VecDeque::new(),
+            bbo,
+        };
+        self.markets.insert(symbol.to_string(), market);
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        return self.markets.keys().map(|symbol| symbol.as_str());
+    }
+
+    pub fn order_book(&self, symbol: &str) -> Option<&OrderBook> {
+        return self.markets.get(symbol).map(|market| &market.order_book);
+    }
+
+    /// Handles a request from `connection`. Returns the responses to send
+    /// and the connections to send them to.
+    pub fn handle(&mut self, connection: u64, request: Request) -> Vec<(u64, Response)> {
+        let symbol = match &request {
+            Request::Subscribe { symbol, .. }
+            | Request::Unsubscribe { symbol, .. }
+            | Request::Submit { symbol, .. }
+            | Request::Cancel { symbol, .. } => symbol.clone(),
+        };
+        let market = match self.markets.get_mut(&symbol) {
+            Some(market) => market,
+            None => return vec![(connection, error(format!("unknown symbol {}", symbol)))],
+        };
+
+        let mut responses = Vec::new();
+        match request {
+            Request::Subscribe { channel, .. } => {
+                market
+                    .subscribers
+                    .entry(channel)
+                    .or_default()
+                    .insert(connection);
+                let snapshot = Response::Snapshot {
+                    symbol: symbol.clone(),
+                    sequence: market.sequences[&channel],
+                    data: market.snapshot(channel),
+                };
+                responses.push((connection, snapshot));
+            }
+            Request::Unsubscribe { channel, .. } => {
+                if let Some(subscribers) = market.subscribers.get_mut(&channel) {
+                    subscribers.remove(&connection);
+                }
+                let unsubscribed = Response::Unsubscribed {
+                    symbol: symbol.clone(),
+                    channel,
+                };
+                responses.push((connection, unsubscribed));
+            }
+            Request::Submit {
+                side,
+                quantity,
+                price,
+                client_id,
+                ..
+            } => {
+                responses = market.submit(connection, &symbol, side, quantity, price, client_id);
+            }
+            Request::Cancel { id, .. } => {
+                if market.owners.get(&id) != Some(&connection) {
+                    return vec![(connection, error(format!("unknown order {}", id)))];
+                }
+                market.owners.remove(&id);
+                let order = market.order_book.remove(id).unwrap();
+                let canceled = Response::Canceled {
+                    symbol: symbol.clone(),
+                    id,
+                    quantity: order.quantity,
+                };
+                responses.push((connection, canceled));
+            }
+        }
+
+        market.publish(&symbol, &mut responses);
+        return responses;
+    }
+
+    /// Drops a connection's subscriptions and cancels its orders. Returns
+    /// the updates that follow for everyone else.
+    pub fn disconnected(&mut self, connection: u64) -> Vec<(u64, Response)> {
+        let mut responses = Vec::new();
+        for (symbol, market) in self.markets.iter_mut() {
+            for subscribers in market.subscribers.values_mut() {
+                subscribers.remove(&connection);
+            }
+            let ids: Vec<Uuid> = market
+                .owners
+                .iter()
+                .filter(|(_, owner)| **owner == connection)
+                .map(|(id, _)| *id)
+                .collect();
+            for id in ids {
+                market.owners.remove(&id);
+                market.order_book.remove(id);
+            }
+            market.publish(symbol, &mut responses);
+        }
+        return responses;
+    }
+}
+
+impl Market {
+    fn snapshot(&self, channel: Channel) -> Snapshot {
+        match channel {
+            Channel::L2 => {
+                return Snapshot::L2 {
+                    bids: self.levels(Side::Bid),
+                    asks: self.levels(Side::Ask),
+                }
+            }
+            Channel::L3 => {
+                let orders = |side| {
+                    return self
+                        .order_book
+                        .levels(side)
+                        .flat_map(|level| self.order_book.orders_at(side, level.price))
+                        .copied()
+                        .collect();
+                };
+                return Snapshot::L3 {
+                    bids: orders(Side::Bid),
+                    asks: orders(Side::Ask),
+                };
+            }
+            Channel::Trades => return Snapshot::Trades(self.trades.iter().copied().collect()),
+            Channel::Bbo => return Snapshot::Bbo(self.bbo),
+        }
+    }
+
+    fn levels(&self, side: Side) -> Vec<Level> {
+        return self
+            .order_book
+            .levels(side)
+            .map(|level| Level {
+                price: level.price,
+                quantity: level.volume,
+            })
+            .collect();
+    }
+
+    /// Submits an order. Returns the response to the submitter, followed by
+    /// fills for the owners of the resting orders it traded with.
+    fn submit(
+        &mut self,
+        connection: u64,
+        symbol: &str,
+        side: Side,
+        quantity: Decimal,
+        price: Option<Decimal>,
+        client_id: Option<String>,
+    ) -> Vec<(u64, Response)> {
+        if quantity <= Decimal::zero() {
+            return vec![(connection, error(String::from("quantity must be positive")))];
+        }
+        if price.is_some_and(|price| price <= Decimal::zero()) {
+            return vec![(connection, error(String::from("price must be positive")))];
+        }
+
+        let result = match price {
+            Some(price) => self.order_book.submit_limit_order(side, quantity, price),
+            None => self.order_book.submit_market_order(side, quantity),
+        };
+        let mut responses = Vec::new();
+        for fill in result.done.iter() {
+            let owner = match self.owners.get(&fill.order_id) {
+                Some(owner) => *owner,
+                None => continue,
+            };
+            let remaining = match fill.status {
+                FillStatus::Full => {
+                    self.owners.remove(&fill.order_id);
+                    Decimal::zero()
+                }
+                FillStatus::Partial => self.order_book.get(fill.order_id).unwrap().quantity,
+            };
+            let filled = Response::Filled {
+                symbol: symbol.to_string(),
+                id: fill.order_id,
+                price: fill.price,
+                quantity: fill.quantity,
+                remaining,
+            };
+            responses.push((owner, filled));
+        }
+
+        if let Some(order) = result.partial {
+            self.owners.insert(order.id, connection);
+        }
+        let submitted = Response::Submitted {
+            symbol: symbol.to_string(),
+            client_id,
+            id: result.partial.map(|order| order.id),
+            side,
+            filled: result.quantity_filled,
+            resting: result
+                .partial
+                .map_or(Decimal::zero(), |order| order.quantity),
+            fills: result
+                .done
+                .iter()
+                .map(|fill| Execution {
+                    price: fill.price,
+                    quantity: fill.quantity,
+                })
+                .collect(),
+        };
+        responses.insert(0, (connection, submitted));
+        return responses;
+    }
+
+    /// Sends an update on each channel the book's latest events changed.
+    fn publish(&mut self, symbol: &str, responses: &mut Vec<(u64, Response)>) {
+        let events = self.order_book.take_events();
+        if events.is_empty() {
+            return;
+        }
+
+        let mut changed_bids = BTreeSet::new();
+        let mut changed_asks = BTreeSet::new();
+        let mut trades = Vec::new();
+        for (timestamp, event) in events.iter() {
+            match event.side() {
+                Side::Bid => changed_bids.insert(event.price()),
+                Side::Ask => changed_asks.insert(event.price()),
+            };
+            if let BookEvent::Execute {
+                side,
+                price,
+                quantity,
+                ..
+            } = *event
+            {
+                trades.push(TradeReport {
+                    timestamp: *timestamp,
+                    aggressor: match side {
+                        Side::Bid => Side::Ask,
+                        Side::Ask => Side::Bid,
+                    },
+                    price,
+                    quantity,
+                });
+            }
+        }
+
+        let level = |side, price| Level {
+            price,
+            quantity: self
+                .order_book
+                .orders_at(side, price)
+                .map(|order| order.quantity)
+                .sum(),
+        };
+        let bids = changed_bids
+            .into_iter()
+            .rev()
+            .map(|price| level(Side::Bid, price))
+            .collect();
+        let asks = changed_asks
+            .into_iter()
+            .map(|price| level(Side::Ask, price))
+            .collect();
+
+        let mut updates = vec![
+            (Channel::L2, Update::L2 { bids, asks }),
+            (
+                Channel::L3,
+                Update::L3(events.iter().map(|(_, event)| *event).collect()),
+            ),
+        ];
+        if !trades.is_empty() {
+            self.trades.extend(trades.iter().copied());
+            while self.trades.len() > RECENT_TRADES {
+                self.trades.pop_front();
+            }
+            updates.push((Channel::Trades, Update::Trades(trades)));
+        }
+        let bbo = bbo(&self.order_book);
+        if bbo != self.bbo {
+            self.bbo = bbo;
+            updates.push((Channel::Bbo, Update::Bbo(bbo)));
+        }
+
+        for (channel, data) in updates {
+            let sequence = self.sequences.get_mut(&channel).unwrap();
+            *sequence += 1;
+            let subscribers = self.subscribers.get(&channel).into_iter().flatten();
+            for &subscriber in subscribers {
+                let update = Response::Update {
+                    symbol: symbol.to_string(),
+                    sequence: *sequence,
+                    data: data.clone(),
+                };
+                responses.push((subscriber, update));
+            }
+        }
+    }
+}
+
+fn bbo(order_book: &OrderBook) -> Bbo {
+    let best = |side| {
+        return order_book.levels(side).next().map(|level| Level {
+            price: level.price,
+            quantity: level.volume,
+        });
+    };
+    return Bbo {
+        bid: best(Side::Bid),
+        ask: best(Side::Ask),
+    };
+}
+
+fn error(message: String) -> Response {
+    return Response::Error { message };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use rust_decimal_macros::*;
+
+    fn hub() -> Hub {
+        let mut hub = Hub::new();
+        for symbol in ["ABC", "XYZ"].iter() {
+            let order_book =
+                OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+            hub.add_market(symbol, order_book);
+        }
+        return hub;
+    }
+
+    fn subscribe(channel: Channel) -> Request {
+        return Request::Subscribe {
+            symbol: String::from("ABC"),
+            channel,
+        };
+    }
+
+    fn submit(side: Side, quantity: Decimal, price: Option<Decimal>) -> Request {
+        return Request::Submit {
+            symbol: String::from("ABC"),
+            side,
+            quantity,
+            price,
+            client_id: None,
+        };
+    }
+
+    /// Each response as "connection: type channel sequence".
+    fn summary(responses: &[(u64, Response)]) -> Vec<String> {
+        return responses
+            .iter()
+            .map(|(connection, response)| {
+                let description = match response {
+                    Response::Snapshot { sequence, data, .. } => {
+                        let json = serde_json::to_value(data).unwrap();
+                        format!("snapshot {} {}", json["channel"], sequence)
+                    }
+                    Response::Update { sequence, data, .. } => {
+                        let json = serde_json::to_value(data).unwrap();
+                        format!("update {} {}", json["channel"], sequence)
+                    }
+                    Response::Unsubscribed { .. } => String::from("unsubscribed"),
+                    Response::Submitted {
+                        filled, resting, ..
+                    } => {
+                        format!("submitted {}/{}", filled, resting)
+                    }
+                    Response::Filled { remaining, .. } => format!("filled {}", remaining),
+                    Response::Canceled { quantity, .. } => format!("canceled {}", quantity),
+                    Response::Error { message } => format!("error {}", message),
+                };
+                return format!("{}: {}", connection, description);
+            })
+            .collect();
+    }
+
+    #[test]
+    fn test_snapshot_then_updates() {
+        let mut hub = hub();
+        hub.handle(1, submit(Side::Ask, dec!(5), Some(dec!(10))));
+        hub.handle(1, submit(Side::Ask, dec!(5), Some(dec!(11))));
+
+        let responses = hub.handle(2, subscribe(Channel::L2));
+        assert_eq!(summary(&responses), ["2: snapshot \"l2\" 2"]);
+        match &responses[0].1 {
+            Response::Snapshot {
+                data: Snapshot::L2 { bids, asks },
+                ..
+            } => {
+                assert!(bids.is_empty());
+                assert_eq!(asks.len(), 2);
+                assert_eq!(asks[0].price, dec!(10));
+            }
+            response => panic!("unexpected {:?}", response),
+        }
+        hub.handle(2, subscribe(Channel::Trades));
+        hub.handle(2, subscribe(Channel::Bbo));
+
+        // A trade updates every channel that changed, in order
+        let responses = hub.handle(3, submit(Side::Bid, dec!(7), None));
+        assert_eq!(
+            summary(&responses),
+            [
+                "3: submitted 7/0",
+                "1: filled 0",
+                "1: filled 3",
+                "2: update \"l2\" 3",
+                "2: update \"trades\" 1",
+                "2: update \"bbo\" 2"
+            ]
+        );
+        match &responses[3].1 {
+            Response::Update {
+                data: Update::L2 { asks, .. },
+                ..
+            } => assert_eq!(
+                asks,
+                &[
+                    Level {
+                        price: dec!(10),
+                        quantity: dec!(0)
+                    },
+                    Level {
+                        price: dec!(11),
+                        quantity: dec!(3)
+                    }
+                ]
+            ),
+            response => panic!("unexpected {:?}", response),
+        }
+
+        // Late subscribers see the same state
+        let responses = hub.handle(4, subscribe(Channel::Trades));
+        match &responses[0].1 {
+            Response::Snapshot {
+                sequence,
+                data: Snapshot::Trades(trades),
+                ..
+            } => {
+                assert_eq!(*sequence, 1);
+                assert_eq!(trades.len(), 2);
+                assert_eq!(trades[0].aggressor, Side::Bid);
+            }
+            response => panic!("unexpected {:?}", response),
+        }
+
+        // Other symbols have their own channels
+        let responses = hub.handle(
+            1,
+            Request::Submit {
+                symbol: String::from("XYZ"),
+                side: Side::Bid,
+                quantity: dec!(1),
+                price: Some(dec!(1)),
+                client_id: None,
+            },
+        );
+        assert_eq!(summary(&responses), ["1: submitted 0/1"]);
+
+        let responses = hub.handle(
+            2,
+            Request::Unsubscribe {
+                symbol: String::from("ABC"),
+                channel: Channel::L2,
+            },
+        );
+        assert_eq!(summary(&responses), ["2: unsubscribed"]);
+        let responses = hub.handle(3, submit(Side::Bid, dec!(1), Some(dec!(9))));
+        assert_eq!(
+            summary(&responses),
+            ["3: submitted 0/1", "2: update \"bbo\" 3"]
+        );
+    }
+
+    #[test]
+    fn test_l3_and_cancels() {
+        let mut hub = hub();
+        let responses = hub.handle(1, submit(Side::Bid, dec!(5), Some(dec!(10))));
+        let id = match &responses[0].1 {
+            Response::Submitted { id, .. } => id.unwrap(),
+            response => panic!("unexpected {:?}", response),
+        };
+        hub.handle(2, submit(Side::Bid, dec!(2), Some(dec!(10))));
+
+        let responses = hub.handle(3, subscribe(Channel::L3));
+        match &responses[0].1 {
+            Response::Snapshot {
+                data: Snapshot::L3 { bids, .. },
+                ..
+            } => {
+                let queue: Vec<Decimal> = bids.iter().map(|order| order.quantity).collect();
+                assert_eq!(queue, [dec!(5), dec!(2)]);
+            }
+            response => panic!("unexpected {:?}", response),
+        }
+
+        let cancel = Request::Cancel {
+            symbol: String::from("ABC"),
+            id,
+        };
+        let responses = hub.handle(2, cancel.clone());
+        assert_eq!(
+            summary(&responses),
+            [format!("2: error unknown order {}", id)]
+        );
+        let responses = hub.handle(1, cancel);
+        assert_eq!(summary(&responses), ["1: canceled 5", "3: update \"l3\" 3"]);
+
+        // Leaving cancels what's left
+        let responses = hub.disconnected(2);
+        assert_eq!(summary(&responses), ["3: update \"l3\" 4"]);
+        match &responses[0].1 {
+            Response::Update {
+                data: Update::L3(events),
+                ..
+            } => assert!(matches!(events[..], [BookEvent::Delete { .. }])),
+            response => panic!("unexpected {:?}", response),
+        }
+        assert_eq!(hub.order_book("ABC").unwrap().best_bid(), None);
+
+        let responses = hub.handle(1, submit(Side::Bid, dec!(0), None));
+        assert_eq!(summary(&responses), ["1: error quantity must be positive"]);
+        let responses = hub.handle(
+            1,
+            Request::Subscribe {
+                symbol: String::from("NOPE"),
+                channel: Channel::L2,
+            },
+        );
+        assert_eq!(summary(&responses), ["1: error unknown symbol NOPE"]);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod hub;
+pub mod protocol;
+pub mod server;
+
+pub use hub::Hub;
+pub use protocol::{Channel, Request, Response};
+pub use server::Server;
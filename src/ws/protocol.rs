@@ -0,0 +1,204 @@
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::book_event::BookEvent;
+use crate::order::{Order, Side};
+pub use crate::trade::TradeReport;
+
+/// A stream of market data for one symbol.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    /// Total quantity at each price.
+    L2,
+    /// Every order on the book.
+    L3,
+    Trades,
+    /// The best bid and offer.
+    Bbo,
+}
+
+/// A message from a client, tagged by its `type`.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Subscribe {
+        symbol: String,
+        channel: Channel,
+    },
+    Unsubscribe {
+        symbol: String,
+        channel: Channel,
+    },
+    /// A limit order, or a market order without a price.
+    Submit {
+        symbol: String,
+        side: Side,
+        quantity: Decimal,
+        #[serde(default)]
+        price: Option<Decimal>,
+        /// Echoed back in the response, to match them up.
+        #[serde(default)]
+        client_id: Option<String>,
+    },
+    Cancel {
+        symbol: String,
+        id: Uuid,
+    },
+}
+
+/// A message to a client, tagged by its `type`.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// The state of a channel on subscribing, as of `sequence`.
+    Snapshot {
+        symbol: String,
+        sequence: u64,
+        #[serde(flatten)]
+        data: Snapshot,
+    },
+    /// A change to a channel. Each symbol's channels count their updates
+    /// separately, with the first after a snapshot at its `sequence` plus
+    /// one, so a gap means updates were missed.
+    Update {
+        symbol: String,
+        sequence: u64,
+        #[serde(flatten)]
+        data: Update,
+    },
+    Unsubscribed {
+        symbol: String,
+        channel: Channel,
+    },
+    /// The outcome of a `Submit`. `id` is set if part of the order is
+    /// resting on the book.
+    Submitted {
+        symbol: String,
+        client_id: Option<String>,
+        id: Option<Uuid>,
+        side: Side,
+        filled: Decimal,
+        resting: Decimal,
+        fills: Vec<Execution>,
+    },
+    /// A resting order traded, leaving `remaining` on the book.
+    Filled {
+        symbol: String,
+        id: Uuid,
+        price: Decimal,
+        quantity: Decimal,
+        remaining: Decimal,
+    },
+    Canceled {
+        symbol: String,
+        id: Uuid,
+        quantity: Decimal,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "channel", content = "data", rename_all = "snake_case")]
+pub enum Snapshot {
+    /// Every price level, best first.
+    L2 {
+        bids: Vec<Level>,
+        asks: Vec<Level>,
+    },
+    /// Every order, in priority order.
+    L3 {
+        bids: Vec<Order>,
+        asks: Vec<Order>,
+    },
+    /// The most recent trades, oldest first.
+    Trades(Vec<TradeReport>),
+    Bbo(Bbo),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "channel", content = "data", rename_all = "snake_case")]
+pub enum Update {
+    /// The levels that changed, with their new quantity. A quantity of zero
+    /// means the level is gone.
+    L2 {
+        bids: Vec<Level>,
+        asks: Vec<Level>,
+    },
+    L3(Vec<BookEvent>),
+    Trades(Vec<TradeReport>),
+    Bbo(Bbo),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Level {
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Bbo {
+    pub bid: Option<Level>,
+    pub ask: Option<Level>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Execution {
+    pub price: Decimal,
+    pub quantity: Decimal,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::*;
+
+    #[test]
+    fn test_json() {
+        let request: Request = serde_json::from_str(
+            r#"{"type": "submit", "symbol": "ABC", "side": "Bid", "quantity": "10"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            Request::Submit {
+                symbol: String::from("ABC"),
+                side: Side::Bid,
+                quantity: dec!(10),
+                price: None,
+                client_id: None,
+            }
+        );
+        let request: Request =
+            serde_json::from_str(r#"{"type": "subscribe", "symbol": "ABC", "channel": "bbo"}"#)
+                .unwrap();
+        assert_eq!(
+            request,
+            Request::Subscribe {
+                symbol: String::from("ABC"),
+                channel: Channel::Bbo,
+            }
+        );
+
+        let update = Response::Update {
+            symbol: String::from("ABC"),
+            sequence: 3,
+            data: Update::L2 {
+                bids: vec![Level {
+                    price: dec!(10.5),
+                    quantity: dec!(0),
+                }],
+                asks: Vec::new(),
+            },
+        };
+        let json = serde_json::to_string(&update).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"update","symbol":"ABC","sequence":3,"channel":"l2","data":{"bids":[{"price":"10.5","quantity":"0"}],"asks":[]}}"#
+        );
+        assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), update);
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+use crate::ws::hub::Hub;
+use crate::ws::protocol::{Request, Response};
+
+/// How long a connection waits for a message before sending what's queued
+/// for it.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Serves a `Hub` to WebSocket clients, with requests and responses as JSON
+/// text messages.
+///
+/// Connections share the hub behind one lock. Responses meant for other
+/// connections, such as market data updates and fills of their orders, are
+/// queued for their threads to send.
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    shared: Arc<Mutex<Shared>>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    hub: Hub,
+    outboxes: HashMap<u64, Sender<String>>,
+    next_connection: u64,
+}
+
+impl Server {
+    /// Listens on `addr`. The WebSocket handshake isn't authenticated, so
+    /// anyone who can reach `addr` can subscribe and trade.
+    pub fn bind(addr: impl ToSocketAddrs, hub: Hub) -> io::Result<Self> {
+        let shared = Shared {
+            hub,
+            outboxes: HashMap::new(),
+            next_connection: 1,
+        };
+        return Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            shared: Arc::new(Mutex::new(shared)),
+        });
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        return self.listener.local_addr();
+    }
+
+    /// Upgrades each connection on its own thread, until the listener fails.
+    pub fn run(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || serve(stream, shared));
+        }
+        return Ok(());
+    }
+}
+
+/// Runs one connection until it closes.
+fn serve(stream: TcpStream, shared: Arc<Mutex<Shared>>) {
+    let _ = stream.set_nodelay(true);
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(_) => return,
+    };
+    if socket
+        .get_ref()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .is_err()
+    {
+        return;
+    }
+
+    let (sender, outbox) = mpsc::channel();
+    let connection = {
+        let mut shared = shared.lock().unwrap();
+        let connection = shared.next_connection;
+        shared.next_connection += 1;
+        shared.outboxes.insert(connection, sender);
+        connection
+    };
+
+    while let Ok(request) = receive(&mut socket) {
+        if let Some(text) = request {
+            // The lock is held until the responses are queued, so updates
+            // reach every connection in the order the hub made them.
+            let mut shared = shared.lock().unwrap();
+            let responses = match serde_json::from_str::<Request>(&text) {
+                Ok(request) => shared.hub.handle(connection, request),
+                Err(e) => {
+                    let message = format!("invalid request: {}", e);
+                    vec![(connection, Response::Error { message })]
+                }
+            };
+            shared.send(responses);
+        }
+        if flush(&mut socket, &outbox).is_err() {
+            break;
+        }
+    }
+
+    let mut shared = shared.lock().unwrap();
+    shared.outboxes.remove(&connection);
+    let responses = shared.hub.disconnected(connection);
+    shared.send(responses);
+}
+
+/// Waits for the next text message, for up to `POLL_INTERVAL`. Fails once
+/// the connection is closed.
+fn receive(socket: &mut WebSocket<TcpStream>) -> Result<Option<String>, tungstenite::Error> {
+    match socket.read() {
+        Ok(Message::Text(text)) => return Ok(Some(text.to_string())),
+        Ok(Message::Close(_)) => return Err(tungstenite::Error::ConnectionClosed),
+        // Pings are answered by the next flush
+        Ok(_) => return Ok(None),
+        Err(tungstenite::Error::Io(e))
+            if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+        {
+            return Ok(None)
+        }
+        Err(e) => return Err(e),
+    }
+}
+
+fn flush(socket: &mut WebSocket<TcpStream>, outbox: &Receiver<String>) -> tungstenite::Result<()> {
+    for text in outbox.try_iter() {
+        socket.write(Message::text(text))?;
+    }
+    return socket.flush();
+}
+
+impl Shared {
+    fn send(&mut self, responses: Vec<(u64, Response)>) {
+        for (to, response) in responses {
+            if let Some(outbox) = self.outboxes.get(&to) {
+                let _ = outbox.send(serde_json::to_string(&response).unwrap());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id_generator::SequentialIdGenerator;
+    use crate::OrderBook;
+    use serde_json::{json, Value};
+
+    struct Client {
+        socket: WebSocket<tungstenite::stream::MaybeTlsStream<TcpStream>>,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Self {
+            let (socket, _) = tungstenite::connect(format!("ws://{}", addr)).unwrap();
+            return Client { socket };
+        }
+
+        fn send(&mut self, request: Value) {
+            self.socket
+                .send(Message::text(request.to_string()))
+                .unwrap();
+        }
+
+        fn receive(&mut self) -> Value {
+            loop {
+                if let Message::Text(text) = self.socket.read().unwrap() {
+                    return serde_json::from_str(&text).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_over_websocket() {
+        let order_book = OrderBook::with_clock(ManualClock::new(0), SequentialIdGenerator::new());
+        let mut hub = Hub::new();
+        hub.add_market("ABC", order_book);
+        let server = Server::bind("127.0.0.1:0", hub).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let mut watcher = Client::connect(addr);
+        watcher.send(json!({"type": "subscribe", "symbol": "ABC", "channel": "bbo"}));
+        let snapshot = watcher.receive();
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["sequence"], 0);
+        assert_eq!(snapshot["data"], json!({"bid": null, "ask": null}));
+
+        let mut trader = Client::connect(addr);
+        trader.send(json!({
+            "type": "submit",
+            "symbol": "ABC",
+            "side": "Ask",
+            "quantity": "5",
+            "price": "10.5",
+            "client_id": "first"
+        }));
+        let submitted = trader.receive();
+        assert_eq!(submitted["type"], "submitted");
+        assert_eq!(submitted["client_id"], "first");
+        assert_eq!(submitted["resting"], "5");
+
+        let update = watcher.receive();
+        assert_eq!(update["type"], "update");
+        assert_eq!(update["sequence"], 1);
+        assert_eq!(
+            update["data"]["ask"],
+            json!({"price": "10.5", "quantity": "5"})
+        );
+
+        trader.send(json!({"type": "cancel", "symbol": "ABC", "id": submitted["id"]}));
+        assert_eq!(trader.receive()["type"], "canceled");
+        assert_eq!(watcher.receive()["sequence"], 2);
+
+        trader.send(json!({"type": "submit", "symbol": "ABC"}));
+        let error = trader.receive();
+        assert_eq!(error["type"], "error");
+        assert!(error["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid request"));
+    }
+}